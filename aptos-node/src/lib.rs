@@ -10,7 +10,7 @@ use aptos_config::{
     network_id::NetworkId,
     utils::get_genesis_txn,
 };
-use aptos_data_client::aptosnet::AptosNetDataClient;
+use aptos_data_client::aptosnet::{AptosNetDataClient, TrustedLedgerInfoSource};
 use aptos_infallible::RwLock;
 use aptos_logger::{prelude::*, Logger};
 use aptos_metrics::{get_public_json_metrics, get_public_metrics, metric_server};
@@ -24,6 +24,7 @@ use aptos_types::{
     account_config::aptos_root_address,
     account_view::AccountView,
     chain_id::ChainId,
+    ledger_info::LedgerInfoWithSignatures,
     move_resource::MoveStorage,
     on_chain_config::{VMPublishingOption, ON_CHAIN_CONFIG_REGISTRY},
     waypoint::Waypoint,
@@ -52,6 +53,7 @@ use state_sync_v1::network::{StateSyncEvents, StateSyncSender};
 use std::{
     boxed::Box,
     collections::{HashMap, HashSet},
+    fmt,
     io::Write,
     net::ToSocketAddrs,
     path::PathBuf,
@@ -62,7 +64,7 @@ use std::{
     thread,
     time::Instant,
 };
-use storage_interface::{state_view::DbStateViewAtVersion, DbReaderWriter};
+use storage_interface::{state_view::DbStateViewAtVersion, DbReader, DbReaderWriter};
 use storage_service::start_storage_service_with_db;
 use storage_service_client::{StorageServiceClient, StorageServiceMultiSender};
 use storage_service_server::{
@@ -277,10 +279,13 @@ fn create_state_sync_runtimes<M: MempoolNotificationSender + 'static>(
     // Start the data client
     let (aptos_data_client, aptos_data_client_runtime) = setup_aptos_data_client(
         node_config.state_sync.storage_service,
-        node_config.state_sync.aptos_data_client,
+        node_config.state_sync.aptos_data_client.clone(),
         storage_service_client_network_handles,
         peer_metadata_storage,
     );
+    aptos_data_client.set_trusted_ledger_info_source(Arc::new(StorageTrustedLedgerInfoSource {
+        storage: db_rw.reader.clone(),
+    }));
 
     // Start the data streaming service
     let (streaming_service_client, streaming_service_runtime) = setup_data_streaming_service(
@@ -316,6 +321,24 @@ fn create_state_sync_runtimes<M: MempoolNotificationSender + 'static>(
     )
 }
 
+/// A trusted ledger info source that returns the latest ledger info committed
+/// to local storage
+struct StorageTrustedLedgerInfoSource {
+    storage: Arc<dyn DbReader>,
+}
+
+impl fmt::Debug for StorageTrustedLedgerInfoSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StorageTrustedLedgerInfoSource")
+    }
+}
+
+impl TrustedLedgerInfoSource for StorageTrustedLedgerInfoSource {
+    fn get_latest_trusted_ledger_info(&self) -> Option<LedgerInfoWithSignatures> {
+        self.storage.get_latest_ledger_info().ok()
+    }
+}
+
 fn setup_data_streaming_service(
     config: DataStreamingServiceConfig,
     aptos_data_client: AptosNetDataClient,
@@ -231,6 +231,7 @@ impl NodeConfig {
 
         let input_dir = RootPath::new(input_path);
         config.execution.load(&input_dir)?;
+        config.state_sync.sanitize()?;
 
         let mut config = config.validate_network_configs()?;
         config.set_data_dir(config.data_dir().to_path_buf());
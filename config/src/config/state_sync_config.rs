@@ -1,7 +1,9 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::config::{invariant, Error};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
@@ -55,6 +57,14 @@ impl Default for StateSyncConfig {
     }
 }
 
+impl StateSyncConfig {
+    /// Sanitizes the state sync config, i.e., verifies that the config values
+    /// are within their valid ranges.
+    pub fn sanitize(&self) -> Result<(), Error> {
+        self.aptos_data_client.sanitize()
+    }
+}
+
 /// The bootstrapping mode determines how the node will bootstrap to the latest
 /// blockchain state, e.g., directly download the latest account states.
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -153,6 +163,11 @@ pub struct DataStreamingServiceConfig {
 
     // The interval (milliseconds) at which to check the progress of each stream.
     pub progress_check_interval_ms: u64,
+
+    // The modulus to which transaction (and transaction output) chunk
+    // boundaries are aligned, i.e., chunks will start at multiples of this
+    // value (except the first chunk). Zero disables alignment.
+    pub transaction_chunk_alignment: u64,
 }
 
 impl Default for DataStreamingServiceConfig {
@@ -164,22 +179,514 @@ impl Default for DataStreamingServiceConfig {
             max_request_retry: 3,
             max_notification_id_mappings: 2000,
             progress_check_interval_ms: 100,
+            transaction_chunk_alignment: 0,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+/// The compression algorithms that can be used for storage service responses
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum CompressionAlgo {
+    Lz4,  // Favours compression speed
+    Zstd, // Favours compression ratio
+}
+
+/// The policies that can be used to select a peer for a data request
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum PeerSelectionPolicy {
+    HighestScore,  // Selects the peer with the highest score
+    LowestLatency, // Selects the peer with the lowest (measured) response latency
+    Random,        // Selects a random peer (weighted by recent errors, if enabled)
+}
+
+/// The strategies that can be used to select the high-priority peer to poll
+/// (for its storage summary) in each polling round
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum PeerSelectionStrategy {
+    FreshnessWeighted, // Favours peers advertising higher synced versions (round robin on ties)
+    RoundRobin,        // Polls the peer that was polled the longest ago
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct AptosDataClientConfig {
+    pub caching: ResponseCachingConfig,
+    pub cancel_inflight_on_ban: bool, // Whether the in-flight requests to a peer are cancelled (and re-routed to other peers) when the peer is banned. Otherwise, they're left to complete.
+    pub chunk_sizes: ChunkSizeConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub enforce_stream_proof_anchor: bool, // Whether every chunk of a transaction stream must be proven against the stream's proof version (chunks proven against a different version are rejected and re-fetched from other peers)
+    pub fail_fast_on_unavailable_data_type: bool, // Whether requests fail fast (with a dedicated error) if no peer serves the requested data type at all
+    pub hedge_delay_ms: u64, // Delay (in milliseconds) after which a request without a response is also sent to another peer (i.e., hedged). The first successful response is used. Zero disables this.
+    pub hedge_only_high_priority: bool, // Whether only high priority requests are hedged (e.g., requests at the tip, but not bulk backfill requests)
+    pub late_response_grace_ms: u64, // Grace period (in milliseconds) after a timeout during which a late response partially reverts the timeout penalty. Zero disables this.
+    pub max_response_bytes: u64, // Maximum size (in bytes) of a response on the wire (or decompressed). Larger responses are rejected before they are deserialized (and the peer is penalized). Zero disables this (but decompressed responses are still bounded).
+    pub max_retry_after_ms: u64, // Maximum time (in milliseconds) to honour a peer's retry-after backpressure hint
+    pub monitoring: DataClientMonitoringConfig,
+    pub peer_scoring: PeerScoringConfig,
+    pub peer_selection: PeerSelectionConfig,
+    pub preferred_compression_algorithms: Vec<CompressionAlgo>, // The supported response compression algorithms (in order of preference). Empty disables compression.
     pub response_timeout_ms: u64, // Timeout (in milliseconds) when waiting for a response
+    pub retry_malformed_responses: bool, // Whether requests that receive a malformed (i.e., undeserializable) response are resent to another peer
+    pub summary_persistence: SummaryPersistenceConfig,
     pub summary_poll_interval_ms: u64, // Interval (in milliseconds) between data summary polls
+    pub summary_poller: SummaryPollerConfig,
+    pub warm_connection_before_bulk: bool, // Whether to send a cheap probe request to a peer before the first bulk data request to that peer
 }
 
 impl Default for AptosDataClientConfig {
     fn default() -> Self {
         Self {
+            caching: ResponseCachingConfig::default(),
+            cancel_inflight_on_ban: false,
+            chunk_sizes: ChunkSizeConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            enforce_stream_proof_anchor: false,
+            fail_fast_on_unavailable_data_type: false,
+            hedge_delay_ms: 0,
+            hedge_only_high_priority: false,
+            late_response_grace_ms: 0,
+            max_response_bytes: 0,
+            max_retry_after_ms: 60_000,
+            monitoring: DataClientMonitoringConfig::default(),
+            peer_scoring: PeerScoringConfig::default(),
+            peer_selection: PeerSelectionConfig::default(),
+            preferred_compression_algorithms: vec![],
             response_timeout_ms: 10000,
+            retry_malformed_responses: false,
+            summary_persistence: SummaryPersistenceConfig::default(),
             summary_poll_interval_ms: 100,
+            summary_poller: SummaryPollerConfig::default(),
+            warm_connection_before_bulk: false,
+        }
+    }
+}
+
+/// The config for calculating (and probing) the chunk sizes of data requests
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkSizeConfig {
+    pub max_epoch_span_per_request: u64, // Maximum number of epochs that can be requested in a single epoch ending ledger info request
+    pub min_summaries_for_chunk_calc: u64, // Minimum number of (non-ignored) peer summaries before optimal chunk sizes are calculated from them. Until then, conservative chunk sizes are used.
+    pub percentile: f64, // The percentile (between 0 and 1) of the chunk sizes advertised by peers used as the optimal chunk size (e.g., 0.5 selects the median). Lower is more conservative, higher is more aggressive.
+    pub probe_initial_size: u64, // Initial transaction chunk size used to probe the serving limits of the network (the size doubles after each successful probe, until a probe fails or the max chunk size is reached). Zero disables probing.
+}
+
+impl Default for ChunkSizeConfig {
+    fn default() -> Self {
+        Self {
+            max_epoch_span_per_request: 10_000,
+            min_summaries_for_chunk_calc: 0,
+            percentile: 0.5,
+            probe_initial_size: 0,
+        }
+    }
+}
+
+/// The config for the circuit breaker that stops sending requests after repeated failures
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    pub cooldown_ms: u64, // Time (in milliseconds) the circuit breaker stays open (i.e., new requests fail without being sent) before it half-opens and lets requests through again
+    pub failure_threshold: u64, // Number of consecutive failed requests (within the failure window) after which the circuit breaker opens. Zero disables the circuit breaker.
+    pub failure_window_ms: u64, // Window (in milliseconds) within which consecutive failed requests must occur to open the circuit breaker
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            cooldown_ms: 5_000,
+            failure_threshold: 0,
+            failure_window_ms: 10_000,
+        }
+    }
+}
+
+/// The config for monitoring the health of the data client (and the network)
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DataClientMonitoringConfig {
+    pub min_peer_diversity: u64, // Minimum number of distinct (non-ignored) peers with advertised data before a warning is emitted. Zero disables this.
+    pub network_stall_threshold_ms: u64, // Time (in milliseconds) without the highest synced version advancing before the network is considered stalled
+    pub selection_latency_window: u64, // Number of recent peer selection latencies used to report selection latency stats. Zero disables this.
+    pub sync_phase_response_staleness_ms: u64, // Max time (in milliseconds) since the last successful data response for the client to be considered following
+}
+
+impl Default for DataClientMonitoringConfig {
+    fn default() -> Self {
+        Self {
+            min_peer_diversity: 0,
+            network_stall_threshold_ms: 60_000,
+            selection_latency_window: 1_000,
+            sync_phase_response_staleness_ms: 30_000,
+        }
+    }
+}
+
+/// The config for scoring (and banning) peers according to their responses
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerScoringConfig {
+    pub ignore_peer_threshold: f64, // Peers with scores at or below this threshold are ignored (i.e., banned). Scores range from 0 to 100 (starting at 50).
+    pub max_success_streak_multiplier: f64, // Maximum multiplier applied to a peer's success reward (after a streak of consecutive successes)
+    pub penalize_short_epoch_ending_responses: bool, // Whether peers are penalized for returning fewer epoch ending ledger infos than they advertise (truncation at the advertised end is never penalized)
+    pub require_data_probe_for_recovery: bool, // Whether banned peers must successfully serve a (small) data request, proven against the latest trusted ledger info, before they're un-banned. Otherwise, peers are un-banned once their scores recover (e.g., by answering summary polls).
+    pub score_penalty_multiplier: f64, // Multiplier that scales the fraction of a peer's score lost for each bad response (e.g., two doubles the penalty). One preserves the built-in penalties.
+    pub score_recovery_increment: f64, // Score added to a peer for each successful response (including summary polls), before any success streak multiplier
+    pub success_streak_growth_factor: f64, // Factor by which a peer's success reward multiplier grows with each consecutive success (any failure resets it). One disables this.
+}
+
+impl Default for PeerScoringConfig {
+    fn default() -> Self {
+        Self {
+            ignore_peer_threshold: 25.0,
+            max_success_streak_multiplier: 4.0,
+            penalize_short_epoch_ending_responses: false,
+            require_data_probe_for_recovery: false,
+            score_penalty_multiplier: 1.0,
+            score_recovery_increment: 1.0,
+            success_streak_growth_factor: 1.0,
+        }
+    }
+}
+
+/// The config for selecting the peers to which data requests are sent
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerSelectionConfig {
+    pub allow_partial_coverage_peers: bool, // Whether to use peers that only advertise the start of a requested range (if no peer advertises the full range)
+    pub allow_regular_peer_fallback: bool, // Whether requests that fail on a priority peer are resent to a regular peer (if one can service the request)
+    pub diversify_cross_check_peers: bool, // Whether the peers chosen for cross-checked requests are diversified across distinct IP addresses and peer identities (before falling back to score order). Otherwise, the highest scoring peers are chosen.
+    pub edge_margin_versions: u64, // Minimum margin (in versions, or epochs for epoch ending ledger infos) between a requested range and the edges of a peer's advertised range before the peer is considered to serve from its edge. Peers with this margin are preferred. Zero disables this.
+    pub max_in_flight_per_peer: u64, // Maximum number of concurrent in-flight requests to each peer. Saturated peers are avoided (if possible), otherwise requests wait for a slot. Zero disables this.
+    pub max_requests_fraction_per_ip: f64, // Maximum fraction of requests routed to the peers behind any single IP address (peers without an IP aren't grouped). One disables this.
+    pub max_requests_per_sec_per_peer: u64, // Maximum number of requests per second sent to each peer for each data type (i.e., the refill rate of the peer's token bucket). Rate limited peers are avoided. Zero disables this.
+    pub min_peers_for_request: u64, // Minimum number of distinct peers that must advertise the requested data before a request is sent (i.e., a quorum requirement)
+    pub recent_error_weight: u64, // Weight of each recent error when selecting peers (i.e., a peer's selection weight is 1 / (1 + weight * recent errors)). Zero disables this.
+    pub recent_error_window_ms: u64, // Window (in milliseconds) over which errors are considered recent when selecting peers
+    pub reconcile_summaries_across_networks: bool, // Whether the summaries of peers with the same identity on different networks (as linked by the peer identity resolver) are merged into a unified view for peer selection
+    pub selection_policy: PeerSelectionPolicy, // The policy used to select a peer for each data request (unless overridden by the request)
+}
+
+impl Default for PeerSelectionConfig {
+    fn default() -> Self {
+        Self {
+            allow_partial_coverage_peers: false,
+            allow_regular_peer_fallback: true,
+            diversify_cross_check_peers: false,
+            edge_margin_versions: 0,
+            max_in_flight_per_peer: 0,
+            max_requests_fraction_per_ip: 1.0,
+            max_requests_per_sec_per_peer: 0,
+            min_peers_for_request: 1,
+            recent_error_weight: 0,
+            recent_error_window_ms: 10_000,
+            reconcile_summaries_across_networks: false,
+            selection_policy: PeerSelectionPolicy::Random,
+        }
+    }
+}
+
+/// The config for caching (and coalescing) data client responses
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseCachingConfig {
+    pub coalesce_window_ms: u64, // Window (in milliseconds) after a request is sent during which identical requests reuse its response (if request coalescing is enabled). Zero only coalesces in-flight requests.
+    pub enable_request_coalescing: bool, // Whether identical data requests (with identical request options) attach to an in-flight (or recently completed) request instead of sending their own RPCs
+    pub max_epoch_ending_cache_entries: u64, // Maximum number of epoch ending ledger info batches cached (keyed by the returned epochs). Zero disables this.
+    pub verify_cache_integrity: bool, // Whether cached (and shared coalesced) responses are hashed on insert and verified on read (corrupted entries are re-fetched)
+}
+
+impl Default for ResponseCachingConfig {
+    fn default() -> Self {
+        Self {
+            coalesce_window_ms: 0,
+            enable_request_coalescing: false,
+            max_epoch_ending_cache_entries: 0,
+            verify_cache_integrity: false,
+        }
+    }
+}
+
+/// The config for persisting the global data summary (to warm-start on restart)
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryPersistenceConfig {
+    pub interval_ms: u64, // Interval (in milliseconds) at which the global data summary is persisted
+    pub path: Option<PathBuf>, // The path at which to persist the global data summary (to warm-start on restart). None disables persistence.
+}
+
+impl Default for SummaryPersistenceConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 60_000,
+            path: None,
+        }
+    }
+}
+
+/// The config for polling peers for their storage summaries
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryPollerConfig {
+    pub dedup_summary_updates: bool, // Whether the global data summary is only recomputed after polls that change a peer's summary (or the set of ignored peers). Otherwise, it's recomputed after every poll.
+    pub gap_poll_timeout_ms: u64, // Timeout (in milliseconds) for each summary request of a gap-triggered poll
+    pub max_concurrent_summary_polls: u64, // Maximum number of concurrent summary requests when polling all peers
+    pub max_concurrent_summary_polls_per_network: u64, // Maximum number of concurrent summary requests per network when polling all peers. Zero disables this.
+    pub max_consecutive_summary_poll_failures: u64, // Maximum number of consecutive failed summary polls before a peer is excluded from polling (for a cooldown). Zero disables this.
+    pub max_summary_poll_interval_ms: u64, // Maximum interval (in milliseconds) between data summary poll rounds when the interval adapts to the number of connected peers
+    pub min_summary_poll_interval_ms: u64, // Minimum interval (in milliseconds) between data summary poll rounds when the interval adapts to the number of connected peers
+    pub no_peers_idle_interval_ms: u64, // Time (in milliseconds) the data summary poller idles when there are no connected peers (unless a peer connects). Zero disables this.
+    pub poll_on_gap: bool, // Whether to poll all peers (and re-evaluate the request) before failing a request for data that no peer advertises
+    pub poll_selection_strategy: PeerSelectionStrategy, // The strategy used to select the high-priority peer to poll in each polling round
+    pub poller_startup_delay_ms: u64, // Delay (in milliseconds) after construction before the data summary poller starts polling
+    pub reject_inconsistent_summaries: bool, // Whether storage summaries with advertised ranges that are inconsistent with the synced ledger info are dropped (and the peer is penalized)
+    pub summary_poll_failure_cooldown_ms: u64, // Time (in milliseconds) a peer is excluded from polling after too many consecutive failed summary polls
+    pub summary_poll_interval_per_peer_ms: u64, // Interval (in milliseconds) between data summary poll rounds per connected peer, i.e., the interval scales with the number of connected peers (within the min and max bounds). Zero disables this (and the fixed poll interval is used).
+}
+
+impl Default for SummaryPollerConfig {
+    fn default() -> Self {
+        Self {
+            dedup_summary_updates: false,
+            gap_poll_timeout_ms: 1000,
+            max_concurrent_summary_polls: 10,
+            max_concurrent_summary_polls_per_network: 0,
+            max_consecutive_summary_poll_failures: 0,
+            max_summary_poll_interval_ms: 5_000,
+            min_summary_poll_interval_ms: 100,
+            no_peers_idle_interval_ms: 0,
+            poll_on_gap: false,
+            poll_selection_strategy: PeerSelectionStrategy::RoundRobin,
+            poller_startup_delay_ms: 0,
+            reject_inconsistent_summaries: false,
+            summary_poll_failure_cooldown_ms: 60_000,
+            summary_poll_interval_per_peer_ms: 0,
+        }
+    }
+}
+
+impl AptosDataClientConfig {
+    /// Sanitizes the data client config, i.e., verifies that the config values
+    /// (e.g., probabilities, multipliers and intervals) are within their valid
+    /// ranges. Amongst others, this guarantees that peer scores can never
+    /// become non-finite.
+    pub fn sanitize(&self) -> Result<(), Error> {
+        sanitize_non_zero("response_timeout_ms", self.response_timeout_ms)?;
+        sanitize_non_zero("summary_poll_interval_ms", self.summary_poll_interval_ms)?;
+        self.chunk_sizes.sanitize()?;
+        self.circuit_breaker.sanitize()?;
+        self.peer_scoring.sanitize()?;
+        self.peer_selection.sanitize()?;
+        self.summary_persistence.sanitize()?;
+        self.summary_poller.sanitize()
+    }
+}
+
+impl ChunkSizeConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        sanitize_range("chunk_sizes.percentile", self.percentile, 0.0, 1.0)?;
+        sanitize_non_zero(
+            "chunk_sizes.max_epoch_span_per_request",
+            self.max_epoch_span_per_request,
+        )
+    }
+}
+
+impl CircuitBreakerConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        if self.failure_threshold == 0 {
+            return Ok(()); // The circuit breaker is disabled
+        }
+        sanitize_non_zero("circuit_breaker.cooldown_ms", self.cooldown_ms)?;
+        sanitize_non_zero("circuit_breaker.failure_window_ms", self.failure_window_ms)
+    }
+}
+
+impl PeerScoringConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        sanitize_range(
+            "peer_scoring.ignore_peer_threshold",
+            self.ignore_peer_threshold,
+            0.0,
+            100.0,
+        )?;
+        sanitize_range(
+            "peer_scoring.max_success_streak_multiplier",
+            self.max_success_streak_multiplier,
+            1.0,
+            f64::INFINITY,
+        )?;
+        sanitize_range(
+            "peer_scoring.score_penalty_multiplier",
+            self.score_penalty_multiplier,
+            0.0,
+            f64::INFINITY,
+        )?;
+        sanitize_range(
+            "peer_scoring.score_recovery_increment",
+            self.score_recovery_increment,
+            0.0,
+            100.0,
+        )?;
+        sanitize_range(
+            "peer_scoring.success_streak_growth_factor",
+            self.success_streak_growth_factor,
+            1.0,
+            f64::INFINITY,
+        )
+    }
+}
+
+impl PeerSelectionConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        sanitize_range(
+            "peer_selection.max_requests_fraction_per_ip",
+            self.max_requests_fraction_per_ip,
+            0.0,
+            1.0,
+        )?;
+        sanitize_non_zero(
+            "peer_selection.recent_error_window_ms",
+            self.recent_error_window_ms,
+        )
+    }
+}
+
+impl SummaryPersistenceConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        if self.path.is_none() {
+            return Ok(()); // Persistence is disabled
+        }
+        sanitize_non_zero("summary_persistence.interval_ms", self.interval_ms)
+    }
+}
+
+impl SummaryPollerConfig {
+    fn sanitize(&self) -> Result<(), Error> {
+        sanitize_non_zero(
+            "summary_poller.gap_poll_timeout_ms",
+            self.gap_poll_timeout_ms,
+        )?;
+        sanitize_non_zero(
+            "summary_poller.max_concurrent_summary_polls",
+            self.max_concurrent_summary_polls,
+        )?;
+        invariant(
+            self.min_summary_poll_interval_ms <= self.max_summary_poll_interval_ms,
+            format!(
+                "The config value summary_poller.min_summary_poll_interval_ms ({}) must not exceed summary_poller.max_summary_poll_interval_ms ({})!",
+                self.min_summary_poll_interval_ms, self.max_summary_poll_interval_ms
+            ),
+        )
+    }
+}
+
+/// Verifies that the given config value is finite and within the given
+/// (inclusive) bounds
+fn sanitize_range(field_name: &str, value: f64, min: f64, max: f64) -> Result<(), Error> {
+    invariant(
+        value.is_finite() && value >= min && value <= max,
+        format!(
+            "The config value {} must be finite and within {:?}..={:?}, but it is: {:?}",
+            field_name, min, max, value
+        ),
+    )
+}
+
+/// Verifies that the given config value (e.g., an interval) is non-zero
+fn sanitize_non_zero(field_name: &str, value: u64) -> Result<(), Error> {
+    invariant(
+        value > 0,
+        format!("The config value {} must be non-zero!", field_name),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_default_configs_are_sanitized() {
+        StateSyncConfig::default().sanitize().unwrap();
+    }
+
+    #[test]
+    fn verify_invalid_scoring_parameters_are_rejected() {
+        for invalid_value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0] {
+            let config = create_scoring_config(PeerScoringConfig {
+                score_penalty_multiplier: invalid_value,
+                ..Default::default()
+            });
+            assert!(config.sanitize().is_err());
+        }
+
+        let config = create_scoring_config(PeerScoringConfig {
+            score_recovery_increment: f64::NAN,
+            ..Default::default()
+        });
+        assert!(config.sanitize().is_err());
+
+        let config = create_scoring_config(PeerScoringConfig {
+            success_streak_growth_factor: 0.5,
+            ..Default::default()
+        });
+        assert!(config.sanitize().is_err());
+    }
+
+    #[test]
+    fn verify_invalid_ranges_are_rejected() {
+        // Verify probabilities must be within 0 and 1
+        let config = AptosDataClientConfig {
+            chunk_sizes: ChunkSizeConfig {
+                percentile: 1.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(config.sanitize().is_err());
+        let config = AptosDataClientConfig {
+            peer_selection: PeerSelectionConfig {
+                max_requests_fraction_per_ip: -0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(config.sanitize().is_err());
+
+        // Verify intervals must be non-zero (and consistent)
+        let config = AptosDataClientConfig {
+            response_timeout_ms: 0,
+            ..Default::default()
+        };
+        assert!(config.sanitize().is_err());
+        let config = AptosDataClientConfig {
+            summary_poller: SummaryPollerConfig {
+                min_summary_poll_interval_ms: 10_000,
+                max_summary_poll_interval_ms: 1_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(config.sanitize().is_err());
+
+        // Verify the circuit breaker window is only checked if it's enabled
+        let mut config = AptosDataClientConfig {
+            circuit_breaker: CircuitBreakerConfig {
+                failure_window_ms: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.sanitize().unwrap();
+        config.circuit_breaker.failure_threshold = 3;
+        assert!(config.sanitize().is_err());
+    }
+
+    /// Creates a data client config with the given scoring config
+    fn create_scoring_config(peer_scoring: PeerScoringConfig) -> AptosDataClientConfig {
+        AptosDataClientConfig {
+            peer_scoring,
+            ..Default::default()
         }
     }
 }
@@ -77,7 +77,8 @@ impl<TMessage: Clone + Message + Send, Sender: ApplicationNetworkSender<TMessage
         }
     }
 
-    fn sender(&self, network_id: &NetworkId) -> &Sender {
+    /// Returns the sender for the given network
+    pub fn sender(&self, network_id: &NetworkId) -> &Sender {
         self.senders.get(network_id).expect("Unknown NetworkId")
     }
 
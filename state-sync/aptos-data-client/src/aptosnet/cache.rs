@@ -0,0 +1,293 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::aptosnet::logging::{LogEntry, LogEvent, LogSchema};
+use aptos_config::network_id::PeerNetworkId;
+use aptos_crypto::HashValue;
+use aptos_logger::prelude::*;
+use aptos_types::ledger_info::LedgerInfoWithSignatures;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::size_of,
+};
+use storage_service_types::Epoch;
+
+/// The key for each cache entry: the start and end epochs (inclusive) of the
+/// cached ledger infos (i.e., the epochs that were returned, not requested)
+pub(crate) type EpochRange = (Epoch, Epoch);
+
+/// A batch of epoch ending ledger infos previously fetched from a peer
+#[derive(Clone, Debug)]
+pub(crate) struct CachedLedgerInfos {
+    pub peer: PeerNetworkId, // The peer that served the ledger infos
+    pub ledger_infos: Vec<LedgerInfoWithSignatures>,
+}
+
+impl CachedLedgerInfos {
+    /// Returns the range of epochs covered by the ledger infos. Returns None
+    /// if the ledger infos are empty or their epochs are not contiguous.
+    fn get_epoch_range(&self) -> Option<EpochRange> {
+        let first_epoch = self.ledger_infos.first()?.ledger_info().epoch();
+        for (expected_epoch, ledger_info) in (first_epoch..).zip(&self.ledger_infos) {
+            if ledger_info.ledger_info().epoch() != expected_epoch {
+                return None;
+            }
+        }
+        let last_epoch = self.ledger_infos.last()?.ledger_info().epoch();
+        Some((first_epoch, last_epoch))
+    }
+}
+
+/// A cache entry holding the cached ledger infos, along with the hash of the
+/// ledger infos computed at insert time (if integrity verification is enabled).
+#[derive(Debug)]
+struct CacheEntry {
+    cached_ledger_infos: CachedLedgerInfos,
+    payload_hash: Option<HashValue>,
+}
+
+/// A simple LRU cache holding previously fetched epoch ending ledger infos.
+/// This avoids network round trips when the same epochs are requested
+/// repeatedly (e.g., during bootstrapping). Requests for epochs within a
+/// cached range are served by slicing the cached ledger infos.
+#[derive(Debug)]
+pub(crate) struct EpochEndingLedgerInfoCache {
+    max_entries: usize,
+    verify_integrity: bool, // Whether the entries are verified against their hashes on read
+    entries: HashMap<EpochRange, CacheEntry>,
+    recency_queue: VecDeque<EpochRange>, // The least recently used entry is at the front
+}
+
+impl EpochEndingLedgerInfoCache {
+    pub fn new(max_entries: usize, verify_integrity: bool) -> Self {
+        Self {
+            max_entries,
+            verify_integrity,
+            entries: HashMap::new(),
+            recency_queue: VecDeque::new(),
+        }
+    }
+
+    /// Returns the cached ledger infos for the given epoch range (if any),
+    /// falling back to an entry whose range contains the given range.
+    pub fn get(&mut self, epoch_range: &EpochRange) -> Option<CachedLedgerInfos> {
+        let cached_range = self.find_containing_range(epoch_range)?;
+        let mut cached_ledger_infos = self.get_entry(&cached_range)?;
+        if cached_range != *epoch_range {
+            let (start_epoch, end_epoch) = *epoch_range;
+            cached_ledger_infos.ledger_infos.retain(|ledger_info| {
+                let epoch = ledger_info.ledger_info().epoch();
+                epoch >= start_epoch && epoch <= end_epoch
+            });
+            let first_epoch = cached_ledger_infos
+                .ledger_infos
+                .first()
+                .map(|ledger_info| ledger_info.ledger_info().epoch());
+            if first_epoch != Some(start_epoch) {
+                return None; // The cached ledger infos don't cover the start epoch
+            }
+        }
+        Some(cached_ledger_infos)
+    }
+
+    /// Returns the cached ledger infos for exactly the given epoch range (if
+    /// any), updating the recency queue. If the ledger infos no longer match
+    /// the hash computed at insert time (i.e., the entry is corrupted), the
+    /// entry is removed and treated as a miss.
+    fn get_entry(&mut self, epoch_range: &EpochRange) -> Option<CachedLedgerInfos> {
+        let cache_entry = self.entries.get(epoch_range)?;
+        if let Some(payload_hash) = cache_entry.payload_hash {
+            let ledger_infos = &cache_entry.cached_ledger_infos.ledger_infos;
+            let is_corrupted = hash_payload(ledger_infos).map_or(true, |hash| hash != payload_hash);
+            if is_corrupted {
+                error!(
+                    (LogSchema::new(LogEntry::StorageServiceResponse)
+                        .event(LogEvent::CacheIntegrityMismatch)
+                        .peer(&cache_entry.cached_ledger_infos.peer)
+                        .message(&format!(
+                            "The cached epoch ending ledger infos for epochs {:?} are corrupted! \
+                            Removing the entry and treating it as a miss.",
+                            epoch_range
+                        )))
+                );
+                self.remove(epoch_range);
+                return None;
+            }
+        }
+
+        let cached_ledger_infos = cache_entry.cached_ledger_infos.clone();
+        self.mark_recently_used(epoch_range);
+        Some(cached_ledger_infos)
+    }
+
+    /// Returns the cached ledger infos for exactly the given epoch range (if
+    /// any), without verifying their integrity or updating the recency queue.
+    #[cfg(test)]
+    pub fn get_mut_unchecked(
+        &mut self,
+        epoch_range: &EpochRange,
+    ) -> Option<&mut CachedLedgerInfos> {
+        self.entries
+            .get_mut(epoch_range)
+            .map(|cache_entry| &mut cache_entry.cached_ledger_infos)
+    }
+
+    /// Inserts the ledger infos (keyed by the epochs they cover), evicting
+    /// the least recently used entry if the cache is full. Ledger infos that
+    /// don't cover a contiguous range of epochs are not cached.
+    pub fn insert(&mut self, cached_ledger_infos: CachedLedgerInfos) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let epoch_range = match cached_ledger_infos.get_epoch_range() {
+            Some(epoch_range) => epoch_range,
+            None => return,
+        };
+
+        let payload_hash = if self.verify_integrity {
+            match hash_payload(&cached_ledger_infos.ledger_infos) {
+                Ok(payload_hash) => Some(payload_hash),
+                Err(error) => {
+                    warn!(
+                        (LogSchema::new(LogEntry::StorageServiceResponse)
+                            .event(LogEvent::CacheIntegrityMismatch)
+                            .peer(&cached_ledger_infos.peer)
+                            .message(&format!(
+                                "Unable to hash the epoch ending ledger infos for epochs {:?}! \
+                                The ledger infos won't be cached. Error: {:?}",
+                                epoch_range, error
+                            )))
+                    );
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        let cache_entry = CacheEntry {
+            cached_ledger_infos,
+            payload_hash,
+        };
+        if self.entries.insert(epoch_range, cache_entry).is_some() {
+            self.mark_recently_used(&epoch_range);
+            return;
+        }
+        self.recency_queue.push_back(epoch_range);
+
+        while self.entries.len() > self.max_entries {
+            match self.recency_queue.pop_front() {
+                Some(evicted_range) => {
+                    let _ = self.entries.remove(&evicted_range);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Removes the entry for the given epoch range (if any)
+    pub fn remove(&mut self, epoch_range: &EpochRange) {
+        if self.entries.remove(epoch_range).is_some() {
+            self.recency_queue.retain(|range| range != epoch_range);
+        }
+    }
+
+    /// Removes the entry for the given epoch range, along with any entries
+    /// whose ranges contain it (i.e., entries that could have served it).
+    pub fn remove_containing(&mut self, epoch_range: &EpochRange) {
+        let containing_ranges: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|cached_range| range_contains(cached_range, epoch_range))
+            .copied()
+            .collect();
+        for cached_range in containing_ranges {
+            self.remove(&cached_range);
+        }
+    }
+
+    /// Returns the estimated memory (in bytes) used by the cache entries
+    pub fn memory_usage(&self) -> u64 {
+        let entry_size = size_of::<(EpochRange, CacheEntry)>() + size_of::<EpochRange>();
+        self.entries
+            .values()
+            .map(|cache_entry| {
+                let ledger_infos_size: usize = cache_entry
+                    .cached_ledger_infos
+                    .ledger_infos
+                    .iter()
+                    .map(|ledger_info| bcs::serialized_size(ledger_info).unwrap_or(0))
+                    .sum();
+                (entry_size + ledger_infos_size) as u64
+            })
+            .sum()
+    }
+
+    /// Returns the cached range that should serve the given epoch range: the
+    /// range itself (if cached), otherwise the most recently used cached range
+    /// that contains it.
+    fn find_containing_range(&self, epoch_range: &EpochRange) -> Option<EpochRange> {
+        if self.entries.contains_key(epoch_range) {
+            return Some(*epoch_range);
+        }
+        self.recency_queue
+            .iter()
+            .rev()
+            .find(|cached_range| range_contains(cached_range, epoch_range))
+            .copied()
+    }
+
+    /// Moves the given epoch range to the back of the recency queue
+    fn mark_recently_used(&mut self, epoch_range: &EpochRange) {
+        self.recency_queue.retain(|range| range != epoch_range);
+        self.recency_queue.push_back(*epoch_range);
+    }
+}
+
+/// Counts the requests served from the client-side caches (i.e., cached
+/// ledger infos or coalesced responses) and the requests sent to the network,
+/// so that the cache hit rate can be reported.
+#[derive(Debug, Default)]
+pub(crate) struct CacheHitTracker {
+    num_cache_hits: u64,
+    num_network_requests: u64,
+}
+
+impl CacheHitTracker {
+    /// Records a request served from a cache
+    pub fn record_cache_hit(&mut self) {
+        self.num_cache_hits = self.num_cache_hits.saturating_add(1);
+    }
+
+    /// Records a request sent to the network
+    pub fn record_network_request(&mut self) {
+        self.num_network_requests = self.num_network_requests.saturating_add(1);
+    }
+
+    /// Returns the fraction of requests served from a cache (zero if no
+    /// requests were recorded)
+    pub fn cache_hit_rate(&self) -> f64 {
+        let num_requests = self.num_cache_hits + self.num_network_requests;
+        if num_requests == 0 {
+            return 0.0;
+        }
+        self.num_cache_hits as f64 / num_requests as f64
+    }
+
+    /// Resets the counters (e.g., to start a new measurement window)
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Returns the hash of the given payload (used to detect corrupted cache
+/// entries and shared responses). Returns an error if the payload can't be
+/// serialized (so that every payload has a hash it can be verified against).
+pub(crate) fn hash_payload<T: Serialize + ?Sized>(payload: &T) -> Result<HashValue, bcs::Error> {
+    Ok(HashValue::sha3_256_of(&bcs::to_bytes(payload)?))
+}
+
+/// Returns true iff the outer epoch range contains the inner epoch range
+fn range_contains(outer_range: &EpochRange, inner_range: &EpochRange) -> bool {
+    outer_range.0 <= inner_range.0 && inner_range.1 <= outer_range.1
+}
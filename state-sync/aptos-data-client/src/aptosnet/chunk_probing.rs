@@ -0,0 +1,80 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cmp::min;
+
+/// The factor by which the probe chunk size grows after each successful probe
+const PROBE_GROWTH_FACTOR: u64 = 2;
+
+/// Discovers the transaction chunk size that the network can safely serve, by
+/// starting with small chunks and growing the chunk size after each successful
+/// probe (i.e., a request for a full chunk). Probing stops at the first failed
+/// probe (or once the max chunk size is reached), and the largest successful
+/// chunk size is recorded as the safe chunk size.
+#[derive(Debug)]
+pub(crate) struct ChunkSizeProber {
+    probe_chunk_size: u64,
+    max_chunk_size: u64,
+    last_successful_chunk_size: Option<u64>,
+    safe_chunk_size: Option<u64>, // Only set once probing has completed
+}
+
+impl ChunkSizeProber {
+    pub fn new(initial_chunk_size: u64, max_chunk_size: u64) -> Self {
+        Self {
+            probe_chunk_size: min(initial_chunk_size, max_chunk_size),
+            max_chunk_size,
+            last_successful_chunk_size: None,
+            safe_chunk_size: None,
+        }
+    }
+
+    /// Returns the chunk size that should currently be used
+    pub fn effective_chunk_size(&self) -> u64 {
+        self.safe_chunk_size.unwrap_or(self.probe_chunk_size)
+    }
+
+    /// Returns the discovered safe chunk size (if probing has completed)
+    pub fn safe_chunk_size(&self) -> Option<u64> {
+        self.safe_chunk_size
+    }
+
+    /// Records a successful request for the given chunk size. Returns true iff
+    /// the effective chunk size changed.
+    pub fn record_success(&mut self, chunk_size: u64) -> bool {
+        if !self.is_probe(chunk_size) {
+            return false;
+        }
+
+        self.last_successful_chunk_size = Some(self.probe_chunk_size);
+        if self.probe_chunk_size >= self.max_chunk_size {
+            self.safe_chunk_size = Some(self.max_chunk_size);
+            return false;
+        }
+        self.probe_chunk_size = min(
+            self.probe_chunk_size.saturating_mul(PROBE_GROWTH_FACTOR),
+            self.max_chunk_size,
+        );
+        true
+    }
+
+    /// Records a failed request for the given chunk size. Returns true iff
+    /// the effective chunk size changed.
+    pub fn record_failure(&mut self, chunk_size: u64) -> bool {
+        if !self.is_probe(chunk_size) {
+            return false;
+        }
+
+        let safe_chunk_size = self
+            .last_successful_chunk_size
+            .unwrap_or(self.probe_chunk_size);
+        self.safe_chunk_size = Some(safe_chunk_size);
+        safe_chunk_size != self.probe_chunk_size
+    }
+
+    /// Returns true iff a request for the given chunk size probes the current
+    /// probe chunk size (i.e., probing is ongoing and the chunk is full).
+    fn is_probe(&self, chunk_size: u64) -> bool {
+        self.safe_chunk_size.is_none() && chunk_size >= self.probe_chunk_size
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::aptosnet::elapsed_since;
+use std::time::{Duration, Instant};
+
+/// The state of the circuit breaker
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CircuitState {
+    Closed,
+    Open(Instant), // The time at which the circuit was opened
+    HalfOpen,
+}
+
+/// Halts all requests after too many consecutive failures (e.g., when the
+/// whole network is degraded), so that peers aren't hammered (and their scores
+/// aren't driven down) while the network recovers. Once opened, the circuit
+/// stays open for the cooldown, after which it half-opens and lets requests
+/// through again. The circuit closes on the first success, and re-opens on the
+/// first failure while half-open.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u64, // Zero disables the circuit breaker
+    failure_window: Duration,
+    cooldown: Duration,
+    consecutive_failures: u64,
+    first_failure_time: Option<Instant>, // The time of the first failure in the streak
+    state: CircuitState,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u64, failure_window: Duration, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            failure_window,
+            cooldown,
+            consecutive_failures: 0,
+            first_failure_time: None,
+            state: CircuitState::Closed,
+        }
+    }
+
+    /// Returns the time remaining until the circuit half-opens (if the circuit
+    /// is currently open). Otherwise, requests are allowed, and None is returned.
+    pub fn remaining_cooldown(&mut self, time_now: Instant) -> Option<Duration> {
+        if let CircuitState::Open(open_time) = self.state {
+            let time_open = elapsed_since(time_now, open_time);
+            if time_open < self.cooldown {
+                return Some(self.cooldown - time_open);
+            }
+            self.state = CircuitState::HalfOpen;
+        }
+        None
+    }
+
+    /// Records a successful request. Returns true iff the circuit was closed
+    /// (i.e., it was previously open or half-open).
+    pub fn record_success(&mut self) -> bool {
+        self.consecutive_failures = 0;
+        self.first_failure_time = None;
+        let was_closed = self.state == CircuitState::Closed;
+        self.state = CircuitState::Closed;
+        !was_closed
+    }
+
+    /// Records a failed request. Returns true iff the circuit was opened.
+    pub fn record_failure(&mut self, time_now: Instant) -> bool {
+        if self.failure_threshold == 0 {
+            return false; // The circuit breaker is disabled
+        }
+
+        match self.state {
+            CircuitState::Open(_) => false, // The request was sent before the circuit opened
+            CircuitState::HalfOpen => self.open(time_now),
+            CircuitState::Closed => {
+                // Start a new streak if the failure window has elapsed
+                let window_elapsed = self.first_failure_time.map_or(true, |first_failure_time| {
+                    elapsed_since(time_now, first_failure_time) > self.failure_window
+                });
+                if window_elapsed {
+                    self.consecutive_failures = 0;
+                    self.first_failure_time = Some(time_now);
+                }
+
+                self.consecutive_failures += 1;
+                if self.consecutive_failures >= self.failure_threshold {
+                    self.open(time_now)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Opens the circuit (and resets the failure streak)
+    fn open(&mut self, time_now: Instant) -> bool {
+        self.consecutive_failures = 0;
+        self.first_failure_time = None;
+        self.state = CircuitState::Open(time_now);
+        true
+    }
+}
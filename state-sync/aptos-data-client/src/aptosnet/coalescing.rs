@@ -0,0 +1,163 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    aptosnet::cache::hash_payload, RequestOptions, RequestPriority, ResponseCallback,
+    ResponseError, ResponseQuality, Result, RetryPolicy,
+};
+use aptos_config::{config::PeerSelectionPolicy, network_id::PeerNetworkId};
+use aptos_crypto::HashValue;
+use aptos_infallible::Mutex;
+use futures::future::{BoxFuture, Shared};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use storage_service_types::{StorageServiceRequest, StorageServiceResponse};
+
+/// The shared result of a coalesced request
+pub(crate) type CoalescedResponse = Shared<BoxFuture<'static, Result<SharedResponse>>>;
+
+/// The key of a coalesced request. This contains the request and the request
+/// options that affect how the request is sent, so that requests only attach
+/// to requests that would have been sent in the same way.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct CoalescingKey {
+    request: StorageServiceRequest,
+    prefer_archival: bool,
+    priority: RequestPriority,
+    retry_policy: Option<RetryPolicy>,
+    selection_policy_override: Option<PeerSelectionPolicy>,
+    timeout_override: Option<Duration>,
+}
+
+impl CoalescingKey {
+    pub fn new(request: StorageServiceRequest, request_options: &RequestOptions) -> Self {
+        Self {
+            request,
+            prefer_archival: request_options.prefer_archival,
+            priority: request_options.priority,
+            retry_policy: request_options.retry_policy,
+            selection_policy_override: request_options.selection_policy_override,
+            timeout_override: request_options.timeout_override,
+        }
+    }
+
+    /// Returns the request identified by this key
+    pub fn request(&self) -> &StorageServiceRequest {
+        &self.request
+    }
+}
+
+/// The response to a coalesced request, shared by all requests that attached
+/// to it (i.e., the peer that serviced the request, the response payload, the
+/// hash of the payload computed when the response was received (if integrity
+/// verification is enabled) and the single callback for the response).
+#[derive(Clone, Debug)]
+pub(crate) struct SharedResponse {
+    pub peer: PeerNetworkId,
+    pub payload: StorageServiceResponse,
+    pub payload_hash: Option<HashValue>,
+    pub response_callback: SharedResponseCallback,
+}
+
+impl SharedResponse {
+    pub fn new(
+        peer: PeerNetworkId,
+        payload: StorageServiceResponse,
+        payload_hash: Option<HashValue>,
+        response_callback: Box<dyn ResponseCallback>,
+    ) -> Self {
+        Self {
+            peer,
+            payload,
+            payload_hash,
+            response_callback: SharedResponseCallback::new(response_callback),
+        }
+    }
+
+    /// Returns true iff the payload no longer matches the hash computed when
+    /// the response was received (i.e., the shared payload is corrupted).
+    /// Payloads without a hash are never considered corrupted.
+    pub fn is_corrupted(&self) -> bool {
+        match self.payload_hash {
+            Some(payload_hash) => {
+                hash_payload(&self.payload).map_or(true, |hash| hash != payload_hash)
+            }
+            None => false,
+        }
+    }
+}
+
+/// A request that identical requests can attach to (instead of sending
+/// their own RPCs).
+pub(crate) struct CoalescedRequest {
+    pub start_time: Instant, // The time at which the first request was sent
+    pub response: CoalescedResponse,
+}
+
+impl CoalescedRequest {
+    pub fn new(start_time: Instant, response: CoalescedResponse) -> Self {
+        Self {
+            start_time,
+            response,
+        }
+    }
+
+    /// Returns true iff identical requests can no longer attach to this
+    /// request. This is the case if the request has completed and either: (i)
+    /// the request failed; or (ii) the coalescing window has elapsed.
+    pub fn is_expired(&self, time_now: Instant, coalesce_window: Duration) -> bool {
+        match self.response.peek() {
+            None => false, // The request is still in-flight
+            Some(Err(_)) => true,
+            Some(Ok(_)) => time_now >= self.start_time + coalesce_window,
+        }
+    }
+}
+
+impl fmt::Debug for CoalescedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CoalescedRequest")
+            .field("start_time", &self.start_time)
+            .field("completed", &self.response.peek().is_some())
+            .finish()
+    }
+}
+
+/// A response callback shared by all requests that attached to the same
+/// coalesced request. The response was only sent once, so only the first
+/// notification is forwarded (i.e., the peer is only penalized or rewarded
+/// once, regardless of the number of requests that received the response).
+#[derive(Clone, Debug)]
+pub(crate) struct SharedResponseCallback {
+    response_callback: Arc<Mutex<Option<Box<dyn ResponseCallback>>>>,
+}
+
+impl SharedResponseCallback {
+    pub fn new(response_callback: Box<dyn ResponseCallback>) -> Self {
+        Self {
+            response_callback: Arc::new(Mutex::new(Some(response_callback))),
+        }
+    }
+
+    /// Takes the inner callback (if no notification has been forwarded yet)
+    fn take(&self) -> Option<Box<dyn ResponseCallback>> {
+        self.response_callback.lock().take()
+    }
+}
+
+impl ResponseCallback for SharedResponseCallback {
+    fn notify_bad_response(&self, error: ResponseError) {
+        if let Some(response_callback) = self.take() {
+            response_callback.notify_bad_response(error);
+        }
+    }
+
+    fn notify_good_response(&self, quality: ResponseQuality) {
+        if let Some(response_callback) = self.take() {
+            response_callback.notify_good_response(quality);
+        }
+    }
+}
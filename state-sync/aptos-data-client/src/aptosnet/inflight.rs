@@ -0,0 +1,116 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::ResponseId;
+use aptos_config::network_id::PeerNetworkId;
+use aptos_infallible::Mutex;
+use futures::future::AbortHandle;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Semaphore;
+
+/// Tracks the requests that are currently in-flight to each peer, so that
+/// they can be cancelled (e.g., when the peer is banned).
+#[derive(Debug, Default)]
+pub(crate) struct InflightRequests {
+    peer_to_requests: HashMap<PeerNetworkId, HashMap<ResponseId, AbortHandle>>,
+}
+
+impl InflightRequests {
+    /// Records an in-flight request to the peer (that can be aborted using
+    /// the given handle).
+    pub fn insert(&mut self, peer: PeerNetworkId, id: ResponseId, abort_handle: AbortHandle) {
+        self.peer_to_requests
+            .entry(peer)
+            .or_default()
+            .insert(id, abort_handle);
+    }
+
+    /// Removes the in-flight request to the peer (e.g., once it completes)
+    pub fn remove(&mut self, peer: &PeerNetworkId, id: ResponseId) {
+        if let Some(requests) = self.peer_to_requests.get_mut(peer) {
+            requests.remove(&id);
+            if requests.is_empty() {
+                self.peer_to_requests.remove(peer);
+            }
+        }
+    }
+
+    /// Cancels all in-flight requests to the peer and returns the number of
+    /// cancelled requests.
+    pub fn cancel_peer_requests(&mut self, peer: &PeerNetworkId) -> usize {
+        let requests = self.peer_to_requests.remove(peer).unwrap_or_default();
+        for abort_handle in requests.values() {
+            abort_handle.abort();
+        }
+        requests.len()
+    }
+}
+
+/// Tracks an in-flight request until the guard is dropped (i.e., once the
+/// request completes, or if the request is dropped before it completes).
+pub(crate) struct InflightRequestGuard {
+    inflight_requests: Arc<Mutex<InflightRequests>>,
+    peer: PeerNetworkId,
+    id: ResponseId,
+}
+
+impl InflightRequestGuard {
+    pub fn new(
+        inflight_requests: Arc<Mutex<InflightRequests>>,
+        peer: PeerNetworkId,
+        id: ResponseId,
+        abort_handle: AbortHandle,
+    ) -> Self {
+        inflight_requests.lock().insert(peer, id, abort_handle);
+        Self {
+            inflight_requests,
+            peer,
+            id,
+        }
+    }
+}
+
+impl Drop for InflightRequestGuard {
+    fn drop(&mut self) {
+        self.inflight_requests.lock().remove(&self.peer, self.id);
+    }
+}
+
+/// Limits the number of concurrent in-flight requests to each peer, using a
+/// semaphore (with a permit per in-flight request) for each peer.
+#[derive(Debug)]
+pub(crate) struct InflightLimiter {
+    max_in_flight_per_peer: u64, // Zero disables the limit
+    peer_semaphores: HashMap<PeerNetworkId, Arc<Semaphore>>,
+}
+
+impl InflightLimiter {
+    pub fn new(max_in_flight_per_peer: u64) -> Self {
+        Self {
+            max_in_flight_per_peer,
+            peer_semaphores: HashMap::new(),
+        }
+    }
+
+    /// Returns true iff the peer already has the maximum number of in-flight
+    /// requests (i.e., no permits are available).
+    pub fn is_saturated(&self, peer: &PeerNetworkId) -> bool {
+        self.peer_semaphores
+            .get(peer)
+            .map_or(false, |semaphore| semaphore.available_permits() == 0)
+    }
+
+    /// Returns the semaphore limiting the in-flight requests to the peer, or
+    /// `None` if the number of in-flight requests isn't limited.
+    pub fn get_peer_semaphore(&mut self, peer: PeerNetworkId) -> Option<Arc<Semaphore>> {
+        if self.max_in_flight_per_peer == 0 {
+            return None;
+        }
+        let max_in_flight_per_peer = self.max_in_flight_per_peer as usize;
+        let semaphore = self
+            .peer_semaphores
+            .entry(peer)
+            .or_insert_with(|| Arc::new(Semaphore::new(max_in_flight_per_peer)));
+        Some(semaphore.clone())
+    }
+}
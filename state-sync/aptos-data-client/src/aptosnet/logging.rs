@@ -1,7 +1,7 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::Error;
+use crate::{Error, TraceContext};
 use aptos_config::network_id::PeerNetworkId;
 use aptos_logger::Schema;
 use serde::Serialize;
@@ -20,6 +20,8 @@ pub struct LogSchema<'a> {
     request_data: Option<&'a StorageServiceRequest>,
     request_id: Option<u64>,
     request_type: Option<&'a str>,
+    #[schema(debug)]
+    trace_context: Option<&'a TraceContext>,
 }
 
 impl<'a> LogSchema<'a> {
@@ -33,6 +35,7 @@ impl<'a> LogSchema<'a> {
             request_data: None,
             request_id: None,
             request_type: None,
+            trace_context: None,
         }
     }
 }
@@ -46,18 +49,45 @@ pub enum LogEntry {
     StorageServiceResponse,
     StorageSummaryRequest,
     StorageSummaryResponse,
+    TimeService,
 }
 
 #[derive(Clone, Copy, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum LogEvent {
     AggregateSummary,
+    CacheIntegrityMismatch,
+    CancelledRequestReroute,
+    CircuitBreakerClosed,
+    CircuitBreakerOpened,
+    ConnectionProbeFailed,
+    CreateRequestSpan,
+    CrossCheckMismatch,
+    HedgedRequest,
+    InconsistentSummary,
+    InflightRequestsCancelled,
+    InvalidPeerScore,
+    LateResponse,
+    LowPeerDiversity,
+    MaintenanceMode,
+    MalformedResponseRetry,
+    MissedPollRounds,
     NoPeersToPoll,
+    NonMonotonicTime,
     PeerIgnored,
     PeerNoLongerIgnored,
     PeerPollingError,
     PeerSelectionError,
+    PersistedSummaryLoaded,
+    ProofAnchorMismatch,
+    ProtocolVersionFetchFailed,
+    PollingExclusion,
+    RecoveryProbeFailed,
+    RegularPeerFallback,
     ResponseError,
     ResponseSuccess,
     SendRequest,
+    SummaryPersistenceError,
+    TransientErrorRetry,
+    VerificationSkipped,
 }
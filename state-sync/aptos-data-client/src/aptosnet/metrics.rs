@@ -3,13 +3,27 @@
 
 use aptos_crypto::_once_cell::sync::Lazy;
 use aptos_metrics::{
-    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramTimer,
-    HistogramVec, IntCounterVec, IntGaugeVec,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
 };
+use std::time::Duration;
 
 /// The special label TOTAL_COUNT stores the sum of all values in the counter.
 pub const TOTAL_COUNT_LABEL: &str = "TOTAL_COUNT";
 
+/// The buckets (in bytes) of the response size histogram (1 KiB to 1 GiB)
+const RESPONSE_SIZE_BUCKETS: &[f64] = &[
+    1_024.0,
+    16_384.0,
+    262_144.0,
+    1_048_576.0,
+    4_194_304.0,
+    16_777_216.0,
+    67_108_864.0,
+    268_435_456.0,
+    1_073_741_824.0,
+];
+
 /// Counter for tracking sent requests
 pub static SENT_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -50,6 +64,28 @@ pub static REQUEST_LATENCIES: Lazy<HistogramVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Histogram for tracking RPC latencies (in seconds, as measured by the time
+/// service) by request type and network
+pub static RPC_LATENCIES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_data_client_rpc_latencies",
+        "Histogram of the RPC latencies (in seconds) by request type and network",
+        &["request_type", "network"]
+    )
+    .unwrap()
+});
+
+/// Histogram for tracking response sizes (in bytes)
+pub static RESPONSE_SIZES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_data_client_response_sizes",
+        "Histogram of the response sizes (in bytes)",
+        &["request_type"],
+        RESPONSE_SIZE_BUCKETS.to_vec()
+    )
+    .unwrap()
+});
+
 /// Gauge for the highest advertised data
 pub static HIGHEST_ADVERTISED_DATA: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
@@ -80,8 +116,18 @@ pub static OPTIMAL_CHUNK_SIZES: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Counter for tracking low peer diversity warnings
+pub static LOW_PEER_DIVERSITY_WARNINGS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_data_client_low_peer_diversity_warnings",
+        "Counters related to low peer diversity warnings"
+    )
+    .unwrap()
+});
+
 /// An enum representing the various types of data that can be
 /// fetched via the data client.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum DataType {
     AccountStates,
     LedgerInfos,
@@ -120,6 +166,19 @@ pub fn set_gauge(counter: &Lazy<IntGaugeVec>, label: String, value: u64) {
     counter.with_label_values(&[&label]).set(value as i64);
 }
 
+/// Observes the given value in the histogram with the provided label values.
+pub fn observe_value(histogram: &Lazy<HistogramVec>, label: String, value: u64) {
+    histogram.with_label_values(&[&label]).observe(value as f64);
+}
+
+/// Observes the given duration (in seconds) in the histogram with the
+/// provided label values.
+pub fn observe_duration(histogram: &Lazy<HistogramVec>, labels: &[&str], duration: Duration) {
+    histogram
+        .with_label_values(labels)
+        .observe(duration.as_secs_f64());
+}
+
 /// Starts the timer for the provided histogram and label values.
 pub fn start_timer(histogram: &Lazy<HistogramVec>, label: String) -> HistogramTimer {
     histogram.with_label_values(&[&label]).start_timer()
@@ -3,52 +3,175 @@
 
 use crate::{
     aptosnet::{
+        cache::{hash_payload, CacheHitTracker, CachedLedgerInfos, EpochEndingLedgerInfoCache},
+        chunk_probing::ChunkSizeProber,
+        circuit_breaker::CircuitBreaker,
+        coalescing::{CoalescedRequest, CoalescingKey, SharedResponse},
+        inflight::{InflightLimiter, InflightRequestGuard, InflightRequests},
         logging::{LogEntry, LogEvent, LogSchema},
-        metrics::{increment_counter, start_timer, DataType},
-        state::{ErrorType, PeerStates},
+        metrics::{increment_counter, observe_duration, observe_value, start_timer},
+        persistence::{load_global_summary, persist_global_summary},
+        rate_limiting::PeerRateLimiter,
+        request_rate::RequestRateTracker,
+        selection_latency::SelectionLatencyTracker,
+        serviceability::RangeWatcher,
+        state::{coalesce_ranges, ErrorType, PeerStates, RequestOutcome},
+        verification::{
+            verify_epoch_change_continuity, verify_epoch_ending_coverage,
+            verify_epoch_ending_ledger_infos, verify_inclusion_proofs, verify_recovery_probe,
+            verify_size_limited_transactions, verify_summary_consistency,
+            verify_target_ledger_info, verify_transaction_gap_coverage,
+            verify_transaction_proof_anchor, verify_transactions_from_sender,
+            verify_transactions_with_event,
+        },
     },
-    AptosDataClient, Error, GlobalDataSummary, Response, ResponseCallback, ResponseContext,
-    ResponseError, ResponseId, Result,
+    AdvertisedData, AptosDataClient, CacheMemoryStats, Error, FilteredTransactions,
+    GlobalDataSummary, InvalidRequestReason, LatencyStats, OutcomeStats, PeerExclusionReason,
+    PeerState, PeerTier, RequestCompletionEvent, RequestOptions, RequestPriority, Response,
+    ResponseCallback, ResponseContext, ResponseError, ResponseId, ResponseQuality, Result,
+    ServiceabilityEvent, SyncPhase, TraceContext, VerificationLevel,
 };
 use aptos_config::{
-    config::{AptosDataClientConfig, StorageServiceConfig},
-    network_id::PeerNetworkId,
+    config::{
+        AptosDataClientConfig, PeerSelectionPolicy, PeerSelectionStrategy, StorageServiceConfig,
+    },
+    network_id::{NetworkId, PeerNetworkId},
 };
 use aptos_id_generator::{IdGenerator, U64IdGenerator};
-use aptos_infallible::RwLock;
+use aptos_infallible::{Mutex, RwLock};
 use aptos_logger::prelude::*;
 use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
+    account_address::AccountAddress,
     epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
     ledger_info::LedgerInfoWithSignatures,
-    state_store::state_value::StateValueChunkWithProof,
-    transaction::{TransactionListWithProof, TransactionOutputListWithProof, Version},
+    proof::{AccumulatorConsistencyProof, TransactionAccumulatorProof},
+    state_proof::StateProof,
+    state_store::{
+        state_key::StateKey,
+        state_key_prefix::StateKeyPrefix,
+        state_value::{StateValue, StateValueChunkWithProof},
+    },
+    transaction::{
+        Transaction, TransactionListWithProof, TransactionOutputListWithProof,
+        TransactionWithProof, Version,
+    },
+    PeerId,
 };
 use async_trait::async_trait;
-use futures::StreamExt;
+use futures::{
+    channel::mpsc,
+    future::{self, join_all, AbortHandle, Abortable, Aborted, Either},
+    stream::{self, FuturesUnordered},
+    FutureExt, Stream, StreamExt,
+};
+use move_core_types::language_storage::TypeTag;
 use network::{
     application::interface::NetworkInterface,
     protocols::{rpc::error::RpcError, wire::handshake::v1::ProtocolId},
 };
 use rand::seq::SliceRandom;
-use std::{convert::TryFrom, fmt, sync::Arc, time::Duration};
+use std::{
+    cmp::{max, min, Ordering},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    fmt,
+    mem::{self, size_of},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_service_client::StorageServiceClient;
 use storage_service_types::{
-    AccountStatesChunkWithProofRequest, Epoch, EpochEndingLedgerInfoRequest, StorageServerSummary,
-    StorageServiceRequest, StorageServiceResponse, TransactionOutputsWithProofRequest,
-    TransactionsWithProofRequest,
+    strip_events, strip_write_sets, AccountStatesByPrefixRequest,
+    AccountStatesChunkWithProofRequest, CompleteDataRange, CompressedResponseRequest, Epoch,
+    EpochEndingLedgerInfoRequest, ServerProtocolVersion, SizeLimitedTransactions,
+    StorageServerSummary, StorageServiceError, StorageServiceRequest, StorageServiceResponse,
+    TransactionInclusionProofsRequest, TransactionOutputsWithProofRequest,
+    TransactionsFromSenderRequest, TransactionsWithEventRequest, TransactionsWithProofRequest,
+    TransactionsWithSizeLimitRequest, MAX_DECOMPRESSED_RESPONSE_BYTES,
 };
+use tokio::sync::{broadcast, watch};
 
+mod cache;
+mod chunk_probing;
+mod circuit_breaker;
+mod coalescing;
+mod inflight;
 mod logging;
 mod metrics;
+mod persistence;
+mod rate_limiting;
+mod request_rate;
+mod selection_latency;
+mod serviceability;
 mod state;
 #[cfg(test)]
 mod tests;
+mod verification;
+
+pub use metrics::DataType;
 
 // Useful constants for the Aptos Data Client
 const GLOBAL_DATA_LOG_FREQ_SECS: u64 = 5;
 const GLOBAL_DATA_METRIC_FREQ_SECS: u64 = 1;
 const POLLER_ERROR_LOG_FREQ_SECS: u64 = 1;
+const NON_MONOTONIC_TIME_LOG_FREQ_SECS: u64 = 5;
+const REQUEST_RATE_WINDOW_SECS: u64 = 10;
+const REQUEST_EVENT_CHANNEL_SIZE: usize = 1_000; // Lagging subscribers miss the oldest events
+
+/// A source of operator-supplied peer hints. Hinted peers are preferred when
+/// polling for data summaries (once they connect), but the hints never bypass
+/// the usual connection requirements.
+pub trait PeerHintSource: fmt::Debug + Send + Sync + 'static {
+    /// Returns the set of peers that should be prioritized for polling
+    fn get_peer_hints(&self) -> Vec<PeerNetworkId>;
+}
+
+/// A source of the latest trusted ledger info (e.g., the latest ledger info
+/// committed to local storage), used to verify the data served by peers.
+pub trait TrustedLedgerInfoSource: fmt::Debug + Send + Sync + 'static {
+    /// Returns the latest trusted ledger info (if any)
+    fn get_latest_trusted_ledger_info(&self) -> Option<LedgerInfoWithSignatures>;
+}
+
+/// A strategy for adjusting peer scores before they are used for peer selection
+/// and ignore threshold comparisons. Non-finite scores are treated as neutral.
+pub trait PeerScoringStrategy: fmt::Debug + Send + Sync + 'static {
+    /// Returns the adjusted score for the given peer and (internal) score
+    fn adjust_score(&self, peer: &PeerNetworkId, score: f64) -> f64;
+}
+
+/// The default peer scoring strategy, which uses the internal scores as is
+#[derive(Debug, Default)]
+pub struct DefaultPeerScoringStrategy;
+
+impl PeerScoringStrategy for DefaultPeerScoringStrategy {
+    fn adjust_score(&self, _peer: &PeerNetworkId, score: f64) -> f64 {
+        score
+    }
+}
+
+/// A resolver of the identities of peers, used to link the same peer across
+/// networks (e.g., a peer reachable on both the validator and VFN networks).
+pub trait PeerIdentityResolver: fmt::Debug + Send + Sync + 'static {
+    /// Returns the identity of the given peer (if known). Peers on different
+    /// networks with the same identity are treated as the same peer.
+    fn resolve_identity(&self, peer: &PeerNetworkId) -> Option<PeerId>;
+}
+
+/// A listener that is notified whenever a peer is banned (i.e., its score drops
+/// below the ignore threshold) or restored (i.e., its score rises back above the
+/// threshold). Notifications are dispatched asynchronously (when running inside
+/// a tokio runtime), so listeners never block the scoring path.
+pub trait PeerBanListener: fmt::Debug + Send + Sync + 'static {
+    /// Called when the peer is banned, with the peer's new score
+    fn on_peer_banned(&self, peer: PeerNetworkId, score: f64);
+
+    /// Called when the peer is restored, with the peer's new score
+    fn on_peer_restored(&self, peer: PeerNetworkId, score: f64);
+}
 
 /// An [`AptosDataClient`] that fulfills requests from remote peers' Storage Service
 /// over AptosNet.
@@ -80,6 +203,59 @@ pub struct AptosNetDataClient {
     global_summary_cache: Arc<RwLock<GlobalDataSummary>>,
     /// Used for generating the next request/response id.
     response_id_generator: Arc<U64IdGenerator>,
+    /// An optional source of peers to prioritize when polling.
+    peer_hint_source: Arc<RwLock<Option<Arc<dyn PeerHintSource>>>>,
+    /// An optional source of the latest trusted ledger info (used to verify
+    /// recovery probes).
+    trusted_ledger_info_source: Arc<RwLock<Option<Arc<dyn TrustedLedgerInfoSource>>>>,
+    /// The highest synced version advertised by the network, and the time at
+    /// which it was last seen to advance.
+    highest_synced_version: Arc<RwLock<Option<(Version, Instant)>>>,
+    /// The time service used to track elapsed time.
+    time_service: TimeService,
+    /// A cache of previously fetched epoch ending ledger infos.
+    epoch_ending_ledger_info_cache: Arc<Mutex<EpochEndingLedgerInfoCache>>,
+    /// Counts the requests served from the caches and from the network.
+    cache_hit_tracker: Arc<Mutex<CacheHitTracker>>,
+    /// The time of the last successful data (i.e., non-summary) response.
+    last_data_response_time: Arc<RwLock<Option<Instant>>>,
+    /// Whether the peer diversity is currently below the configured minimum.
+    low_peer_diversity: Arc<RwLock<bool>>,
+    /// The requests that identical requests can attach to (i.e., coalesce with).
+    coalesced_requests: Arc<Mutex<HashMap<CoalescingKey, CoalescedRequest>>>,
+    /// Tracks the requests sent over a recent window (to calculate the request rate).
+    request_rate_tracker: Arc<Mutex<RequestRateTracker>>,
+    /// The peers whose connections have been warmed (i.e., successfully probed).
+    warmed_peers: Arc<Mutex<HashSet<PeerNetworkId>>>,
+    /// Used to notify the data summary poller that a peer has connected.
+    peer_connected_sender: mpsc::Sender<()>,
+    /// The number of outstanding summary polls (when polling all peers) per network.
+    outstanding_summary_polls: Arc<Mutex<HashMap<NetworkId, u64>>>,
+    /// The number of requests routed to the peers behind each IP address.
+    ip_request_counts: Arc<Mutex<HashMap<IpAddr, u64>>>,
+    /// Tracks the latencies of recent peer selections.
+    selection_latency_tracker: Arc<Mutex<SelectionLatencyTracker>>,
+    /// Whether the client is in maintenance mode (i.e., data requests are
+    /// refused, but summary polling continues).
+    in_maintenance: Arc<RwLock<bool>>,
+    /// Probes the transaction chunk size the network can safely serve (if
+    /// chunk size probing is enabled).
+    chunk_size_prober: Arc<Mutex<Option<ChunkSizeProber>>>,
+    /// Limits the rate of requests sent to each peer (per data type).
+    rate_limiter: Arc<Mutex<PeerRateLimiter>>,
+    /// The subscribers notified whenever the advertised data changes.
+    summary_change_senders: Arc<Mutex<Vec<mpsc::UnboundedSender<GlobalDataSummary>>>>,
+    /// The watchers notified whenever a watched range becomes (un)serviceable.
+    range_watchers: Arc<Mutex<Vec<RangeWatcher>>>,
+    /// The requests currently in-flight to each peer (only tracked if they're
+    /// cancelled when the peer is banned).
+    inflight_requests: Arc<Mutex<InflightRequests>>,
+    /// Limits the number of concurrent in-flight requests to each peer.
+    inflight_limiter: Arc<Mutex<InflightLimiter>>,
+    /// Halts all requests (for a cooldown) after too many consecutive failures.
+    circuit_breaker: Arc<Mutex<CircuitBreaker>>,
+    /// Broadcasts an event to the subscribers whenever a request completes.
+    request_event_sender: broadcast::Sender<RequestCompletionEvent>,
 }
 
 impl AptosNetDataClient {
@@ -89,21 +265,322 @@ impl AptosNetDataClient {
         time_service: TimeService,
         network_client: StorageServiceClient,
     ) -> (Self, DataSummaryPoller) {
+        let (peer_connected_sender, peer_connected_receiver) = mpsc::channel(1);
+        let selection_latency_tracker = SelectionLatencyTracker::new(
+            data_client_config.monitoring.selection_latency_window as usize,
+        );
+        let epoch_ending_ledger_info_cache = EpochEndingLedgerInfoCache::new(
+            data_client_config.caching.max_epoch_ending_cache_entries as usize,
+            data_client_config.caching.verify_cache_integrity,
+        );
+        let chunk_size_prober = match data_client_config.chunk_sizes.probe_initial_size {
+            0 => None,
+            initial_chunk_size => Some(ChunkSizeProber::new(
+                initial_chunk_size,
+                storage_service_config.max_transaction_chunk_size,
+            )),
+        };
+        let rate_limiter = PeerRateLimiter::new(
+            data_client_config
+                .peer_selection
+                .max_requests_per_sec_per_peer,
+        );
+        let circuit_breaker = CircuitBreaker::new(
+            data_client_config.circuit_breaker.failure_threshold,
+            Duration::from_millis(data_client_config.circuit_breaker.failure_window_ms),
+            Duration::from_millis(data_client_config.circuit_breaker.cooldown_ms),
+        );
+        let inflight_requests = Arc::new(Mutex::new(InflightRequests::default()));
+        let inflight_limiter =
+            InflightLimiter::new(data_client_config.peer_selection.max_in_flight_per_peer);
+        let mut peer_states = PeerStates::new(storage_service_config);
+        peer_states.set_scoring_parameters(
+            data_client_config.peer_scoring.score_penalty_multiplier,
+            data_client_config.peer_scoring.score_recovery_increment,
+            data_client_config.peer_scoring.ignore_peer_threshold,
+        );
+        peer_states.set_require_data_probe_for_recovery(
+            data_client_config
+                .peer_scoring
+                .require_data_probe_for_recovery,
+        );
+        if data_client_config.cancel_inflight_on_ban {
+            peer_states.set_inflight_requests(inflight_requests.clone());
+        }
         let client = Self {
             data_client_config,
             network_client,
-            peer_states: Arc::new(RwLock::new(PeerStates::new(storage_service_config))),
+            peer_states: Arc::new(RwLock::new(peer_states)),
             global_summary_cache: Arc::new(RwLock::new(GlobalDataSummary::empty())),
             response_id_generator: Arc::new(U64IdGenerator::new()),
+            peer_hint_source: Arc::new(RwLock::new(None)),
+            trusted_ledger_info_source: Arc::new(RwLock::new(None)),
+            highest_synced_version: Arc::new(RwLock::new(None)),
+            time_service: time_service.clone(),
+            epoch_ending_ledger_info_cache: Arc::new(Mutex::new(epoch_ending_ledger_info_cache)),
+            cache_hit_tracker: Arc::new(Mutex::new(CacheHitTracker::default())),
+            last_data_response_time: Arc::new(RwLock::new(None)),
+            low_peer_diversity: Arc::new(RwLock::new(false)),
+            coalesced_requests: Arc::new(Mutex::new(HashMap::new())),
+            request_rate_tracker: Arc::new(Mutex::new(RequestRateTracker::new(
+                Duration::from_secs(REQUEST_RATE_WINDOW_SECS),
+            ))),
+            warmed_peers: Arc::new(Mutex::new(HashSet::new())),
+            peer_connected_sender,
+            outstanding_summary_polls: Arc::new(Mutex::new(HashMap::new())),
+            ip_request_counts: Arc::new(Mutex::new(HashMap::new())),
+            selection_latency_tracker: Arc::new(Mutex::new(selection_latency_tracker)),
+            in_maintenance: Arc::new(RwLock::new(false)),
+            chunk_size_prober: Arc::new(Mutex::new(chunk_size_prober)),
+            rate_limiter: Arc::new(Mutex::new(rate_limiter)),
+            summary_change_senders: Arc::new(Mutex::new(vec![])),
+            range_watchers: Arc::new(Mutex::new(vec![])),
+            inflight_requests,
+            inflight_limiter: Arc::new(Mutex::new(inflight_limiter)),
+            circuit_breaker: Arc::new(Mutex::new(circuit_breaker)),
+            request_event_sender: broadcast::channel(REQUEST_EVENT_CHANNEL_SIZE).0,
         };
+        client.warm_start_global_summary();
         let poller = DataSummaryPoller::new(
             time_service,
             client.clone(),
             Duration::from_millis(client.data_client_config.summary_poll_interval_ms),
+            Duration::from_millis(
+                client
+                    .data_client_config
+                    .summary_poller
+                    .poller_startup_delay_ms,
+            ),
+            peer_connected_receiver,
         );
         (client, poller)
     }
 
+    /// Loads the persisted global data summary (if persistence is enabled and
+    /// a summary was previously persisted). The loaded summary is only a
+    /// warm-start hint: it is replaced by the first fresh poll.
+    fn warm_start_global_summary(&self) {
+        let summary_persistence_path = match &self.data_client_config.summary_persistence.path {
+            Some(summary_persistence_path) if summary_persistence_path.exists() => {
+                summary_persistence_path
+            }
+            _ => return,
+        };
+
+        match load_global_summary(summary_persistence_path) {
+            Ok(global_data_summary) => {
+                info!(
+                    (LogSchema::new(LogEntry::DataSummaryPoller)
+                        .event(LogEvent::PersistedSummaryLoaded)
+                        .message(&format!(
+                            "Loaded the persisted global data summary: {:?}",
+                            global_data_summary
+                        )))
+                );
+                *self.global_summary_cache.write() = global_data_summary;
+            }
+            Err(error) => {
+                warn!(
+                    (LogSchema::new(LogEntry::DataSummaryPoller)
+                        .event(LogEvent::SummaryPersistenceError)
+                        .message("Unable to load the persisted global data summary!")
+                        .error(&error))
+                );
+            }
+        }
+    }
+
+    /// Persists the current global data summary to the configured path. This
+    /// is done periodically by the poller, but can also be done on demand
+    /// (e.g., before shutting down).
+    pub fn persist_global_summary(&self) -> Result<(), Error> {
+        let summary_persistence_path = self
+            .data_client_config
+            .summary_persistence
+            .path
+            .as_ref()
+            .ok_or_else(|| {
+                Error::UnexpectedErrorEncountered(
+                    "No summary persistence path is configured!".into(),
+                )
+            })?;
+        persist_global_summary(summary_persistence_path, &self.get_global_data_summary())
+    }
+
+    /// Notifies the data summary poller that a peer has connected. This wakes
+    /// the poller immediately if it is idling because there were no peers.
+    pub fn notify_peer_connected(&self) {
+        // If a notification is already pending, there's nothing more to do
+        let _ = self.peer_connected_sender.clone().try_send(());
+    }
+
+    /// Sets the source of peer hints used to prioritize summary polling
+    pub fn set_peer_hint_source(&self, peer_hint_source: Arc<dyn PeerHintSource>) {
+        *self.peer_hint_source.write() = Some(peer_hint_source);
+    }
+
+    /// Sets the source of the latest trusted ledger info, used to verify the
+    /// recovery probes of banned peers
+    pub fn set_trusted_ledger_info_source(
+        &self,
+        trusted_ledger_info_source: Arc<dyn TrustedLedgerInfoSource>,
+    ) {
+        *self.trusted_ledger_info_source.write() = Some(trusted_ledger_info_source);
+    }
+
+    /// Sets the listener notified whenever a peer is banned or restored
+    pub fn set_peer_ban_listener(&self, ban_listener: Arc<dyn PeerBanListener>) {
+        self.peer_states.write().set_ban_listener(ban_listener);
+    }
+
+    /// Sets the strategy used to adjust peer scores (replacing the default
+    /// strategy, which uses the internal scores as is)
+    pub fn set_peer_scoring_strategy(&self, scoring_strategy: Arc<dyn PeerScoringStrategy>) {
+        self.peer_states
+            .write()
+            .set_scoring_strategy(scoring_strategy);
+    }
+
+    /// Sets the resolver used to link peers with the same identity across
+    /// networks. If summary reconciliation is enabled, the summaries of linked
+    /// peers are reconciled (i.e., merged) for peer selection. Otherwise, the
+    /// resolver is ignored.
+    pub fn set_peer_identity_resolver(&self, identity_resolver: Arc<dyn PeerIdentityResolver>) {
+        if self
+            .data_client_config
+            .peer_selection
+            .reconcile_summaries_across_networks
+        {
+            self.peer_states
+                .write()
+                .set_identity_resolver(identity_resolver);
+        }
+    }
+
+    /// Returns the number of requests per second sent to the network by the
+    /// client over a recent sliding window (including summary polls).
+    pub fn current_request_rate(&self) -> f64 {
+        self.request_rate_tracker
+            .lock()
+            .request_rate(self.time_service.now())
+    }
+
+    /// Returns the (peer, data type) combinations that are currently rate
+    /// limited, along with the time until each can be requested again.
+    /// Requests for these combinations are spread to other peers.
+    pub fn active_rate_limits(&self) -> Vec<(PeerNetworkId, DataType, Duration)> {
+        self.rate_limiter
+            .lock()
+            .active_rate_limits(self.time_service.now())
+    }
+
+    /// Returns the peers currently hinted by the peer hint source (if any)
+    fn get_peer_hints(&self) -> HashSet<PeerNetworkId> {
+        self.peer_hint_source
+            .read()
+            .as_ref()
+            .map(|peer_hint_source| peer_hint_source.get_peer_hints().into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the latest trusted ledger info from the trusted ledger info
+    /// source (if any)
+    fn get_latest_trusted_ledger_info(&self) -> Option<LedgerInfoWithSignatures> {
+        self.trusted_ledger_info_source
+            .read()
+            .as_ref()
+            .and_then(|trusted_ledger_info_source| {
+                trusted_ledger_info_source.get_latest_trusted_ledger_info()
+            })
+    }
+
+    /// Sends a recovery probe to the given peer if its score has recovered
+    /// after a ban, but it must still successfully serve a data request before
+    /// it's un-banned. The probe requests the transaction at the version of the
+    /// latest trusted ledger info, and the response is verified against that
+    /// ledger info. Peers remain banned if there's no trusted ledger info, or
+    /// if they don't advertise the transaction at its version.
+    async fn probe_peer_recovery_if_required(&self, peer: PeerNetworkId) {
+        let advertised_range = {
+            let internal_peer_states = self.peer_states.read();
+            if !internal_peer_states.needs_recovery_probe(&peer) {
+                return;
+            }
+            internal_peer_states.get_advertised_range(&peer, DataType::Transactions)
+        };
+        let trusted_ledger_info = match self.get_latest_trusted_ledger_info() {
+            Some(trusted_ledger_info) => trusted_ledger_info,
+            None => return,
+        };
+        let probe_version = trusted_ledger_info.ledger_info().version();
+        if !advertised_range.map_or(false, |advertised_range| {
+            advertised_range.contains(probe_version)
+        }) {
+            return;
+        }
+
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version: probe_version,
+                start_version: probe_version,
+                end_version: probe_version,
+                include_events: false,
+            });
+        let result: Result<Response<TransactionListWithProof>> =
+            self.send_request_to_peer_and_decode(peer, request).await;
+        let error = match result {
+            Ok(response) => {
+                match verify_recovery_probe(trusted_ledger_info.ledger_info(), &response.payload) {
+                    Ok(()) => {
+                        self.peer_states.write().record_recovery_probe_success(peer);
+                        return;
+                    }
+                    Err(error) => {
+                        response
+                            .context
+                            .response_callback
+                            .notify_bad_response(error.get_response_error());
+                        Error::from(error)
+                    }
+                }
+            }
+            Err(error) => error,
+        };
+        debug!(
+            (LogSchema::new(LogEntry::PeerStates)
+                .event(LogEvent::RecoveryProbeFailed)
+                .message("The peer failed its recovery probe and remains banned")
+                .peer(&peer)
+                .error(&error))
+        );
+    }
+
+    /// Fetches the storage server protocol version run by the given peer (if
+    /// it hasn't been fetched yet). Until the version is known, the peer is
+    /// only sent the original requests (i.e., those supported by all versions).
+    async fn fetch_server_protocol_version_if_required(&self, peer: PeerNetworkId) {
+        if !self.peer_states.read().needs_server_protocol_version(&peer) {
+            return;
+        }
+
+        let result: Result<Response<ServerProtocolVersion>> = self
+            .send_request_to_peer_and_decode(peer, StorageServiceRequest::GetServerProtocolVersion)
+            .await;
+        match result {
+            Ok(response) => self
+                .peer_states
+                .write()
+                .update_server_protocol_version(peer, response.payload.protocol_version),
+            Err(error) => debug!(
+                (LogSchema::new(LogEntry::PeerStates)
+                    .event(LogEvent::ProtocolVersionFetchFailed)
+                    .message("Failed to fetch the server protocol version of the peer")
+                    .peer(&peer)
+                    .error(&error))
+            ),
+        }
+    }
+
     /// Generates a new response id
     fn next_response_id(&self) -> u64 {
         self.response_id_generator.next()
@@ -111,261 +588,3365 @@ impl AptosNetDataClient {
 
     /// Update a peer's data summary.
     fn update_summary(&self, peer: PeerNetworkId, summary: StorageServerSummary) {
-        self.peer_states.write().update_summary(peer, summary)
+        // Drop inconsistent summaries and penalize the peer (if required), as
+        // the peer is advertising data that it can't serve.
+        if self
+            .data_client_config
+            .summary_poller
+            .reject_inconsistent_summaries
+        {
+            if let Err(error) = verify_summary_consistency(&summary) {
+                warn!(
+                    (LogSchema::new(LogEntry::StorageSummaryResponse)
+                        .event(LogEvent::InconsistentSummary)
+                        .message("Dropping an inconsistent storage summary!")
+                        .error(&Error::from(error))
+                        .peer(&peer))
+                );
+                self.peer_states
+                    .write()
+                    .update_score_error(peer, ErrorType::Malicious);
+                return;
+            }
+        }
+
+        let update_time = self.time_service.now();
+        self.peer_states
+            .write()
+            .update_summary(peer, summary, update_time)
+    }
+
+    /// Returns the elapsed time since each peer's storage summary was last
+    /// refreshed. This is useful for identifying peers with stale summaries.
+    pub fn summary_ages(&self) -> HashMap<PeerNetworkId, Duration> {
+        let now = self.time_service.now();
+        self.peer_states
+            .read()
+            .summary_update_times()
+            .into_iter()
+            .map(|(peer, update_time)| (peer, elapsed_since(now, update_time)))
+            .collect()
+    }
+
+    /// Returns the (smoothed) bandwidth at which each peer serves responses,
+    /// in bytes per second. Together with latency, this helps identify peers
+    /// that respond on time but serve large payloads slowly.
+    pub fn peer_serving_bandwidth(&self) -> HashMap<PeerNetworkId, f64> {
+        self.peer_states.read().serving_bandwidths()
+    }
+
+    /// Returns a snapshot of the scoring state of each known peer (e.g., to
+    /// understand why a peer was banned). The snapshots are copies, so they
+    /// can't be used to modify the internal state.
+    pub fn get_peer_states(&self) -> HashMap<PeerNetworkId, PeerState> {
+        self.peer_states.read().peer_state_snapshots()
+    }
+
+    /// Returns the estimated memory used by the internal caches and state of
+    /// the client (e.g., peer summaries, scoring state and cached ledger
+    /// infos). This helps to tune memory budgets and diagnose bloat (e.g.,
+    /// when connected to many peers).
+    pub fn memory_usage(&self) -> CacheMemoryStats {
+        let (peer_summaries_bytes, scoring_state_bytes) = {
+            let peer_states = self.peer_states.read();
+            (
+                peer_states.summaries_memory_usage(),
+                peer_states.scoring_memory_usage(),
+            )
+        };
+        let epoch_ending_cache_bytes = self.epoch_ending_ledger_info_cache.lock().memory_usage();
+        let request_tracking_bytes = self.coalesced_requests.lock().len()
+            * size_of::<(CoalescingKey, CoalescedRequest)>()
+            + self.warmed_peers.lock().len() * size_of::<PeerNetworkId>()
+            + self.outstanding_summary_polls.lock().len() * size_of::<(NetworkId, u64)>()
+            + self.ip_request_counts.lock().len() * size_of::<(IpAddr, u64)>()
+            + self.selection_latency_tracker.lock().memory_usage() as usize;
+        CacheMemoryStats {
+            peer_summaries_bytes,
+            scoring_state_bytes,
+            epoch_ending_cache_bytes,
+            request_tracking_bytes: request_tracking_bytes as u64
+                + self.request_rate_tracker.lock().memory_usage(),
+        }
+    }
+
+    /// Returns the fraction of data requests served from the client-side
+    /// caches (i.e., cached epoch ending ledger infos or coalesced responses),
+    /// over all data requests since the client started (or the last reset).
+    /// Returns zero if no data requests were made.
+    pub fn cache_hit_rate(&self) -> f64 {
+        self.cache_hit_tracker.lock().cache_hit_rate()
+    }
+
+    /// Resets the cache hit rate (e.g., to measure it over a new window)
+    pub fn reset_cache_hit_rate(&self) {
+        self.cache_hit_tracker.lock().reset();
+    }
+
+    /// Recompute and update the global data summary cache, but only if the
+    /// peer summaries changed since the last recomputation (if deduplication
+    /// of summary updates is enabled). This avoids needless recomputations
+    /// when the network is quiet (e.g., peers repeatedly advertise the same
+    /// summaries).
+    fn update_global_summary_cache_if_changed(&self) {
+        let summaries_changed = self.peer_states.write().take_summaries_changed();
+        if summaries_changed || !self.data_client_config.summary_poller.dedup_summary_updates {
+            self.update_global_summary_cache();
+        }
     }
 
     /// Recompute and update the global data summary cache.
     fn update_global_summary_cache(&self) {
-        let aggregate = self.peer_states.read().calculate_aggregate_summary();
+        let mut aggregate = self.peer_states.read().calculate_aggregate_summary(
+            self.data_client_config
+                .chunk_sizes
+                .min_summaries_for_chunk_calc,
+            self.data_client_config.chunk_sizes.percentile,
+        );
+        coalesce_advertised_ranges(&mut aggregate.advertised_data);
+        if let Some(chunk_size_prober) = self.chunk_size_prober.lock().as_ref() {
+            let optimal_chunk_sizes = &mut aggregate.optimal_chunk_sizes;
+            optimal_chunk_sizes.transaction_chunk_size = min(
+                optimal_chunk_sizes.transaction_chunk_size,
+                chunk_size_prober.effective_chunk_size(),
+            );
+        }
+        self.update_highest_synced_version(&aggregate);
+        let advertised_data_changed =
+            self.global_summary_cache.read().advertised_data != aggregate.advertised_data;
+        let changed_summary = advertised_data_changed.then(|| aggregate.clone());
         *self.global_summary_cache.write() = aggregate;
+        self.check_peer_diversity();
+
+        // Notify the subscribers of the change (and drop closed subscriptions)
+        if let Some(changed_summary) = changed_summary {
+            self.summary_change_senders
+                .lock()
+                .retain(|sender| sender.unbounded_send(changed_summary.clone()).is_ok());
+        }
+        self.update_range_watchers();
     }
 
-    /// Choose a connected peer that can service the given request. Returns an
-    /// error if no such peer can be found.
-    fn choose_peer_for_request(
-        &self,
-        request: &StorageServiceRequest,
-    ) -> Result<PeerNetworkId, Error> {
-        let all_connected_peers = self.get_all_connected_peers()?;
+    /// Updates the range watchers with the latest peer states (and drops the
+    /// watchers of closed subscriptions).
+    fn update_range_watchers(&self) {
+        let mut range_watchers = self.range_watchers.lock();
+        range_watchers.retain(|range_watcher| !range_watcher.is_closed());
+        if range_watchers.is_empty() {
+            return;
+        }
 
-        // Identify the peers that can service this request
-        let internal_peer_states = self.peer_states.read();
-        let serviceable_peers = all_connected_peers
-            .into_iter()
-            .filter(|peer| internal_peer_states.can_service_request(peer, request))
-            .collect::<Vec<_>>();
+        let peer_states = self.peer_states.read();
+        for range_watcher in range_watchers.iter_mut() {
+            let peer_statuses = peer_states.range_statuses(
+                range_watcher.data_type,
+                range_watcher.start,
+                range_watcher.end,
+            );
+            range_watcher.update(peer_statuses);
+        }
+    }
 
-        // Choose a random peer from those that can service the request
-        serviceable_peers
-            .choose(&mut rand::thread_rng())
-            .copied()
-            .ok_or_else(|| {
-                Error::DataIsUnavailable(
-                    format!("No connected peers are advertising that they can serve this data! Request: {:?}",request),
-                )
-            })
+    /// Returns a stream of global data summaries that emits the new summary
+    /// whenever the advertised data changes (e.g., a peer advertises a new
+    /// version). Summary updates that don't change the advertised data (e.g.,
+    /// repeated polls) aren't emitted.
+    pub fn subscribe_to_summary_changes(&self) -> impl Stream<Item = GlobalDataSummary> {
+        let (summary_change_sender, summary_change_receiver) = mpsc::unbounded();
+        self.summary_change_senders
+            .lock()
+            .push(summary_change_sender);
+        summary_change_receiver
     }
 
-    /// Fetches the next group of peers to poll. The group will contain: (i) any (new) peers that
-    /// have connected since the last time this method was called (i.e., the peers that have not
-    /// been polled yet); (ii) at most one prioritized peer (e.g., those that are upstream); and
-    /// (iii) at most one non-prioritized peer (i.e., those that are downstream).
-    fn fetch_peers_to_poll(&self) -> Result<Vec<PeerNetworkId>, Error> {
-        let mut peers_to_poll = vec![];
+    /// Returns a stream of events that is notified whenever the given range
+    /// (inclusive) for the data type becomes serviceable (along with the
+    /// reason) or unserviceable. Transitions are detected when the global data
+    /// summary is recomputed, by comparing the previous and new peer states.
+    /// Note: for ledger infos, the range is over epochs (not versions).
+    pub fn watch_range_serviceability(
+        &self,
+        data_type: DataType,
+        start: u64,
+        end: u64,
+    ) -> impl Stream<Item = ServiceabilityEvent> {
+        let (event_sender, event_receiver) = mpsc::unbounded();
+        let peer_statuses = self
+            .peer_states
+            .read()
+            .range_statuses(data_type, start, end);
+        self.range_watchers.lock().push(RangeWatcher::new(
+            data_type,
+            start,
+            end,
+            peer_statuses,
+            event_sender,
+        ));
+        event_receiver
+    }
 
-        // Fetch the last polled high-priority peer
-        if let Some(peer) = self.peer_states.write().oldest_polled_priority_peer() {
-            peers_to_poll.push(peer);
+    /// Returns a receiver of the completion events of all requests sent to
+    /// peers (e.g., for real-time monitoring). The channel is bounded, so a
+    /// subscriber that can't keep up lags (i.e., misses the oldest events)
+    /// instead of blocking the client.
+    pub fn subscribe_to_request_events(&self) -> broadcast::Receiver<RequestCompletionEvent> {
+        self.request_event_sender.subscribe()
+    }
+
+    /// Returns the transaction chunk size discovered by chunk size probing.
+    /// Returns `None` if probing is disabled or hasn't completed yet.
+    pub fn discovered_chunk_size(&self) -> Option<u64> {
+        self.chunk_size_prober
+            .lock()
+            .as_ref()
+            .and_then(|chunk_size_prober| chunk_size_prober.safe_chunk_size())
+    }
+
+    /// Records the outcome of a transaction request with the chunk size prober
+    /// (if probing is enabled). If the effective chunk size changes, the
+    /// global data summary is refreshed.
+    fn update_chunk_size_probe(&self, request: &StorageServiceRequest, success: bool) {
+        let chunk_size = match request {
+            StorageServiceRequest::GetTransactionsWithProof(request) => request
+                .end_version
+                .saturating_sub(request.start_version)
+                .saturating_add(1),
+            _ => return,
+        };
+        let chunk_size_changed = match self.chunk_size_prober.lock().as_mut() {
+            Some(chunk_size_prober) if success => chunk_size_prober.record_success(chunk_size),
+            Some(chunk_size_prober) => chunk_size_prober.record_failure(chunk_size),
+            None => false,
+        };
+        if chunk_size_changed {
+            self.update_global_summary_cache();
         }
+    }
 
-        // Fetch all new peers (i.e., those not yet polled)
-        for peer in self.get_all_connected_peers()? {
-            if !self.peer_states.read().already_polled_peer(&peer) {
-                peers_to_poll.push(peer);
-            }
+    /// Returns the number of distinct (non-ignored) peers that contribute
+    /// advertised data to the global data summary.
+    pub fn peer_diversity(&self) -> usize {
+        self.peer_states.read().num_servicing_peers()
+    }
+
+    /// Returns all (non-ignored) peers that advertise any part of the given
+    /// range (inclusive) for the data type, according to the cached peer
+    /// summaries. This is useful for auditing which peers cover served data.
+    /// Note: for ledger infos, the range is over epochs (not versions).
+    pub fn contributors_for_range(
+        &self,
+        data_type: DataType,
+        start: Version,
+        end: Version,
+    ) -> Vec<PeerNetworkId> {
+        self.peer_states
+            .read()
+            .contributors_for_range(data_type, start, end)
+    }
+
+    /// Returns the outcomes of all requests sent by the client, aggregated by
+    /// the tier of the serving peer (e.g., to compare the reliability of
+    /// priority and regular peers).
+    pub fn outcome_breakdown_by_tier(&self) -> HashMap<PeerTier, OutcomeStats> {
+        self.peer_states.read().outcome_breakdown_by_tier()
+    }
+
+    /// Enters maintenance mode. Until maintenance mode is exited, data requests
+    /// fail with `Error::InMaintenance` (without being sent), but the data
+    /// summary poller continues to poll peers (to stay aware of the network).
+    pub fn enter_maintenance_mode(&self) {
+        self.set_maintenance_mode(true);
+    }
+
+    /// Exits maintenance mode (i.e., data requests are sent as usual)
+    pub fn exit_maintenance_mode(&self) {
+        self.set_maintenance_mode(false);
+    }
+
+    /// Returns true iff the client is in maintenance mode
+    pub fn is_in_maintenance(&self) -> bool {
+        *self.in_maintenance.read()
+    }
+
+    /// Updates the maintenance mode (and logs any change)
+    fn set_maintenance_mode(&self, in_maintenance: bool) {
+        let was_in_maintenance =
+            std::mem::replace(&mut *self.in_maintenance.write(), in_maintenance);
+        if in_maintenance != was_in_maintenance {
+            info!(
+                (LogSchema::new(LogEntry::StorageServiceRequest)
+                    .event(LogEvent::MaintenanceMode)
+                    .message(&format!("Maintenance mode enabled: {}", in_maintenance)))
+            );
         }
+    }
 
-        // Handle regular peer polling
-        if peers_to_poll.is_empty() {
-            // Always try and poll at least one peer
-            if let Some(peer) = self.peer_states.write().oldest_polled_regular_peer() {
-                peers_to_poll.push(peer);
-            }
-        } else {
-            // Poll regular peers at a 1/3 reduced frequency
-            sample!(SampleRate::Frequency(3), {
-                if let Some(peer) = self.peer_states.write().oldest_polled_regular_peer() {
-                    peers_to_poll.push(peer);
-                }
-            });
+    /// Returns true iff the peer diversity is below the configured minimum
+    pub fn is_peer_diversity_low(&self) -> bool {
+        *self.low_peer_diversity.read()
+    }
+
+    /// Checks the peer diversity and emits a warning event if the diversity
+    /// has dropped below the configured minimum.
+    fn check_peer_diversity(&self) {
+        let min_peer_diversity = self.data_client_config.monitoring.min_peer_diversity as usize;
+        if min_peer_diversity == 0 {
+            return;
         }
 
-        // Mark all peers as polled
-        for peer in &peers_to_poll {
-            self.peer_states.write().mark_peer_as_polled(peer);
+        let peer_diversity = self.peer_diversity();
+        let low_peer_diversity = peer_diversity < min_peer_diversity;
+        let was_low_peer_diversity =
+            std::mem::replace(&mut *self.low_peer_diversity.write(), low_peer_diversity);
+        if low_peer_diversity && !was_low_peer_diversity {
+            warn!(
+                (LogSchema::new(LogEntry::PeerStates)
+                    .event(LogEvent::LowPeerDiversity)
+                    .message(&format!(
+                        "Peer diversity ({}) has dropped below the minimum ({})!",
+                        peer_diversity, min_peer_diversity
+                    )))
+            );
+            metrics::LOW_PEER_DIVERSITY_WARNINGS.inc();
         }
+    }
 
-        Ok(peers_to_poll)
+    /// Updates the highest synced version advertised by the network (and
+    /// the time at which it last advanced) using the given global summary.
+    fn update_highest_synced_version(&self, global_data_summary: &GlobalDataSummary) {
+        let highest_synced_version = match global_data_summary
+            .advertised_data
+            .highest_synced_ledger_info()
+        {
+            Some(ledger_info) => ledger_info.ledger_info().version(),
+            None => return,
+        };
+
+        let mut tracked_version = self.highest_synced_version.write();
+        let version_advanced = tracked_version
+            .map(|(version, _)| highest_synced_version > version)
+            .unwrap_or(true);
+        if version_advanced {
+            *tracked_version = Some((highest_synced_version, self.time_service.now()));
+        }
     }
 
-    /// Returns all peers connected to us
-    fn get_all_connected_peers(&self) -> Result<Vec<PeerNetworkId>, Error> {
-        let network_peer_metadata = self.network_client.peer_metadata_storage();
-        let connected_peers = network_peer_metadata
-            .networks()
-            .flat_map(|network_id| {
-                network_peer_metadata
-                    .read_filtered(network_id, |(_, peer_metadata)| {
-                        peer_metadata.is_connected()
-                            && peer_metadata.supports_protocol(ProtocolId::StorageServiceRpc)
-                    })
-                    .into_keys()
+    /// Returns the highest synced version advertised by the connected (and
+    /// non-ignored) peers, or `None` if no such peer has advertised a synced
+    /// ledger info. The peer summaries are read directly (i.e., the version
+    /// doesn't wait for the global data summary to be recomputed).
+    pub fn highest_synced_version(&self) -> Option<Version> {
+        let connected_peers = self.get_all_connected_peers().unwrap_or_default();
+        let peer_states = self.peer_states.read();
+        connected_peers
+            .iter()
+            .filter_map(|peer| peer_states.reconciled_summary_if_not_ignored(peer))
+            .filter_map(|summary| {
+                summary
+                    .data_summary
+                    .synced_ledger_info
+                    .as_ref()
+                    .map(|synced_ledger_info| synced_ledger_info.ledger_info().version())
             })
-            .collect::<Vec<_>>();
+            .max()
+    }
 
-        // Ensure connected peers is not empty
-        if connected_peers.is_empty() {
-            return Err(Error::DataIsUnavailable(
-                "No connected AptosNet peers!".to_owned(),
-            ));
+    /// Returns the duration for which the network has been stalled, i.e., the
+    /// time since the highest advertised synced version last advanced. Returns
+    /// `None` if the network is not (yet) considered stalled.
+    pub fn network_stall_duration(&self) -> Option<Duration> {
+        let (_, last_advanced) = (*self.highest_synced_version.read())?;
+        let stall_duration = elapsed_since(self.time_service.now(), last_advanced);
+        let stall_threshold = Duration::from_millis(
+            self.data_client_config
+                .monitoring
+                .network_stall_threshold_ms,
+        );
+        if stall_duration >= stall_threshold {
+            Some(stall_duration)
+        } else {
+            None
         }
-        Ok(connected_peers)
     }
 
-    /// Sends a request (to an undecided peer) and decodes the response
-    async fn send_request_and_decode<T, E>(
-        &self,
-        request: StorageServiceRequest,
-    ) -> Result<Response<T>>
-    where
-        T: TryFrom<StorageServiceResponse, Error = E>,
-        E: Into<Error>,
-    {
-        let peer = self.choose_peer_for_request(&request).map_err(|error| {
-            debug!(
-                (LogSchema::new(LogEntry::StorageServiceRequest)
-                    .event(LogEvent::PeerSelectionError)
-                    .message("Unable to select peer")
-                    .error(&error))
-            );
-            error
-        })?;
-        let _timer = start_timer(&metrics::REQUEST_LATENCIES, request.get_label().into());
-        self.send_request_to_peer_and_decode(peer, request).await
+    /// Returns the time since any peer last successfully served a data (i.e.,
+    /// non-summary) request. Returns `None` if no data request has succeeded.
+    pub fn time_since_last_success(&self) -> Option<Duration> {
+        let last_data_response_time = (*self.last_data_response_time.read())?;
+        Some(elapsed_since(
+            self.time_service.now(),
+            last_data_response_time,
+        ))
     }
 
-    /// Sends a request to a specific peer and decodes the response
-    async fn send_request_to_peer_and_decode<T, E>(
-        &self,
-        peer: PeerNetworkId,
-        request: StorageServiceRequest,
-    ) -> Result<Response<T>>
-    where
-        T: TryFrom<StorageServiceResponse, Error = E>,
-        E: Into<Error>,
-    {
-        let response = self.send_request_to_peer(peer, request).await?;
+    /// Returns the current sync phase of the client. The client is following
+    /// iff the global summary is populated and a data request recently succeeded.
+    pub fn sync_phase(&self) -> SyncPhase {
+        if self.global_summary_cache.read().is_empty() {
+            return SyncPhase::Bootstrapping;
+        }
+
+        let staleness_threshold = Duration::from_millis(
+            self.data_client_config
+                .monitoring
+                .sync_phase_response_staleness_ms,
+        );
+        match *self.last_data_response_time.read() {
+            Some(last_data_response_time)
+                if elapsed_since(self.time_service.now(), last_data_response_time)
+                    <= staleness_threshold =>
+            {
+                SyncPhase::Following
+            }
+            _ => SyncPhase::Bootstrapping,
+        }
+    }
+
+    /// Returns true iff the highest advertised synced version hasn't advanced
+    /// for longer than the configured stall threshold.
+    pub fn is_network_stalled(&self) -> bool {
+        self.network_stall_duration().is_some()
+    }
+
+    /// Choose a connected peer that can service the given request. If archival
+    /// peers are preferred, full-history peers are chosen over pruned peers
+    /// (when available). Returns an error if no such peer can be found.
+    #[cfg(test)]
+    fn choose_peer_for_request(
+        &self,
+        request: &StorageServiceRequest,
+        prefer_archival: bool,
+    ) -> Result<PeerNetworkId, Error> {
+        self.choose_peer_for_request_in_tier(
+            request,
+            prefer_archival,
+            self.data_client_config.peer_selection.selection_policy,
+            None,
+            &HashSet::new(),
+        )
+    }
+
+    /// Returns true iff a connected peer (that isn't excluded by the request
+    /// options) can service the given request, using the same selection as
+    /// sending the request would. Unlike choosing a peer, this doesn't record
+    /// the selection latency, so it can be used to decide which request to send.
+    fn has_serviceable_peer(
+        &self,
+        request: &StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> bool {
+        self.select_peer_in_tier(
+            request,
+            request_options.prefer_archival,
+            self.get_selection_policy(request_options),
+            None,
+            &request_options.excluded_peers,
+        )
+        .is_ok()
+    }
+
+    /// Returns the latency stats of the recent peer selections (i.e., the time
+    /// spent choosing a peer for each request, excluding the request itself).
+    pub fn selection_latency_stats(&self) -> LatencyStats {
+        self.selection_latency_tracker.lock().latency_stats()
+    }
+
+    /// Choose a connected peer that can service the given request (using the
+    /// given selection policy), only considering the peers in the given tier
+    /// (if specified) and ignoring the excluded peers. The latency of the
+    /// selection is recorded.
+    fn choose_peer_for_request_in_tier(
+        &self,
+        request: &StorageServiceRequest,
+        prefer_archival: bool,
+        selection_policy: PeerSelectionPolicy,
+        peer_tier: Option<PeerTier>,
+        excluded_peers: &HashSet<PeerNetworkId>,
+    ) -> Result<PeerNetworkId, Error> {
+        // Note: the selection latency is measured using the wall clock (and
+        // not the time service) as it's a measure of local computation.
+        let selection_start_time = Instant::now();
+        let result = self.select_peer_in_tier(
+            request,
+            prefer_archival,
+            selection_policy,
+            peer_tier,
+            excluded_peers,
+        );
+        self.selection_latency_tracker
+            .lock()
+            .record_latency(selection_start_time.elapsed());
+        result
+    }
+
+    /// Selects a connected peer that can service the given request (using the
+    /// given selection policy), only considering the peers in the given tier
+    /// (if specified) and ignoring the excluded peers.
+    fn select_peer_in_tier(
+        &self,
+        request: &StorageServiceRequest,
+        prefer_archival: bool,
+        selection_policy: PeerSelectionPolicy,
+        peer_tier: Option<PeerTier>,
+        excluded_peers: &HashSet<PeerNetworkId>,
+    ) -> Result<PeerNetworkId, Error> {
+        let candidate_peers = self
+            .get_all_connected_peers()?
+            .into_iter()
+            .filter(|peer| !excluded_peers.contains(peer))
+            .collect::<Vec<_>>();
+
+        // Identify the peers that can service this request (ignoring overloaded
+        // and rate limited peers)
+        let time_now = self.time_service.now();
+        let internal_peer_states = self.peer_states.read();
+
+        // Ensure enough distinct (non-excluded) peers advertise the requested
+        // data (if required)
+        let min_peers_for_request = self.data_client_config.peer_selection.min_peers_for_request;
+        if min_peers_for_request > 1 && !request.is_get_storage_server_summary() {
+            let num_advertising_peers = candidate_peers
+                .iter()
+                .filter(|peer| internal_peer_states.can_service_request(peer, request))
+                .count() as u64;
+            if num_advertising_peers < min_peers_for_request {
+                return Err(Error::DataIsUnavailable(format!(
+                    "Only {} peers are advertising the requested data, but at least {} are required! Request: {:?}",
+                    num_advertising_peers, min_peers_for_request, request
+                )));
+            }
+        }
+
+        let request_data_type = get_request_data_type(request);
+        let available_peers = candidate_peers
+            .into_iter()
+            .filter(|peer| !internal_peer_states.is_peer_overloaded(peer, time_now))
+            .filter(|peer| {
+                request_data_type.map_or(true, |data_type| {
+                    self.rate_limiter
+                        .lock()
+                        .can_send(*peer, data_type, time_now)
+                })
+            })
+            .filter(|peer| {
+                peer_tier.map_or(true, |peer_tier| {
+                    internal_peer_states.get_peer_tier(peer) == peer_tier
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut serviceable_peers = available_peers
+            .iter()
+            .filter(|peer| internal_peer_states.can_service_request(peer, request))
+            .copied()
+            .collect::<Vec<_>>();
+
+        // If no peers can fully service the request, fall back to the peers
+        // that can service the start of the request (if allowed).
+        if serviceable_peers.is_empty()
+            && self
+                .data_client_config
+                .peer_selection
+                .allow_partial_coverage_peers
+        {
+            serviceable_peers = available_peers
+                .into_iter()
+                .filter(|peer| internal_peer_states.can_partially_service_request(peer, request))
+                .collect();
+        }
+
+        // If archival peers are preferred, only fall back to pruned peers if
+        // no archival peer can service the request.
+        if prefer_archival {
+            let archival_peers = serviceable_peers
+                .iter()
+                .filter(|peer| internal_peer_states.is_full_history_peer(peer))
+                .copied()
+                .collect::<Vec<_>>();
+            if !archival_peers.is_empty() {
+                serviceable_peers = archival_peers;
+            }
+        }
+
+        // Prefer peers that don't serve the request from the edge of their
+        // advertised range (if required), as edges are prone to pruning and lag.
+        let edge_margin_versions = self.data_client_config.peer_selection.edge_margin_versions;
+        if edge_margin_versions > 0 {
+            if let (Some(data_type), Some(requested_range)) =
+                (get_request_data_type(request), get_request_range(request))
+            {
+                let margin_peers = serviceable_peers
+                    .iter()
+                    .filter(|peer| {
+                        internal_peer_states
+                            .get_range_margin(peer, data_type, &requested_range)
+                            .map_or(false, |margin| margin >= edge_margin_versions)
+                    })
+                    .copied()
+                    .collect::<Vec<_>>();
+                if !margin_peers.is_empty() {
+                    serviceable_peers = margin_peers;
+                }
+            }
+        }
+
+        // Spread requests across distinct IP addresses (if required)
+        serviceable_peers = self.filter_saturated_ip_groups(serviceable_peers);
+
+        // Avoid peers with the maximum number of in-flight requests (if possible)
+        serviceable_peers = self.filter_saturated_peers(serviceable_peers);
+
+        // Choose a peer from those that can service the request (according to
+        // the selection policy). Peers without a measured response latency are
+        // only chosen by the latency policy if no latencies are measured.
+        let mut selected_peer = match selection_policy {
+            PeerSelectionPolicy::HighestScore => serviceable_peers
+                .iter()
+                .max_by(|peer, other_peer| {
+                    internal_peer_states
+                        .get_selection_score(peer)
+                        .partial_cmp(&internal_peer_states.get_selection_score(other_peer))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .copied(),
+            PeerSelectionPolicy::LowestLatency => serviceable_peers
+                .iter()
+                .filter_map(|peer| {
+                    internal_peer_states
+                        .get_response_latency(peer)
+                        .map(|latency| (peer, latency))
+                })
+                .min_by_key(|(_, latency)| *latency)
+                .map(|(peer, _)| *peer),
+            PeerSelectionPolicy::Random => None,
+        };
+
+        // Otherwise, choose a random peer. If enabled, peers with recent
+        // errors are (softly) deprioritized.
+        if selected_peer.is_none() {
+            let recent_error_weight = self.data_client_config.peer_selection.recent_error_weight;
+            let recent_error_window = Duration::from_millis(
+                self.data_client_config
+                    .peer_selection
+                    .recent_error_window_ms,
+            );
+            selected_peer = serviceable_peers
+                .choose_weighted(&mut rand::thread_rng(), |peer| {
+                    let num_recent_errors =
+                        internal_peer_states.num_recent_errors(peer, time_now, recent_error_window);
+                    1.0 / (1.0 + (recent_error_weight as f64) * (num_recent_errors as f64))
+                })
+                .ok()
+                .copied();
+        }
+        selected_peer.ok_or_else(|| {
+            // Distinguish data types that no peer serves from range gaps (if required)
+            if self.data_client_config.fail_fast_on_unavailable_data_type {
+                if let Some(data_type) = get_request_data_type(request) {
+                    if !internal_peer_states.is_data_type_advertised(data_type) {
+                        return Error::DataTypeUnavailable(format!(
+                            "No peers are advertising any {} data! Request: {:?}",
+                            data_type.as_str(),
+                            request
+                        ));
+                    }
+                }
+            }
+
+            // Distinguish requests that only the excluded peers can service
+            if excluded_peers
+                .iter()
+                .any(|peer| internal_peer_states.can_service_request(peer, request))
+            {
+                return Error::DataIsUnavailable(format!(
+                    "All peers advertising the requested data are excluded! Excluded peers: {:?}, request: {:?}",
+                    excluded_peers, request
+                ));
+            }
+            Error::DataIsUnavailable(
+                format!("No connected peers are advertising that they can serve this data! Request: {:?}",request),
+            )
+        })
+    }
+
+    /// Removes the peers behind IP addresses that have already received the
+    /// maximum fraction of requests. If this would remove all peers, the
+    /// peers are returned unfiltered (i.e., the cap is best-effort).
+    fn filter_saturated_ip_groups(&self, peers: Vec<PeerNetworkId>) -> Vec<PeerNetworkId> {
+        let max_requests_fraction = self
+            .data_client_config
+            .peer_selection
+            .max_requests_fraction_per_ip;
+        if max_requests_fraction >= 1.0 {
+            return peers;
+        }
+
+        let ip_request_counts = self.ip_request_counts.lock();
+        let total_requests: u64 = ip_request_counts.values().sum();
+        if total_requests == 0 {
+            return peers;
+        }
+
+        let unsaturated_peers = peers
+            .iter()
+            .filter(|peer| match self.get_peer_ip_address(peer) {
+                Some(ip_address) => {
+                    let ip_requests = ip_request_counts.get(&ip_address).copied().unwrap_or(0);
+                    (ip_requests as f64) / (total_requests as f64) < max_requests_fraction
+                }
+                None => true, // Peers without an IP address aren't grouped
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        if unsaturated_peers.is_empty() {
+            peers
+        } else {
+            unsaturated_peers
+        }
+    }
+
+    /// Removes the peers that already have the maximum number of in-flight
+    /// requests. If this would remove all peers, the peers are returned
+    /// unfiltered (i.e., the request waits for an in-flight slot).
+    fn filter_saturated_peers(&self, peers: Vec<PeerNetworkId>) -> Vec<PeerNetworkId> {
+        let inflight_limiter = self.inflight_limiter.lock();
+        let unsaturated_peers = peers
+            .iter()
+            .filter(|peer| !inflight_limiter.is_saturated(peer))
+            .copied()
+            .collect::<Vec<_>>();
+        if unsaturated_peers.is_empty() {
+            peers
+        } else {
+            unsaturated_peers
+        }
+    }
+
+    /// Returns the IP address of the given peer's active connection (if any)
+    fn get_peer_ip_address(&self, peer: &PeerNetworkId) -> Option<IpAddr> {
+        self.network_client
+            .peer_metadata_storage()
+            .read(*peer)
+            .and_then(|peer_info| peer_info.active_connection.addr.find_ip_addr())
+    }
+
+    /// Records that a request was routed to the given peer (to track the
+    /// fraction of requests routed to each IP address).
+    fn record_ip_request(&self, peer: &PeerNetworkId) {
+        if self
+            .data_client_config
+            .peer_selection
+            .max_requests_fraction_per_ip
+            >= 1.0
+        {
+            return;
+        }
+        if let Some(ip_address) = self.get_peer_ip_address(peer) {
+            *self.ip_request_counts.lock().entry(ip_address).or_default() += 1;
+        }
+    }
+
+    /// Returns true iff a connected peer advertises the data for the given
+    /// request (according to the cached peer summaries). This is a dry-run of
+    /// the availability check performed when the request is sent.
+    pub fn can_service(&self, request: &StorageServiceRequest) -> bool {
+        !self.serviceable_peers(request).is_empty()
+    }
+
+    /// Returns the connected (non-ignored) peers that advertise the data for
+    /// the given request, according to the cached peer summaries. If partial
+    /// coverage peers are allowed, and no peer can fully service the request,
+    /// the peers that can service the start of the request are returned.
+    /// Note: transient conditions (e.g., overloaded or rate limited peers)
+    /// are not considered.
+    pub fn serviceable_peers(&self, request: &StorageServiceRequest) -> Vec<PeerNetworkId> {
+        let all_connected_peers = self.get_all_connected_peers().unwrap_or_default();
+        let internal_peer_states = self.peer_states.read();
+        let serviceable_peers = all_connected_peers
+            .iter()
+            .filter(|peer| internal_peer_states.can_service_request(peer, request))
+            .copied()
+            .collect::<Vec<_>>();
+        if serviceable_peers.is_empty()
+            && self
+                .data_client_config
+                .peer_selection
+                .allow_partial_coverage_peers
+        {
+            all_connected_peers
+                .into_iter()
+                .filter(|peer| internal_peer_states.can_partially_service_request(peer, request))
+                .collect()
+        } else {
+            serviceable_peers
+        }
+    }
+
+    /// Chooses the given number of distinct peers to cross-check the given
+    /// request (i.e., peers that advertise the requested data, in descending
+    /// score order). If diversification is enabled, peers on distinct IP
+    /// addresses with distinct peer identities are chosen first, followed by
+    /// peers with distinct identities, and finally any remaining peers. This
+    /// reduces the chance that all chosen peers are controlled by a single
+    /// operator. Returns an error if too few peers can service the request.
+    pub fn choose_cross_check_peers(
+        &self,
+        request: &StorageServiceRequest,
+        num_peers: usize,
+    ) -> Result<Vec<PeerNetworkId>, Error> {
+        let mut candidate_peers = self.serviceable_peers(request);
+        if candidate_peers.len() < num_peers {
+            return Err(Error::DataIsUnavailable(format!(
+                "Only {} peers can service the request, but {} are required to cross-check it! Request: {:?}",
+                candidate_peers.len(),
+                num_peers,
+                request
+            )));
+        }
+
+        // Sort the candidates by descending score (breaking ties by peer)
+        {
+            let internal_peer_states = self.peer_states.read();
+            candidate_peers.sort_by(|peer, other_peer| {
+                internal_peer_states
+                    .get_selection_score(other_peer)
+                    .partial_cmp(&internal_peer_states.get_selection_score(peer))
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| peer.cmp(other_peer))
+            });
+        }
+        if !self
+            .data_client_config
+            .peer_selection
+            .diversify_cross_check_peers
+        {
+            candidate_peers.truncate(num_peers);
+            return Ok(candidate_peers);
+        }
+
+        // Greedily choose the peers, relaxing the diversity requirements with
+        // each pass. Peers without an IP address are treated as distinct.
+        let mut selected_peers = vec![];
+        let mut selected_ip_addresses = HashSet::new();
+        let mut selected_peer_ids = HashSet::new();
+        for (require_distinct_ip, require_distinct_id) in
+            [(true, true), (false, true), (false, false)]
+        {
+            for peer in &candidate_peers {
+                if selected_peers.len() == num_peers {
+                    return Ok(selected_peers);
+                }
+                if selected_peers.contains(peer) {
+                    continue;
+                }
+                let ip_address = self.get_peer_ip_address(peer);
+                let is_distinct_ip = ip_address.map_or(true, |ip_address| {
+                    !selected_ip_addresses.contains(&ip_address)
+                });
+                let is_distinct_id = !selected_peer_ids.contains(&peer.peer_id());
+                if (require_distinct_ip && !is_distinct_ip)
+                    || (require_distinct_id && !is_distinct_id)
+                {
+                    continue;
+                }
+                selected_peers.push(*peer);
+                selected_ip_addresses.extend(ip_address);
+                selected_peer_ids.insert(peer.peer_id());
+            }
+        }
+        Ok(selected_peers)
+    }
+
+    /// Returns the reason the given peer would be excluded when selecting a
+    /// peer for the specified request (or `NotExcluded` if it wouldn't be).
+    pub fn explain_peer_exclusion(
+        &self,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+    ) -> PeerExclusionReason {
+        let is_connected = self
+            .get_all_connected_peers()
+            .map(|connected_peers| connected_peers.contains(&peer))
+            .unwrap_or(false);
+        if !is_connected {
+            return PeerExclusionReason::NotConnected;
+        }
+
+        self.peer_states.read().explain_peer_exclusion(
+            &peer,
+            request,
+            self.time_service.now(),
+            self.data_client_config
+                .peer_selection
+                .allow_partial_coverage_peers,
+        )
+    }
+
+    /// Fetches the storage server summary of the given peer immediately (i.e.,
+    /// out-of-band of the poller), and updates the peer's summary and the
+    /// global data summary in the same way as the poller. This is useful when
+    /// a peer has just (re)connected and its advertisement is needed now.
+    pub async fn refresh_peer_summary(&self, peer: PeerNetworkId) -> Result<StorageServerSummary> {
+        let result: Result<StorageServerSummary> = self
+            .send_request_to_peer_and_decode(peer, StorageServiceRequest::GetStorageServerSummary)
+            .await
+            .map(Response::into_payload);
+        match result {
+            Ok(storage_summary) => {
+                self.update_summary(peer, storage_summary.clone());
+                self.update_global_summary_cache_if_changed();
+                self.fetch_server_protocol_version_if_required(peer).await;
+                Ok(storage_summary)
+            }
+            Err(error) => {
+                self.handle_summary_poll_failure(peer);
+                Err(error)
+            }
+        }
+    }
+
+    /// Requests the storage server summary from all connected peers in parallel
+    /// (bounded by the maximum number of concurrent summary polls), and returns
+    /// the result for each peer. This does not update the cached summaries.
+    ///
+    /// Polls are started round-robin across networks, and the number of
+    /// concurrent polls per network can also be bounded, so that a network
+    /// with many peers can't starve the polls of the other networks.
+    pub async fn poll_all_peers(
+        &self,
+        timeout: Duration,
+    ) -> HashMap<PeerNetworkId, Result<StorageServerSummary>> {
+        let connected_peers = match self.get_all_connected_peers() {
+            Ok(connected_peers) => connected_peers,
+            Err(_) => return HashMap::new(),
+        };
+
+        // Group the peers to poll by network
+        let mut pending_peers: BTreeMap<NetworkId, VecDeque<PeerNetworkId>> = BTreeMap::new();
+        for peer in connected_peers {
+            pending_peers
+                .entry(peer.network_id())
+                .or_default()
+                .push_back(peer);
+        }
+
+        let max_concurrent_polls = max(
+            1,
+            self.data_client_config
+                .summary_poller
+                .max_concurrent_summary_polls,
+        ) as usize;
+        let max_concurrent_polls_per_network = match self
+            .data_client_config
+            .summary_poller
+            .max_concurrent_summary_polls_per_network
+        {
+            0 => max_concurrent_polls,
+            max_polls_per_network => max_polls_per_network as usize,
+        };
+
+        let mut outstanding_polls = FuturesUnordered::new();
+        let mut polls_per_network: HashMap<NetworkId, usize> = HashMap::new();
+        let mut poll_results = HashMap::new();
+        loop {
+            // Start as many polls as the limits allow (round-robin across networks)
+            let mut started_poll = true;
+            while started_poll && outstanding_polls.len() < max_concurrent_polls {
+                started_poll = false;
+                for (network_id, peers) in pending_peers.iter_mut() {
+                    let num_polls = polls_per_network.entry(*network_id).or_default();
+                    if outstanding_polls.len() >= max_concurrent_polls
+                        || *num_polls >= max_concurrent_polls_per_network
+                    {
+                        continue;
+                    }
+                    if let Some(peer) = peers.pop_front() {
+                        *num_polls += 1;
+                        *self
+                            .outstanding_summary_polls
+                            .lock()
+                            .entry(*network_id)
+                            .or_default() += 1;
+                        outstanding_polls.push(self.poll_peer_summary(peer, timeout));
+                        started_poll = true;
+                    }
+                }
+            }
+
+            // Wait for the next poll to complete
+            let (peer, result) = match outstanding_polls.next().await {
+                Some(poll_result) => poll_result,
+                None => break, // All peers have been polled
+            };
+            let network_id = peer.network_id();
+            if let Some(num_polls) = polls_per_network.get_mut(&network_id) {
+                *num_polls = num_polls.saturating_sub(1);
+            }
+            if let Some(num_polls) = self.outstanding_summary_polls.lock().get_mut(&network_id) {
+                *num_polls = num_polls.saturating_sub(1);
+            }
+            poll_results.insert(peer, result);
+        }
+        poll_results
+    }
+
+    /// Returns the number of outstanding summary polls (when polling all
+    /// peers) for each network.
+    pub fn outstanding_summary_polls(&self) -> HashMap<NetworkId, u64> {
+        self.outstanding_summary_polls.lock().clone()
+    }
+
+    /// Requests the storage server summary from the given peer (with a timeout)
+    async fn poll_peer_summary(
+        &self,
+        peer: PeerNetworkId,
+        timeout: Duration,
+    ) -> (PeerNetworkId, Result<StorageServerSummary>) {
+        let result = self
+            .time_service
+            .timeout(
+                timeout,
+                self.send_request_to_peer_and_decode(
+                    peer,
+                    StorageServiceRequest::GetStorageServerSummary,
+                ),
+            )
+            .await
+            .map_err(|_| {
+                Error::TimeoutWaitingForResponse(format!(
+                    "Timed out polling the storage server summary of peer: {:?}",
+                    peer
+                ))
+            })
+            .and_then(|result| result.map(Response::into_payload));
+        (peer, result)
+    }
+
+    /// Fetches the next group of peers to poll. The group will contain: (i) any (new) peers that
+    /// have connected since the last time this method was called (i.e., the peers that have not
+    /// been polled yet); (ii) at most one prioritized peer (e.g., those that are upstream); and
+    /// (iii) at most one non-prioritized peer (i.e., those that are downstream).
+    ///
+    /// Peers supplied by the peer hint source are treated as prioritized peers,
+    /// and new hinted peers are polled ahead of new non-hinted peers.
+    fn fetch_peers_to_poll(&self) -> Result<Vec<PeerNetworkId>, Error> {
+        let mut peers_to_poll = vec![];
+
+        // Update the hinted peers
+        let peer_hints = self.get_peer_hints();
+        self.peer_states
+            .write()
+            .update_peer_hints(peer_hints.clone());
+
+        // Fetch the last polled high-priority peer
+        let time_now = self.time_service.now();
+        let priority_peer = match self
+            .data_client_config
+            .summary_poller
+            .poll_selection_strategy
+        {
+            PeerSelectionStrategy::FreshnessWeighted => self
+                .peer_states
+                .write()
+                .freshness_weighted_priority_peer(time_now),
+            PeerSelectionStrategy::RoundRobin => self
+                .peer_states
+                .write()
+                .oldest_polled_priority_peer(time_now),
+        };
+        if let Some(peer) = priority_peer {
+            peers_to_poll.push(peer);
+        }
+
+        // Fetch all new peers (i.e., those not yet polled), hinted peers first
+        let mut new_peers = self
+            .get_all_connected_peers()?
+            .into_iter()
+            .filter(|peer| !self.peer_states.read().already_polled_peer(peer))
+            .collect::<Vec<_>>();
+        new_peers.sort_by_key(|peer| !peer_hints.contains(peer));
+        peers_to_poll.extend(new_peers);
+
+        // Handle regular peer polling
+        if peers_to_poll.is_empty() {
+            // Always try and poll at least one peer
+            if let Some(peer) = self
+                .peer_states
+                .write()
+                .oldest_polled_regular_peer(time_now)
+            {
+                peers_to_poll.push(peer);
+            }
+        } else {
+            // Poll regular peers at a 1/3 reduced frequency
+            sample!(SampleRate::Frequency(3), {
+                if let Some(peer) = self
+                    .peer_states
+                    .write()
+                    .oldest_polled_regular_peer(time_now)
+                {
+                    peers_to_poll.push(peer);
+                }
+            });
+        }
+
+        // Mark all peers as polled
+        for peer in &peers_to_poll {
+            self.peer_states.write().mark_peer_as_polled(peer);
+        }
+
+        Ok(peers_to_poll)
+    }
+
+    /// Returns the newest application protocol (i.e., the protocol with the
+    /// highest id) advertised by each connected peer in its handshake. This
+    /// shows the protocol version distribution across peers (e.g., during
+    /// network upgrades). Peers without any known protocols are omitted.
+    pub fn peer_protocol_versions(&self) -> HashMap<PeerNetworkId, ProtocolId> {
+        let network_peer_metadata = self.network_client.peer_metadata_storage();
+        network_peer_metadata
+            .networks()
+            .flat_map(|network_id| {
+                network_peer_metadata.read_filtered(network_id, |(_, peer_metadata)| {
+                    peer_metadata.is_connected()
+                })
+            })
+            .filter_map(|(peer, peer_metadata)| {
+                peer_metadata
+                    .active_connection
+                    .application_protocols
+                    .iter()
+                    .max_by_key(|protocol| *protocol as u8)
+                    .map(|protocol| (peer, protocol))
+            })
+            .collect()
+    }
+
+    /// Returns all peers connected to us
+    fn get_all_connected_peers(&self) -> Result<Vec<PeerNetworkId>, Error> {
+        let network_peer_metadata = self.network_client.peer_metadata_storage();
+        let connected_peers = network_peer_metadata
+            .networks()
+            .flat_map(|network_id| {
+                network_peer_metadata
+                    .read_filtered(network_id, |(_, peer_metadata)| {
+                        peer_metadata.is_connected()
+                            && peer_metadata.supports_protocol(ProtocolId::StorageServiceRpc)
+                    })
+                    .into_keys()
+            })
+            .collect::<Vec<_>>();
+
+        // Ensure connected peers is not empty
+        if connected_peers.is_empty() {
+            return Err(Error::DataIsUnavailable(
+                "No connected AptosNet peers!".to_owned(),
+            ));
+        }
+        Ok(connected_peers)
+    }
+
+    /// Sends a request (to an undecided peer) and decodes the response
+    async fn send_request_and_decode<T, E>(
+        &self,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Result<Response<T>>
+    where
+        T: TryFrom<StorageServiceResponse, Error = E>,
+        E: Into<Error>,
+    {
+        self.send_request_and_decode_with_peer(request, request_options)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Sends a request (to an undecided peer) and decodes the response.
+    /// Also returns the peer that the request was sent to.
+    async fn send_request_and_decode_with_peer<T, E>(
+        &self,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Result<(PeerNetworkId, Response<T>)>
+    where
+        T: TryFrom<StorageServiceResponse, Error = E>,
+        E: Into<Error>,
+    {
+        // Refuse all data requests while in maintenance mode
+        if self.is_in_maintenance() {
+            return Err(Error::InMaintenance);
+        }
+
+        // Refuse all data requests while the circuit breaker is open
+        let remaining_cooldown = self
+            .circuit_breaker
+            .lock()
+            .remaining_cooldown(self.time_service.now());
+        if let Some(remaining_cooldown) = remaining_cooldown {
+            return Err(Error::CircuitOpen(format!(
+                "Requests will be sent again in {:?}",
+                remaining_cooldown
+            )));
+        }
+
+        // Send the request directly if coalescing is disabled. Requests that
+        // exclude peers are never coalesced (the peer may be excluded), and
+        // requests that bypass the caches never reuse a shared response.
+        if !self.data_client_config.caching.enable_request_coalescing
+            || !request_options.excluded_peers.is_empty()
+            || request_options.bypass_cache
+        {
+            return self
+                .send_uncoalesced_request_and_decode(request, request_options)
+                .await;
+        }
+
+        // Attach to an identical request (if one exists), otherwise send a new one
+        let time_now = self.time_service.now();
+        let coalesce_window =
+            Duration::from_millis(self.data_client_config.caching.coalesce_window_ms);
+        let coalescing_key = CoalescingKey::new(request.clone(), request_options);
+        let coalesced_response = {
+            let mut coalesced_requests = self.coalesced_requests.lock();
+            let existing_response = coalesced_requests
+                .get(&coalescing_key)
+                .filter(|coalesced_request| {
+                    !coalesced_request.is_expired(time_now, coalesce_window)
+                })
+                .map(|coalesced_request| coalesced_request.response.clone());
+            match existing_response {
+                Some(existing_response) => {
+                    self.cache_hit_tracker.lock().record_cache_hit();
+                    existing_response
+                }
+                None => {
+                    self.cache_hit_tracker.lock().record_network_request();
+                    let data_client = self.clone();
+                    let request_clone = request.clone();
+                    let request_options = request_options.clone();
+                    let response = async move {
+                        // The circuit breaker is only updated once per sent request
+                        // (i.e., not once for every request attached to it).
+                        let result = data_client
+                            .send_request_with_retries(request_clone.clone(), &request_options)
+                            .await;
+                        data_client.update_circuit_breaker(&result);
+                        let (peer, payload) = result?;
+
+                        // Hash the payload (if required), so that attached
+                        // requests can verify the shared payload.
+                        let payload_hash = if data_client
+                            .data_client_config
+                            .caching
+                            .verify_cache_integrity
+                        {
+                            let payload_hash = hash_payload(&payload).map_err(|error| {
+                                Error::UnexpectedErrorEncountered(format!(
+                                    "Unable to hash the shared response payload! Error: {:?}",
+                                    error
+                                ))
+                            })?;
+                            Some(payload_hash)
+                        } else {
+                            None
+                        };
+
+                        // All attached requests share a single callback for the peer
+                        let response_callback =
+                            data_client.create_response_callback(peer, request_clone);
+                        Ok(SharedResponse::new(
+                            peer,
+                            payload,
+                            payload_hash,
+                            response_callback,
+                        ))
+                    }
+                    .boxed()
+                    .shared();
+                    coalesced_requests.insert(
+                        coalescing_key.clone(),
+                        CoalescedRequest::new(time_now, response.clone()),
+                    );
+                    response
+                }
+            }
+        };
+        let result = coalesced_response.await;
+
+        // Garbage collect any expired requests
+        let time_now = self.time_service.now();
+        self.coalesced_requests
+            .lock()
+            .retain(|_, coalesced_request| {
+                !coalesced_request.is_expired(time_now, coalesce_window)
+            });
+
+        // Verify the shared payload before using it. Corrupted payloads are
+        // never shared again, and the request is sent to the network instead.
+        let shared_response = result?;
+        if shared_response.is_corrupted() {
+            error!(
+                (LogSchema::new(LogEntry::StorageServiceResponse)
+                    .event(LogEvent::CacheIntegrityMismatch)
+                    .peer(&shared_response.peer)
+                    .request_type(request.get_label())
+                    .message(
+                        "The shared response is corrupted! Removing it and sending the request again."
+                    ))
+            );
+            self.coalesced_requests.lock().remove(&coalescing_key);
+            return self
+                .send_uncoalesced_request_and_decode(request, request_options)
+                .await;
+        }
+
+        // Create a response (with the shared callback) and decode it
+        let SharedResponse {
+            peer,
+            payload,
+            response_callback,
+            ..
+        } = shared_response;
+        let response = self.create_response_with_callback(
+            peer,
+            &request,
+            payload,
+            Box::new(response_callback),
+            request_options,
+        );
+        Ok((peer, decode_response(response)?))
+    }
+
+    /// Sends a request (to an undecided peer) without coalescing it with any
+    /// identical requests, and decodes the response. Also returns the peer
+    /// that the request was sent to.
+    async fn send_uncoalesced_request_and_decode<T, E>(
+        &self,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Result<(PeerNetworkId, Response<T>)>
+    where
+        T: TryFrom<StorageServiceResponse, Error = E>,
+        E: Into<Error>,
+    {
+        self.cache_hit_tracker.lock().record_network_request();
+        let result = self
+            .send_request_with_retries(request.clone(), request_options)
+            .await;
+        self.update_circuit_breaker(&result);
+
+        // Create a response (with a callback for the peer) and decode it
+        let (peer, payload) = result?;
+        let response = self.create_response(peer, request, payload, request_options);
+        Ok((peer, decode_response(response)?))
+    }
+
+    /// Updates the circuit breaker with the result of a request (and logs any
+    /// change to the circuit)
+    fn update_circuit_breaker<T>(&self, result: &Result<T>) {
+        let mut circuit_breaker = self.circuit_breaker.lock();
+        match result {
+            Ok(_) => {
+                if circuit_breaker.record_success() {
+                    info!(
+                        (LogSchema::new(LogEntry::StorageServiceRequest)
+                            .event(LogEvent::CircuitBreakerClosed)
+                            .message("The circuit breaker closed after a successful request"))
+                    );
+                }
+            }
+            Err(error) => {
+                if circuit_breaker.record_failure(self.time_service.now()) {
+                    warn!(
+                        (LogSchema::new(LogEntry::StorageServiceRequest)
+                            .event(LogEvent::CircuitBreakerOpened)
+                            .message("The circuit breaker opened after repeated failures")
+                            .error(error))
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns the response timeout for a request with the given options
+    fn get_response_timeout(&self, request_options: &RequestOptions) -> Duration {
+        request_options
+            .timeout_override
+            .unwrap_or_else(|| Duration::from_millis(self.data_client_config.response_timeout_ms))
+    }
+
+    /// Returns the peer selection policy for a request with the given options
+    fn get_selection_policy(&self, request_options: &RequestOptions) -> PeerSelectionPolicy {
+        request_options
+            .selection_policy_override
+            .unwrap_or(self.data_client_config.peer_selection.selection_policy)
+    }
+
+    /// Sends a request (to an undecided peer) and returns the peer and the
+    /// response payload. If the request options specify a retry policy, the
+    /// request is retried on transient errors (after an exponential backoff),
+    /// and a peer is re-selected for each attempt.
+    async fn send_request_with_retries(
+        &self,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Result<(PeerNetworkId, StorageServiceResponse)> {
+        let retry_policy = match request_options.retry_policy {
+            Some(retry_policy) => retry_policy,
+            None => return self.send_request(request, request_options).await,
+        };
+
+        let mut num_attempts = 1;
+        loop {
+            match self.send_request(request.clone(), request_options).await {
+                Err(error) if error.is_transient() && num_attempts < retry_policy.max_attempts => {
+                    let backoff_multiplier = 2u32.saturating_pow((num_attempts - 1) as u32);
+                    let backoff = retry_policy.base_backoff.saturating_mul(backoff_multiplier);
+                    debug!(
+                        (LogSchema::new(LogEntry::StorageServiceRequest)
+                            .event(LogEvent::TransientErrorRetry)
+                            .request_type(request.get_label())
+                            .message(&format!(
+                                "Retrying the request after {:?} (attempt {} of {})",
+                                backoff,
+                                num_attempts + 1,
+                                retry_policy.max_attempts
+                            ))
+                            .error(&error))
+                    );
+                    self.time_service.sleep(backoff).await;
+                    num_attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a request (to an undecided peer) and returns the peer and the
+    /// response payload.
+    async fn send_request(
+        &self,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Result<(PeerNetworkId, StorageServiceResponse)> {
+        let prefer_archival = request_options.prefer_archival;
+        let selection_policy = self.get_selection_policy(request_options);
+        let choose_peer = || {
+            self.choose_peer_for_request_in_tier(
+                &request,
+                prefer_archival,
+                selection_policy,
+                None,
+                &request_options.excluded_peers,
+            )
+        };
+
+        // If no peer advertises the data, the summaries may be stale. So, poll
+        // all peers (if enabled) and re-evaluate the request before failing.
+        let peer = match choose_peer() {
+            Err(Error::DataIsUnavailable(_))
+                if self.data_client_config.summary_poller.poll_on_gap =>
+            {
+                self.refresh_summaries_on_gap().await;
+                choose_peer()
+            }
+            result => result,
+        };
+        let peer = peer.map_err(|error| {
+            debug!(
+                (LogSchema::new(LogEntry::StorageServiceRequest)
+                    .event(LogEvent::PeerSelectionError)
+                    .message("Unable to select peer")
+                    .error(&error))
+            );
+            error
+        })?;
+        self.record_ip_request(&peer);
+        let peer = self
+            .warm_selected_peer(
+                peer,
+                &request,
+                request_options,
+                None,
+                &request_options.excluded_peers,
+            )
+            .await?;
+        let _timer = start_timer(&metrics::REQUEST_LATENCIES, request.get_label().into());
+        let (peer, result) = self
+            .send_request_with_hedging(peer, request.clone(), request_options)
+            .await;
+        match result {
+            Ok(response) => Ok((peer, response.into_payload())),
+            Err(error @ Error::MalformedResponse(_))
+                if self.data_client_config.retry_malformed_responses =>
+            {
+                self.retry_with_another_peer(peer, request, request_options, error)
+                    .await
+            }
+            Err(error @ Error::RequestCancelled(_)) => {
+                self.retry_with_another_peer(peer, request, request_options, error)
+                    .await
+            }
+            Err(error) => {
+                self.fall_back_to_regular_peer(peer, request, request_options, error)
+                    .await
+            }
+        }
+    }
+
+    /// Sends a request to the given peer. If hedging applies to the request,
+    /// and no response is received within the hedge delay, the request is
+    /// also sent to another peer (if another peer can service the request).
+    /// The first successful response is returned (along with the peer that
+    /// sent it), and the outstanding request is dropped. If both requests
+    /// fail, the error of the last failed request is returned.
+    async fn send_request_with_hedging(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> (
+        PeerNetworkId,
+        Result<Response<StorageServiceResponse>, Error>,
+    ) {
+        let response_timeout = self.get_response_timeout(request_options);
+        let primary_response = self
+            .send_request_to_peer_with_timeout(peer, request.clone(), response_timeout)
+            .boxed();
+        if !self.should_hedge_request(&request, request_options) {
+            return (peer, primary_response.await);
+        }
+
+        // Wait for the primary response until the hedge delay elapses
+        let hedge_delay = Duration::from_millis(self.data_client_config.hedge_delay_ms);
+        let primary_response = match future::select(
+            primary_response,
+            self.time_service.sleep(hedge_delay).boxed(),
+        )
+        .await
+        {
+            Either::Left((result, _)) => return (peer, result),
+            Either::Right((_, primary_response)) => primary_response,
+        };
+
+        // Choose another peer for the hedged request
+        let mut excluded_peers = request_options.excluded_peers.clone();
+        excluded_peers.insert(peer);
+        let hedge_peer = match self.choose_peer_for_request_in_tier(
+            &request,
+            request_options.prefer_archival,
+            self.get_selection_policy(request_options),
+            None,
+            &excluded_peers,
+        ) {
+            Ok(hedge_peer) => hedge_peer,
+            Err(_) => return (peer, primary_response.await), // No other peer can service the request
+        };
+        debug!(
+            (LogSchema::new(LogEntry::StorageServiceRequest)
+                .event(LogEvent::HedgedRequest)
+                .request_type(request.get_label())
+                .peer(&hedge_peer)
+                .message(&format!(
+                    "No response was received within the hedge delay ({:?})",
+                    hedge_delay
+                )))
+        );
+        self.record_ip_request(&hedge_peer);
+        let hedge_response = self
+            .send_request_to_peer_with_timeout(hedge_peer, request, response_timeout)
+            .boxed();
+
+        // Return the first successful response
+        match future::select(primary_response, hedge_response).await {
+            Either::Left((Ok(response), _)) => (peer, Ok(response)),
+            Either::Left((Err(_), hedge_response)) => (hedge_peer, hedge_response.await),
+            Either::Right((Ok(response), _)) => (hedge_peer, Ok(response)),
+            Either::Right((Err(_), primary_response)) => (peer, primary_response.await),
+        }
+    }
+
+    /// Returns true iff the request should be hedged (i.e., hedging is
+    /// enabled, and applies to the priority of the request)
+    fn should_hedge_request(
+        &self,
+        request: &StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> bool {
+        if self.data_client_config.hedge_delay_ms == 0 || request.is_get_storage_server_summary() {
+            return false;
+        }
+        !self.data_client_config.hedge_only_high_priority
+            || request_options.priority == RequestPriority::High
+    }
+
+    /// Resends a request that received a malformed response (or that was
+    /// cancelled because the peer was banned) to another peer (if another
+    /// peer can service the request). Otherwise, the original error is
+    /// returned. Both peers are scored as usual.
+    async fn retry_with_another_peer(
+        &self,
+        failed_peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+        error: Error,
+    ) -> Result<(PeerNetworkId, StorageServiceResponse)> {
+        let mut excluded_peers = request_options.excluded_peers.clone();
+        excluded_peers.insert(failed_peer);
+        let peer = match self.choose_peer_for_request_in_tier(
+            &request,
+            request_options.prefer_archival,
+            self.get_selection_policy(request_options),
+            None,
+            &excluded_peers,
+        ) {
+            Ok(peer) => peer,
+            Err(_) => return Err(error), // No other peer can service the request
+        };
+
+        let log_event = match error {
+            Error::RequestCancelled(_) => LogEvent::CancelledRequestReroute,
+            _ => LogEvent::MalformedResponseRetry,
+        };
+        debug!(
+            (LogSchema::new(LogEntry::StorageServiceRequest)
+                .event(log_event)
+                .request_type(request.get_label())
+                .peer(&peer)
+                .error(&error))
+        );
+        self.record_ip_request(&peer);
+        let peer = match self
+            .warm_selected_peer(peer, &request, request_options, None, &excluded_peers)
+            .await
+        {
+            Ok(peer) => peer,
+            Err(_) => return Err(error), // No other (warm) peer can service the request
+        };
+        let response_timeout = self.get_response_timeout(request_options);
+        let response = self
+            .send_request_to_peer_with_timeout(peer, request, response_timeout)
+            .await?;
+        Ok((peer, response.into_payload()))
+    }
+
+    /// Resends a request that failed on a priority peer to a regular peer (if
+    /// enabled and a regular peer can service the request). Otherwise, the
+    /// original error is returned. Both peers are scored as usual.
+    async fn fall_back_to_regular_peer(
+        &self,
+        failed_peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        request_options: &RequestOptions,
+        error: Error,
+    ) -> Result<(PeerNetworkId, StorageServiceResponse)> {
+        if !self
+            .data_client_config
+            .peer_selection
+            .allow_regular_peer_fallback
+            || self.peer_states.read().get_peer_tier(&failed_peer) != PeerTier::Priority
+        {
+            return Err(error);
+        }
+        let peer = match self.choose_peer_for_request_in_tier(
+            &request,
+            request_options.prefer_archival,
+            self.get_selection_policy(request_options),
+            Some(PeerTier::Regular),
+            &request_options.excluded_peers,
+        ) {
+            Ok(peer) => peer,
+            Err(_) => return Err(error), // No regular peer can service the request
+        };
+
+        debug!(
+            (LogSchema::new(LogEntry::StorageServiceRequest)
+                .event(LogEvent::RegularPeerFallback)
+                .request_type(request.get_label())
+                .peer(&peer)
+                .error(&error))
+        );
+        self.record_ip_request(&peer);
+        let peer = match self
+            .warm_selected_peer(
+                peer,
+                &request,
+                request_options,
+                Some(PeerTier::Regular),
+                &request_options.excluded_peers,
+            )
+            .await
+        {
+            Ok(peer) => peer,
+            Err(_) => return Err(error), // No (warm) regular peer can service the request
+        };
+        let response_timeout = self.get_response_timeout(request_options);
+        let response = self
+            .send_request_to_peer_with_timeout(peer, request, response_timeout)
+            .await?;
+        Ok((peer, response.into_payload()))
+    }
+
+    /// Polls all connected peers (with a short timeout) and updates the peer
+    /// summaries and the global data summary with the fresh summaries.
+    async fn refresh_summaries_on_gap(&self) {
+        let timeout =
+            Duration::from_millis(self.data_client_config.summary_poller.gap_poll_timeout_ms);
+        let poll_results = self.poll_all_peers(timeout).await;
+        for (peer, result) in poll_results {
+            if let Ok(storage_summary) = result {
+                self.update_summary(peer, storage_summary);
+            }
+        }
+        self.update_global_summary_cache();
+    }
+
+    /// Warms the connection to the selected peer (if required). If the probe
+    /// fails, the peer is excluded and another peer is selected (and warmed)
+    /// in its place, in the same way as the original peer. Returns an error
+    /// if no other peer can service the request.
+    async fn warm_selected_peer(
+        &self,
+        mut peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        request_options: &RequestOptions,
+        peer_tier: Option<PeerTier>,
+        excluded_peers: &HashSet<PeerNetworkId>,
+    ) -> Result<PeerNetworkId> {
+        let mut excluded_peers = excluded_peers.clone();
+        while !self.warm_connection(peer, request, request_options).await {
+            excluded_peers.insert(peer);
+            peer = self.choose_peer_for_request_in_tier(
+                request,
+                request_options.prefer_archival,
+                self.get_selection_policy(request_options),
+                peer_tier,
+                &excluded_peers,
+            )?;
+            self.record_ip_request(&peer);
+        }
+        Ok(peer)
+    }
+
+    /// Sends a cheap probe request to the peer before the first bulk data
+    /// request to the peer (if enabled). This confirms the connection is
+    /// healthy (and warm) before sending bulk data requests. The probe is sent
+    /// (and scored) like any other request to the peer, using the response
+    /// timeout of the bulk request. Returns false iff the probe fails.
+    async fn warm_connection(
+        &self,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> bool {
+        if !self.data_client_config.warm_connection_before_bulk
+            || request.is_get_storage_server_summary()
+            || matches!(request, StorageServiceRequest::GetServerProtocolVersion)
+            || self.warmed_peers.lock().contains(&peer)
+        {
+            return true;
+        }
+
+        let probe_result = self
+            .send_request_to_peer_with_timeout(
+                peer,
+                StorageServiceRequest::GetServerProtocolVersion,
+                self.get_response_timeout(request_options),
+            )
+            .await;
+        match probe_result {
+            Ok(_) => {
+                self.warmed_peers.lock().insert(peer);
+                true
+            }
+            Err(error) => {
+                warn!(
+                    (LogSchema::new(LogEntry::StorageServiceRequest)
+                        .event(LogEvent::ConnectionProbeFailed)
+                        .peer(&peer)
+                        .message(&format!(
+                            "The connection probe to the peer failed: {:?}",
+                            error
+                        )))
+                );
+                false
+            }
+        }
+    }
+
+    /// Forgets the warmed connections of peers that are no longer connected
+    /// (so that their connections are warmed again if they reconnect)
+    fn remove_disconnected_warmed_peers(&self) {
+        let connected_peers = match self.get_all_connected_peers() {
+            Ok(connected_peers) => connected_peers.into_iter().collect::<HashSet<_>>(),
+            Err(_) => return,
+        };
+        self.warmed_peers
+            .lock()
+            .retain(|peer| connected_peers.contains(peer));
+    }
+
+    /// Sends a request to a specific peer and decodes the response
+    async fn send_request_to_peer_and_decode<T, E>(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+    ) -> Result<Response<T>>
+    where
+        T: TryFrom<StorageServiceResponse, Error = E>,
+        E: Into<Error>,
+    {
+        let response = self.send_request_to_peer(peer, request).await?;
+        decode_response(response)
+    }
+
+    /// Sends a request to a specific peer (with the configured response timeout)
+    async fn send_request_to_peer(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+    ) -> Result<Response<StorageServiceResponse>, Error> {
+        let response_timeout = self.get_response_timeout(&RequestOptions::default());
+        self.send_request_to_peer_with_timeout(peer, request, response_timeout)
+            .await
+    }
+
+    /// Sends a request to a specific peer with the given response timeout, and
+    /// notifies the request event subscribers once the request completes
+    async fn send_request_to_peer_with_timeout(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        response_timeout: Duration,
+    ) -> Result<Response<StorageServiceResponse>, Error> {
+        let request_type = request.get_label();
+        let request_start_time = self.time_service.now();
+        let result = self
+            .send_request_to_peer_and_score(peer, request, response_timeout)
+            .await;
+        self.publish_request_event(peer, request_type, request_start_time, &result);
+        result.map(|(response, _)| response)
+    }
+
+    /// Notifies the request event subscribers (if any) of a completed request
+    fn publish_request_event(
+        &self,
+        peer: PeerNetworkId,
+        request_type: &'static str,
+        request_start_time: Instant,
+        result: &Result<(Response<StorageServiceResponse>, u64), Error>,
+    ) {
+        if self.request_event_sender.receiver_count() == 0 {
+            return; // Avoid creating the event if there are no subscribers
+        }
+
+        let (outcome, response_bytes) = match result {
+            Ok((_, response_bytes)) => (Ok(()), *response_bytes),
+            Err(error) => (Err(error.clone()), 0),
+        };
+        let event = RequestCompletionEvent {
+            peer,
+            request_type,
+            outcome,
+            latency: elapsed_since(self.time_service.now(), request_start_time),
+            response_bytes,
+        };
+        let _ = self.request_event_sender.send(event); // Subscribers may have been dropped
+    }
+
+    /// Sends a request to a specific peer with the given response timeout, and
+    /// updates the peer's score according to the response. The response is
+    /// returned along with its size (in bytes) on the wire.
+    async fn send_request_to_peer_and_score(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        response_timeout: Duration,
+    ) -> Result<(Response<StorageServiceResponse>, u64), Error> {
+        let id = self.next_response_id();
+
+        debug!(
+            (LogSchema::new(LogEntry::StorageServiceRequest)
+                .event(LogEvent::SendRequest)
+                .request_type(request.get_label())
+                .request_id(id)
+                .peer(&peer)
+                .request_data(&request))
+        );
+
+        increment_counter(&metrics::SENT_REQUESTS, request.get_label().into());
+        self.request_rate_tracker
+            .lock()
+            .record_request(self.time_service.now());
+        if let Some(data_type) = get_request_data_type(&request) {
+            self.rate_limiter
+                .lock()
+                .record_request(peer, data_type, self.time_service.now());
+        }
+
+        // Wait for an in-flight slot to the peer (if the in-flight requests are
+        // limited). The slot is released when the permit is dropped (i.e., once
+        // the request completes, regardless of the outcome).
+        let peer_semaphore = self.inflight_limiter.lock().get_peer_semaphore(peer);
+        let _inflight_permit = match peer_semaphore {
+            Some(peer_semaphore) => peer_semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+
+        let request_start_time = self.time_service.now();
+        let result = match self
+            .send_cancellable_network_request(id, peer, &request, response_timeout)
+            .await
+        {
+            Ok(result) => result,
+            Err(Aborted) => {
+                // The peer was banned while the request was in-flight. The peer
+                // isn't scored again, as the ban already accounts for it.
+                debug!(
+                    (LogSchema::new(LogEntry::StorageServiceResponse)
+                        .event(LogEvent::InflightRequestsCancelled)
+                        .request_type(request.get_label())
+                        .request_id(id)
+                        .peer(&peer))
+                );
+                return Err(Error::RequestCancelled(format!(
+                    "The peer was banned: {:?}",
+                    peer
+                )));
+            }
+        };
+        observe_duration(
+            &metrics::RPC_LATENCIES,
+            &[request.get_label(), peer.network_id().as_str()],
+            elapsed_since(self.time_service.now(), request_start_time),
+        );
+
+        match result {
+            Ok((response, response_bytes)) => {
+                // Track the size of the (wire) response and the bandwidth at
+                // which the peer served it. Responses that are too large are
+                // rejected by the network client (before being deserialized).
+                observe_value(
+                    &metrics::RESPONSE_SIZES,
+                    request.get_label().into(),
+                    response_bytes,
+                );
+                self.record_serving_bandwidth(peer, response_bytes, request_start_time);
+
+                // Track the latency at which the peer responded
+                let response_latency = elapsed_since(self.time_service.now(), request_start_time);
+                self.peer_states
+                    .write()
+                    .record_response_latency(peer, response_latency);
+
+                // Decompress the response (if the peer compressed it)
+                let response = match self.decompress_response(response) {
+                    Ok(response) => response,
+                    Err(error) => {
+                        error!(
+                            (LogSchema::new(LogEntry::StorageServiceResponse)
+                                .event(LogEvent::ResponseError)
+                                .request_type(request.get_label())
+                                .request_id(id)
+                                .peer(&peer)
+                                .error(&error))
+                        );
+
+                        increment_counter(&metrics::ERROR_RESPONSES, request.get_label().into());
+
+                        self.notify_bad_response(id, peer, &request, ErrorType::NotUseful);
+                        return Err(error);
+                    }
+                };
+
+                debug!(
+                    (LogSchema::new(LogEntry::StorageServiceResponse)
+                        .event(LogEvent::ResponseSuccess)
+                        .request_type(request.get_label())
+                        .request_id(id)
+                        .peer(&peer))
+                );
+
+                increment_counter(&metrics::SUCCESS_RESPONSES, request.get_label().into());
+
+                // For now, record all responses that at least pass the data
+                // client layer successfully. An alternative might also have the
+                // consumer notify both success and failure via the callback.
+                // On the one hand, scoring dynamics are simpler when each request
+                // is successful or failed but not both; on the other hand, this
+                // feels simpler for the consumer.
+                self.peer_states.write().update_score_success(
+                    peer,
+                    self.data_client_config
+                        .peer_scoring
+                        .success_streak_growth_factor,
+                    self.data_client_config
+                        .peer_scoring
+                        .max_success_streak_multiplier,
+                );
+                self.peer_states
+                    .write()
+                    .record_request_outcome(&peer, RequestOutcome::Success);
+                if !request.is_get_storage_server_summary() {
+                    *self.last_data_response_time.write() = Some(self.time_service.now());
+                }
+                self.update_chunk_size_probe(&request, true);
+
+                // Package up all of the context needed to fully report an error
+                // with this RPC.
+                let response_callback = AptosNetResponseCallback {
+                    data_client: self.clone(),
+                    id,
+                    peer,
+                    request,
+                };
+                let context = ResponseContext {
+                    id,
+                    peer: Some(peer),
+                    response_callback: Box::new(response_callback),
+                    trace_context: None,
+                };
+                Ok((Response::new(context, response), response_bytes))
+            }
+            Err(err) => {
+                // Convert network error and storage service error types into
+                // data client errors. Also categorize the error type for scoring
+                // purposes.
+                let mut peer_is_overloaded = false;
+                let mut request_outcome = RequestOutcome::Failure;
+                let mut error_type = ErrorType::NotUseful;
+                let client_err = match err {
+                    storage_service_client::Error::RpcError(err) => match err {
+                        RpcError::NotConnected(_) => Error::DataIsUnavailable(err.to_string()),
+                        RpcError::BcsError(_) => {
+                            // The response couldn't be deserialized (i.e., it's corrupt)
+                            error_type = ErrorType::Malicious;
+                            Error::MalformedResponse(err.to_string())
+                        }
+                        RpcError::TimedOut => {
+                            request_outcome = RequestOutcome::Timeout;
+                            Error::TimeoutWaitingForResponse(err.to_string())
+                        }
+                        _ => Error::NetworkError(err.to_string()),
+                    },
+                    storage_service_client::Error::StorageServiceError(err) => {
+                        if let StorageServiceError::Overloaded { retry_after_ms } = err {
+                            self.handle_overloaded_peer(peer, retry_after_ms);
+                            peer_is_overloaded = true;
+                            Error::PeerIsOverloaded(err.to_string())
+                        } else {
+                            Error::UnexpectedErrorEncountered(err.to_string())
+                        }
+                    }
+                    storage_service_client::Error::ResponseTooLarge(err) => {
+                        Error::ResponseTooLarge(err)
+                    }
+                };
+
+                error!(
+                    (LogSchema::new(LogEntry::StorageServiceResponse)
+                        .event(LogEvent::ResponseError)
+                        .request_type(request.get_label())
+                        .request_id(id)
+                        .peer(&peer)
+                        .error(&client_err))
+                );
+
+                increment_counter(&metrics::ERROR_RESPONSES, request.get_label().into());
+
+                // The connection may be stale, so it must be warmed again
+                self.warmed_peers.lock().remove(&peer);
+                self.peer_states
+                    .write()
+                    .record_request_outcome(&peer, request_outcome);
+
+                // Overloaded peers are cooperatively signaling backpressure,
+                // so we don't penalize them.
+                if !peer_is_overloaded {
+                    self.notify_bad_response(id, peer, &request, error_type);
+                    self.update_chunk_size_probe(&request, false);
+                }
+                Err(client_err)
+            }
+        }
+    }
+
+    /// Sends the request to the peer over the network. If in-flight requests
+    /// are cancelled on bans, the request is tracked (while in-flight) and
+    /// `Aborted` is returned if the peer is banned before a response arrives.
+    /// The request stops being tracked once it completes (or is dropped).
+    async fn send_cancellable_network_request(
+        &self,
+        id: ResponseId,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        response_timeout: Duration,
+    ) -> Result<Result<(StorageServiceResponse, u64), storage_service_client::Error>, Aborted> {
+        if !self.data_client_config.cancel_inflight_on_ban {
+            return Ok(self
+                .send_network_request(peer, request, response_timeout)
+                .await);
+        }
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let _inflight_request_guard =
+            InflightRequestGuard::new(self.inflight_requests.clone(), peer, id, abort_handle);
+        Abortable::new(
+            self.send_network_request(peer, request, response_timeout),
+            abort_registration,
+        )
+        .await
+    }
+
+    /// Sends the request to the peer over the network and returns the response
+    /// along with its size (in bytes) on the wire. Responses larger than the
+    /// max response size are rejected before they are deserialized. If a late
+    /// response grace period is configured, the network waits for the grace
+    /// period after the response timeout. If a response arrives within the
+    /// grace period, the timeout penalty is partially reverted (the peer is
+    /// slow, not broken).
+    async fn send_network_request(
+        &self,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        response_timeout: Duration,
+    ) -> Result<(StorageServiceResponse, u64), storage_service_client::Error> {
+        let network_request = self.create_network_request(peer, request);
+        let max_response_bytes = self.data_client_config.max_response_bytes;
+        let late_response_grace =
+            Duration::from_millis(self.data_client_config.late_response_grace_ms);
+
+        // If there's no grace period, the network enforces the response timeout
+        if late_response_grace.is_zero() {
+            return self
+                .network_client
+                .send_request_with_max_response_bytes(
+                    peer,
+                    network_request,
+                    response_timeout,
+                    max_response_bytes,
+                )
+                .await;
+        }
+
+        // Otherwise, the network enforces the extended timeout and the client
+        // enforces the response timeout.
+        let network_client = self.network_client.clone();
+        let mut network_response = Box::pin(async move {
+            network_client
+                .send_request_with_max_response_bytes(
+                    peer,
+                    network_request,
+                    response_timeout + late_response_grace,
+                    max_response_bytes,
+                )
+                .await
+        });
+        match self
+            .time_service
+            .timeout(response_timeout, &mut network_response)
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                // Wait for a late response in the background
+                let data_client = self.clone();
+                let request_label = request.get_label();
+                tokio::spawn(async move {
+                    if network_response.await.is_ok() {
+                        debug!(
+                            (LogSchema::new(LogEntry::StorageServiceResponse)
+                                .event(LogEvent::LateResponse)
+                                .request_type(request_label)
+                                .peer(&peer)
+                                .message("Received a late response within the grace period"))
+                        );
+                        data_client
+                            .peer_states
+                            .write()
+                            .update_score_late_response(peer);
+                    }
+                });
+                Err(storage_service_client::Error::RpcError(RpcError::TimedOut))
+            }
+        }
+    }
+
+    /// Records the bandwidth at which the peer served a response of the given size
+    fn record_serving_bandwidth(
+        &self,
+        peer: PeerNetworkId,
+        response_bytes: u64,
+        request_start_time: Instant,
+    ) {
+        let elapsed_time = elapsed_since(self.time_service.now(), request_start_time);
+        self.peer_states
+            .write()
+            .record_serving_bandwidth(peer, response_bytes, elapsed_time);
+    }
+
+    /// Stops routing requests to the given peer for the duration requested by
+    /// the peer (bounded by the maximum configured retry-after duration).
+    fn handle_overloaded_peer(&self, peer: PeerNetworkId, retry_after_ms: u64) {
+        let retry_after_ms = min(retry_after_ms, self.data_client_config.max_retry_after_ms);
+        let retry_after_time = self.time_service.now() + Duration::from_millis(retry_after_ms);
+        self.peer_states
+            .write()
+            .update_retry_after_time(peer, retry_after_time);
+    }
+
+    /// Handles a failed summary poll for the given peer. If the peer has failed
+    /// too many consecutive polls, it is excluded from polling for a cooldown.
+    fn handle_summary_poll_failure(&self, peer: PeerNetworkId) {
+        let cooldown = Duration::from_millis(
+            self.data_client_config
+                .summary_poller
+                .summary_poll_failure_cooldown_ms,
+        );
+        let exclusion_end_time = self.time_service.now() + cooldown;
+        let peer_excluded = self.peer_states.write().update_poll_failure(
+            peer,
+            self.data_client_config
+                .summary_poller
+                .max_consecutive_summary_poll_failures,
+            exclusion_end_time,
+        );
+        if peer_excluded {
+            warn!(
+                (LogSchema::new(LogEntry::StorageSummaryResponse)
+                    .event(LogEvent::PollingExclusion)
+                    .message(&format!(
+                        "Too many consecutive poll failures! Excluding peer from polling for: {:?}",
+                        cooldown
+                    ))
+                    .peer(&peer))
+            );
+        }
+    }
+
+    /// Returns the request to send over the network. If partial coverage peers
+    /// are allowed, requests that the peer can only partially service are
+    /// trimmed to the peer's advertised range. If compression algorithms
+    /// are configured, data requests ask the peer for a compressed response.
+    /// Peers that run a protocol version without compression support (or
+    /// whose protocol version hasn't been fetched yet) are sent uncompressed
+    /// requests.
+    fn create_network_request(
+        &self,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+    ) -> StorageServiceRequest {
+        let request = if self
+            .data_client_config
+            .peer_selection
+            .allow_partial_coverage_peers
+        {
+            self.peer_states
+                .read()
+                .trim_request_to_advertised_range(&peer, request)
+        } else {
+            request.clone()
+        };
+
+        let supported_algorithms = &self.data_client_config.preferred_compression_algorithms;
+        if supported_algorithms.is_empty()
+            || request.is_get_storage_server_summary()
+            || !self.peer_states.read().supports_compression(&peer)
+        {
+            request
+        } else {
+            StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+                request: Box::new(request),
+                supported_algorithms: supported_algorithms.clone(),
+            })
+        }
+    }
+
+    /// Decompresses the given response (if it was compressed by the peer).
+    /// Responses compressed using an unsupported algorithm (or that decompress
+    /// to more than the max response size) are rejected.
+    fn decompress_response(
+        &self,
+        response: StorageServiceResponse,
+    ) -> Result<StorageServiceResponse, Error> {
+        match response {
+            StorageServiceResponse::CompressedResponse(compressed_response) => {
+                let compression_algorithm = compressed_response.compression_algorithm;
+                if !self
+                    .data_client_config
+                    .preferred_compression_algorithms
+                    .contains(&compression_algorithm)
+                {
+                    return Err(Error::InvalidResponse(format!(
+                        "The response was compressed using an unsupported algorithm: {:?}",
+                        compression_algorithm
+                    )));
+                }
+                let max_response_bytes = self.data_client_config.max_response_bytes;
+                let max_decompressed_bytes = if max_response_bytes > 0 {
+                    max_response_bytes
+                } else {
+                    MAX_DECOMPRESSED_RESPONSE_BYTES
+                };
+                compressed_response
+                    .decompress(max_decompressed_bytes)
+                    .map_err(|error| Error::InvalidResponse(error.to_string()))
+            }
+            response => Ok(response),
+        }
+    }
+
+    /// Updates the score of the peer who sent the response with the specified id
+    fn notify_bad_response(
+        &self,
+        _id: ResponseId,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        error_type: ErrorType,
+    ) {
+        // Bad data should never be served from the cache (or coalesced)
+        self.coalesced_requests
+            .lock()
+            .retain(|coalescing_key, _| coalescing_key.request() != request);
+        if let StorageServiceRequest::GetEpochEndingLedgerInfos(request) = request {
+            self.epoch_ending_ledger_info_cache
+                .lock()
+                .remove_containing(&(request.start_epoch, request.expected_end_epoch));
+        }
+
+        let mut peer_states = self.peer_states.write();
+        peer_states.update_score_error(peer, error_type);
+        peer_states.record_recent_error(
+            peer,
+            self.time_service.now(),
+            Duration::from_millis(
+                self.data_client_config
+                    .peer_selection
+                    .recent_error_window_ms,
+            ),
+        );
+    }
+
+    /// Rewards the peer who sent the response with the specified id
+    fn notify_good_response(&self, _id: ResponseId, peer: PeerNetworkId, quality: ResponseQuality) {
+        self.peer_states
+            .write()
+            .update_score_good_response(peer, quality);
+    }
+
+    /// Creates a response for data that wasn't received directly from a network
+    /// request (e.g., cached or coalesced data). The peer that originally
+    /// served the data remains responsible for it.
+    fn create_response<T>(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+        payload: T,
+        request_options: &RequestOptions,
+    ) -> Response<T> {
+        let id = self.next_response_id();
+        let trace_context = self.create_request_span(id, &request, request_options);
+        let response_callback = AptosNetResponseCallback {
+            data_client: self.clone(),
+            id,
+            peer,
+            request,
+        };
+        let context = ResponseContext {
+            id,
+            peer: Some(peer),
+            response_callback: Box::new(response_callback),
+            trace_context,
+        };
+        Response::new(context, payload)
+    }
+
+    /// Creates a callback (for a new response id) that holds the given peer
+    /// responsible for the response to the request
+    fn create_response_callback(
+        &self,
+        peer: PeerNetworkId,
+        request: StorageServiceRequest,
+    ) -> Box<dyn ResponseCallback> {
+        Box::new(AptosNetResponseCallback {
+            data_client: self.clone(),
+            id: self.next_response_id(),
+            peer,
+            request,
+        })
+    }
+
+    /// Creates a response that notifies the given callback (e.g., a callback
+    /// shared by all coalesced requests, so that the peer is only penalized
+    /// or rewarded once for the response).
+    fn create_response_with_callback<T>(
+        &self,
+        peer: PeerNetworkId,
+        request: &StorageServiceRequest,
+        payload: T,
+        response_callback: Box<dyn ResponseCallback>,
+        request_options: &RequestOptions,
+    ) -> Response<T> {
+        let id = self.next_response_id();
+        let trace_context = self.create_request_span(id, request, request_options);
+        let context = ResponseContext {
+            id,
+            peer: Some(peer),
+            response_callback,
+            trace_context,
+        };
+        Response::new(context, payload)
+    }
+
+    /// Creates a span for the request as a child of the caller-provided span
+    /// (if any). The span id is the id of the response for the request.
+    fn create_request_span(
+        &self,
+        id: ResponseId,
+        request: &StorageServiceRequest,
+        request_options: &RequestOptions,
+    ) -> Option<TraceContext> {
+        let trace_context = request_options.trace_context?.new_child(id);
+        debug!(
+            (LogSchema::new(LogEntry::StorageServiceRequest)
+                .event(LogEvent::CreateRequestSpan)
+                .request_type(request.get_label())
+                .request_id(id)
+                .trace_context(&trace_context))
+        );
+        Some(trace_context)
+    }
+
+    /// Returns all epoch ending ledger infos between start and end (inclusive),
+    /// using the given request options. Unless the options bypass the cache,
+    /// previously fetched ledger infos are served without a network request.
+    /// All ledger infos are verified according to the requested verification
+    /// level before being returned.
+    pub async fn get_epoch_ending_ledger_infos_with_options(
+        &self,
+        start_epoch: Epoch,
+        expected_end_epoch: Epoch,
+        request_options: RequestOptions,
+    ) -> Result<Response<Vec<LedgerInfoWithSignatures>>> {
+        // Ensure the request doesn't span too many epochs
+        let max_epoch_span = self
+            .data_client_config
+            .chunk_sizes
+            .max_epoch_span_per_request;
+        let epoch_span = expected_end_epoch
+            .saturating_sub(start_epoch)
+            .saturating_add(1);
+        if epoch_span > max_epoch_span {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::exceeds_config_limit(
+                    "chunk_sizes.max_epoch_span_per_request",
+                    max_epoch_span,
+                    epoch_span,
+                ),
+            ));
+        }
+
+        let request =
+            StorageServiceRequest::GetEpochEndingLedgerInfos(EpochEndingLedgerInfoRequest {
+                start_epoch,
+                expected_end_epoch,
+            });
+
+        // Check if the ledger infos are already cached
+        let epoch_range = (start_epoch, expected_end_epoch);
+        let cached_ledger_infos = if request_options.bypass_cache {
+            None
+        } else {
+            self.epoch_ending_ledger_info_cache.lock().get(&epoch_range)
+        };
+
+        // Otherwise, fetch the ledger infos from the network
+        let (response, fetched_from_peer) = match cached_ledger_infos {
+            Some(cached_ledger_infos) => {
+                self.cache_hit_tracker.lock().record_cache_hit();
+                let response = self.create_response(
+                    cached_ledger_infos.peer,
+                    request,
+                    cached_ledger_infos.ledger_infos,
+                    &request_options,
+                );
+                (response, None)
+            }
+            None => {
+                let (peer, response): (_, Response<EpochChangeProof>) = self
+                    .send_request_and_decode_with_peer(request, &request_options)
+                    .await?;
+                let response = response.map(|epoch_change| epoch_change.ledger_info_with_sigs);
+                (response, Some(peer))
+            }
+        };
+
+        // Verify the ledger infos (cached ledger infos may have been
+        // fetched using a weaker verification level).
+        let mut verification_result = verify_epoch_ending_ledger_infos(
+            start_epoch,
+            expected_end_epoch,
+            &response.payload,
+            request_options.verification_level,
+        );
+
+        // Verify that the peer didn't return fewer ledger infos than it advertises
+        if let Some(peer) = fetched_from_peer {
+            if verification_result.is_ok()
+                && self
+                    .data_client_config
+                    .peer_scoring
+                    .penalize_short_epoch_ending_responses
+            {
+                let advertised_end_epoch = self
+                    .peer_states
+                    .read()
+                    .get_advertised_range(&peer, DataType::LedgerInfos)
+                    .map(|advertised_range| advertised_range.highest());
+                verification_result = verify_epoch_ending_coverage(
+                    start_epoch,
+                    expected_end_epoch,
+                    advertised_end_epoch,
+                    &response.payload,
+                );
+            }
+        }
+        if let Err(error) = verification_result {
+            let (context, _) = response.into_parts();
+            context
+                .response_callback
+                .notify_bad_response(error.get_response_error());
+            return Err(error.into());
+        }
+
+        // Update the cache with any newly fetched ledger infos. These are
+        // cached by the epochs returned (the peer may return fewer epochs).
+        if let Some(peer) = fetched_from_peer {
+            self.epoch_ending_ledger_info_cache
+                .lock()
+                .insert(CachedLedgerInfos {
+                    peer,
+                    ledger_infos: response.payload.clone(),
+                });
+        }
+        Ok(response)
+    }
+
+    /// Returns all epoch ending ledger infos between start and end (inclusive),
+    /// fetched in chunks that respect the maximum epoch span per request. One
+    /// response is returned per chunk (so that bad chunks can be reported).
+    pub async fn get_all_epoch_ending_ledger_infos(
+        &self,
+        start_epoch: Epoch,
+        expected_end_epoch: Epoch,
+        request_options: RequestOptions,
+    ) -> Result<Vec<Response<Vec<LedgerInfoWithSignatures>>>> {
+        let max_epoch_span = self
+            .data_client_config
+            .chunk_sizes
+            .max_epoch_span_per_request;
+        if max_epoch_span == 0 {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::InvalidConfigValue {
+                    config_field: "chunk_sizes.max_epoch_span_per_request".into(),
+                    value: max_epoch_span,
+                },
+            ));
+        }
+
+        let mut responses = vec![];
+        let mut chunk_start_epoch = start_epoch;
+        while chunk_start_epoch <= expected_end_epoch {
+            let chunk_end_epoch = min(
+                chunk_start_epoch.saturating_add(max_epoch_span - 1),
+                expected_end_epoch,
+            );
+            let response = self
+                .get_epoch_ending_ledger_infos_with_options(
+                    chunk_start_epoch,
+                    chunk_end_epoch,
+                    request_options.clone(),
+                )
+                .await?;
+            responses.push(response);
+
+            if chunk_end_epoch == expected_end_epoch {
+                break;
+            }
+            chunk_start_epoch = chunk_end_epoch + 1;
+        }
+        Ok(responses)
+    }
+
+    /// Returns the account states chunk at the given version (from the start
+    /// to end account indices, inclusive), using the given request options.
+    pub async fn get_account_states_with_proof_with_options(
+        &self,
+        version: u64,
+        start_account_index: u64,
+        end_account_index: u64,
+        request_options: RequestOptions,
+    ) -> Result<Response<StateValueChunkWithProof>> {
+        let request = StorageServiceRequest::GetAccountStatesChunkWithProof(
+            AccountStatesChunkWithProofRequest {
+                version,
+                start_account_index,
+                end_account_index,
+            },
+        );
+        self.send_request_and_decode(request, &request_options)
+            .await
+    }
+
+    /// Returns a page of the account states at the given version with state
+    /// keys matching the given prefix. The account states are ordered by state
+    /// key, and the page holds (at most) `limit` account states, starting after
+    /// the `cursor` state key (e.g., the last state key of the previous page).
+    /// Note: the account states are returned without a proof.
+    pub async fn get_account_states_by_prefix(
+        &self,
+        version: Version,
+        prefix: StateKeyPrefix,
+        cursor: Option<StateKey>,
+        limit: u64,
+    ) -> Result<Response<Vec<(StateKey, StateValue)>>> {
+        let request =
+            StorageServiceRequest::GetAccountStatesByPrefix(AccountStatesByPrefixRequest {
+                version,
+                prefix,
+                cursor,
+                limit,
+            });
+        self.send_request_and_decode(request, &RequestOptions::default())
+            .await
+    }
+
+    /// Returns the transaction outputs from start to end versions (inclusive),
+    /// using the given request options. If write sets (or events) should be
+    /// omitted, peers that support write set (or event) pruning omit them,
+    /// otherwise the client falls back to fetching the full outputs and
+    /// stripping them locally. Peers only prune one of the two, so if both
+    /// should be omitted, the events are always stripped locally.
+    /// Note: the write set and event hashes in the proof don't apply to
+    /// stripped outputs.
+    pub async fn get_transaction_outputs_with_proof_with_options(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        include_events: bool,
+        request_options: RequestOptions,
+    ) -> Result<Response<TransactionOutputListWithProof>> {
+        let outputs_request = TransactionOutputsWithProofRequest {
+            proof_version,
+            start_version,
+            end_version,
+        };
+        let pruning_request = if request_options.omit_write_sets {
+            Some(
+                StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(
+                    outputs_request.clone(),
+                ),
+            )
+        } else if !include_events {
+            Some(StorageServiceRequest::GetTransactionOutputsWithoutEvents(
+                outputs_request.clone(),
+            ))
+        } else {
+            None
+        };
+
+        // Have the peer prune the outputs (if required and any peer supports
+        // it). Otherwise, fetch the full outputs.
+        let response: Response<TransactionOutputListWithProof> = match pruning_request {
+            Some(pruning_request)
+                if self.has_serviceable_peer(&pruning_request, &request_options) =>
+            {
+                self.send_request_and_decode(pruning_request, &request_options)
+                    .await?
+            }
+            _ => {
+                let request =
+                    StorageServiceRequest::GetTransactionOutputsWithProof(outputs_request);
+                self.send_request_and_decode(request, &request_options)
+                    .await?
+            }
+        };
+
+        // Strip anything the peer didn't prune locally (stripping pruned
+        // outputs has no effect).
+        let response = if request_options.omit_write_sets {
+            response.map(strip_write_sets)
+        } else {
+            response
+        };
+        if include_events {
+            Ok(response)
+        } else {
+            Ok(response.map(strip_events))
+        }
+    }
+
+    /// Returns the transactions from start to end versions (inclusive), using
+    /// the given request options. If a sender filter is given, only the
+    /// transactions sent by that account are returned: peers that support
+    /// sender filtering prune the transactions themselves (with a proof for
+    /// each transaction), otherwise the client falls back to fetching all
+    /// transactions and filtering them locally (leaving them unverified).
+    pub async fn get_transactions_with_proof_with_options(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        include_events: bool,
+        request_options: RequestOptions,
+    ) -> Result<Response<FilteredTransactions>> {
+        if let Some(event_type) = request_options.event_type_filter.clone() {
+            if request_options.sender_filter.is_some()
+                || request_options.max_transaction_bytes.is_some()
+            {
+                return Err(Error::InvalidRequest(InvalidRequestReason::InvalidArguments(
+                    "An event type filter can't be combined with a sender filter or size limit!"
+                        .into(),
+                )));
+            }
+            return self
+                .get_transactions_with_event(
+                    proof_version,
+                    start_version,
+                    end_version,
+                    event_type,
+                    request_options,
+                )
+                .await;
+        }
+
+        if let Some(max_transaction_bytes) = request_options.max_transaction_bytes {
+            if request_options.sender_filter.is_some() {
+                return Err(Error::InvalidRequest(
+                    InvalidRequestReason::InvalidArguments(
+                        "A sender filter and a transaction size limit can't be combined!".into(),
+                    ),
+                ));
+            }
+            return self
+                .get_transactions_with_size_limit(
+                    proof_version,
+                    start_version,
+                    end_version,
+                    include_events,
+                    max_transaction_bytes,
+                    request_options,
+                )
+                .await;
+        }
+
+        let transactions_request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+            });
+        let sender = match request_options.sender_filter {
+            Some(sender) => sender,
+            None => {
+                let response: Response<TransactionListWithProof> = self
+                    .send_request_and_decode(transactions_request, &request_options)
+                    .await?;
+                return Ok(response.map(FilteredTransactions::Unfiltered));
+            }
+        };
+
+        // Have the peer prune the transactions (if any peer supports it)
+        let request =
+            StorageServiceRequest::GetTransactionsFromSender(TransactionsFromSenderRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+                sender,
+            });
+        if self.has_serviceable_peer(&request, &request_options) {
+            let response: Response<Vec<TransactionWithProof>> = self
+                .send_request_and_decode(request, &request_options)
+                .await?;
+            if let Err(error) = verify_transactions_from_sender(
+                start_version,
+                end_version,
+                sender,
+                &response.payload,
+            ) {
+                let (context, _) = response.into_parts();
+                context
+                    .response_callback
+                    .notify_bad_response(error.get_response_error());
+                return Err(error.into());
+            }
+            return Ok(response.map(FilteredTransactions::PrunedByPeer));
+        }
+
+        // Otherwise, fetch all transactions and filter them locally
+        let response: Response<TransactionListWithProof> = self
+            .send_request_and_decode(transactions_request, &request_options)
+            .await?;
+        Ok(response.map(|transaction_list_with_proof| {
+            FilteredTransactions::UnverifiedAfterFilter(filter_transactions_by_sender(
+                transaction_list_with_proof,
+                sender,
+            ))
+        }))
+    }
+
+    /// Returns the transactions from start to end versions (inclusive) that
+    /// emitted at least one event of the given type (together with their
+    /// events). Peers that support event filtering prune the transactions
+    /// themselves, otherwise the client fetches all transactions (and events)
+    /// and flags the matching transactions locally.
+    async fn get_transactions_with_event(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        event_type: TypeTag,
+        request_options: RequestOptions,
+    ) -> Result<Response<FilteredTransactions>> {
+        // Have the peer prune the transactions (if any peer supports it)
+        let request =
+            StorageServiceRequest::GetTransactionsWithEvent(TransactionsWithEventRequest {
+                proof_version,
+                start_version,
+                end_version,
+                event_type: event_type.clone(),
+            });
+        if self.has_serviceable_peer(&request, &request_options) {
+            let response: Response<Vec<TransactionWithProof>> = self
+                .send_request_and_decode(request, &request_options)
+                .await?;
+            if let Err(error) = verify_transactions_with_event(
+                start_version,
+                end_version,
+                &event_type,
+                &response.payload,
+            ) {
+                let (context, _) = response.into_parts();
+                context
+                    .response_callback
+                    .notify_bad_response(error.get_response_error());
+                return Err(error.into());
+            }
+            return Ok(response.map(FilteredTransactions::EventFilteredByPeer));
+        }
+
+        // Otherwise, fetch all transactions (and events) and flag the matches locally
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events: true,
+            });
+        let response: Response<TransactionListWithProof> = self
+            .send_request_and_decode(request, &request_options)
+            .await?;
+        Ok(response.map(|transactions| {
+            let matching_versions = find_transactions_with_event(&transactions, &event_type);
+            FilteredTransactions::FlaggedEventMatches {
+                transactions,
+                matching_versions,
+            }
+        }))
+    }
+
+    /// Returns the transactions from start to end versions (inclusive),
+    /// excluding the transactions larger than the size limit. Peers that
+    /// support size limits exclude the oversized transactions themselves,
+    /// otherwise the client fetches all transactions and flags them locally.
+    async fn get_transactions_with_size_limit(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        include_events: bool,
+        max_transaction_bytes: usize,
+        request_options: RequestOptions,
+    ) -> Result<Response<FilteredTransactions>> {
+        // Have the peer exclude the oversized transactions (if any peer supports it)
+        let request =
+            StorageServiceRequest::GetTransactionsWithSizeLimit(TransactionsWithSizeLimitRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+                max_transaction_bytes: max_transaction_bytes as u64,
+            });
+        if self.has_serviceable_peer(&request, &request_options) {
+            let response: Response<SizeLimitedTransactions> = self
+                .send_request_and_decode(request, &request_options)
+                .await?;
+            if let Err(error) = verify_size_limited_transactions(
+                start_version,
+                end_version,
+                max_transaction_bytes,
+                &response.payload,
+            ) {
+                let (context, _) = response.into_parts();
+                context
+                    .response_callback
+                    .notify_bad_response(error.get_response_error());
+                return Err(error.into());
+            }
+            return Ok(response.map(FilteredTransactions::SizeLimitedByPeer));
+        }
+
+        // Otherwise, fetch all transactions and flag the oversized ones locally
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+            });
+        let response: Response<TransactionListWithProof> = self
+            .send_request_and_decode(request, &request_options)
+            .await?;
+        Ok(response.map(|transactions| {
+            let oversized_versions =
+                find_oversized_transactions(&transactions, max_transaction_bytes);
+            FilteredTransactions::FlaggedOversized {
+                transactions,
+                oversized_versions,
+            }
+        }))
+    }
+
+    /// Fetches the transactions for each of the given ranges concurrently, and
+    /// returns one result per range (in the order of the given requests). Each
+    /// range is sent to a peer that advertises it, and distinct peers are
+    /// preferred across the batch (so that a single slow peer doesn't serve
+    /// the entire batch). A failed range doesn't affect the other ranges.
+    pub async fn get_transactions_with_proof_batch(
+        &self,
+        requests: Vec<TransactionsWithProofRequest>,
+    ) -> Vec<Result<Response<TransactionListWithProof>>> {
+        if self.is_in_maintenance() {
+            return requests.iter().map(|_| Err(Error::InMaintenance)).collect();
+        }
+
+        // Exclude the peers selected for the earlier requests in the batch from
+        // each request, unless no other peer can service the request.
+        let mut selected_peers = HashSet::new();
+        let requests_and_options = requests
+            .into_iter()
+            .map(|request| {
+                let request = StorageServiceRequest::GetTransactionsWithProof(request);
+                let mut request_options = RequestOptions::default();
+                if let Ok(peer) = self.choose_peer_for_request_in_tier(
+                    &request,
+                    false,
+                    self.data_client_config.peer_selection.selection_policy,
+                    None,
+                    &selected_peers,
+                ) {
+                    request_options.excluded_peers = selected_peers.clone();
+                    selected_peers.insert(peer);
+                }
+                (request, request_options)
+            })
+            .collect::<Vec<_>>();
+
+        // Send the requests concurrently
+        join_all(
+            requests_and_options
+                .into_iter()
+                .map(|(request, request_options)| async move {
+                    self.send_request_and_decode(request, &request_options)
+                        .await
+                }),
+        )
+        .await
+    }
+
+    /// Fetches the transactions with proof from the given number of peers
+    /// (see `choose_cross_check_peers`), and returns the response iff all
+    /// peers returned identical transactions. This protects against a single
+    /// equivocating peer. If the responses conflict, an error is returned (but
+    /// no peer is penalized, as the faulty peer can't be identified).
+    pub async fn get_transactions_with_proof_cross_checked(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        include_events: bool,
+        num_peers: usize,
+    ) -> Result<Response<TransactionListWithProof>> {
+        if self.is_in_maintenance() {
+            return Err(Error::InMaintenance);
+        }
+
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+            });
+        let peers = self.choose_cross_check_peers(&request, num_peers)?;
+        let mut responses = join_all(peers.iter().map(|peer| {
+            self.record_ip_request(peer);
+            self.send_request_to_peer_and_decode(*peer, request.clone())
+        }))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<Response<TransactionListWithProof>>>>()?;
+        if responses.is_empty() {
+            return Err(Error::DataIsUnavailable(
+                "No peers were chosen to cross-check the request!".into(),
+            ));
+        }
+
+        // Verify that all peers returned the same transactions
+        if responses
+            .iter()
+            .any(|response| response.payload != responses[0].payload)
+        {
+            let error = Error::InvalidResponse(format!(
+                "The cross-checked peers returned conflicting responses! Peers: {:?}, request: {:?}",
+                peers, request
+            ));
+            warn!(
+                (LogSchema::new(LogEntry::StorageServiceResponse)
+                    .event(LogEvent::CrossCheckMismatch)
+                    .message("Cross-checked peers returned conflicting transactions")
+                    .error(&error))
+            );
+            return Err(error);
+        }
+        Ok(responses.swap_remove(0))
+    }
+
+    /// Returns a stream of the transactions from start to end versions
+    /// (inclusive), fetched in order and in chunks of the optimal transaction
+    /// chunk size. The chunk size is re-read from the global data summary
+    /// before each chunk (so that updates by the poller take effect
+    /// mid-stream). The stream ends after the first error.
+    ///
+    /// If proof anchor enforcement is enabled, every chunk must be proven
+    /// against the given proof version. Chunks proven against a different
+    /// version are rejected (and the peers penalized), and re-fetched from
+    /// other peers.
+    pub fn stream_transactions(
+        &self,
+        start_version: Version,
+        end_version: Version,
+        proof_version: Version,
+        include_events: bool,
+    ) -> impl Stream<Item = Result<Response<TransactionListWithProof>>> {
+        let data_client = self.clone();
+        stream::unfold(Some(start_version), move |next_version| {
+            let data_client = data_client.clone();
+            async move {
+                let chunk_start_version = next_version.filter(|version| *version <= end_version)?;
+                let (chunk_end_version, result) = data_client
+                    .get_next_transaction_chunk(
+                        chunk_start_version,
+                        end_version,
+                        proof_version,
+                        include_events,
+                    )
+                    .await;
+                let next_version = match result {
+                    Ok(_) => chunk_end_version.checked_add(1),
+                    Err(_) => None,
+                };
+                Some((result, next_version))
+            }
+        })
+    }
+
+    /// Fetches the next chunk of a transaction stream (starting at the given
+    /// start version), and returns the end version of the chunk along with
+    /// the response.
+    async fn get_next_transaction_chunk(
+        &self,
+        start_version: Version,
+        end_version: Version,
+        proof_version: Version,
+        include_events: bool,
+    ) -> (Version, Result<Response<TransactionListWithProof>>) {
+        let global_data_summary = self.get_global_data_summary();
+        let chunk_size = global_data_summary
+            .optimal_chunk_sizes
+            .transaction_chunk_size;
+        if chunk_size == 0 {
+            let error =
+                Error::DataIsUnavailable("The optimal transaction chunk size is unknown!".into());
+            return (start_version, Err(error));
+        }
+
+        let chunk_end_version = min(end_version, start_version.saturating_add(chunk_size - 1));
+        if !AdvertisedData::contains_range(
+            start_version,
+            chunk_end_version,
+            &global_data_summary.advertised_data.transactions,
+        ) {
+            let error = Error::DataIsUnavailable(format!(
+                "No peers advertise the transactions from {} to {}!",
+                start_version, chunk_end_version
+            ));
+            return (chunk_end_version, Err(error));
+        }
+
+        let result = if self.data_client_config.enforce_stream_proof_anchor {
+            self.get_anchored_transactions_with_proof(
+                proof_version,
+                start_version,
+                chunk_end_version,
+                include_events,
+            )
+            .await
+        } else {
+            self.get_transactions_with_proof(
+                proof_version,
+                start_version,
+                chunk_end_version,
+                include_events,
+            )
+            .await
+        };
+        (chunk_end_version, result)
+    }
 
-        let (context, payload) = response.into_parts();
+    /// Fetches the transactions and verifies that they're proven against the
+    /// given proof version. Responses proven against a different version are
+    /// rejected (and the peers penalized), and the transactions are re-fetched
+    /// from another peer (until no other peer can service the request).
+    async fn get_anchored_transactions_with_proof(
+        &self,
+        proof_version: Version,
+        start_version: Version,
+        end_version: Version,
+        include_events: bool,
+    ) -> Result<Response<TransactionListWithProof>> {
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version,
+                start_version,
+                end_version,
+                include_events,
+            });
+        let mut request_options = RequestOptions::default();
+        loop {
+            let response: Response<TransactionListWithProof> = self
+                .send_request_and_decode(request.clone(), &request_options)
+                .await?;
+            let verification_error =
+                match verify_transaction_proof_anchor(proof_version, &response.payload) {
+                    Ok(()) => return Ok(response),
+                    Err(verification_error) => verification_error,
+                };
 
-        // try to convert the storage service enum into the exact variant we're expecting.
-        match T::try_from(payload) {
-            Ok(new_payload) => Ok(Response::new(context, new_payload)),
-            // if the variant doesn't match what we're expecting, report the issue.
-            Err(err) => {
-                context
-                    .response_callback
-                    .notify_bad_response(ResponseError::InvalidPayloadDataType);
-                Err(err.into())
+            // Reject the response and re-route the request to another peer
+            let (context, _) = response.into_parts();
+            context
+                .response_callback
+                .notify_bad_response(verification_error.get_response_error());
+            let error = Error::from(verification_error);
+            warn!(
+                (LogSchema::new(LogEntry::StorageServiceResponse)
+                    .event(LogEvent::ProofAnchorMismatch)
+                    .message("Rejecting transactions proven against a different proof version")
+                    .error(&error))
+            );
+            match context.peer {
+                Some(peer) => {
+                    request_options.excluded_peers.insert(peer);
+                }
+                None => return Err(error),
             }
         }
     }
 
-    /// Sends a request to a specific peer
-    async fn send_request_to_peer(
+    /// Returns the transaction accumulator inclusion proof for each of the
+    /// given versions (in order), relative to the accumulator at the
+    /// `accumulator_version`. This allows light clients that maintain their own
+    /// accumulator to verify transactions without fetching the full data.
+    pub async fn get_inclusion_proofs(
         &self,
-        peer: PeerNetworkId,
-        request: StorageServiceRequest,
-    ) -> Result<Response<StorageServiceResponse>, Error> {
-        let id = self.next_response_id();
+        versions: Vec<Version>,
+        accumulator_version: Version,
+    ) -> Result<Response<Vec<TransactionAccumulatorProof>>, Error> {
+        if versions.is_empty() {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::InvalidArguments(
+                    "At least one version must be requested!".into(),
+                ),
+            ));
+        }
+        if let Some(version) = versions
+            .iter()
+            .find(|version| **version > accumulator_version)
+        {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::InvalidArguments(format!(
+                    "Requested version {:?} is higher than the accumulator version {:?}!",
+                    version, accumulator_version
+                )),
+            ));
+        }
 
-        debug!(
-            (LogSchema::new(LogEntry::StorageServiceRequest)
-                .event(LogEvent::SendRequest)
-                .request_type(request.get_label())
-                .request_id(id)
-                .peer(&peer)
-                .request_data(&request))
+        let request = StorageServiceRequest::GetTransactionInclusionProofs(
+            TransactionInclusionProofsRequest {
+                versions: versions.clone(),
+                accumulator_version,
+            },
         );
+        let response: Response<Vec<TransactionAccumulatorProof>> = self
+            .send_request_and_decode(request, &RequestOptions::default())
+            .await?;
+        if let Err(error) =
+            verify_inclusion_proofs(&versions, accumulator_version, &response.payload)
+        {
+            let (context, _) = response.into_parts();
+            context
+                .response_callback
+                .notify_bad_response(error.get_response_error());
+            return Err(error.into());
+        }
+        Ok(response)
+    }
 
-        increment_counter(&metrics::SENT_REQUESTS, request.get_label().into());
+    /// Returns the transactions between the given trusted versions (i.e., the
+    /// versions after `trusted_low`, up to and including `trusted_high`),
+    /// proven against the ledger info at `trusted_high`. This is the minimal
+    /// data needed to connect the two trusted versions: nothing at or before
+    /// `trusted_low` (which is already trusted) is fetched. Note: the proof
+    /// must be verified by the caller using the ledger info at `trusted_high`.
+    pub async fn get_minimal_transaction_proof(
+        &self,
+        trusted_low: Version,
+        trusted_high: Version,
+    ) -> Result<Response<TransactionListWithProof>, Error> {
+        if trusted_low >= trusted_high {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::InvalidArguments(format!(
+                "The trusted low version {:?} must be lower than the trusted high version {:?}!",
+                trusted_low, trusted_high
+            )),
+            ));
+        }
 
-        let result = self
-            .network_client
-            .send_request(
-                peer,
-                request.clone(),
-                Duration::from_millis(self.data_client_config.response_timeout_ms),
-            )
-            .await;
+        let request =
+            StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+                proof_version: trusted_high,
+                start_version: trusted_low + 1,
+                end_version: trusted_high,
+                include_events: false,
+            });
+        let response: Response<TransactionListWithProof> = self
+            .send_request_and_decode(request, &RequestOptions::default())
+            .await?;
+        if let Err(error) =
+            verify_transaction_gap_coverage(trusted_low, trusted_high, &response.payload)
+        {
+            let (context, _) = response.into_parts();
+            context
+                .response_callback
+                .notify_bad_response(error.get_response_error());
+            return Err(error.into());
+        }
+        Ok(response)
+    }
 
-        match result {
-            Ok(response) => {
-                debug!(
-                    (LogSchema::new(LogEntry::StorageServiceResponse)
-                        .event(LogEvent::ResponseSuccess)
-                        .request_type(request.get_label())
-                        .request_id(id)
-                        .peer(&peer))
-                );
+    /// Returns a state proof that allows a light client at the known version
+    /// (with the given trusted epoch state) to advance to the target version
+    /// (across any number of epochs). The proof is composed of the ledger info
+    /// at the target version (as advertised by a peer) and the epoch changes
+    /// since the trusted epoch (served via the epoch ending ledger info cache).
+    /// The epoch changes are verified to form a continuous chain from the
+    /// trusted epoch state, and the target ledger info is verified against the
+    /// validator set of its epoch. The response is attributed to the peer that
+    /// advertised the target ledger info.
+    /// Note: the consistency proof of the returned state proof is empty.
+    pub async fn get_state_proof_across_epoch(
+        &self,
+        known_version: Version,
+        trusted_epoch_state: &EpochState,
+        target_version: Version,
+    ) -> Result<Response<StateProof>, Error> {
+        if known_version > target_version {
+            return Err(Error::InvalidRequest(
+                InvalidRequestReason::InvalidArguments(format!(
+                    "The known version {:?} is higher than the target version {:?}!",
+                    known_version, target_version
+                )),
+            ));
+        }
 
-                increment_counter(&metrics::SUCCESS_RESPONSES, request.get_label().into());
+        // Find a peer that advertises the ledger info at the target version
+        // (and that can service the epoch changes since the trusted epoch).
+        let trusted_epoch = trusted_epoch_state.epoch;
+        let (peer, target_ledger_info) = self
+            .peer_states
+            .read()
+            .find_synced_ledger_info(target_version, trusted_epoch)
+            .ok_or_else(|| {
+                Error::DataIsUnavailable(format!(
+                    "No peers are advertising a ledger info at the target version: {:?}",
+                    target_version
+                ))
+            })?;
 
-                // For now, record all responses that at least pass the data
-                // client layer successfully. An alternative might also have the
-                // consumer notify both success and failure via the callback.
-                // On the one hand, scoring dynamics are simpler when each request
-                // is successful or failed but not both; on the other hand, this
-                // feels simpler for the consumer.
-                self.peer_states.write().update_score_success(peer);
+        // The state proof is attributed to the request for the epoch changes.
+        // If the target is in the trusted epoch, there are no epoch changes and
+        // the target ledger info was served by the storage summary request.
+        let target_epoch = target_ledger_info.ledger_info().epoch();
+        let request = create_epoch_changes_request(trusted_epoch, target_epoch)
+            .unwrap_or(StorageServiceRequest::GetStorageServerSummary);
 
-                // Package up all of the context needed to fully report an error
-                // with this RPC.
-                let response_callback = AptosNetResponseCallback {
-                    data_client: self.clone(),
-                    id,
-                    peer,
-                    request,
-                };
-                let context = ResponseContext {
-                    id,
-                    response_callback: Box::new(response_callback),
-                };
-                Ok(Response::new(context, response))
-            }
-            Err(err) => {
-                // Convert network error and storage service error types into
-                // data client errors. Also categorize the error type for scoring
-                // purposes.
-                let client_err = match err {
-                    storage_service_client::Error::RpcError(err) => match err {
-                        RpcError::NotConnected(_) => Error::DataIsUnavailable(err.to_string()),
-                        RpcError::TimedOut => Error::TimeoutWaitingForResponse(err.to_string()),
-                        _ => Error::UnexpectedErrorEncountered(err.to_string()),
-                    },
-                    storage_service_client::Error::StorageServiceError(err) => {
-                        Error::UnexpectedErrorEncountered(err.to_string())
+        // Fetch the (fully verified) epoch changes since the trusted epoch, and
+        // verify that each chunk continues the epoch state verified so far.
+        let mut epoch_changes = vec![];
+        let mut epoch_state = trusted_epoch_state.clone();
+        if target_epoch > trusted_epoch {
+            let request_options = RequestOptions {
+                verification_level: VerificationLevel::Full,
+                ..Default::default()
+            };
+            let responses = self
+                .get_all_epoch_ending_ledger_infos(trusted_epoch, target_epoch - 1, request_options)
+                .await?;
+            for response in responses {
+                let (context, ledger_infos) = response.into_parts();
+                match verify_epoch_change_continuity(known_version, &epoch_state, &ledger_infos) {
+                    Ok(next_epoch_state) => epoch_state = next_epoch_state.clone(),
+                    Err(error) => {
+                        context
+                            .response_callback
+                            .notify_bad_response(error.get_response_error());
+                        return Err(error.into());
                     }
-                };
-
-                error!(
-                    (LogSchema::new(LogEntry::StorageServiceResponse)
-                        .event(LogEvent::ResponseError)
-                        .request_type(request.get_label())
-                        .request_id(id)
-                        .peer(&peer)
-                        .error(&client_err))
-                );
-
-                increment_counter(&metrics::ERROR_RESPONSES, request.get_label().into());
-
-                self.notify_bad_response(id, peer, &request, ErrorType::NotUseful);
-                Err(client_err)
+                }
+                epoch_changes.extend(ledger_infos);
             }
         }
-    }
 
-    /// Updates the score of the peer who sent the response with the specified id
-    fn notify_bad_response(
-        &self,
-        _id: ResponseId,
-        peer: PeerNetworkId,
-        _request: &StorageServiceRequest,
-        error_type: ErrorType,
-    ) {
-        self.peer_states
-            .write()
-            .update_score_error(peer, error_type);
+        // Compose the state proof and verify the target ledger info
+        let state_proof = StateProof::new(
+            target_ledger_info,
+            EpochChangeProof::new(epoch_changes, false),
+            AccumulatorConsistencyProof::new(vec![]),
+        );
+        let response = self.create_response(peer, request, state_proof, &RequestOptions::default());
+        let verification_result =
+            verify_target_ledger_info(&epoch_state, response.payload.latest_ledger_info_w_sigs());
+        if let Err(error) = verification_result {
+            let (context, _) = response.into_parts();
+            context
+                .response_callback
+                .notify_bad_response(error.get_response_error());
+            return Err(error.into());
+        }
+        Ok(response)
     }
 }
 
@@ -381,14 +3962,13 @@ impl AptosDataClient for AptosNetDataClient {
         start_account_index: u64,
         end_account_index: u64,
     ) -> Result<Response<StateValueChunkWithProof>> {
-        let request = StorageServiceRequest::GetAccountStatesChunkWithProof(
-            AccountStatesChunkWithProofRequest {
-                version,
-                start_account_index,
-                end_account_index,
-            },
-        );
-        self.send_request_and_decode(request).await
+        self.get_account_states_with_proof_with_options(
+            version,
+            start_account_index,
+            end_account_index,
+            RequestOptions::default(),
+        )
+        .await
     }
 
     async fn get_epoch_ending_ledger_infos(
@@ -396,18 +3976,18 @@ impl AptosDataClient for AptosNetDataClient {
         start_epoch: Epoch,
         expected_end_epoch: Epoch,
     ) -> Result<Response<Vec<LedgerInfoWithSignatures>>> {
-        let request =
-            StorageServiceRequest::GetEpochEndingLedgerInfos(EpochEndingLedgerInfoRequest {
-                start_epoch,
-                expected_end_epoch,
-            });
-        let response: Response<EpochChangeProof> = self.send_request_and_decode(request).await?;
-        Ok(response.map(|epoch_change| epoch_change.ledger_info_with_sigs))
+        self.get_epoch_ending_ledger_infos_with_options(
+            start_epoch,
+            expected_end_epoch,
+            RequestOptions::default(),
+        )
+        .await
     }
 
     async fn get_number_of_account_states(&self, version: Version) -> Result<Response<u64>> {
         let request = StorageServiceRequest::GetNumberOfAccountsAtVersion(version);
-        self.send_request_and_decode(request).await
+        self.send_request_and_decode(request, &RequestOptions::default())
+            .await
     }
 
     async fn get_transaction_outputs_with_proof(
@@ -415,15 +3995,16 @@ impl AptosDataClient for AptosNetDataClient {
         proof_version: Version,
         start_version: Version,
         end_version: Version,
+        include_events: bool,
     ) -> Result<Response<TransactionOutputListWithProof>> {
-        let request = StorageServiceRequest::GetTransactionOutputsWithProof(
-            TransactionOutputsWithProofRequest {
-                proof_version,
-                start_version,
-                end_version,
-            },
-        );
-        self.send_request_and_decode(request).await
+        self.get_transaction_outputs_with_proof_with_options(
+            proof_version,
+            start_version,
+            end_version,
+            include_events,
+            RequestOptions::default(),
+        )
+        .await
     }
 
     async fn get_transactions_with_proof(
@@ -440,7 +4021,8 @@ impl AptosDataClient for AptosNetDataClient {
                 end_version,
                 include_events,
             });
-        self.send_request_and_decode(request).await
+        self.send_request_and_decode(request, &RequestOptions::default())
+            .await
     }
 }
 
@@ -458,6 +4040,11 @@ impl ResponseCallback for AptosNetResponseCallback {
         self.data_client
             .notify_bad_response(self.id, self.peer, &self.request, error_type);
     }
+
+    fn notify_good_response(&self, quality: ResponseQuality) {
+        self.data_client
+            .notify_good_response(self.id, self.peer, quality);
+    }
 }
 
 impl fmt::Debug for AptosNetResponseCallback {
@@ -475,6 +4062,8 @@ pub struct DataSummaryPoller {
     time_service: TimeService,
     data_client: AptosNetDataClient,
     poll_interval: Duration,
+    first_poll_time: Instant, // The time at which the first poll round can start
+    peer_connected_receiver: mpsc::Receiver<()>, // Notifications of newly connected peers
 }
 
 impl DataSummaryPoller {
@@ -482,26 +4071,86 @@ impl DataSummaryPoller {
         time_service: TimeService,
         data_client: AptosNetDataClient,
         poll_interval: Duration,
+        startup_delay: Duration,
+        peer_connected_receiver: mpsc::Receiver<()>,
     ) -> Self {
+        // Delaying the first poll round gives connections time to stabilize
+        // (so that freshly-connecting peers aren't penalized for early failures).
+        let first_poll_time = time_service.now() + startup_delay;
         Self {
             time_service,
             data_client,
             poll_interval,
+            first_poll_time,
+            peer_connected_receiver,
         }
     }
 
     /// Runs the poller that continuously updates the global data summary
-    pub async fn start_poller(self) {
+    pub async fn start_poller(mut self) {
+        // The stop sender is held (and never signalled) until the poller exits
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        self.run_poller(stop_receiver).await
+    }
+
+    /// Runs the poller until a stop is signalled on the given receiver. The
+    /// stop signal is only checked while waiting for the next round and
+    /// between the polled peers (so received summaries are always processed).
+    async fn run_poller(&mut self, mut stop_receiver: watch::Receiver<bool>) {
         info!(
             (LogSchema::new(LogEntry::DataSummaryPoller)
                 .message("Starting the Aptos data poller!"))
         );
-        let ticker = self.time_service.interval(self.poll_interval);
+        let ticker = self
+            .time_service
+            .interval_at(self.first_poll_time, self.poll_interval);
         futures::pin_mut!(ticker);
 
+        let mut last_poll_round_time = None;
+        let mut last_persistence_time = None;
         loop {
-            // Wait for next round before polling
-            ticker.next().await;
+            // Exit if the poller was stopped (e.g., during the last round)
+            if *stop_receiver.borrow() {
+                break;
+            }
+
+            // Wait for next round before polling (or until the poller is stopped)
+            let poll_round_time = {
+                let next_poll_round = async {
+                    if self.is_poll_interval_adaptive() {
+                        // Schedule the next round relative to the start of the last
+                        // round (so missed rounds are implicitly coalesced).
+                        let next_poll_round_time = match last_poll_round_time {
+                            Some(last_poll_round_time) => {
+                                last_poll_round_time + self.adaptive_poll_interval()
+                            }
+                            None => self.first_poll_time,
+                        };
+                        self.time_service.sleep_until(next_poll_round_time).await;
+                        self.time_service.now()
+                    } else {
+                        // Note: the ticker's delay is reset relative to the time it
+                        // fires, so if time jumped forward, the missed rounds are
+                        // coalesced into a single catch-up round.
+                        ticker.next().await;
+                        let poll_round_time = self.time_service.now();
+                        if let Some(last_poll_round_time) = last_poll_round_time {
+                            self.log_missed_poll_rounds(last_poll_round_time, poll_round_time);
+                        }
+                        poll_round_time
+                    }
+                };
+                let poller_stopped = wait_for_stop(&mut stop_receiver);
+                futures::pin_mut!(next_poll_round, poller_stopped);
+                match future::select(next_poll_round, poller_stopped).await {
+                    Either::Left((poll_round_time, _)) => poll_round_time,
+                    Either::Right(_) => break,
+                }
+            };
+            last_poll_round_time = Some(poll_round_time);
+
+            // Forget the warmed connections of disconnected peers
+            self.data_client.remove_disconnected_warmed_peers();
 
             // Fetch the peers to poll
             let peers_to_poll = match self.data_client.fetch_peers_to_poll() {
@@ -516,6 +4165,12 @@ impl DataSummaryPoller {
                                 .error(&error))
                         );
                     );
+
+                    // Idle until a peer connects (or the idle interval elapses).
+                    // The missed poll rounds are expected, so they aren't logged.
+                    if self.idle_until_peer_connects().await {
+                        last_poll_round_time = None;
+                    }
                     continue;
                 }
             };
@@ -534,6 +4189,11 @@ impl DataSummaryPoller {
 
             // Go through each peer and poll individually
             for peer in peers_to_poll {
+                // Stop polling the remaining peers if the poller was stopped
+                if *stop_receiver.borrow() {
+                    break;
+                }
+
                 // Start the peer polling timer
                 let timer = start_timer(
                     &metrics::REQUEST_LATENCIES,
@@ -564,13 +4224,21 @@ impl DataSummaryPoller {
                                 .error(&error)
                                 .peer(&peer))
                         );
+                        self.data_client.handle_summary_poll_failure(peer);
                         continue;
                     }
                 };
 
                 // Update the global storage summary and the summary for the peer
+                // (and probe the peer's recovery after a ban, if required)
                 self.data_client.update_summary(peer, storage_summary);
-                self.data_client.update_global_summary_cache();
+                self.data_client.probe_peer_recovery_if_required(peer).await;
+                self.data_client.update_global_summary_cache_if_changed();
+
+                // Fetch the protocol version run by the peer (if required)
+                self.data_client
+                    .fetch_server_protocol_version_if_required(peer)
+                    .await;
 
                 // Log the new global data summary and update the metrics
                 sample!(
@@ -590,10 +4258,382 @@ impl DataSummaryPoller {
                     update_advertised_data_metrics(global_data_summary);
                 );
             }
+
+            // Persist the global data summary (if required)
+            self.maybe_persist_global_summary(&mut last_persistence_time);
+        }
+
+        info!(
+            (LogSchema::new(LogEntry::DataSummaryPoller).message("Stopped the Aptos data poller!"))
+        );
+    }
+
+    /// Idles until a peer connects or the idle interval elapses (if idling is
+    /// enabled). Returns true iff the poller idled.
+    async fn idle_until_peer_connects(&mut self) -> bool {
+        let no_peers_idle_interval_ms = self
+            .data_client
+            .data_client_config
+            .summary_poller
+            .no_peers_idle_interval_ms;
+        if no_peers_idle_interval_ms == 0 {
+            return false;
+        }
+
+        let _ = self
+            .time_service
+            .timeout(
+                Duration::from_millis(no_peers_idle_interval_ms),
+                self.peer_connected_receiver.next(),
+            )
+            .await;
+        true
+    }
+
+    /// Persists the global data summary if persistence is enabled and the
+    /// persistence interval has elapsed since the summary was last persisted.
+    fn maybe_persist_global_summary(&self, last_persistence_time: &mut Option<Instant>) {
+        let data_client_config = &self.data_client.data_client_config;
+        if data_client_config.summary_persistence.path.is_none() {
+            return;
+        }
+
+        let time_now = self.time_service.now();
+        let persistence_interval =
+            Duration::from_millis(data_client_config.summary_persistence.interval_ms);
+        if let Some(last_persistence_time) = last_persistence_time {
+            if elapsed_since(time_now, *last_persistence_time) < persistence_interval {
+                return;
+            }
+        }
+        *last_persistence_time = Some(time_now);
+
+        if let Err(error) = self.data_client.persist_global_summary() {
+            warn!(
+                (LogSchema::new(LogEntry::DataSummaryPoller)
+                    .event(LogEvent::SummaryPersistenceError)
+                    .message("Unable to persist the global data summary!")
+                    .error(&error))
+            );
+        }
+    }
+
+    /// Returns true iff the poll interval adapts to the number of connected peers
+    fn is_poll_interval_adaptive(&self) -> bool {
+        self.data_client
+            .data_client_config
+            .summary_poller
+            .summary_poll_interval_per_peer_ms
+            > 0
+    }
+
+    /// Returns the interval until the next poll round, scaled by the number
+    /// of currently connected peers (i.e., fewer peers are polled more often),
+    /// and bounded by the configured min and max intervals.
+    fn adaptive_poll_interval(&self) -> Duration {
+        let data_client_config = &self.data_client.data_client_config;
+        let num_connected_peers = self
+            .data_client
+            .get_all_connected_peers()
+            .map(|connected_peers| connected_peers.len() as u64)
+            .unwrap_or(0);
+        let poll_interval_ms = data_client_config
+            .summary_poller
+            .summary_poll_interval_per_peer_ms
+            .saturating_mul(num_connected_peers);
+        let poll_interval_ms = max(
+            data_client_config
+                .summary_poller
+                .min_summary_poll_interval_ms,
+            min(
+                poll_interval_ms,
+                data_client_config
+                    .summary_poller
+                    .max_summary_poll_interval_ms,
+            ),
+        );
+        Duration::from_millis(poll_interval_ms)
+    }
+
+    /// Logs the number of poll rounds that were missed (and coalesced into
+    /// the current round) between the last poll round and the current one.
+    fn log_missed_poll_rounds(&self, last_poll_round_time: Instant, poll_round_time: Instant) {
+        let elapsed_time = elapsed_since(poll_round_time, last_poll_round_time);
+        let elapsed_rounds = elapsed_time.as_nanos() / self.poll_interval.as_nanos();
+        if elapsed_rounds > 1 {
+            debug!(
+                (LogSchema::new(LogEntry::DataSummaryPoller)
+                    .event(LogEvent::MissedPollRounds)
+                    .message(&format!(
+                        "Coalesced {} missed poll rounds into a single catch-up round",
+                        elapsed_rounds - 1
+                    )))
+            );
+        }
+    }
+}
+
+/// A handle that starts and stops the data summary poller (e.g., to pause
+/// polling during reconfiguration). The poller can be restarted after it has
+/// been stopped. Note: the poller is spawned on the current tokio runtime.
+pub struct PollerHandle {
+    poller: Arc<tokio::sync::Mutex<DataSummaryPoller>>,
+    stop_sender: Option<watch::Sender<bool>>, // Only set while the poller is running
+}
+
+impl PollerHandle {
+    pub fn new(poller: DataSummaryPoller) -> Self {
+        Self {
+            poller: Arc::new(tokio::sync::Mutex::new(poller)),
+            stop_sender: None,
+        }
+    }
+
+    /// Starts the poller (if it isn't already running). If the poller was just
+    /// stopped, the new run only starts once the previous run has exited.
+    pub fn start(&mut self) {
+        if self.is_running() {
+            return;
+        }
+
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        self.stop_sender = Some(stop_sender);
+        let poller = self.poller.clone();
+        tokio::spawn(async move {
+            poller.lock().await.run_poller(stop_receiver).await;
+        });
+    }
+
+    /// Stops the poller. The poller exits once the summary currently being
+    /// polled (if any) has been processed, so received summaries aren't dropped.
+    pub fn stop(&mut self) {
+        if let Some(stop_sender) = self.stop_sender.take() {
+            let _ = stop_sender.send(true); // The poller may have already exited
+        }
+    }
+
+    /// Returns true iff the poller has been started (and not stopped)
+    pub fn is_running(&self) -> bool {
+        self.stop_sender.is_some()
+    }
+}
+
+/// Decodes the given response into the expected payload type. If the payload
+/// doesn't match the expected type, the issue is reported via the callback.
+fn decode_response<T, E>(response: Response<StorageServiceResponse>) -> Result<Response<T>>
+where
+    T: TryFrom<StorageServiceResponse, Error = E>,
+    E: Into<Error>,
+{
+    let (context, payload) = response.into_parts();
+
+    // try to convert the storage service enum into the exact variant we're expecting.
+    match T::try_from(payload) {
+        Ok(new_payload) => Ok(Response::new(context, new_payload)),
+        // if the variant doesn't match what we're expecting, report the issue.
+        Err(err) => {
+            context
+                .response_callback
+                .notify_bad_response(ResponseError::InvalidPayloadDataType);
+            Err(err.into())
+        }
+    }
+}
+
+/// Returns the transactions (and their versions) in the given list that were
+/// sent by the specified sender.
+fn filter_transactions_by_sender(
+    transaction_list_with_proof: TransactionListWithProof,
+    sender: AccountAddress,
+) -> Vec<(Version, Transaction)> {
+    let first_transaction_version = match transaction_list_with_proof.first_transaction_version {
+        Some(first_transaction_version) => first_transaction_version,
+        None => return vec![], // The transaction list is empty
+    };
+    (first_transaction_version..)
+        .zip(transaction_list_with_proof.transactions)
+        .filter(|(_, transaction)| match transaction {
+            Transaction::UserTransaction(signed_transaction) => {
+                signed_transaction.sender() == sender
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+/// Returns the versions of the transactions in the list that emitted at least
+/// one event of the given type
+fn find_transactions_with_event(
+    transaction_list_with_proof: &TransactionListWithProof,
+    event_type: &TypeTag,
+) -> Vec<Version> {
+    let (first_transaction_version, events) = match (
+        transaction_list_with_proof.first_transaction_version,
+        &transaction_list_with_proof.events,
+    ) {
+        (Some(first_transaction_version), Some(events)) => (first_transaction_version, events),
+        _ => return vec![], // The transaction list (or its events) is empty
+    };
+    (first_transaction_version..)
+        .zip(events.iter())
+        .filter(|(_, transaction_events)| {
+            transaction_events
+                .iter()
+                .any(|event| event.type_tag() == event_type)
+        })
+        .map(|(version, _)| version)
+        .collect()
+}
+
+/// Returns the versions of the transactions in the list that are larger than
+/// the given size limit (when serialized)
+fn find_oversized_transactions(
+    transaction_list_with_proof: &TransactionListWithProof,
+    max_transaction_bytes: usize,
+) -> Vec<Version> {
+    let first_transaction_version = match transaction_list_with_proof.first_transaction_version {
+        Some(first_transaction_version) => first_transaction_version,
+        None => return vec![], // The transaction list is empty
+    };
+    (first_transaction_version..)
+        .zip(transaction_list_with_proof.transactions.iter())
+        .filter(|(_, transaction)| {
+            bcs::serialized_size(transaction)
+                .map(|transaction_bytes| transaction_bytes > max_transaction_bytes)
+                .unwrap_or(true)
+        })
+        .map(|(version, _)| version)
+        .collect()
+}
+
+/// Waits until a stop is signalled on the given receiver (or the sender is dropped)
+async fn wait_for_stop(stop_receiver: &mut watch::Receiver<bool>) {
+    while !*stop_receiver.borrow() {
+        if stop_receiver.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Returns the request for the epoch changes from the trusted epoch up to
+/// (but excluding) the target epoch, or None if there are no epoch changes.
+pub(crate) fn create_epoch_changes_request(
+    trusted_epoch: Epoch,
+    target_epoch: Epoch,
+) -> Option<StorageServiceRequest> {
+    if target_epoch > trusted_epoch {
+        Some(StorageServiceRequest::GetEpochEndingLedgerInfos(
+            EpochEndingLedgerInfoRequest {
+                start_epoch: trusted_epoch,
+                expected_end_epoch: target_epoch - 1,
+            },
+        ))
+    } else {
+        None
+    }
+}
+
+/// Returns the time elapsed between the earlier time and the given time. If
+/// time went backwards (e.g., the clock was adjusted), a warning is logged and
+/// zero is returned, so that latency and age calculations never underflow.
+pub(crate) fn elapsed_since(time_now: Instant, earlier_time: Instant) -> Duration {
+    match time_now.checked_duration_since(earlier_time) {
+        Some(elapsed_time) => elapsed_time,
+        None => {
+            sample!(
+                SampleRate::Duration(Duration::from_secs(NON_MONOTONIC_TIME_LOG_FREQ_SECS)),
+                warn!(
+                    (LogSchema::new(LogEntry::TimeService)
+                        .event(LogEvent::NonMonotonicTime)
+                        .message(&format!(
+                            "Time went backwards by {:?}, using zero elapsed time instead!",
+                            earlier_time.duration_since(time_now)
+                        )))
+                );
+            );
+            Duration::from_secs(0)
+        }
+    }
+}
+
+/// Returns the type of data fetched by the given request (if any)
+fn get_request_data_type(request: &StorageServiceRequest) -> Option<DataType> {
+    match request {
+        StorageServiceRequest::GetAccountStatesByPrefix(_)
+        | StorageServiceRequest::GetAccountStatesChunkWithProof(_)
+        | StorageServiceRequest::GetNumberOfAccountsAtVersion(_) => Some(DataType::AccountStates),
+        StorageServiceRequest::GetCompressedResponse(request) => {
+            get_request_data_type(&request.request)
         }
+        StorageServiceRequest::GetEpochEndingLedgerInfos(_) => Some(DataType::LedgerInfos),
+        StorageServiceRequest::GetTransactionOutputsWithProof(_)
+        | StorageServiceRequest::GetTransactionOutputsWithoutEvents(_)
+        | StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(_) => {
+            Some(DataType::TransactionOutputs)
+        }
+        StorageServiceRequest::GetTransactionInclusionProofs(_)
+        | StorageServiceRequest::GetTransactionsFromSender(_)
+        | StorageServiceRequest::GetTransactionsWithEvent(_)
+        | StorageServiceRequest::GetTransactionsWithProof(_)
+        | StorageServiceRequest::GetTransactionsWithSizeLimit(_) => Some(DataType::Transactions),
+        StorageServiceRequest::GetServerProtocolVersion
+        | StorageServiceRequest::GetStorageServerSummary => None,
     }
 }
 
+/// Returns the range of data requested by the given request (if the request
+/// fetches a range of the data type)
+fn get_request_range(request: &StorageServiceRequest) -> Option<CompleteDataRange<u64>> {
+    let (start, end) = match request {
+        StorageServiceRequest::GetAccountStatesByPrefix(request) => {
+            (request.version, request.version)
+        }
+        StorageServiceRequest::GetAccountStatesChunkWithProof(request) => {
+            (request.version, request.version)
+        }
+        StorageServiceRequest::GetCompressedResponse(request) => {
+            return get_request_range(&request.request)
+        }
+        StorageServiceRequest::GetEpochEndingLedgerInfos(request) => {
+            (request.start_epoch, request.expected_end_epoch)
+        }
+        StorageServiceRequest::GetNumberOfAccountsAtVersion(version) => (*version, *version),
+        StorageServiceRequest::GetTransactionOutputsWithProof(request)
+        | StorageServiceRequest::GetTransactionOutputsWithoutEvents(request)
+        | StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(request) => {
+            (request.start_version, request.end_version)
+        }
+        StorageServiceRequest::GetTransactionsFromSender(request) => {
+            (request.start_version, request.end_version)
+        }
+        StorageServiceRequest::GetTransactionsWithEvent(request) => {
+            (request.start_version, request.end_version)
+        }
+        StorageServiceRequest::GetTransactionsWithProof(request) => {
+            (request.start_version, request.end_version)
+        }
+        StorageServiceRequest::GetTransactionsWithSizeLimit(request) => {
+            (request.start_version, request.end_version)
+        }
+        StorageServiceRequest::GetServerProtocolVersion
+        | StorageServiceRequest::GetStorageServerSummary
+        | StorageServiceRequest::GetTransactionInclusionProofs(_) => return None,
+    };
+    CompleteDataRange::new(start, end).ok()
+}
+
+/// Coalesces the advertised ranges of each data type (i.e., overlapping and
+/// adjacent ranges advertised by different peers are merged).
+fn coalesce_advertised_ranges(advertised_data: &mut AdvertisedData) {
+    advertised_data.account_states =
+        coalesce_ranges(mem::take(&mut advertised_data.account_states));
+    advertised_data.epoch_ending_ledger_infos =
+        coalesce_ranges(mem::take(&mut advertised_data.epoch_ending_ledger_infos));
+    advertised_data.transactions = coalesce_ranges(mem::take(&mut advertised_data.transactions));
+    advertised_data.transaction_outputs =
+        coalesce_ranges(mem::take(&mut advertised_data.transaction_outputs));
+}
+
 /// Updates the advertised data metrics using the given global
 /// data summary.
 fn update_advertised_data_metrics(global_data_summary: GlobalDataSummary) {
@@ -0,0 +1,46 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Error, GlobalDataSummary};
+use std::{fs, path::Path};
+
+/// Persists the global data summary to the given path. The summary is first
+/// written to a temporary file (and then renamed) so that a failure mid-write
+/// can't corrupt a previously persisted summary.
+pub(crate) fn persist_global_summary(
+    path: &Path,
+    global_data_summary: &GlobalDataSummary,
+) -> Result<(), Error> {
+    let summary_bytes = bcs::to_bytes(global_data_summary).map_err(|error| {
+        Error::UnexpectedErrorEncountered(format!(
+            "Failed to serialize the global data summary: {:?}",
+            error
+        ))
+    })?;
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, summary_bytes)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|error| {
+            Error::UnexpectedErrorEncountered(format!(
+                "Failed to persist the global data summary to {:?}: {:?}",
+                path, error
+            ))
+        })
+}
+
+/// Loads a previously persisted global data summary from the given path
+pub(crate) fn load_global_summary(path: &Path) -> Result<GlobalDataSummary, Error> {
+    let summary_bytes = fs::read(path).map_err(|error| {
+        Error::UnexpectedErrorEncountered(format!(
+            "Failed to read the persisted global data summary from {:?}: {:?}",
+            path, error
+        ))
+    })?;
+    bcs::from_bytes(&summary_bytes).map_err(|error| {
+        Error::UnexpectedErrorEncountered(format!(
+            "Failed to deserialize the persisted global data summary: {:?}",
+            error
+        ))
+    })
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::aptosnet::{elapsed_since, metrics::DataType};
+use aptos_config::network_id::PeerNetworkId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// A token bucket that holds up to one second's worth of requests, and is
+/// refilled continuously at the configured rate.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill_time: Instant,
+}
+
+/// Limits the rate of requests sent to each peer, per data type, using a
+/// token bucket for each (peer, data type) combination. Requests without a
+/// data type (e.g., summary polls) are never limited.
+#[derive(Debug)]
+pub(crate) struct PeerRateLimiter {
+    max_requests_per_sec: u64, // Zero disables rate limiting
+    buckets: HashMap<(PeerNetworkId, DataType), TokenBucket>,
+}
+
+impl PeerRateLimiter {
+    pub fn new(max_requests_per_sec: u64) -> Self {
+        Self {
+            max_requests_per_sec,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Returns true iff a request for the given data type can currently be
+    /// sent to the peer (i.e., the peer's bucket holds at least one token).
+    pub fn can_send(
+        &mut self,
+        peer: PeerNetworkId,
+        data_type: DataType,
+        time_now: Instant,
+    ) -> bool {
+        if self.max_requests_per_sec == 0 {
+            return true; // Rate limiting is disabled
+        }
+        let capacity = self.max_requests_per_sec as f64;
+        match self.buckets.get_mut(&(peer, data_type)) {
+            Some(bucket) => Self::refill(capacity, bucket, time_now) >= 1.0,
+            None => true, // The bucket is full
+        }
+    }
+
+    /// Records a request for the given data type sent to the peer (i.e.,
+    /// consumes a token from the peer's bucket).
+    pub fn record_request(&mut self, peer: PeerNetworkId, data_type: DataType, time_now: Instant) {
+        if self.max_requests_per_sec == 0 {
+            return; // Rate limiting is disabled
+        }
+        let capacity = self.max_requests_per_sec as f64;
+        let bucket = self
+            .buckets
+            .entry((peer, data_type))
+            .or_insert(TokenBucket {
+                tokens: capacity,
+                last_refill_time: time_now,
+            });
+        let tokens = Self::refill(capacity, bucket, time_now);
+        bucket.tokens = (tokens - 1.0).max(0.0);
+    }
+
+    /// Returns the currently throttled (peer, data type) combinations, along
+    /// with the time until each bucket refills (i.e., holds a token again).
+    pub fn active_rate_limits(
+        &mut self,
+        time_now: Instant,
+    ) -> Vec<(PeerNetworkId, DataType, Duration)> {
+        if self.max_requests_per_sec == 0 {
+            return vec![];
+        }
+        let capacity = self.max_requests_per_sec as f64;
+
+        // Remove the buckets that are full (they no longer need to be tracked)
+        self.buckets
+            .retain(|_, bucket| Self::refill(capacity, bucket, time_now) < capacity);
+
+        self.buckets
+            .iter()
+            .filter(|(_, bucket)| bucket.tokens < 1.0)
+            .map(|((peer, data_type), bucket)| {
+                let time_until_refill = Duration::from_secs_f64((1.0 - bucket.tokens) / capacity);
+                (*peer, *data_type, time_until_refill)
+            })
+            .collect()
+    }
+
+    /// Adds the tokens accrued since the last refill to the bucket (up to the
+    /// capacity) and returns the number of tokens the bucket holds.
+    fn refill(capacity: f64, bucket: &mut TokenBucket, time_now: Instant) -> f64 {
+        let elapsed_secs = elapsed_since(time_now, bucket.last_refill_time).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed_secs * capacity).min(capacity);
+        bucket.last_refill_time = time_now;
+        bucket.tokens
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::aptosnet::elapsed_since;
+use std::{
+    collections::VecDeque,
+    mem::size_of,
+    time::{Duration, Instant},
+};
+
+/// A sliding window counter that tracks the requests sent within the most
+/// recent window, so that the current request rate can be calculated.
+#[derive(Debug)]
+pub(crate) struct RequestRateTracker {
+    window: Duration,
+    request_times: VecDeque<Instant>, // The oldest request time is at the front
+}
+
+impl RequestRateTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            request_times: VecDeque::new(),
+        }
+    }
+
+    /// Records a request sent at the given time
+    pub fn record_request(&mut self, time_now: Instant) {
+        self.request_times.push_back(time_now);
+        self.remove_expired_requests(time_now);
+    }
+
+    /// Returns the number of requests per second over the window
+    pub fn request_rate(&mut self, time_now: Instant) -> f64 {
+        self.remove_expired_requests(time_now);
+        if self.window.is_zero() {
+            return 0.0;
+        }
+        self.request_times.len() as f64 / self.window.as_secs_f64()
+    }
+
+    /// Returns the estimated memory (in bytes) used by the tracked requests
+    pub fn memory_usage(&self) -> u64 {
+        (self.request_times.len() * size_of::<Instant>()) as u64
+    }
+
+    /// Removes all requests that were sent before the window
+    fn remove_expired_requests(&mut self, time_now: Instant) {
+        while let Some(request_time) = self.request_times.front() {
+            if elapsed_since(time_now, *request_time) < self.window {
+                break;
+            }
+            self.request_times.pop_front();
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::LatencyStats;
+use std::{collections::VecDeque, mem::size_of, time::Duration};
+
+/// Tracks the latencies of the most recent peer selections, so that the
+/// selection latency stats can be reported.
+#[derive(Debug)]
+pub(crate) struct SelectionLatencyTracker {
+    window: usize,
+    latencies: VecDeque<Duration>, // The oldest latency is at the front
+}
+
+impl SelectionLatencyTracker {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            latencies: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Records the latency of a single peer selection
+    pub fn record_latency(&mut self, latency: Duration) {
+        if self.window == 0 {
+            return; // Tracking is disabled
+        }
+        if self.latencies.len() == self.window {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+    }
+
+    /// Returns the stats of the latencies in the window
+    pub fn latency_stats(&self) -> LatencyStats {
+        if self.latencies.is_empty() {
+            return LatencyStats::default();
+        }
+
+        let mut latencies = self.latencies.iter().copied().collect::<Vec<_>>();
+        latencies.sort_unstable();
+        let num_samples = latencies.len();
+        let total_latency: Duration = latencies.iter().sum();
+        let p99_index = ((num_samples * 99) / 100).min(num_samples - 1);
+        LatencyStats {
+            num_samples: num_samples as u64,
+            min: latencies[0],
+            max: latencies[num_samples - 1],
+            mean: total_latency / num_samples as u32,
+            p99: latencies[p99_index],
+        }
+    }
+
+    /// Returns the estimated memory (in bytes) used by the tracked latencies
+    pub fn memory_usage(&self) -> u64 {
+        (self.latencies.len() * size_of::<Duration>()) as u64
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{aptosnet::metrics::DataType, ServiceabilityEvent, ServiceableReason};
+use aptos_config::network_id::PeerNetworkId;
+use futures::channel::mpsc;
+use std::collections::HashMap;
+
+/// The status of a single peer (with a storage summary) relative to a range
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct PeerRangeStatus {
+    pub advertises_range: bool, // Whether the peer's summary advertises the entire range
+    pub is_ignored: bool,       // Whether the peer's score is below the ignore threshold
+}
+
+impl PeerRangeStatus {
+    /// Returns true iff the peer can service the range
+    fn can_service(&self) -> bool {
+        self.advertises_range && !self.is_ignored
+    }
+}
+
+/// Watches a range (inclusive) of a data type, and notifies the subscriber
+/// whenever the range transitions between serviceable (i.e., at least one
+/// non-ignored peer advertises the entire range) and unserviceable.
+#[derive(Debug)]
+pub(crate) struct RangeWatcher {
+    pub data_type: DataType,
+    pub start: u64,
+    pub end: u64,
+    peer_statuses: HashMap<PeerNetworkId, PeerRangeStatus>, // The statuses at the last update
+    event_sender: mpsc::UnboundedSender<ServiceabilityEvent>,
+}
+
+impl RangeWatcher {
+    pub fn new(
+        data_type: DataType,
+        start: u64,
+        end: u64,
+        peer_statuses: HashMap<PeerNetworkId, PeerRangeStatus>,
+        event_sender: mpsc::UnboundedSender<ServiceabilityEvent>,
+    ) -> Self {
+        Self {
+            data_type,
+            start,
+            end,
+            peer_statuses,
+            event_sender,
+        }
+    }
+
+    /// Returns true iff the subscriber has dropped its receiver
+    pub fn is_closed(&self) -> bool {
+        self.event_sender.is_closed()
+    }
+
+    /// Updates the peer statuses, and notifies the subscriber if the range
+    /// became serviceable or unserviceable.
+    pub fn update(&mut self, peer_statuses: HashMap<PeerNetworkId, PeerRangeStatus>) {
+        let was_serviceable = is_serviceable(&self.peer_statuses);
+        let event = match (was_serviceable, is_serviceable(&peer_statuses)) {
+            (false, true) => Some(ServiceabilityEvent::Serviceable(
+                self.identify_serviceable_reason(&peer_statuses),
+            )),
+            (true, false) => Some(ServiceabilityEvent::Unserviceable),
+            _ => None,
+        };
+        self.peer_statuses = peer_statuses;
+
+        if let Some(event) = event {
+            let _ = self.event_sender.unbounded_send(event);
+        }
+    }
+
+    /// Identifies why the range became serviceable by comparing the previous
+    /// and new statuses of the peers that can now service it. Recovered peers
+    /// take precedence over new peers, which take precedence over extensions.
+    fn identify_serviceable_reason(
+        &self,
+        peer_statuses: &HashMap<PeerNetworkId, PeerRangeStatus>,
+    ) -> ServiceableReason {
+        let mut servicing_peers = peer_statuses
+            .iter()
+            .filter(|(_, status)| status.can_service())
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<_>>();
+        servicing_peers.sort();
+
+        let recovered_peer = servicing_peers.iter().find(|peer| {
+            self.peer_statuses
+                .get(peer)
+                .map_or(false, |status| status.is_ignored)
+        });
+        if let Some(peer) = recovered_peer {
+            return ServiceableReason::PeerRecovered(*peer);
+        }
+        let new_peer = servicing_peers
+            .iter()
+            .find(|peer| !self.peer_statuses.contains_key(peer));
+        match new_peer {
+            Some(peer) => ServiceableReason::NewPeer(*peer),
+            None => ServiceableReason::RangeExtended(servicing_peers[0]),
+        }
+    }
+}
+
+/// Returns true iff any of the peers can service the range
+fn is_serviceable(peer_statuses: &HashMap<PeerNetworkId, PeerRangeStatus>) -> bool {
+    peer_statuses.values().any(|status| status.can_service())
+}
@@ -2,29 +2,61 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    aptosnet::logging::{LogEntry, LogEvent, LogSchema},
-    AdvertisedData, GlobalDataSummary, OptimalChunkSizes, ResponseError,
+    aptosnet::{
+        create_epoch_changes_request, elapsed_since,
+        inflight::InflightRequests,
+        logging::{LogEntry, LogEvent, LogSchema},
+        metrics::DataType,
+        serviceability::PeerRangeStatus,
+        DefaultPeerScoringStrategy, PeerBanListener, PeerIdentityResolver, PeerScoringStrategy,
+    },
+    AdvertisedData, GlobalDataSummary, OptimalChunkSizes, OutcomeStats, PeerExclusionReason,
+    PeerTier, ResponseError, ResponseQuality,
 };
 use aptos_config::{config::StorageServiceConfig, network_id::PeerNetworkId};
-use aptos_logger::debug;
+use aptos_infallible::Mutex;
+use aptos_logger::prelude::*;
+use aptos_types::{ledger_info::LedgerInfoWithSignatures, transaction::Version};
+use rand::seq::SliceRandom;
 use std::{
-    cmp::min,
+    borrow::Cow,
+    cmp::{max, min},
     collections::{HashMap, HashSet, VecDeque},
+    mem::size_of,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use storage_service_types::{
+    CompleteDataRange, Epoch, StorageServerSummary, StorageServiceRequest, BASE_PROTOCOL_VERSION,
+    EXTENDED_PROTOCOL_VERSION,
 };
-use storage_service_types::{StorageServerSummary, StorageServiceRequest};
 
 /// Scores for peer rankings based on preferences and behavior.
 const MAX_SCORE: f64 = 100.0;
 const MIN_SCORE: f64 = 0.0;
 const STARTING_SCORE: f64 = 50.0;
-/// Add this score on a successful response.
+/// Add this score on a successful response (by default).
 const SUCCESSFUL_RESPONSE_DELTA: f64 = 1.0;
 /// Not necessarily a malicious response, but not super useful.
 const NOT_USEFUL_MULTIPLIER: f64 = 0.95;
+/// Add this score when a response is reported as good (according to its quality).
+const NORMAL_RESPONSE_DELTA: f64 = 5.0;
+const EXCEPTIONAL_RESPONSE_DELTA: f64 = 10.0;
+/// Partially revert a timeout penalty when a late response arrives within the grace period.
+const LATE_RESPONSE_MULTIPLIER: f64 = 1.025;
 /// Likely to be a malicious response.
 const MALICIOUS_MULTIPLIER: f64 = 0.8;
-/// Ignore a peer when their score dips below this threshold.
+/// Ignore a peer when their score dips below this threshold (by default).
 const IGNORE_PEER_THRESHOLD: f64 = 25.0;
+/// The frequency at which to log invalid peer scores.
+const INVALID_SCORE_LOG_FREQ_SECS: u64 = 5;
+/// The weight of the latest sample when updating a peer's serving bandwidth.
+const SERVING_BANDWIDTH_SMOOTHING_FACTOR: f64 = 0.5;
+/// The weight of the latest sample when updating a peer's response latency.
+const RESPONSE_LATENCY_SMOOTHING_FACTOR: f64 = 0.5;
+/// The divisor applied to the configured max chunk sizes when too few peer
+/// summaries exist to reliably calculate optimal chunk sizes.
+const CONSERVATIVE_CHUNK_SIZE_DIVISOR: u64 = 2;
 
 pub(crate) enum ErrorType {
     /// A response or error that's not actively malicious but also doesn't help
@@ -35,6 +67,14 @@ pub(crate) enum ErrorType {
     Malicious,
 }
 
+/// The outcome of a single request sent to a peer
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RequestOutcome {
+    Success,
+    Failure,
+    Timeout,
+}
+
 impl From<ResponseError> for ErrorType {
     fn from(error: ResponseError) -> Self {
         match error {
@@ -51,6 +91,32 @@ struct PeerState {
     /// The latest observed advertised data for this peer, or `None` if we
     /// haven't polled them yet.
     storage_summary: Option<StorageServerSummary>,
+    /// The time at which the storage summary was last updated (if any)
+    storage_summary_update_time: Option<Instant>,
+    /// The storage server protocol version run by the peer, or `None` if we
+    /// haven't fetched it yet.
+    server_protocol_version: Option<u64>,
+    /// The time until which requests should not be sent to the peer, as
+    /// requested by the peer itself (i.e., backpressure).
+    retry_after_time: Option<Instant>,
+    /// The number of consecutive summary polls that have failed for the peer
+    consecutive_poll_failures: u64,
+    /// The time until which the peer is excluded from summary polling (after
+    /// too many consecutive poll failures).
+    poll_exclusion_end_time: Option<Instant>,
+    /// The times of the peer's recent errors (oldest first)
+    recent_error_times: VecDeque<Instant>,
+    /// The number of consecutive successful responses served by the peer
+    consecutive_successes: u64,
+    /// The (smoothed) bandwidth at which the peer serves responses, in bytes
+    /// per second, or `None` if no responses have been measured yet.
+    serving_bandwidth: Option<f64>,
+    /// The (smoothed) latency at which the peer responds to requests, or
+    /// `None` if no responses have been measured yet.
+    response_latency: Option<Duration>,
+    /// Whether the peer was banned and must successfully serve a data request
+    /// (i.e., a recovery probe) before it's un-banned.
+    awaiting_recovery_probe: bool,
     /// For now, a simplified port of the original state-sync v1 scoring system.
     score: f64,
 }
@@ -59,6 +125,16 @@ impl Default for PeerState {
     fn default() -> Self {
         Self {
             storage_summary: None,
+            storage_summary_update_time: None,
+            server_protocol_version: None,
+            retry_after_time: None,
+            consecutive_poll_failures: 0,
+            poll_exclusion_end_time: None,
+            recent_error_times: VecDeque::new(),
+            consecutive_successes: 0,
+            serving_bandwidth: None,
+            response_latency: None,
+            awaiting_recovery_probe: false,
             score: STARTING_SCORE,
         }
     }
@@ -66,31 +142,68 @@ impl Default for PeerState {
 
 impl PeerState {
     /// Updates the storage summary for the peer
-    fn update_storage_summary(&mut self, storage_summary: StorageServerSummary) {
+    fn update_storage_summary(
+        &mut self,
+        storage_summary: StorageServerSummary,
+        update_time: Instant,
+    ) {
         self.storage_summary = Some(storage_summary);
+        self.storage_summary_update_time = Some(update_time);
+        self.consecutive_poll_failures = 0;
     }
 
-    /// Returns the storage summary iff the peer is not below the ignore threshold
-    fn storage_summary_if_not_ignored(&self) -> Option<&StorageServerSummary> {
-        if self.score <= IGNORE_PEER_THRESHOLD {
-            None
+    /// Updates the score of the peer according to a successful operation. The
+    /// reward (i.e., the recovery increment) grows (by the growth factor) with
+    /// each consecutive success, up to the maximum multiplier.
+    fn update_score_success(
+        &mut self,
+        recovery_increment: f64,
+        growth_factor: f64,
+        max_multiplier: f64,
+    ) {
+        let streak_multiplier = growth_factor.powf(self.consecutive_successes as f64);
+        let reward_multiplier = f64::max(1.0, f64::min(streak_multiplier, max_multiplier));
+        let reward = if reward_multiplier.is_finite() {
+            recovery_increment * reward_multiplier
         } else {
-            self.storage_summary.as_ref()
-        }
+            recovery_increment
+        };
+        self.update_score(self.score + reward);
+        self.consecutive_successes = self.consecutive_successes.saturating_add(1);
     }
 
-    /// Updates the score of the peer according to a successful operation
-    fn update_score_success(&mut self) {
-        self.score = f64::min(self.score + SUCCESSFUL_RESPONSE_DELTA, MAX_SCORE);
+    /// Updates the score of the peer according to a response that was reported
+    /// as good (with the given quality)
+    fn update_score_good_response(&mut self, quality: ResponseQuality) {
+        let reward = match quality {
+            ResponseQuality::Normal => NORMAL_RESPONSE_DELTA,
+            ResponseQuality::Exceptional => EXCEPTIONAL_RESPONSE_DELTA,
+        };
+        self.update_score(self.score + reward);
     }
 
-    /// Updates the score of the peer according to an error
-    fn update_score_error(&mut self, error: ErrorType) {
-        let multiplier = match error {
+    /// Updates the score of the peer according to a late (but successful) response
+    fn update_score_late_response(&mut self) {
+        self.update_score(self.score * LATE_RESPONSE_MULTIPLIER);
+    }
+
+    /// Updates the score of the peer according to an error. The fraction of the
+    /// score lost for the error is scaled by the penalty multiplier.
+    fn update_score_error(&mut self, error: ErrorType, penalty_multiplier: f64) {
+        let error_multiplier = match error {
             ErrorType::NotUseful => NOT_USEFUL_MULTIPLIER,
             ErrorType::Malicious => MALICIOUS_MULTIPLIER,
         };
-        self.score = f64::max(self.score * multiplier, MIN_SCORE);
+        let multiplier = (1.0 - (1.0 - error_multiplier) * penalty_multiplier).clamp(0.0, 1.0);
+        self.update_score(self.score * multiplier);
+        self.consecutive_successes = 0;
+    }
+
+    /// Updates the score of the peer, bounded by the min and max scores. Note:
+    /// the scoring parameters are sanitized when the config is loaded, so the
+    /// score is always finite.
+    fn update_score(&mut self, score: f64) {
+        self.score = score.clamp(MIN_SCORE, MAX_SCORE);
     }
 }
 
@@ -104,6 +217,17 @@ pub(crate) struct PeerStates {
     polled_peers: HashSet<PeerNetworkId>, // The peers already marked as polled
     prioritized_peer_queue: VecDeque<PeerNetworkId>, // The order in which high-priority peers were polled
     regular_peer_queue: VecDeque<PeerNetworkId>,     // The order in which regular peers were polled
+    hinted_peers: HashSet<PeerNetworkId>, // The peers hinted by the operator (treated as high-priority)
+    scoring_strategy: Arc<dyn PeerScoringStrategy>, // Adjusts peer scores before they're used
+    ban_listener: Option<Arc<dyn PeerBanListener>>, // Notified when peers are banned or restored (if set)
+    inflight_requests: Option<Arc<Mutex<InflightRequests>>>, // Cancelled when their peers are banned (if set)
+    identity_resolver: Option<Arc<dyn PeerIdentityResolver>>, // Links peers across networks to reconcile their summaries (if set)
+    outcome_stats_by_tier: HashMap<PeerTier, OutcomeStats>, // The request outcomes for each peer tier
+    summaries_changed: bool, // Whether the summaries (or ignored peers) changed since the last take
+    score_penalty_multiplier: f64, // Scales the fraction of the score lost for each error
+    score_recovery_increment: f64, // The score added for each successful response
+    ignore_peer_threshold: f64, // Peers with scores at or below this threshold are ignored
+    require_data_probe_for_recovery: bool, // Whether banned peers must pass a recovery probe before they're un-banned
 }
 
 impl PeerStates {
@@ -114,9 +238,272 @@ impl PeerStates {
             polled_peers: HashSet::new(),
             prioritized_peer_queue: VecDeque::new(),
             regular_peer_queue: VecDeque::new(),
+            hinted_peers: HashSet::new(),
+            scoring_strategy: Arc::new(DefaultPeerScoringStrategy),
+            ban_listener: None,
+            inflight_requests: None,
+            identity_resolver: None,
+            outcome_stats_by_tier: HashMap::new(),
+            summaries_changed: true,
+            score_penalty_multiplier: 1.0,
+            score_recovery_increment: SUCCESSFUL_RESPONSE_DELTA,
+            ignore_peer_threshold: IGNORE_PEER_THRESHOLD,
+            require_data_probe_for_recovery: false,
+        }
+    }
+
+    /// Sets the parameters of the scoring curves: the multiplier that scales
+    /// the penalty for each error, the score added for each successful
+    /// response and the threshold at (or below) which peers are ignored.
+    pub fn set_scoring_parameters(
+        &mut self,
+        score_penalty_multiplier: f64,
+        score_recovery_increment: f64,
+        ignore_peer_threshold: f64,
+    ) {
+        self.score_penalty_multiplier = score_penalty_multiplier;
+        self.score_recovery_increment = score_recovery_increment;
+        self.ignore_peer_threshold = ignore_peer_threshold;
+    }
+
+    /// Sets whether banned peers must successfully serve a data request (i.e.,
+    /// a recovery probe) before they're un-banned. Otherwise, peers are
+    /// un-banned as soon as their scores recover.
+    pub fn set_require_data_probe_for_recovery(&mut self, require_data_probe_for_recovery: bool) {
+        self.require_data_probe_for_recovery = require_data_probe_for_recovery;
+    }
+
+    /// Returns true iff the given (effective) score is at or below the
+    /// ignore threshold
+    fn is_ignored_score(&self, score: f64) -> bool {
+        score <= self.ignore_peer_threshold
+    }
+
+    /// Returns true iff the peer is ignored, i.e., its (effective) score is at
+    /// or below the ignore threshold, or it's awaiting a recovery probe.
+    fn is_peer_ignored(&self, peer: &PeerNetworkId) -> bool {
+        self.is_ignored_score(self.get_effective_score(peer))
+            || self.is_awaiting_recovery_probe(peer)
+    }
+
+    /// Returns true iff the peer was banned and hasn't passed a recovery probe
+    fn is_awaiting_recovery_probe(&self, peer: &PeerNetworkId) -> bool {
+        self.peer_to_state
+            .get(peer)
+            .map_or(false, |peer_state| peer_state.awaiting_recovery_probe)
+    }
+
+    /// Returns true iff the peer's score has recovered, but the peer must still
+    /// pass a recovery probe before it's un-banned
+    pub fn needs_recovery_probe(&self, peer: &PeerNetworkId) -> bool {
+        self.is_awaiting_recovery_probe(peer)
+            && !self.is_ignored_score(self.get_effective_score(peer))
+    }
+
+    /// Records that the peer passed a recovery probe, and un-bans the peer
+    /// (if its score has recovered).
+    pub fn record_recovery_probe_success(&mut self, peer: PeerNetworkId) {
+        let was_ignored = self.is_peer_ignored(&peer);
+        if let Some(peer_state) = self.peer_to_state.get_mut(&peer) {
+            peer_state.awaiting_recovery_probe = false;
+        }
+        if was_ignored && !self.is_peer_ignored(&peer) {
+            self.restore_peer(peer);
+        }
+    }
+
+    /// Handles a peer that is no longer ignored (i.e., un-banned)
+    fn restore_peer(&mut self, peer: PeerNetworkId) {
+        debug!(
+            (LogSchema::new(LogEntry::PeerStates)
+                .event(LogEvent::PeerNoLongerIgnored)
+                .message("Peer will no longer be ignored")
+                .peer(&peer))
+        );
+        let score = self.get_effective_score(&peer);
+        self.notify_ban_listener(peer, score, false);
+        self.summaries_changed = true;
+    }
+
+    /// Sets the listener notified whenever a peer is banned or restored
+    pub fn set_ban_listener(&mut self, ban_listener: Arc<dyn PeerBanListener>) {
+        self.ban_listener = Some(ban_listener);
+    }
+
+    /// Sets the in-flight requests that are cancelled whenever their peer is banned
+    pub fn set_inflight_requests(&mut self, inflight_requests: Arc<Mutex<InflightRequests>>) {
+        self.inflight_requests = Some(inflight_requests);
+    }
+
+    /// Cancels the in-flight requests to the banned peer (if enabled)
+    fn cancel_inflight_requests(&self, peer: PeerNetworkId) {
+        let num_cancelled_requests = match &self.inflight_requests {
+            Some(inflight_requests) => inflight_requests.lock().cancel_peer_requests(&peer),
+            None => return,
+        };
+        if num_cancelled_requests > 0 {
+            debug!(
+                (LogSchema::new(LogEntry::PeerStates)
+                    .event(LogEvent::InflightRequestsCancelled)
+                    .message(&format!(
+                        "Cancelled {} in-flight requests to the banned peer",
+                        num_cancelled_requests
+                    ))
+                    .peer(&peer))
+            );
+        }
+    }
+
+    /// Notifies the ban listener (if any) that the peer was banned or
+    /// restored. If a tokio runtime is available, the listener is invoked on a
+    /// separate task so that it doesn't block the scoring path.
+    fn notify_ban_listener(&self, peer: PeerNetworkId, score: f64, banned: bool) {
+        let ban_listener = match &self.ban_listener {
+            Some(ban_listener) => ban_listener.clone(),
+            None => return,
+        };
+        let notify_listener = move || {
+            if banned {
+                ban_listener.on_peer_banned(peer, score);
+            } else {
+                ban_listener.on_peer_restored(peer, score);
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let _ = runtime.spawn(async move { notify_listener() });
+            }
+            Err(_) => notify_listener(),
+        }
+    }
+
+    /// Sets the resolver used to reconcile the summaries of peers with the
+    /// same identity across networks
+    pub fn set_identity_resolver(&mut self, identity_resolver: Arc<dyn PeerIdentityResolver>) {
+        self.identity_resolver = Some(identity_resolver);
+    }
+
+    /// Returns the storage summary of the peer (if the peer isn't ignored),
+    /// reconciled with the summaries advertised by the same identity on other
+    /// networks (if an identity resolver is set). Reconciliation merges the
+    /// advertised ranges, so that the peer's coverage isn't underestimated
+    /// (e.g., due to the summaries being polled at different times).
+    pub fn reconciled_summary_if_not_ignored(
+        &self,
+        peer: &PeerNetworkId,
+    ) -> Option<Cow<StorageServerSummary>> {
+        let summary = self.storage_summary_if_not_ignored(peer)?;
+        let identity_resolver = match &self.identity_resolver {
+            Some(identity_resolver) => identity_resolver,
+            None => return Some(Cow::Borrowed(summary)),
+        };
+        let identity = match identity_resolver.resolve_identity(peer) {
+            Some(identity) => identity,
+            None => return Some(Cow::Borrowed(summary)),
+        };
+
+        let mut reconciled_summary = Cow::Borrowed(summary);
+        for other_peer in self.peer_to_state.keys() {
+            if other_peer.network_id() == peer.network_id()
+                || identity_resolver.resolve_identity(other_peer) != Some(identity)
+            {
+                continue;
+            }
+            if let Some(other_summary) = self.storage_summary_if_not_ignored(other_peer) {
+                reconcile_summaries(reconciled_summary.to_mut(), other_summary);
+            }
+        }
+        Some(reconciled_summary)
+    }
+
+    /// Sets the strategy used to adjust peer scores
+    pub fn set_scoring_strategy(&mut self, scoring_strategy: Arc<dyn PeerScoringStrategy>) {
+        self.scoring_strategy = scoring_strategy;
+    }
+
+    /// Returns the score of the given peer (as adjusted by the scoring
+    /// strategy). Non-finite scores are clamped to the starting (neutral) score
+    /// so that a buggy strategy can't break selection or threshold comparisons.
+    fn get_effective_score(&self, peer: &PeerNetworkId) -> f64 {
+        let score = self
+            .peer_to_state
+            .get(peer)
+            .map(|peer_state| peer_state.score)
+            .unwrap_or(STARTING_SCORE);
+        let score = self.scoring_strategy.adjust_score(peer, score);
+
+        if score.is_finite() {
+            score
+        } else {
+            sample!(
+                SampleRate::Duration(Duration::from_secs(INVALID_SCORE_LOG_FREQ_SECS)),
+                warn!(
+                    (LogSchema::new(LogEntry::PeerStates)
+                        .event(LogEvent::InvalidPeerScore)
+                        .message(&format!(
+                            "Non-finite peer score ({}), using the neutral score instead!",
+                            score
+                        ))
+                        .peer(peer))
+                );
+            );
+            STARTING_SCORE
+        }
+    }
+
+    /// Returns the storage summary of the peer iff the peer is not below the
+    /// ignore threshold.
+    fn storage_summary_if_not_ignored(
+        &self,
+        peer: &PeerNetworkId,
+    ) -> Option<&StorageServerSummary> {
+        if self.is_peer_ignored(peer) {
+            None
+        } else {
+            self.peer_to_state
+                .get(peer)
+                .and_then(|peer_state| peer_state.storage_summary.as_ref())
         }
     }
 
+    /// Returns the storage server protocol version run by the peer. If the
+    /// version hasn't been fetched yet, the peer is assumed to run the base
+    /// version (i.e., it only supports the original requests).
+    fn get_server_protocol_version(&self, peer: &PeerNetworkId) -> u64 {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.server_protocol_version)
+            .unwrap_or(BASE_PROTOCOL_VERSION)
+    }
+
+    /// Returns true iff the server protocol version of the peer hasn't been
+    /// fetched yet
+    pub fn needs_server_protocol_version(&self, peer: &PeerNetworkId) -> bool {
+        self.peer_to_state.get(peer).map_or(true, |peer_state| {
+            peer_state.server_protocol_version.is_none()
+        })
+    }
+
+    /// Updates the storage server protocol version run by the peer
+    pub fn update_server_protocol_version(&mut self, peer: PeerNetworkId, protocol_version: u64) {
+        self.peer_to_state
+            .entry(peer)
+            .or_default()
+            .server_protocol_version = Some(protocol_version);
+    }
+
+    /// Returns true iff the peer runs a storage server protocol version that
+    /// supports the request (older servers can't even deserialize it).
+    fn supports_request(&self, peer: &PeerNetworkId, request: &StorageServiceRequest) -> bool {
+        request.min_protocol_version() <= self.get_server_protocol_version(peer)
+    }
+
+    /// Returns true iff the peer runs a storage server protocol version that
+    /// supports compressed responses
+    pub fn supports_compression(&self, peer: &PeerNetworkId) -> bool {
+        self.get_server_protocol_version(peer) >= EXTENDED_PROTOCOL_VERSION
+    }
+
     /// Returns true if a connected storage service peer can actually fulfill a
     /// request, given our current view of their advertised data summary.
     pub fn can_service_request(
@@ -130,47 +517,344 @@ impl PeerStates {
         if request.is_get_storage_server_summary() {
             return true;
         }
+        if !self.supports_request(peer, request) {
+            return false;
+        }
 
-        self.peer_to_state
-            .get(peer)
-            .and_then(PeerState::storage_summary_if_not_ignored)
+        self.reconciled_summary_if_not_ignored(peer)
             .map(|summary| summary.can_service(request))
             .unwrap_or(false)
     }
 
-    /// Updates the score of the peer according to a successful operation
-    pub fn update_score_success(&mut self, peer: PeerNetworkId) {
-        let old_score = self.peer_to_state.entry(peer).or_default().score;
+    /// Returns true if a connected storage service peer can fulfill the start
+    /// of a request (i.e., a prefix), given their advertised data summary.
+    pub fn can_partially_service_request(
+        &self,
+        peer: &PeerNetworkId,
+        request: &StorageServiceRequest,
+    ) -> bool {
+        if request.is_get_storage_server_summary() {
+            return true;
+        }
+        if !self.supports_request(peer, request) {
+            return false;
+        }
+
+        self.reconciled_summary_if_not_ignored(peer)
+            .map(|summary| summary.can_partially_service(request))
+            .unwrap_or(false)
+    }
+
+    /// Returns the request trimmed to end at the end of the range advertised
+    /// by the peer, if the peer can only service the start of the request
+    /// (e.g., because it was selected as a partial coverage peer). Otherwise,
+    /// the request is returned unchanged.
+    pub fn trim_request_to_advertised_range(
+        &self,
+        peer: &PeerNetworkId,
+        request: &StorageServiceRequest,
+    ) -> StorageServiceRequest {
+        let mut request = request.clone();
+        if self.can_service_request(peer, &request)
+            || !self.can_partially_service_request(peer, &request)
+        {
+            return request;
+        }
+
+        if let Some(summary) = self.reconciled_summary_if_not_ignored(peer) {
+            trim_request_end(&mut request, &summary);
+        }
+        request
+    }
+
+    /// Updates the score of the peer according to a successful operation (where
+    /// the reward grows with the peer's streak of consecutive successes).
+    pub fn update_score_success(
+        &mut self,
+        peer: PeerNetworkId,
+        growth_factor: f64,
+        max_multiplier: f64,
+    ) {
+        let was_ignored = self.is_peer_ignored(&peer);
+        let recovery_increment = self.score_recovery_increment;
         self.peer_to_state
             .entry(peer)
             .or_default()
-            .update_score_success();
-        let new_score = self.peer_to_state.entry(peer).or_default().score;
-        if old_score <= IGNORE_PEER_THRESHOLD && new_score > IGNORE_PEER_THRESHOLD {
-            debug!(
-                (LogSchema::new(LogEntry::PeerStates)
-                    .event(LogEvent::PeerNoLongerIgnored)
-                    .message("Peer will no longer be ignored")
-                    .peer(&peer))
-            );
+            .update_score_success(recovery_increment, growth_factor, max_multiplier);
+        if was_ignored && !self.is_peer_ignored(&peer) {
+            self.restore_peer(peer);
+        }
+    }
+
+    /// Updates the score of the peer according to a response that was reported
+    /// as good (e.g., after the consumer independently verified the data).
+    pub fn update_score_good_response(&mut self, peer: PeerNetworkId, quality: ResponseQuality) {
+        let was_ignored = self.is_peer_ignored(&peer);
+        self.peer_to_state
+            .entry(peer)
+            .or_default()
+            .update_score_good_response(quality);
+        if was_ignored && !self.is_peer_ignored(&peer) {
+            self.restore_peer(peer);
+        }
+    }
+
+    /// Updates the score of the peer according to a response that arrived
+    /// after the request timed out (but within the grace period).
+    pub fn update_score_late_response(&mut self, peer: PeerNetworkId) {
+        let was_ignored = self.is_peer_ignored(&peer);
+        self.peer_to_state
+            .entry(peer)
+            .or_default()
+            .update_score_late_response();
+        if was_ignored && !self.is_peer_ignored(&peer) {
+            self.restore_peer(peer);
+        }
+    }
+
+    /// Returns the current score of the given peer (if any)
+    #[cfg(test)]
+    pub fn get_peer_score(&self, peer: &PeerNetworkId) -> Option<f64> {
+        self.peer_to_state
+            .get(peer)
+            .map(|peer_state| peer_state.score)
+    }
+
+    /// Marks the peer as overloaded. Requests should not be sent to the peer
+    /// until the given retry time. This does not affect the peer's score.
+    pub fn update_retry_after_time(&mut self, peer: PeerNetworkId, retry_after_time: Instant) {
+        self.peer_to_state.entry(peer).or_default().retry_after_time = Some(retry_after_time);
+    }
+
+    /// Records an error for the peer at the given time, and garbage collects
+    /// any errors that fall outside the recent error window.
+    pub fn record_recent_error(
+        &mut self,
+        peer: PeerNetworkId,
+        time_now: Instant,
+        recent_error_window: Duration,
+    ) {
+        let recent_error_times = &mut self
+            .peer_to_state
+            .entry(peer)
+            .or_default()
+            .recent_error_times;
+        recent_error_times.push_back(time_now);
+        while let Some(error_time) = recent_error_times.front() {
+            if elapsed_since(time_now, *error_time) < recent_error_window {
+                break;
+            }
+            recent_error_times.pop_front();
+        }
+    }
+
+    /// Records the bandwidth at which the peer served a response, i.e., the
+    /// response size divided by the elapsed RPC time. Responses that took no
+    /// measurable time are ignored.
+    pub fn record_serving_bandwidth(
+        &mut self,
+        peer: PeerNetworkId,
+        response_bytes: u64,
+        elapsed_time: Duration,
+    ) {
+        let elapsed_secs = elapsed_time.as_secs_f64();
+        if elapsed_secs <= 0.0 {
+            return;
+        }
+
+        let sample = response_bytes as f64 / elapsed_secs;
+        let peer_state = self.peer_to_state.entry(peer).or_default();
+        peer_state.serving_bandwidth = Some(match peer_state.serving_bandwidth {
+            Some(bandwidth) => {
+                SERVING_BANDWIDTH_SMOOTHING_FACTOR * sample
+                    + (1.0 - SERVING_BANDWIDTH_SMOOTHING_FACTOR) * bandwidth
+            }
+            None => sample,
+        });
+    }
+
+    /// Returns the serving bandwidth (in bytes per second) of each peer with
+    /// measured responses.
+    pub fn serving_bandwidths(&self) -> HashMap<PeerNetworkId, f64> {
+        self.peer_to_state
+            .iter()
+            .filter_map(|(peer, peer_state)| {
+                peer_state
+                    .serving_bandwidth
+                    .map(|bandwidth| (*peer, bandwidth))
+            })
+            .collect()
+    }
+
+    /// Records the latency at which the peer responded to a request
+    pub fn record_response_latency(&mut self, peer: PeerNetworkId, latency: Duration) {
+        let peer_state = self.peer_to_state.entry(peer).or_default();
+        peer_state.response_latency = Some(match peer_state.response_latency {
+            Some(response_latency) => Duration::from_secs_f64(
+                RESPONSE_LATENCY_SMOOTHING_FACTOR * latency.as_secs_f64()
+                    + (1.0 - RESPONSE_LATENCY_SMOOTHING_FACTOR) * response_latency.as_secs_f64(),
+            ),
+            None => latency,
+        });
+    }
+
+    /// Returns the (smoothed) response latency of the peer (if measured)
+    pub fn get_response_latency(&self, peer: &PeerNetworkId) -> Option<Duration> {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.response_latency)
+    }
+
+    /// Returns a snapshot of the scoring state of each known peer
+    pub fn peer_state_snapshots(&self) -> HashMap<PeerNetworkId, crate::PeerState> {
+        self.peer_to_state
+            .keys()
+            .map(|peer| {
+                let score = self.get_effective_score(peer);
+                let peer_state = crate::PeerState {
+                    score,
+                    is_ignored: self.is_peer_ignored(peer),
+                    advertised_version: self.get_synced_version(peer),
+                };
+                (*peer, peer_state)
+            })
+            .collect()
+    }
+
+    /// Returns the score of the peer used for peer selection (i.e., the score
+    /// as adjusted by the scoring strategy, with non-finite scores treated as
+    /// neutral).
+    pub fn get_selection_score(&self, peer: &PeerNetworkId) -> f64 {
+        self.get_effective_score(peer)
+    }
+
+    /// Returns the number of errors for the peer within the recent error window
+    pub fn num_recent_errors(
+        &self,
+        peer: &PeerNetworkId,
+        time_now: Instant,
+        recent_error_window: Duration,
+    ) -> usize {
+        self.peer_to_state
+            .get(peer)
+            .map(|peer_state| {
+                peer_state
+                    .recent_error_times
+                    .iter()
+                    .filter(|error_time| {
+                        elapsed_since(time_now, **error_time) < recent_error_window
+                    })
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns true iff the peer has asked us not to send requests until
+    /// after the given time.
+    pub fn is_peer_overloaded(&self, peer: &PeerNetworkId, time_now: Instant) -> bool {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.retry_after_time)
+            .map(|retry_after_time| time_now < retry_after_time)
+            .unwrap_or(false)
+    }
+
+    /// Updates the summary poll failures of the peer. If the peer has failed
+    /// the maximum number of consecutive polls, it is excluded from polling
+    /// until the given end time. Returns true iff the peer was excluded.
+    pub fn update_poll_failure(
+        &mut self,
+        peer: PeerNetworkId,
+        max_consecutive_failures: u64,
+        exclusion_end_time: Instant,
+    ) -> bool {
+        let peer_state = self.peer_to_state.entry(peer).or_default();
+        peer_state.consecutive_poll_failures += 1;
+        if max_consecutive_failures == 0
+            || peer_state.consecutive_poll_failures < max_consecutive_failures
+        {
+            return false;
+        }
+
+        // Exclude the peer and reset the failures (to give it another chance)
+        peer_state.consecutive_poll_failures = 0;
+        peer_state.poll_exclusion_end_time = Some(exclusion_end_time);
+        true
+    }
+
+    /// Returns true iff the peer is currently excluded from summary polling
+    fn is_excluded_from_polling(&self, peer: &PeerNetworkId, time_now: Instant) -> bool {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.poll_exclusion_end_time)
+            .map(|exclusion_end_time| time_now < exclusion_end_time)
+            .unwrap_or(false)
+    }
+
+    /// Returns the reason the given (connected) peer would be excluded when
+    /// selecting a peer for the request. This uses the same checks as selection.
+    pub fn explain_peer_exclusion(
+        &self,
+        peer: &PeerNetworkId,
+        request: &StorageServiceRequest,
+        time_now: Instant,
+        allow_partial_coverage: bool,
+    ) -> PeerExclusionReason {
+        if self.is_peer_overloaded(peer, time_now) {
+            return PeerExclusionReason::Overloaded;
+        }
+        if self.can_service_request(peer, request) {
+            return PeerExclusionReason::NotExcluded;
+        }
+
+        let has_storage_summary = self
+            .peer_to_state
+            .get(peer)
+            .map(|peer_state| peer_state.storage_summary.is_some())
+            .unwrap_or(false);
+        if !has_storage_summary {
+            return PeerExclusionReason::MissingSummary;
+        }
+
+        if self.storage_summary_if_not_ignored(peer).is_none() {
+            PeerExclusionReason::Ignored
+        } else if !self.supports_request(peer, request) {
+            PeerExclusionReason::UnsupportedRequest
+        } else if self.can_partially_service_request(peer, request) {
+            if allow_partial_coverage {
+                PeerExclusionReason::PartialCoverage
+            } else {
+                PeerExclusionReason::MissingCoverage
+            }
+        } else {
+            PeerExclusionReason::MissingCoverage
         }
     }
 
     /// Updates the score of the peer according to an error
     pub fn update_score_error(&mut self, peer: PeerNetworkId, error: ErrorType) {
-        let old_score = self.peer_to_state.entry(peer).or_default().score;
+        let was_ignored = self.is_peer_ignored(&peer);
+        let penalty_multiplier = self.score_penalty_multiplier;
         self.peer_to_state
             .entry(peer)
             .or_default()
-            .update_score_error(error);
-        let new_score = self.peer_to_state.entry(peer).or_default().score;
-        if old_score > IGNORE_PEER_THRESHOLD && new_score <= IGNORE_PEER_THRESHOLD {
+            .update_score_error(error, penalty_multiplier);
+        let new_score = self.get_effective_score(&peer);
+        if !was_ignored && self.is_ignored_score(new_score) {
+            if self.require_data_probe_for_recovery {
+                if let Some(peer_state) = self.peer_to_state.get_mut(&peer) {
+                    peer_state.awaiting_recovery_probe = true;
+                }
+            }
             debug!(
                 (LogSchema::new(LogEntry::PeerStates)
                     .event(LogEvent::PeerIgnored)
                     .message("Peer will be ignored")
                     .peer(&peer))
             );
+            self.notify_ban_listener(peer, new_score, true);
+            self.cancel_inflight_requests(peer);
+            self.summaries_changed = true;
         }
     }
 
@@ -178,38 +862,320 @@ impl PeerStates {
     pub fn mark_peer_as_polled(&mut self, peer: &PeerNetworkId) {
         let _ = self.polled_peers.insert(*peer);
 
-        if is_priority_peer(peer) {
+        if self.is_priority_peer(peer) {
             self.prioritized_peer_queue.push_front(*peer);
         } else {
             self.regular_peer_queue.push_front(*peer);
         }
     }
 
+    /// Updates the set of peers hinted by the operator
+    pub fn update_peer_hints(&mut self, hinted_peers: HashSet<PeerNetworkId>) {
+        self.hinted_peers = hinted_peers;
+    }
+
+    /// Returns true iff the given peer is high-priority (i.e., it is either
+    /// hinted by the operator, or considered upstream).
+    fn is_priority_peer(&self, peer: &PeerNetworkId) -> bool {
+        self.hinted_peers.contains(peer) || is_priority_peer(peer)
+    }
+
+    /// Returns the tier of the given peer
+    pub fn get_peer_tier(&self, peer: &PeerNetworkId) -> PeerTier {
+        if self.is_priority_peer(peer) {
+            PeerTier::Priority
+        } else {
+            PeerTier::Regular
+        }
+    }
+
+    /// Records the outcome of a request sent to the given peer (aggregated
+    /// by the peer's tier).
+    pub fn record_request_outcome(&mut self, peer: &PeerNetworkId, outcome: RequestOutcome) {
+        let peer_tier = self.get_peer_tier(peer);
+        let outcome_stats = self.outcome_stats_by_tier.entry(peer_tier).or_default();
+        match outcome {
+            RequestOutcome::Success => outcome_stats.successes += 1,
+            RequestOutcome::Failure => outcome_stats.failures += 1,
+            RequestOutcome::Timeout => outcome_stats.timeouts += 1,
+        }
+    }
+
+    /// Returns the request outcomes aggregated by peer tier
+    pub fn outcome_breakdown_by_tier(&self) -> HashMap<PeerTier, OutcomeStats> {
+        self.outcome_stats_by_tier.clone()
+    }
+
     /// Returns true iff the given peer has already been polled
     pub fn already_polled_peer(&self, peer: &PeerNetworkId) -> bool {
         self.polled_peers.contains(peer)
     }
 
     /// Returns the high-priority peer that was last polled and contains the oldest data
-    pub fn oldest_polled_priority_peer(&mut self) -> Option<PeerNetworkId> {
-        self.prioritized_peer_queue.pop_back()
+    /// (ignoring any peers currently excluded from polling).
+    pub fn oldest_polled_priority_peer(&mut self, time_now: Instant) -> Option<PeerNetworkId> {
+        let index = self
+            .prioritized_peer_queue
+            .iter()
+            .rposition(|peer| !self.is_excluded_from_polling(peer, time_now))?;
+        self.prioritized_peer_queue.remove(index)
+    }
+
+    /// Returns a high-priority peer to poll, chosen randomly with a bias towards
+    /// the peers advertising higher synced versions (ignoring any peers
+    /// currently excluded from polling). If all peers advertise the same
+    /// version, the peer that was polled the longest ago is returned instead.
+    pub fn freshness_weighted_priority_peer(&mut self, time_now: Instant) -> Option<PeerNetworkId> {
+        let candidates = self
+            .prioritized_peer_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, peer)| !self.is_excluded_from_polling(peer, time_now))
+            .map(|(index, peer)| (index, self.get_synced_version(peer).unwrap_or(0)))
+            .collect::<Vec<_>>();
+        let lowest_version = candidates.iter().map(|(_, version)| *version).min()?;
+        let highest_version = candidates.iter().map(|(_, version)| *version).max()?;
+        if lowest_version == highest_version {
+            return self.oldest_polled_priority_peer(time_now);
+        }
+
+        let index = candidates
+            .choose_weighted(&mut rand::thread_rng(), |(_, version)| {
+                (version - lowest_version + 1) as f64
+            })
+            .ok()
+            .map(|(index, _)| *index)?;
+        self.prioritized_peer_queue.remove(index)
+    }
+
+    /// Returns the synced version advertised by the peer (if any)
+    fn get_synced_version(&self, peer: &PeerNetworkId) -> Option<Version> {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.storage_summary.as_ref())
+            .and_then(|summary| summary.data_summary.synced_ledger_info.as_ref())
+            .map(|synced_ledger_info| synced_ledger_info.ledger_info().version())
     }
 
     /// Returns the regular peer that was last polled and contains the oldest data
-    pub fn oldest_polled_regular_peer(&mut self) -> Option<PeerNetworkId> {
-        self.regular_peer_queue.pop_back()
+    /// (ignoring any peers currently excluded from polling).
+    pub fn oldest_polled_regular_peer(&mut self, time_now: Instant) -> Option<PeerNetworkId> {
+        let index = self
+            .regular_peer_queue
+            .iter()
+            .rposition(|peer| !self.is_excluded_from_polling(peer, time_now))?;
+        self.regular_peer_queue.remove(index)
     }
 
-    /// Updates the storage summary for the given peer
-    pub fn update_summary(&mut self, peer: PeerNetworkId, summary: StorageServerSummary) {
+    /// Updates the storage summary for the given peer. The update time is
+    /// always refreshed, but the summaries are only marked as changed if the
+    /// summary differs from the peer's previous summary.
+    pub fn update_summary(
+        &mut self,
+        peer: PeerNetworkId,
+        summary: StorageServerSummary,
+        update_time: Instant,
+    ) {
+        let peer_state = self.peer_to_state.entry(peer).or_default();
+        if peer_state.storage_summary.as_ref() != Some(&summary) {
+            self.summaries_changed = true;
+        }
+        peer_state.update_storage_summary(summary, update_time);
+    }
+
+    /// Returns true iff the peer summaries (or the set of ignored peers)
+    /// changed since this was last called, and resets the changed flag.
+    pub fn take_summaries_changed(&mut self) -> bool {
+        std::mem::take(&mut self.summaries_changed)
+    }
+
+    /// Returns the time at which each peer's storage summary was last updated
+    pub fn summary_update_times(&self) -> HashMap<PeerNetworkId, Instant> {
         self.peer_to_state
-            .entry(peer)
-            .or_default()
-            .update_storage_summary(summary);
+            .iter()
+            .filter_map(|(peer, peer_state)| {
+                peer_state
+                    .storage_summary_update_time
+                    .map(|update_time| (*peer, update_time))
+            })
+            .collect()
+    }
+
+    /// Returns the estimated memory (in bytes) used by the peer storage summaries
+    pub fn summaries_memory_usage(&self) -> u64 {
+        self.peer_to_state
+            .values()
+            .filter_map(|peer_state| peer_state.storage_summary.as_ref())
+            .map(|summary| bcs::serialized_size(summary).unwrap_or(0) as u64)
+            .sum()
+    }
+
+    /// Returns the estimated memory (in bytes) used by the peer states (e.g.,
+    /// scores and recent errors), the polling queues and the outcome stats.
+    pub fn scoring_memory_usage(&self) -> u64 {
+        let peer_states_size: usize = self
+            .peer_to_state
+            .values()
+            .map(|peer_state| {
+                size_of::<(PeerNetworkId, PeerState)>()
+                    + peer_state.recent_error_times.len() * size_of::<Instant>()
+            })
+            .sum();
+        let num_queued_peers = self.polled_peers.len()
+            + self.prioritized_peer_queue.len()
+            + self.regular_peer_queue.len()
+            + self.hinted_peers.len();
+        let outcome_stats_size =
+            self.outcome_stats_by_tier.len() * size_of::<(PeerTier, OutcomeStats)>();
+        (peer_states_size + num_queued_peers * size_of::<PeerNetworkId>() + outcome_stats_size)
+            as u64
+    }
+
+    /// Returns true iff the peer is a full-history (i.e., archival) peer. This
+    /// is the case if the peer advertises all transactions since genesis.
+    pub fn is_full_history_peer(&self, peer: &PeerNetworkId) -> bool {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.storage_summary.as_ref())
+            .and_then(|summary| summary.data_summary.transactions)
+            .map(|transactions| transactions.lowest() == 0)
+            .unwrap_or(false)
     }
 
-    /// Calculates a global data summary using all known storage summaries
-    pub fn calculate_aggregate_summary(&self) -> GlobalDataSummary {
+    /// Returns the number of distinct (non-ignored) peers with storage
+    /// summaries, i.e., the peers contributing to the global data summary.
+    pub fn num_servicing_peers(&self) -> usize {
+        self.peer_to_state
+            .keys()
+            .filter_map(|peer| self.storage_summary_if_not_ignored(peer))
+            .count()
+    }
+
+    /// Returns all (non-ignored) peers advertising any overlap with the given
+    /// range (inclusive) for the data type. The peers are returned in order.
+    pub fn contributors_for_range(
+        &self,
+        data_type: DataType,
+        start: u64,
+        end: u64,
+    ) -> Vec<PeerNetworkId> {
+        let mut contributors: Vec<_> = self
+            .peer_to_state
+            .keys()
+            .filter(|peer| {
+                self.storage_summary_if_not_ignored(peer)
+                    .and_then(|summary| get_advertised_range(summary, data_type))
+                    .map(|advertised_range| range_overlaps(&advertised_range, start, end))
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        contributors.sort();
+        contributors
+    }
+
+    /// Returns the status of each peer (with a storage summary) relative to
+    /// the given range (inclusive) for the data type.
+    pub fn range_statuses(
+        &self,
+        data_type: DataType,
+        start: u64,
+        end: u64,
+    ) -> HashMap<PeerNetworkId, PeerRangeStatus> {
+        self.peer_to_state
+            .iter()
+            .filter_map(|(peer, peer_state)| {
+                let summary = peer_state.storage_summary.as_ref()?;
+                let advertises_range = get_advertised_range(summary, data_type)
+                    .map(|advertised_range| {
+                        advertised_range.lowest() <= start && end <= advertised_range.highest()
+                    })
+                    .unwrap_or(false);
+                let status = PeerRangeStatus {
+                    advertises_range,
+                    is_ignored: self.is_peer_ignored(peer),
+                };
+                Some((*peer, status))
+            })
+            .collect()
+    }
+
+    /// Returns the range of the data type advertised by the peer (if any)
+    pub fn get_advertised_range(
+        &self,
+        peer: &PeerNetworkId,
+        data_type: DataType,
+    ) -> Option<CompleteDataRange<u64>> {
+        self.peer_to_state
+            .get(peer)
+            .and_then(|peer_state| peer_state.storage_summary.as_ref())
+            .and_then(|summary| get_advertised_range(summary, data_type))
+    }
+
+    /// Returns a (non-ignored) peer that advertises a synced ledger info at
+    /// the given version, along with the ledger info (if any such peer exists).
+    /// If the ledger info is in a later epoch than the trusted epoch, the peer
+    /// must also be able to service the epoch changes since the trusted epoch.
+    pub fn find_synced_ledger_info(
+        &self,
+        version: Version,
+        trusted_epoch: Epoch,
+    ) -> Option<(PeerNetworkId, LedgerInfoWithSignatures)> {
+        let mut peers = self.peer_to_state.keys().collect::<Vec<_>>();
+        peers.sort();
+        peers.into_iter().find_map(|peer| {
+            self.storage_summary_if_not_ignored(peer)
+                .and_then(|summary| summary.data_summary.synced_ledger_info.as_ref())
+                .filter(|ledger_info| ledger_info.ledger_info().version() == version)
+                .filter(|ledger_info| {
+                    create_epoch_changes_request(trusted_epoch, ledger_info.ledger_info().epoch())
+                        .map(|request| self.can_service_request(peer, &request))
+                        .unwrap_or(true)
+                })
+                .map(|ledger_info| (*peer, ledger_info.clone()))
+        })
+    }
+
+    /// Returns the margin between the requested range and the closest edge of
+    /// the range of the data type advertised by the peer (if any). The margin
+    /// is zero if the peer doesn't advertise the entire requested range.
+    pub fn get_range_margin(
+        &self,
+        peer: &PeerNetworkId,
+        data_type: DataType,
+        requested_range: &CompleteDataRange<u64>,
+    ) -> Option<u64> {
+        self.get_advertised_range(peer, data_type)
+            .map(|advertised_range| {
+                let lower_margin = requested_range
+                    .lowest()
+                    .saturating_sub(advertised_range.lowest());
+                let upper_margin = advertised_range
+                    .highest()
+                    .saturating_sub(requested_range.highest());
+                min(lower_margin, upper_margin)
+            })
+    }
+
+    /// Returns true iff any (non-ignored) peer advertises any data of the
+    /// given data type.
+    pub fn is_data_type_advertised(&self, data_type: DataType) -> bool {
+        self.peer_to_state.keys().any(|peer| {
+            self.storage_summary_if_not_ignored(peer)
+                .and_then(|summary| get_advertised_range(summary, data_type))
+                .is_some()
+        })
+    }
+
+    /// Calculates a global data summary using all known storage summaries.
+    /// If there are fewer summaries than the given minimum, conservative chunk
+    /// sizes are used (instead of extrapolating from too few peers). Otherwise,
+    /// the given percentile of the advertised chunk sizes is used.
+    pub fn calculate_aggregate_summary(
+        &self,
+        min_summaries_for_chunk_calc: u64,
+        chunk_size_percentile: f64,
+    ) -> GlobalDataSummary {
         let mut advertised_data = AdvertisedData::empty();
         let mut max_epoch_chunk_sizes = vec![];
         let mut max_transaction_chunk_sizes = vec![];
@@ -219,8 +1185,8 @@ impl PeerStates {
         // Only include likely-not-malicious peers in the data summary aggregation
         let summaries = self
             .peer_to_state
-            .values()
-            .filter_map(PeerState::storage_summary_if_not_ignored);
+            .keys()
+            .filter_map(|peer| self.storage_summary_if_not_ignored(peer));
 
         // Collect each peer's protocol and data advertisements
         for summary in summaries {
@@ -258,13 +1224,25 @@ impl PeerStates {
         }
 
         // Calculate optimal chunk sizes based on the advertised data
-        let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
-            &self.config,
-            max_account_states_chunk_sizes,
-            max_epoch_chunk_sizes,
-            max_transaction_chunk_sizes,
-            max_transaction_output_chunk_sizes,
-        );
+        let num_summaries = max_epoch_chunk_sizes.len() as u64;
+        let optimal_chunk_sizes = if num_summaries < min_summaries_for_chunk_calc {
+            calculate_conservative_chunk_sizes(
+                &self.config,
+                max_account_states_chunk_sizes,
+                max_epoch_chunk_sizes,
+                max_transaction_chunk_sizes,
+                max_transaction_output_chunk_sizes,
+            )
+        } else {
+            calculate_optimal_chunk_sizes(
+                &self.config,
+                chunk_size_percentile,
+                max_account_states_chunk_sizes,
+                max_epoch_chunk_sizes,
+                max_transaction_chunk_sizes,
+                max_transaction_output_chunk_sizes,
+            )
+        };
         GlobalDataSummary {
             advertised_data,
             optimal_chunk_sizes,
@@ -272,27 +1250,175 @@ impl PeerStates {
     }
 }
 
-/// To calculate the optimal chunk size, we take the median for each
-/// chunk size parameter. This works well when we have an honest
-/// majority that mostly agrees on the same chunk sizes.
+/// Returns the range advertised in the storage summary for the data type
+fn get_advertised_range(
+    summary: &StorageServerSummary,
+    data_type: DataType,
+) -> Option<CompleteDataRange<u64>> {
+    let data_summary = &summary.data_summary;
+    match data_type {
+        DataType::AccountStates => data_summary.account_states,
+        DataType::LedgerInfos => data_summary.epoch_ending_ledger_infos,
+        DataType::TransactionOutputs => data_summary.transaction_outputs,
+        DataType::Transactions => data_summary.transactions,
+    }
+}
+
+/// Trims the end of the given request to the end of the corresponding range
+/// advertised in the summary (if the request ends after the advertised range)
+fn trim_request_end(request: &mut StorageServiceRequest, summary: &StorageServerSummary) {
+    let data_summary = &summary.data_summary;
+    let (end, advertised_range) = match request {
+        StorageServiceRequest::GetCompressedResponse(request) => {
+            return trim_request_end(&mut request.request, summary)
+        }
+        StorageServiceRequest::GetEpochEndingLedgerInfos(request) => (
+            &mut request.expected_end_epoch,
+            data_summary.epoch_ending_ledger_infos,
+        ),
+        StorageServiceRequest::GetTransactionOutputsWithProof(request)
+        | StorageServiceRequest::GetTransactionOutputsWithoutEvents(request)
+        | StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(request) => {
+            (&mut request.end_version, data_summary.transaction_outputs)
+        }
+        StorageServiceRequest::GetTransactionsFromSender(request) => {
+            (&mut request.end_version, data_summary.transactions)
+        }
+        StorageServiceRequest::GetTransactionsWithEvent(request) => {
+            (&mut request.end_version, data_summary.transactions)
+        }
+        StorageServiceRequest::GetTransactionsWithProof(request) => {
+            (&mut request.end_version, data_summary.transactions)
+        }
+        StorageServiceRequest::GetTransactionsWithSizeLimit(request) => {
+            (&mut request.end_version, data_summary.transactions)
+        }
+        _ => return,
+    };
+    if let Some(advertised_range) = advertised_range {
+        *end = min(*end, advertised_range.highest());
+    }
+}
+
+/// Reconciles the summary with another summary advertised by the same identity
+/// (on a different network), by merging the advertised ranges and keeping the
+/// highest synced ledger info. The protocol metadata isn't reconciled.
+fn reconcile_summaries(summary: &mut StorageServerSummary, other_summary: &StorageServerSummary) {
+    let data_summary = &mut summary.data_summary;
+    let other_data_summary = &other_summary.data_summary;
+    data_summary.account_states = merge_ranges(
+        data_summary.account_states,
+        other_data_summary.account_states,
+    );
+    data_summary.epoch_ending_ledger_infos = merge_ranges(
+        data_summary.epoch_ending_ledger_infos,
+        other_data_summary.epoch_ending_ledger_infos,
+    );
+    data_summary.transactions =
+        merge_ranges(data_summary.transactions, other_data_summary.transactions);
+    data_summary.transaction_outputs = merge_ranges(
+        data_summary.transaction_outputs,
+        other_data_summary.transaction_outputs,
+    );
+
+    let synced_version = |synced_ledger_info: &Option<LedgerInfoWithSignatures>| {
+        synced_ledger_info
+            .as_ref()
+            .map(|synced_ledger_info| synced_ledger_info.ledger_info().version())
+    };
+    if synced_version(&other_data_summary.synced_ledger_info)
+        > synced_version(&data_summary.synced_ledger_info)
+    {
+        data_summary.synced_ledger_info = other_data_summary.synced_ledger_info.clone();
+    }
+}
+
+/// Returns the union of the given ranges. If the ranges are disjoint (i.e.,
+/// they neither overlap nor are adjacent), the first range is returned.
+fn merge_ranges(
+    range: Option<CompleteDataRange<u64>>,
+    other_range: Option<CompleteDataRange<u64>>,
+) -> Option<CompleteDataRange<u64>> {
+    match (range, other_range) {
+        (Some(range), Some(other_range))
+            if range.lowest() <= other_range.highest().saturating_add(1)
+                && other_range.lowest() <= range.highest().saturating_add(1) =>
+        {
+            CompleteDataRange::new(
+                min(range.lowest(), other_range.lowest()),
+                max(range.highest(), other_range.highest()),
+            )
+            .ok()
+            .or(Some(range))
+        }
+        (None, other_range) => other_range,
+        (range, _) => range,
+    }
+}
+
+/// Coalesces the given ranges into the minimal set of spanning ranges (i.e.,
+/// overlapping and adjacent ranges are merged), sorted by the lowest value.
+pub(crate) fn coalesce_ranges(
+    mut ranges: Vec<CompleteDataRange<u64>>,
+) -> Vec<CompleteDataRange<u64>> {
+    ranges.sort_by_key(|range| (range.lowest(), range.highest()));
+
+    let mut coalesced_ranges: Vec<CompleteDataRange<u64>> = vec![];
+    for range in ranges {
+        if let Some(last_range) = coalesced_ranges.last_mut() {
+            if range.lowest() <= last_range.highest().saturating_add(1) {
+                // Ranges that can't be merged (i.e., the merged range length
+                // would overflow) are kept separate.
+                let merged_range = CompleteDataRange::new(
+                    last_range.lowest(),
+                    max(last_range.highest(), range.highest()),
+                );
+                if let Ok(merged_range) = merged_range {
+                    *last_range = merged_range;
+                    continue;
+                }
+            }
+        }
+        coalesced_ranges.push(range);
+    }
+    coalesced_ranges
+}
+
+/// Returns true iff the advertised range overlaps the range [start, end]
+fn range_overlaps(advertised_range: &CompleteDataRange<u64>, start: u64, end: u64) -> bool {
+    advertised_range.lowest() <= end && start <= advertised_range.highest()
+}
+
+/// To calculate the optimal chunk size, we take the given percentile (e.g.,
+/// the median) for each chunk size parameter. The median works well when we
+/// have an honest majority that mostly agrees on the same chunk sizes. Lower
+/// percentiles are more conservative, and higher percentiles more aggressive.
 pub(crate) fn calculate_optimal_chunk_sizes(
     config: &StorageServiceConfig,
+    chunk_size_percentile: f64,
     max_account_states_chunk_sizes: Vec<u64>,
     max_epoch_chunk_sizes: Vec<u64>,
     max_transaction_chunk_sizes: Vec<u64>,
     max_transaction_output_chunk_size: Vec<u64>,
 ) -> OptimalChunkSizes {
-    let account_states_chunk_size = median_or_max(
+    let account_states_chunk_size = percentile_or_max(
         max_account_states_chunk_sizes,
+        chunk_size_percentile,
         config.max_account_states_chunk_sizes,
     );
-    let epoch_chunk_size = median_or_max(max_epoch_chunk_sizes, config.max_epoch_chunk_size);
-    let transaction_chunk_size = median_or_max(
+    let epoch_chunk_size = percentile_or_max(
+        max_epoch_chunk_sizes,
+        chunk_size_percentile,
+        config.max_epoch_chunk_size,
+    );
+    let transaction_chunk_size = percentile_or_max(
         max_transaction_chunk_sizes,
+        chunk_size_percentile,
         config.max_transaction_chunk_size,
     );
-    let transaction_output_chunk_size = median_or_max(
+    let transaction_output_chunk_size = percentile_or_max(
         max_transaction_output_chunk_size,
+        chunk_size_percentile,
         config.max_transaction_output_chunk_size,
     );
 
@@ -304,6 +1430,46 @@ pub(crate) fn calculate_optimal_chunk_sizes(
     }
 }
 
+/// Calculates conservative chunk sizes for when too few peers have advertised
+/// their chunk sizes to reliably calculate the optimal sizes. Each chunk size
+/// is a fraction of the configured max, capped by the smallest advertised size.
+pub(crate) fn calculate_conservative_chunk_sizes(
+    config: &StorageServiceConfig,
+    max_account_states_chunk_sizes: Vec<u64>,
+    max_epoch_chunk_sizes: Vec<u64>,
+    max_transaction_chunk_sizes: Vec<u64>,
+    max_transaction_output_chunk_size: Vec<u64>,
+) -> OptimalChunkSizes {
+    OptimalChunkSizes {
+        account_states_chunk_size: conservative_chunk_size(
+            max_account_states_chunk_sizes,
+            config.max_account_states_chunk_sizes,
+        ),
+        epoch_chunk_size: conservative_chunk_size(
+            max_epoch_chunk_sizes,
+            config.max_epoch_chunk_size,
+        ),
+        transaction_chunk_size: conservative_chunk_size(
+            max_transaction_chunk_sizes,
+            config.max_transaction_chunk_size,
+        ),
+        transaction_output_chunk_size: conservative_chunk_size(
+            max_transaction_output_chunk_size,
+            config.max_transaction_output_chunk_size,
+        ),
+    }
+}
+
+/// Returns a fraction of the given max value (but at least one), capped by the
+/// smallest of the given values (if any).
+fn conservative_chunk_size(values: Vec<u64>, max_value: u64) -> u64 {
+    let conservative_max = max(1, max_value / CONSERVATIVE_CHUNK_SIZE_DIVISOR);
+    values
+        .into_iter()
+        .min()
+        .map_or(conservative_max, |smallest| min(smallest, conservative_max))
+}
+
 /// Returns true iff the given peer is high-priority.
 ///
 /// TODO(joshlind): make this less hacky using network topological awareness.
@@ -311,15 +1477,18 @@ fn is_priority_peer(peer: &PeerNetworkId) -> bool {
     peer.network_id().is_validator_network()
 }
 
-/// Calculates the median of the given set of values (if it exists)
-/// and returns the median or the specified max value, whichever is
-/// lower.
-fn median_or_max<T: Ord + Copy>(mut values: Vec<T>, max_value: T) -> T {
-    // Calculate median
+/// Calculates the given percentile (between 0 and 1) of the given set of
+/// values (if it exists) and returns the percentile or the specified max
+/// value, whichever is lower. A percentile of 0.5 selects the median.
+fn percentile_or_max<T: Ord + Copy>(mut values: Vec<T>, percentile: f64, max_value: T) -> T {
+    // Calculate percentile
     values.sort_unstable();
-    let idx = values.len() / 2;
-    let median = values.get(idx).copied();
+    let percentile = percentile.clamp(0.0, 1.0);
+    let idx = ((values.len() as f64) * percentile) as usize;
+    let percentile_value = values
+        .get(min(idx, values.len().saturating_sub(1)))
+        .copied();
 
-    // Return median or max
-    min(median.unwrap_or(max_value), max_value)
+    // Return percentile or max
+    min(percentile_value.unwrap_or(max_value), max_value)
 }
@@ -1,37 +1,97 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{AptosDataClient, AptosNetDataClient, DataSummaryPoller, Error};
-use crate::aptosnet::state::calculate_optimal_chunk_sizes;
+use super::{
+    elapsed_since, AptosDataClient, AptosNetDataClient, DataSummaryPoller, DataType, Error,
+    PeerBanListener, PeerHintSource, PeerIdentityResolver, PeerScoringStrategy, PollerHandle,
+    TrustedLedgerInfoSource, REQUEST_RATE_WINDOW_SECS,
+};
+use crate::{
+    aptosnet::{
+        cache::CachedLedgerInfos,
+        coalescing::{CoalescedRequest, SharedResponse},
+        metrics,
+        state::{calculate_optimal_chunk_sizes, ErrorType},
+    },
+    FilteredTransactions, GlobalDataSummary, InvalidRequestReason, LatencyStats, OutcomeStats,
+    PeerExclusionReason, PeerTier, RequestCompletionEvent, RequestOptions, RequestPriority,
+    Response, ResponseQuality, ServiceabilityEvent, ServiceableReason, SyncPhase, TraceContext,
+    VerificationLevel,
+};
 use aptos_config::{
-    config::{AptosDataClientConfig, StorageServiceConfig},
+    config::{
+        AptosDataClientConfig, ChunkSizeConfig, CircuitBreakerConfig, CompressionAlgo,
+        DataClientMonitoringConfig, PeerScoringConfig, PeerSelectionConfig, PeerSelectionPolicy,
+        PeerSelectionStrategy, ResponseCachingConfig, StorageServiceConfig,
+        SummaryPersistenceConfig, SummaryPollerConfig,
+    },
     network_id::{NetworkId, PeerNetworkId},
 };
-use aptos_crypto::HashValue;
+use aptos_crypto::{
+    hash::{CryptoHash, TransactionAccumulatorHasher},
+    HashValue,
+};
+use aptos_temppath::TempPath;
 use aptos_time_service::{MockTimeService, TimeService};
 use aptos_types::{
+    account_address::AccountAddress,
     block_info::BlockInfo,
+    chain_id::ChainId,
+    contract_event::ContractEvent,
+    epoch_change::EpochChangeProof,
+    epoch_state::EpochState,
+    event::EventKey,
     ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
-    transaction::{TransactionListWithProof, Version},
+    network_address::NetworkAddress,
+    proof::{
+        MerkleTreeInternalNode, TransactionAccumulatorProof, TransactionAccumulatorRangeProof,
+        TransactionInfoListWithProof, TransactionInfoWithProof,
+    },
+    state_store::{state_key::StateKey, state_key_prefix::StateKeyPrefix, state_value::StateValue},
+    transaction::{
+        ExecutionStatus, RawTransaction, Script, SignedTransaction, Transaction, TransactionInfo,
+        TransactionListWithProof, TransactionOutput, TransactionOutputListWithProof,
+        TransactionPayload, TransactionStatus, TransactionWithProof, Version,
+    },
+    validator_signer::ValidatorSigner,
+    validator_verifier::ValidatorVerifier,
+    write_set::{WriteOp, WriteSetMut},
     PeerId,
 };
+use bytes::Bytes;
 use channel::{aptos_channel, message_queues::QueueStyle};
 use claim::{assert_err, assert_matches};
-use futures::StreamExt;
+use futures::{channel::oneshot, future, FutureExt, StreamExt};
 use maplit::hashmap;
+use move_core_types::language_storage::TypeTag;
 use network::{
     application::{interface::MultiNetworkSender, storage::PeerMetadataStorage},
     peer_manager::{ConnectionRequestSender, PeerManagerRequest, PeerManagerRequestSender},
-    protocols::{network::NewNetworkSender, wire::handshake::v1::ProtocolId},
+    protocols::{
+        network::{NewNetworkSender, RpcError},
+        wire::handshake::v1::ProtocolId,
+    },
     transport::ConnectionMetadata,
 };
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use std::{
+    cmp::min,
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use storage_service_client::{StorageServiceClient, StorageServiceNetworkSender};
 use storage_service_server::network::{NetworkRequest, ResponseSender};
 use storage_service_types::{
-    CompleteDataRange, DataSummary, ProtocolMetadata, StorageServerSummary, StorageServiceError,
+    strip_write_sets, AccountStatesByPrefixRequest, CompleteDataRange, CompressedResponse,
+    CompressedResponseRequest, DataSummary, EpochEndingLedgerInfoRequest, ProtocolMetadata,
+    ServerProtocolVersion, SizeLimitedTransactions, StorageServerSummary, StorageServiceError,
     StorageServiceMessage, StorageServiceRequest, StorageServiceResponse,
-    TransactionsWithProofRequest,
+    TransactionOutputsWithProofRequest, TransactionsWithProofRequest, BASE_PROTOCOL_VERSION,
+    EXTENDED_PROTOCOL_VERSION,
 };
 
 fn mock_ledger_info(version: Version) -> LedgerInfoWithSignatures {
@@ -44,6 +104,133 @@ fn mock_ledger_info(version: Version) -> LedgerInfoWithSignatures {
     )
 }
 
+fn mock_epoch_ending_ledger_info(
+    epoch: u64,
+    next_epoch_validator: &ValidatorSigner,
+) -> LedgerInfoWithSignatures {
+    let next_epoch_state = EpochState {
+        epoch: epoch + 1,
+        verifier: ValidatorVerifier::new_single(
+            next_epoch_validator.author(),
+            next_epoch_validator.public_key(),
+        ),
+    };
+    LedgerInfoWithSignatures::new(
+        LedgerInfo::new(
+            BlockInfo::new(
+                epoch,
+                0,
+                HashValue::zero(),
+                HashValue::zero(),
+                0,
+                0,
+                Some(next_epoch_state),
+            ),
+            HashValue::zero(),
+        ),
+        BTreeMap::new(),
+    )
+}
+
+/// Creates a ledger info at the given epoch and version, signed by the given
+/// validator. If a next epoch validator is given, the ledger info ends the epoch.
+fn mock_signed_ledger_info(
+    epoch: u64,
+    version: Version,
+    validator: &ValidatorSigner,
+    next_epoch_validator: Option<&ValidatorSigner>,
+) -> LedgerInfoWithSignatures {
+    let next_epoch_state = next_epoch_validator.map(|next_epoch_validator| EpochState {
+        epoch: epoch + 1,
+        verifier: ValidatorVerifier::new_single(
+            next_epoch_validator.author(),
+            next_epoch_validator.public_key(),
+        ),
+    });
+    let ledger_info = LedgerInfo::new(
+        BlockInfo::new(
+            epoch,
+            0,
+            HashValue::zero(),
+            HashValue::zero(),
+            version,
+            0,
+            next_epoch_state,
+        ),
+        HashValue::zero(),
+    );
+    let signature = validator.sign(&ledger_info);
+    LedgerInfoWithSignatures::new(
+        ledger_info,
+        BTreeMap::from([(validator.author(), signature)]),
+    )
+}
+
+fn mock_user_transaction(sender: AccountAddress, sequence_number: u64) -> Transaction {
+    mock_user_transaction_with_code(sender, sequence_number, vec![])
+}
+
+fn mock_user_transaction_with_code(
+    sender: AccountAddress,
+    sequence_number: u64,
+    code: Vec<u8>,
+) -> Transaction {
+    let signer = ValidatorSigner::random(None);
+    let raw_transaction = RawTransaction::new(
+        sender,
+        sequence_number,
+        TransactionPayload::Script(Script::new(code, vec![], vec![])),
+        0,
+        0,
+        0,
+        ChainId::test(),
+    );
+    let signed_transaction = SignedTransaction::new(
+        raw_transaction.clone(),
+        signer.public_key(),
+        signer.sign(&raw_transaction),
+    );
+    Transaction::UserTransaction(signed_transaction)
+}
+
+fn mock_transaction_with_proof(version: Version, transaction: Transaction) -> TransactionWithProof {
+    let transaction_info = TransactionInfo::new(
+        HashValue::zero(),
+        HashValue::zero(),
+        HashValue::zero(),
+        0,
+        ExecutionStatus::Success,
+    );
+    let proof =
+        TransactionInfoWithProof::new(TransactionAccumulatorProof::new(vec![]), transaction_info);
+    TransactionWithProof::new(version, transaction, None, proof)
+}
+
+fn mock_transaction_output_list_with_proof(
+    start_version: Version,
+    transactions: Vec<Transaction>,
+) -> TransactionOutputListWithProof {
+    let transactions_and_outputs = transactions
+        .into_iter()
+        .map(|transaction| {
+            let write_op = (StateKey::Raw(vec![1]), WriteOp::Value(vec![1]));
+            let write_set = WriteSetMut::new(vec![write_op]).freeze().unwrap();
+            let output = TransactionOutput::new(
+                write_set,
+                vec![],
+                0,
+                TransactionStatus::Keep(ExecutionStatus::Success),
+            );
+            (transaction, output)
+        })
+        .collect();
+    TransactionOutputListWithProof::new(
+        transactions_and_outputs,
+        Some(start_version),
+        TransactionInfoListWithProof::new_empty(),
+    )
+}
+
 fn mock_storage_summary(version: Version) -> StorageServerSummary {
     StorageServerSummary {
         protocol_metadata: ProtocolMetadata {
@@ -62,6 +249,75 @@ fn mock_storage_summary(version: Version) -> StorageServerSummary {
     }
 }
 
+/// A simple peer hint source that always returns the same hints
+#[derive(Debug)]
+struct StaticPeerHintSource {
+    peer_hints: Vec<PeerNetworkId>,
+}
+
+impl PeerHintSource for StaticPeerHintSource {
+    fn get_peer_hints(&self) -> Vec<PeerNetworkId> {
+        self.peer_hints.clone()
+    }
+}
+
+/// A simple trusted ledger info source that always returns the same ledger info
+#[derive(Debug)]
+struct StaticTrustedLedgerInfoSource {
+    ledger_info: LedgerInfoWithSignatures,
+}
+
+impl TrustedLedgerInfoSource for StaticTrustedLedgerInfoSource {
+    fn get_latest_trusted_ledger_info(&self) -> Option<LedgerInfoWithSignatures> {
+        Some(self.ledger_info.clone())
+    }
+}
+
+/// A peer identity resolver that links peers with the same peer id (on any network)
+#[derive(Debug)]
+struct PeerIdIdentityResolver;
+
+impl PeerIdentityResolver for PeerIdIdentityResolver {
+    fn resolve_identity(&self, peer: &PeerNetworkId) -> Option<PeerId> {
+        Some(peer.peer_id())
+    }
+}
+
+/// A (buggy) peer scoring strategy that always returns NaN
+#[derive(Debug)]
+struct NanScoringStrategy;
+
+impl PeerScoringStrategy for NanScoringStrategy {
+    fn adjust_score(&self, _peer: &PeerNetworkId, _score: f64) -> f64 {
+        f64::NAN
+    }
+}
+
+/// A peer ban listener that counts the ban and restore notifications
+#[derive(Debug, Default)]
+struct CountingBanListener {
+    num_bans: AtomicU64,
+    num_restores: AtomicU64,
+}
+
+impl PeerBanListener for CountingBanListener {
+    fn on_peer_banned(&self, _peer: PeerNetworkId, _score: f64) {
+        self.num_bans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_peer_restored(&self, _peer: PeerNetworkId, _score: f64) {
+        self.num_restores.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A request sent from the client, along with the raw response channel
+type RawNetworkRequest = (
+    PeerId,
+    ProtocolId,
+    StorageServiceRequest,
+    oneshot::Sender<Result<Bytes, RpcError>>,
+);
+
 struct MockNetwork {
     peer_mgr_reqs_rx: aptos_channel::Receiver<(PeerId, ProtocolId), PeerManagerRequest>,
     peer_infos: Arc<PeerMetadataStorage>,
@@ -69,6 +325,12 @@ struct MockNetwork {
 
 impl MockNetwork {
     fn new() -> (Self, MockTimeService, AptosNetDataClient, DataSummaryPoller) {
+        Self::new_with_config(AptosDataClientConfig::default())
+    }
+
+    fn new_with_config(
+        data_client_config: AptosDataClientConfig,
+    ) -> (Self, MockTimeService, AptosNetDataClient, DataSummaryPoller) {
         let queue_cfg = aptos_channel::Config::new(10).queue_style(QueueStyle::FIFO);
         let (peer_mgr_reqs_tx, peer_mgr_reqs_rx) = queue_cfg.build();
         let (connection_reqs_tx, _connection_reqs_rx) = queue_cfg.build();
@@ -85,7 +347,7 @@ impl MockNetwork {
 
         let mock_time = TimeService::mock();
         let (client, poller) = AptosNetDataClient::new(
-            AptosDataClientConfig::default(),
+            data_client_config,
             StorageServiceConfig::default(),
             mock_time.clone(),
             network_client,
@@ -111,19 +373,40 @@ impl MockNetwork {
     }
 
     fn add_peer(&mut self, network_id: NetworkId) -> PeerNetworkId {
-        let peer_id = PeerId::random();
-        let mut connection_metadata = ConnectionMetadata::mock(peer_id);
+        let peer = PeerNetworkId::new(network_id, PeerId::random());
+        self.add_peer_with_id(peer);
+        peer
+    }
+
+    /// Add a new peer (with the given id) to the network peer DB
+    fn add_peer_with_id(&mut self, peer: PeerNetworkId) {
+        self.add_peer_with_address(peer, NetworkAddress::mock());
+    }
+
+    /// Add a new peer (with the given id and address) to the network peer DB
+    fn add_peer_with_address(&mut self, peer: PeerNetworkId, address: NetworkAddress) {
+        let mut connection_metadata = ConnectionMetadata::mock(peer.peer_id());
+        connection_metadata.addr = address;
         connection_metadata
             .application_protocols
             .insert(ProtocolId::StorageServiceRpc);
 
         self.peer_infos
-            .insert_connection(network_id, connection_metadata);
-        PeerNetworkId::new(network_id, peer_id)
+            .insert_connection(peer.network_id(), connection_metadata);
     }
 
     /// Get the next request sent from the client.
     async fn next_request(&mut self) -> Option<NetworkRequest> {
+        self.next_raw_request()
+            .await
+            .map(|(peer_id, protocol, request, res_tx)| {
+                (peer_id, protocol, request, ResponseSender::new(res_tx))
+            })
+    }
+
+    /// Get the next request sent from the client, along with the raw response
+    /// channel (e.g., to send responses that can't be deserialized).
+    async fn next_raw_request(&mut self) -> Option<RawNetworkRequest> {
         match self.peer_mgr_reqs_rx.next().await {
             Some(PeerManagerRequest::SendRpc(peer_id, network_request)) => {
                 let protocol = network_request.protocol_id;
@@ -135,14 +418,27 @@ impl MockNetwork {
                     StorageServiceMessage::Request(request) => request,
                     _ => panic!("unexpected: {:?}", message),
                 };
-                let response_sender = ResponseSender::new(res_tx);
 
-                Some((peer_id, protocol, request, response_sender))
+                Some((peer_id, protocol, request, res_tx))
             }
             Some(PeerManagerRequest::SendDirectSend(_, _)) => panic!("Unexpected direct send msg"),
             None => None,
         }
     }
+
+    /// Handles the next request sent from the client, which must be a server
+    /// protocol version request for the given peer (e.g., as sent by the
+    /// poller after fetching the first summary of the peer).
+    async fn handle_protocol_version_request(&mut self, peer_id: PeerId) {
+        let (polled_peer, _, request, response_sender) = self.next_request().await.unwrap();
+        assert_eq!(polled_peer, peer_id);
+        assert_matches!(request, StorageServiceRequest::GetServerProtocolVersion);
+        response_sender.send(Ok(StorageServiceResponse::ServerProtocolVersion(
+            ServerProtocolVersion {
+                protocol_version: EXTENDED_PROTOCOL_VERSION,
+            },
+        )));
+    }
 }
 
 #[tokio::test]
@@ -182,6 +478,9 @@ async fn request_works_only_when_data_available() {
 
     let summary = mock_storage_summary(200);
     response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(summary)));
+    mock_network
+        .handle_protocol_version_request(expected_peer.peer_id())
+        .await;
 
     // Let the poller finish processing the response
     tokio::task::yield_now().await;
@@ -216,6 +515,163 @@ async fn request_works_only_when_data_available() {
     assert_eq!(response.payload, TransactionListWithProof::new_empty());
 }
 
+#[tokio::test]
+async fn missed_poll_rounds_are_coalesced() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, _, poller) = MockNetwork::new();
+
+    // Add two peers and start the poller
+    let peer_1 = mock_network.add_priority_peer();
+    let peer_2 = mock_network.add_priority_peer();
+    tokio::spawn(poller.start_poller());
+
+    // Advance time by many poll intervals at once
+    let poll_interval_ms = AptosDataClientConfig::default().summary_poll_interval_ms;
+    tokio::task::yield_now().await;
+    mock_time
+        .advance_async(Duration::from_millis(poll_interval_ms * 100))
+        .await;
+
+    // Verify each peer is polled exactly once
+    let mut polled_peers = vec![];
+    for _ in 0..2 {
+        let (peer, _, request, response_sender) = mock_network.next_request().await.unwrap();
+        assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+        polled_peers.push(peer);
+
+        response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+            mock_storage_summary(100),
+        )));
+        mock_network.handle_protocol_version_request(peer).await;
+    }
+    assert!(polled_peers.contains(&peer_1.peer_id()));
+    assert!(polled_peers.contains(&peer_2.peer_id()));
+
+    // Verify no other poll rounds occur (i.e., the missed rounds were coalesced)
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn poller_waits_for_startup_delay() {
+    ::aptos_logger::Logger::init_for_testing();
+    let poller_startup_delay_ms = 1000;
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            poller_startup_delay_ms,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let poll_interval_ms = data_client_config.summary_poll_interval_ms;
+    let (mut mock_network, mock_time, _, poller) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer and start the poller
+    let peer = mock_network.add_priority_peer();
+    tokio::spawn(poller.start_poller());
+
+    // Advance time by several poll intervals (but less than the startup delay)
+    tokio::task::yield_now().await;
+    for _ in 0..(poller_startup_delay_ms / poll_interval_ms) - 1 {
+        mock_time
+            .advance_async(Duration::from_millis(poll_interval_ms))
+            .await;
+    }
+
+    // Verify no summary requests have been sent
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+
+    // Elapse the startup delay and verify the peer is polled
+    mock_time
+        .advance_async(Duration::from_millis(poll_interval_ms))
+        .await;
+    let (polled_peer, _, request, _) = mock_network.next_request().await.unwrap();
+    assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+    assert_eq!(polled_peer, peer.peer_id());
+}
+
+#[tokio::test]
+async fn failing_peers_are_excluded_from_polling() {
+    ::aptos_logger::Logger::init_for_testing();
+    let max_consecutive_summary_poll_failures = 3;
+    let summary_poll_failure_cooldown_ms = 10_000;
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            max_consecutive_summary_poll_failures,
+            summary_poll_failure_cooldown_ms,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer and fail the maximum number of consecutive summary polls
+    let peer = mock_network.add_priority_peer();
+    for _ in 0..max_consecutive_summary_poll_failures {
+        assert_eq!(client.fetch_peers_to_poll().unwrap(), vec![peer]);
+        client.handle_summary_poll_failure(peer);
+    }
+
+    // Verify the peer is no longer polled
+    for _ in 0..10 {
+        assert!(client.fetch_peers_to_poll().unwrap().is_empty());
+    }
+
+    // Elapse the cooldown and verify the peer is polled again
+    mock_time.advance(Duration::from_millis(summary_poll_failure_cooldown_ms));
+    assert_eq!(client.fetch_peers_to_poll().unwrap(), vec![peer]);
+}
+
+#[tokio::test]
+async fn poll_all_peers_returns_each_result() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Verify no results are returned when there are no peers
+    assert!(client
+        .poll_all_peers(Duration::from_secs(10))
+        .await
+        .is_empty());
+
+    // Add several peers
+    let responsive_peer_1 = mock_network.add_priority_peer();
+    let responsive_peer_2 = mock_network.add_priority_peer();
+    let erroring_peer = mock_network.add_priority_peer();
+
+    // Spawn a handler that responds to all peers except the erroring peer
+    tokio::spawn(async move {
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+            if peer == erroring_peer.peer_id() {
+                response_sender.send(Err(StorageServiceError::InternalError("".to_string())));
+            } else {
+                response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+                    mock_storage_summary(100),
+                )));
+            }
+        }
+    });
+
+    // Poll all peers and verify the individual results
+    let poll_results = client.poll_all_peers(Duration::from_secs(10)).await;
+    assert_eq!(poll_results.len(), 3);
+    for peer in [responsive_peer_1, responsive_peer_2] {
+        assert_eq!(
+            poll_results[&peer].as_ref().unwrap(),
+            &mock_storage_summary(100)
+        );
+    }
+    assert_matches!(
+        poll_results[&erroring_peer],
+        Err(Error::UnexpectedErrorEncountered(_))
+    );
+}
+
 #[tokio::test]
 async fn fetch_priority_peers_to_poll() {
     ::aptos_logger::Logger::init_for_testing();
@@ -349,6 +805,35 @@ async fn fetch_regular_peers_to_poll() {
     }
 }
 
+#[tokio::test]
+async fn fetch_hinted_peers_to_poll() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Create a hint for a regular peer that has not yet connected
+    let hinted_peer = PeerNetworkId::new(NetworkId::Vfn, PeerId::random());
+    client.set_peer_hint_source(Arc::new(StaticPeerHintSource {
+        peer_hints: vec![hinted_peer],
+    }));
+
+    // Add a regular (non-hinted) peer and verify the hint doesn't bypass the
+    // connection requirements.
+    let regular_peer = mock_network.add_regular_peer();
+    let peers_to_poll = client.fetch_peers_to_poll().unwrap();
+    assert_eq!(peers_to_poll, vec![regular_peer]);
+
+    // Connect the hinted peer and verify it is polled first
+    mock_network.add_peer_with_id(hinted_peer);
+    let peers_to_poll = client.fetch_peers_to_poll().unwrap();
+    assert_eq!(peers_to_poll.first(), Some(&hinted_peer));
+
+    // Verify the hinted peer is now preferentially polled ahead of the regular peer
+    for _ in 0..10 {
+        let peers_to_poll = client.fetch_peers_to_poll().unwrap();
+        assert_eq!(peers_to_poll.first(), Some(&hinted_peer));
+    }
+}
+
 // 1. 2 peers
 // 2. one advertises bad range, one advertises honest range
 // 3. sending a bunch of requests to the bad range (which will always go to the
@@ -393,6 +878,7 @@ async fn bad_peer_is_eventually_banned_internal() {
     });
 
     let mut seen_data_unavailable_err = false;
+    let mut bad_peer_score = client.get_peer_states()[&bad_peer].score;
 
     // Sending a bunch of requests to the bad peer's upper range will fail.
     for _ in 0..20 {
@@ -400,6 +886,11 @@ async fn bad_peer_is_eventually_banned_internal() {
             .get_transactions_with_proof(200, 200, 200, false)
             .await;
 
+        // The bad peer's score should never increase
+        let new_bad_peer_score = client.get_peer_states()[&bad_peer].score;
+        assert!(new_bad_peer_score <= bad_peer_score);
+        bad_peer_score = new_bad_peer_score;
+
         // While the score is still decreasing, we should see a bunch of
         // InternalError's. Once we see a `DataIsUnavailable` error, we should
         // only see that error.
@@ -417,6 +908,17 @@ async fn bad_peer_is_eventually_banned_internal() {
     // range unserviceable.
     assert!(seen_data_unavailable_err);
 
+    // The peer states should show the bad peer (and only the bad peer) as ignored
+    let mut peer_states = client.get_peer_states();
+    assert!(peer_states[&bad_peer].is_ignored);
+    assert_eq!(peer_states[&bad_peer].advertised_version, Some(200));
+    assert!(!peer_states[&good_peer].is_ignored);
+    assert_eq!(peer_states[&good_peer].advertised_version, Some(100));
+
+    // Modifying the snapshot should not affect the client's peer states
+    peer_states.get_mut(&bad_peer).unwrap().is_ignored = false;
+    assert!(client.get_peer_states()[&bad_peer].is_ignored);
+
     // The global summary should no longer contain the bad peer's advertisement.
     client.update_global_summary_cache();
     let global_summary = client.get_global_data_summary();
@@ -431,6 +933,7 @@ async fn bad_peer_is_eventually_banned_internal() {
         .await
         .unwrap();
     assert_eq!(response.payload, TransactionListWithProof::new_empty());
+    assert_eq!(response.context.peer, Some(good_peer));
 }
 
 #[tokio::test]
@@ -516,6 +1019,11 @@ async fn bad_peer_is_eventually_added_back() {
                 StorageServiceRequest::GetStorageServerSummary => response_sender.send(Ok(
                     StorageServiceResponse::StorageServerSummary(mock_storage_summary(200)),
                 )),
+                StorageServiceRequest::GetServerProtocolVersion => response_sender.send(Ok(
+                    StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                        protocol_version: EXTENDED_PROTOCOL_VERSION,
+                    }),
+                )),
                 _ => panic!("unexpected: {:?}", request),
             }
         }
@@ -568,68 +1076,6009 @@ async fn bad_peer_is_eventually_added_back() {
 }
 
 #[tokio::test]
-async fn optimal_chunk_size_calculations() {
-    // Create a test storage service config
-    let max_account_states_chunk_sizes = 500;
-    let max_epoch_chunk_size = 600;
-    let max_transaction_chunk_size = 700;
-    let max_transaction_output_chunk_size = 800;
-    let storage_service_config = StorageServiceConfig {
-        max_account_states_chunk_sizes,
-        max_concurrent_requests: 0,
-        max_epoch_chunk_size,
-        max_network_channel_size: 0,
-        max_transaction_chunk_size,
-        max_transaction_output_chunk_size,
-        storage_summary_refresh_interval_ms: 0,
-    };
+async fn summary_ages_are_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
 
-    // Test median calculations
-    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
-        &storage_service_config,
-        vec![100, 200, 300, 100],
-        vec![7, 5, 6, 8, 10],
-        vec![900, 700, 500],
-        vec![40],
-    );
-    assert_eq!(200, optimal_chunk_sizes.account_states_chunk_size);
-    assert_eq!(7, optimal_chunk_sizes.epoch_chunk_size);
-    assert_eq!(700, optimal_chunk_sizes.transaction_chunk_size);
-    assert_eq!(40, optimal_chunk_sizes.transaction_output_chunk_size);
+    // Update the summary of the first peer
+    let peer_1 = mock_network.add_priority_peer();
+    client.update_summary(peer_1, mock_storage_summary(100));
 
-    // Test no advertised data
-    let optimal_chunk_sizes =
-        calculate_optimal_chunk_sizes(&storage_service_config, vec![], vec![], vec![], vec![]);
-    assert_eq!(
-        max_account_states_chunk_sizes,
-        optimal_chunk_sizes.account_states_chunk_size
-    );
-    assert_eq!(max_epoch_chunk_size, optimal_chunk_sizes.epoch_chunk_size);
-    assert_eq!(
-        max_transaction_chunk_size,
-        optimal_chunk_sizes.transaction_chunk_size
-    );
-    assert_eq!(
-        max_transaction_output_chunk_size,
-        optimal_chunk_sizes.transaction_output_chunk_size
-    );
+    // Advance time and update the summary of the second peer
+    mock_time.advance(Duration::from_secs(10));
+    let peer_2 = mock_network.add_regular_peer();
+    client.update_summary(peer_2, mock_storage_summary(100));
 
-    // Verify the config caps the amount of chunks
-    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
-        &storage_service_config,
-        vec![1000, 1000, 2000, 3000],
-        vec![70, 50, 60, 80, 100],
-        vec![9000, 7000, 5000],
-        vec![400],
-    );
-    assert_eq!(
-        max_account_states_chunk_sizes,
-        optimal_chunk_sizes.account_states_chunk_size
-    );
-    assert_eq!(70, optimal_chunk_sizes.epoch_chunk_size);
-    assert_eq!(
-        max_transaction_chunk_size,
-        optimal_chunk_sizes.transaction_chunk_size
+    // Advance time again and verify the reported ages
+    mock_time.advance(Duration::from_secs(5));
+    let summary_ages = client.summary_ages();
+    assert_eq!(summary_ages.len(), 2);
+    assert_eq!(summary_ages[&peer_1], Duration::from_secs(15));
+    assert_eq!(summary_ages[&peer_2], Duration::from_secs(5));
+
+    // Refresh the first peer's summary and verify its age is reset
+    client.update_summary(peer_1, mock_storage_summary(200));
+    let summary_ages = client.summary_ages();
+    assert_eq!(summary_ages[&peer_1], Duration::from_secs(0));
+    assert_eq!(summary_ages[&peer_2], Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn network_stall_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Verify the network isn't stalled before any data is advertised
+    assert!(!client.is_network_stalled());
+
+    // Add a peer that is stuck at version 200
+    let peer = mock_network.add_priority_peer();
+    let stall_threshold = Duration::from_millis(
+        AptosDataClientConfig::default()
+            .monitoring
+            .network_stall_threshold_ms,
     );
-    assert_eq!(400, optimal_chunk_sizes.transaction_output_chunk_size);
+    let poll_round_duration = stall_threshold / 4;
+
+    // Run several poll rounds where the peer doesn't advance
+    for _ in 0..3 {
+        client.update_summary(peer, mock_storage_summary(200));
+        client.update_global_summary_cache();
+        assert!(!client.is_network_stalled());
+        mock_time.advance(poll_round_duration);
+    }
+
+    // Eventually the stall should be reported
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    mock_time.advance(poll_round_duration);
+    assert!(client.is_network_stalled());
+    assert_eq!(client.network_stall_duration(), Some(stall_threshold));
+
+    // Advance the peer and verify the stall is cleared
+    client.update_summary(peer, mock_storage_summary(201));
+    client.update_global_summary_cache();
+    assert!(!client.is_network_stalled());
+}
+
+#[tokio::test]
+async fn compressed_responses_are_decompressed() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        preferred_compression_algorithms: vec![CompressionAlgo::Zstd],
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds with zstd for the first request and lz4 after
+    tokio::spawn(async move {
+        let compression_algorithms = vec![CompressionAlgo::Zstd, CompressionAlgo::Lz4];
+        for compression_algorithm in compression_algorithms {
+            let (_, _, request, response_sender) = mock_network.next_request().await.unwrap();
+
+            // Verify the client advertised its supported algorithms
+            let inner_request = match request {
+                StorageServiceRequest::GetCompressedResponse(request) => {
+                    assert_eq!(request.supported_algorithms, vec![CompressionAlgo::Zstd]);
+                    *request.request
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            assert_matches!(
+                inner_request,
+                StorageServiceRequest::GetTransactionsWithProof(_)
+            );
+
+            // Respond with a compressed response
+            let response = StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            );
+            let compressed_response =
+                CompressedResponse::compress(&response, compression_algorithm).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::CompressedResponse(
+                compressed_response,
+            )));
+        }
+    });
+
+    // Verify the zstd response is decompressed correctly
+    let response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+
+    // Verify the lz4 response is rejected (it's not in the supported set)
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidResponse(_));
+}
+
+#[tokio::test]
+async fn bypass_cache_sends_request() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts and responds to all epoch requests
+    let validator = ValidatorSigner::random(None);
+    let epoch_ending_ledger_infos: Vec<_> = (0..=10)
+        .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+        .collect();
+    let epoch_ending_ledger_infos_clone = epoch_ending_ledger_infos.clone();
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            let request = match request {
+                StorageServiceRequest::GetEpochEndingLedgerInfos(request) => request,
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            let ledger_infos = epoch_ending_ledger_infos_clone
+                [request.start_epoch as usize..=request.expected_end_epoch as usize]
+                .to_vec();
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                EpochChangeProof::new(ledger_infos, false),
+            )));
+        }
+    });
+
+    // Fetch the ledger infos and verify a request was sent
+    let response = client.get_epoch_ending_ledger_infos(0, 5).await.unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[0..=5].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Fetch the same ledger infos and verify they're served from the cache
+    let response = client.get_epoch_ending_ledger_infos(0, 5).await.unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[0..=5].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Bypass the cache and verify a new request is sent
+    let request_options = RequestOptions {
+        bypass_cache: true,
+        ..Default::default()
+    };
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(0, 5, request_options)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[0..=5].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn overloaded_peer_is_avoided_until_retry_after() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds with an overloaded error and then succeeds
+    let retry_after_ms = 5_000;
+    tokio::spawn(async move {
+        let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        response_sender.send(Err(StorageServiceError::Overloaded { retry_after_ms }));
+
+        let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+            TransactionListWithProof::new_empty(),
+        )));
+    });
+
+    // The first request should fail because the peer is overloaded
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::PeerIsOverloaded(_));
+
+    // The peer should be avoided until the retry-after duration has elapsed
+    mock_time.advance(Duration::from_millis(retry_after_ms - 1));
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+
+    // Once the duration has elapsed, requests should be routed to the peer again
+    mock_time.advance(Duration::from_millis(1));
+    let response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+}
+
+#[tokio::test]
+async fn late_response_partially_reverts_timeout_penalty() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        late_response_grace_ms: 1_000,
+        response_timeout_ms: 1_000,
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    let initial_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+
+    // Send a request and wait for it to reach the peer
+    let client_clone = client.clone();
+    let request_handle = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+    });
+    let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+
+    // Elapse the response timeout and verify the request times out
+    mock_time.advance_async(Duration::from_millis(1_000)).await;
+    let error = request_handle.await.unwrap().unwrap_err();
+    assert_matches!(error, Error::TimeoutWaitingForResponse(_));
+
+    // Verify the peer was penalized for the timeout
+    let timeout_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+    assert!(timeout_score < initial_score);
+
+    // Deliver the response within the grace period
+    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        TransactionListWithProof::new_empty(),
+    )));
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+
+    // Verify the timeout penalty was partially reverted
+    let late_response_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+    assert!(late_response_score > timeout_score);
+    assert!(late_response_score < initial_score);
+}
+
+#[tokio::test]
+async fn sync_phase_transitions_to_following() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Verify the client is bootstrapping before any summaries are known
+    assert_eq!(client.sync_phase(), SyncPhase::Bootstrapping);
+
+    // Add a peer advertising the data and verify the client is still bootstrapping
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    assert_eq!(client.sync_phase(), SyncPhase::Bootstrapping);
+
+    // Spawn a handler that responds successfully to all requests
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send a successful request and verify the client is now following
+    client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(client.sync_phase(), SyncPhase::Following);
+
+    // Elapse the staleness threshold and verify the client is bootstrapping again
+    let staleness_threshold_ms = AptosDataClientConfig::default()
+        .monitoring
+        .sync_phase_response_staleness_ms;
+    mock_time.advance(Duration::from_millis(staleness_threshold_ms + 1));
+    assert_eq!(client.sync_phase(), SyncPhase::Bootstrapping);
+}
+
+#[tokio::test]
+async fn verification_level_is_respected() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Create ledger infos that are structurally valid, but where the second
+    // ledger info is not signed by the validator set of the first epoch.
+    let validator = ValidatorSigner::random(None);
+    let epoch_ending_ledger_infos = vec![
+        mock_epoch_ending_ledger_info(0, &validator),
+        mock_epoch_ending_ledger_info(1, &validator),
+    ];
+
+    // Spawn a handler that responds to all epoch requests
+    let epoch_ending_ledger_infos_clone = epoch_ending_ledger_infos.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            let epoch_change_proof =
+                EpochChangeProof::new(epoch_ending_ledger_infos_clone.clone(), false);
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                epoch_change_proof,
+            )));
+        }
+    });
+
+    // Verify the ledger infos are accepted by default (i.e., without verification)
+    let request_options = RequestOptions {
+        bypass_cache: true,
+        ..Default::default()
+    };
+    assert_eq!(request_options.verification_level, VerificationLevel::None);
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(0, 5, request_options)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos);
+
+    // Verify the ledger infos are accepted using structural verification
+    let request_options = RequestOptions {
+        bypass_cache: true,
+        verification_level: VerificationLevel::StructuralOnly,
+        ..Default::default()
+    };
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(0, 5, request_options)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos);
+
+    // Verify the ledger infos are rejected using full verification
+    let request_options = RequestOptions {
+        bypass_cache: true,
+        verification_level: VerificationLevel::Full,
+        ..Default::default()
+    };
+    let error = client
+        .get_epoch_ending_ledger_infos_with_options(0, 5, request_options)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidResponse(_));
+}
+
+#[tokio::test]
+async fn full_coverage_peers_are_preferred() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            allow_partial_coverage_peers: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a full-coverage peer and a partial-coverage peer
+    let full_coverage_peer = mock_network.add_priority_peer();
+    client.update_summary(full_coverage_peer, mock_storage_summary(200));
+    let partial_coverage_peer = mock_network.add_priority_peer();
+    client.update_summary(partial_coverage_peer, mock_storage_summary(100));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the peers that were sent requests (and the requests)
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send((peer, request)).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify that all (untrimmed) requests are sent to the full-coverage peer
+    for _ in 0..10 {
+        client
+            .get_transactions_with_proof(100, 50, 150, false)
+            .await
+            .unwrap();
+        let (peer, request) = peer_receiver.next().await.unwrap();
+        assert_eq!(peer, full_coverage_peer.peer_id());
+        assert_eq!(request, create_transactions_request(100, 50, 150));
+    }
+
+    // Remove the full-coverage peer's data and verify the partial-coverage
+    // peer is now used (and the request is trimmed to its advertised range).
+    client.update_summary(full_coverage_peer, mock_storage_summary(10));
+    client
+        .get_transactions_with_proof(100, 50, 150, false)
+        .await
+        .unwrap();
+    let (peer, request) = peer_receiver.next().await.unwrap();
+    assert_eq!(peer, partial_coverage_peer.peer_id());
+    assert_eq!(request, create_transactions_request(100, 50, 100));
+}
+
+/// Creates a request for transactions (without events)
+fn create_transactions_request(
+    proof_version: Version,
+    start_version: Version,
+    end_version: Version,
+) -> StorageServiceRequest {
+    StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version,
+        start_version,
+        end_version,
+        include_events: false,
+    })
+}
+
+#[tokio::test]
+async fn archival_peers_are_preferred() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add an archival peer and a pruned peer (both covering a historical range)
+    let archival_peer = mock_network.add_priority_peer();
+    client.update_summary(archival_peer, mock_storage_summary(200));
+    let pruned_peer = mock_network.add_priority_peer();
+    let mut pruned_storage_summary = mock_storage_summary(200);
+    pruned_storage_summary.data_summary.transactions =
+        Some(CompleteDataRange::new(50, 200).unwrap());
+    client.update_summary(pruned_peer, pruned_storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the peers that were sent requests
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify that all requests are sent to the archival peer
+    let request_options = RequestOptions {
+        prefer_archival: true,
+        ..Default::default()
+    };
+    for _ in 0..10 {
+        client
+            .get_transactions_with_proof_with_options(200, 100, 150, false, request_options.clone())
+            .await
+            .unwrap();
+        let peer = peer_receiver.next().await.unwrap();
+        assert_eq!(peer, archival_peer.peer_id());
+    }
+
+    // Remove the archival peer's data and verify the pruned peer is now used
+    client.update_summary(archival_peer, mock_storage_summary(10));
+    client
+        .get_transactions_with_proof_with_options(200, 100, 150, false, request_options)
+        .await
+        .unwrap();
+    let peer = peer_receiver.next().await.unwrap();
+    assert_eq!(peer, pruned_peer.peer_id());
+}
+
+#[tokio::test]
+async fn low_peer_diversity_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        monitoring: DataClientMonitoringConfig {
+            min_peer_diversity: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add several peers advertising data and verify the diversity is sufficient
+    let mut peers = vec![];
+    for _ in 0..3 {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(100));
+        peers.push(peer);
+    }
+    client.update_global_summary_cache();
+    assert_eq!(client.peer_diversity(), 3);
+    assert!(!client.is_peer_diversity_low());
+
+    // Ban a peer until it is ignored
+    let num_warnings = metrics::LOW_PEER_DIVERSITY_WARNINGS.get();
+    for _ in 0..10 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peers[0], ErrorType::Malicious);
+    }
+    client.update_global_summary_cache();
+
+    // Verify the diversity dropped and the warning event fired (once)
+    assert_eq!(client.peer_diversity(), 2);
+    assert!(client.is_peer_diversity_low());
+    assert_eq!(metrics::LOW_PEER_DIVERSITY_WARNINGS.get(), num_warnings + 1);
+    client.update_global_summary_cache();
+    assert_eq!(metrics::LOW_PEER_DIVERSITY_WARNINGS.get(), num_warnings + 1);
+}
+
+#[tokio::test]
+async fn identical_requests_are_coalesced_within_window() {
+    ::aptos_logger::Logger::init_for_testing();
+    let coalesce_window_ms = 10;
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            coalesce_window_ms,
+            enable_request_coalescing: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts and responds to all requests
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send a request and verify an RPC was sent
+    let response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Send an identical request a few milliseconds later and verify it's coalesced
+    mock_time.advance(Duration::from_millis(coalesce_window_ms / 2));
+    let response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Elapse the window and verify a new RPC is sent
+    mock_time.advance(Duration::from_millis(coalesce_window_ms));
+    client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn requests_are_only_coalesced_if_enabled_and_options_match() {
+    ::aptos_logger::Logger::init_for_testing();
+    for enable_request_coalescing in [false, true] {
+        let data_client_config = AptosDataClientConfig {
+            caching: ResponseCachingConfig {
+                coalesce_window_ms: 10,
+                enable_request_coalescing,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+        // Add a peer advertising the data
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(200));
+        client.update_global_summary_cache();
+
+        // Spawn a handler that counts and responds to all requests
+        let num_requests = Arc::new(AtomicU64::new(0));
+        let num_requests_clone = num_requests.clone();
+        tokio::spawn(async move {
+            while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+                num_requests_clone.fetch_add(1, Ordering::SeqCst);
+                response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                )));
+            }
+        });
+
+        // Send two identical requests and verify they're only coalesced if enabled
+        for _ in 0..2 {
+            client
+                .get_transactions_with_proof(200, 0, 200, false)
+                .await
+                .unwrap();
+        }
+        let expected_num_requests = if enable_request_coalescing { 1 } else { 2 };
+        assert_eq!(num_requests.load(Ordering::SeqCst), expected_num_requests);
+
+        // Send an identical request with different options and verify it's never coalesced
+        let request_options = RequestOptions {
+            prefer_archival: true,
+            ..Default::default()
+        };
+        client
+            .get_transactions_with_proof_with_options(200, 0, 200, false, request_options)
+            .await
+            .unwrap();
+        assert_eq!(
+            num_requests.load(Ordering::SeqCst),
+            expected_num_requests + 1
+        );
+    }
+}
+
+#[tokio::test]
+async fn bypass_requests_are_never_coalesced() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            coalesce_window_ms: 10,
+            enable_request_coalescing: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Send a request (and leave it in-flight)
+    let client_clone = client.clone();
+    let first_request = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+    });
+    let (_, _, first_network_request, first_response_sender) =
+        mock_network.next_request().await.unwrap();
+
+    // Send an identical request that bypasses the caches
+    let client_clone = client.clone();
+    let bypass_request = tokio::spawn(async move {
+        let request_options = RequestOptions {
+            bypass_cache: true,
+            ..Default::default()
+        };
+        client_clone
+            .get_transactions_with_proof_with_options(200, 0, 200, false, request_options)
+            .await
+    });
+
+    // Verify a fresh request is sent to the network (even though an
+    // identical request is in-flight).
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    let (_, _, bypass_network_request, bypass_response_sender) =
+        mock_network.next_request().now_or_never().unwrap().unwrap();
+    assert_eq!(bypass_network_request, first_network_request);
+
+    // Respond to both requests and verify they complete
+    for response_sender in [first_response_sender, bypass_response_sender] {
+        response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+            TransactionListWithProof::new_empty(),
+        )));
+    }
+    first_request.await.unwrap().unwrap();
+    bypass_request.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn corrupted_shared_responses_are_refetched() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            coalesce_window_ms: 10,
+            enable_request_coalescing: true,
+            verify_cache_integrity: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts and responds to all requests
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send two identical requests and verify the response is shared
+    for _ in 0..2 {
+        client
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+            .unwrap();
+    }
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Replace the shared response with a response that doesn't match its hash
+    let coalescing_key = client
+        .coalesced_requests
+        .lock()
+        .keys()
+        .next()
+        .unwrap()
+        .clone();
+    let corrupted_response = SharedResponse::new(
+        peer,
+        StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new_empty()),
+        Some(HashValue::zero()),
+        client.create_response_callback(peer, coalescing_key.request().clone()),
+    );
+    let coalesced_request = CoalescedRequest::new(
+        client.time_service.now(),
+        future::ready(Ok(corrupted_response)).boxed().shared(),
+    );
+    client
+        .coalesced_requests
+        .lock()
+        .insert(coalescing_key, coalesced_request);
+
+    // Verify the corruption is detected, the shared response is removed and
+    // the request is sent to the network again.
+    client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+    assert!(client.coalesced_requests.lock().is_empty());
+}
+
+#[tokio::test]
+async fn coalesced_requests_only_penalize_the_peer_once() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            coalesce_window_ms: 10,
+            enable_request_coalescing: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all requests
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send two identical (coalesced) requests
+    let first_response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+    let second_response = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+
+    // Report the first response as bad and verify the peer is penalized
+    let initial_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+    first_response
+        .context
+        .response_callback
+        .notify_bad_response(crate::ResponseError::ProofVerificationError);
+    let penalized_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+    assert!(penalized_score < initial_score);
+
+    // Report the second (coalesced) response as bad and verify the peer isn't penalized again
+    second_response
+        .context
+        .response_callback
+        .notify_bad_response(crate::ResponseError::ProofVerificationError);
+    assert_eq!(
+        client.peer_states.read().get_peer_score(&peer).unwrap(),
+        penalized_score
+    );
+}
+
+#[tokio::test]
+async fn peer_exclusion_is_explained() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Create a request for transactions
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 0,
+        end_version: 200,
+        include_events: false,
+    });
+
+    // Verify a disconnected peer is reported as not connected
+    let peer = PeerNetworkId::new(NetworkId::Validator, PeerId::random());
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &request),
+        PeerExclusionReason::NotConnected
+    );
+
+    // Connect the peer and verify it is missing a summary
+    mock_network.add_peer_with_id(peer);
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &request),
+        PeerExclusionReason::MissingSummary
+    );
+
+    // Advertise data that doesn't cover the request and verify the missing coverage
+    client.update_summary(peer, mock_storage_summary(100));
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &request),
+        PeerExclusionReason::MissingCoverage
+    );
+
+    // Advertise data that covers the request and verify the peer isn't excluded
+    client.update_summary(peer, mock_storage_summary(200));
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &request),
+        PeerExclusionReason::NotExcluded
+    );
+
+    // Verify requests that the peer's protocol version doesn't support are explained
+    let compressed_request =
+        StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+            request: Box::new(request),
+            supported_algorithms: vec![CompressionAlgo::Zstd],
+        });
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &compressed_request),
+        PeerExclusionReason::UnsupportedRequest
+    );
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &compressed_request),
+        PeerExclusionReason::NotExcluded
+    );
+}
+
+#[tokio::test]
+async fn non_finite_scores_are_treated_as_neutral() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Use a scoring strategy that always returns NaN
+    client.set_peer_scoring_strategy(Arc::new(NanScoringStrategy));
+
+    // Add a peer that advertises data and penalize it heavily
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    for _ in 0..20 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peer, ErrorType::Malicious);
+    }
+    client.update_global_summary_cache();
+
+    // Verify the peer is treated as neutral (i.e., it is not ignored)
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 0,
+        end_version: 200,
+        include_events: false,
+    });
+    assert_eq!(
+        client.explain_peer_exclusion(peer, &request),
+        PeerExclusionReason::NotExcluded
+    );
+    let global_summary = client.get_global_data_summary();
+    assert!(global_summary
+        .advertised_data
+        .transactions
+        .contains(&CompleteDataRange::new(0, 200).unwrap()));
+
+    // Spawn a handler for the peer
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify requests are still sent to the peer
+    client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn transactions_are_filtered_by_sender() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that supports sender filtering
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Create transactions from the sender (and another account)
+    let sender = AccountAddress::random();
+    let sender_transaction = mock_user_transaction(sender, 0);
+    let other_transaction = mock_user_transaction(AccountAddress::random(), 0);
+    let transactions = vec![
+        other_transaction.clone(),
+        sender_transaction.clone(),
+        other_transaction,
+    ];
+
+    // Spawn a handler that prunes the transactions (or returns all of them)
+    let pruned_transactions = vec![mock_transaction_with_proof(101, sender_transaction.clone())];
+    let pruned_transactions_clone = pruned_transactions.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetTransactionsFromSender(request) => {
+                    assert_eq!(request.sender, sender);
+                    StorageServiceResponse::TransactionsFromSender(
+                        pruned_transactions_clone.clone(),
+                    )
+                }
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new(
+                        transactions.clone(),
+                        None,
+                        Some(request.start_version),
+                        TransactionInfoListWithProof::new_empty(),
+                    ))
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Verify the peer prunes the transactions
+    let request_options = RequestOptions {
+        sender_filter: Some(sender),
+        ..Default::default()
+    };
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload,
+        FilteredTransactions::PrunedByPeer(pruned_transactions)
+    );
+
+    // Update the peer so that it runs the base protocol version (i.e., it no
+    // longer supports sender filtering)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the client filters the transactions (and marks them as unverified)
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload,
+        FilteredTransactions::UnverifiedAfterFilter(vec![(101, sender_transaction)])
+    );
+}
+
+#[tokio::test]
+async fn filtered_requests_fall_back_if_capable_peers_are_excluded() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that supports sender filtering and a peer that doesn't
+    let extended_peer = mock_network.add_priority_peer();
+    let base_peer = mock_network.add_priority_peer();
+    for peer in [extended_peer, base_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(extended_peer, EXTENDED_PROTOCOL_VERSION);
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(base_peer, BASE_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that returns all transactions (and fails filtered requests)
+    let sender = AccountAddress::random();
+    let sender_transaction = mock_user_transaction(sender, 0);
+    let other_transaction = mock_user_transaction(AccountAddress::random(), 0);
+    let transactions = vec![
+        other_transaction.clone(),
+        sender_transaction.clone(),
+        other_transaction,
+    ];
+    tokio::spawn(async move {
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            assert_eq!(peer, base_peer);
+            let response = match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new(
+                        transactions.clone(),
+                        None,
+                        Some(request.start_version),
+                        TransactionInfoListWithProof::new_empty(),
+                    ))
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Exclude the peer that supports sender filtering and verify the client
+    // filters the transactions itself.
+    let request_options = RequestOptions {
+        sender_filter: Some(sender),
+        excluded_peers: HashSet::from([extended_peer]),
+        ..Default::default()
+    };
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload,
+        FilteredTransactions::UnverifiedAfterFilter(vec![(101, sender_transaction)])
+    );
+
+    // Verify only the selection for the sent request was recorded
+    assert_eq!(client.selection_latency_stats().num_samples, 1);
+}
+
+#[tokio::test]
+async fn oversized_transactions_are_excluded_or_flagged() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that supports size limits
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Create a list of transactions containing a single oversized transaction
+    let max_transaction_bytes = 1_000;
+    let sender = AccountAddress::random();
+    let small_transaction = mock_user_transaction(sender, 0);
+    let oversized_transaction = mock_user_transaction_with_code(sender, 1, vec![0; 2_000]);
+    let transactions = vec![
+        small_transaction.clone(),
+        oversized_transaction,
+        small_transaction.clone(),
+    ];
+
+    // Spawn a handler that excludes the oversized transaction (or returns all of them)
+    let size_limited_transactions = SizeLimitedTransactions {
+        transactions: vec![
+            mock_transaction_with_proof(100, small_transaction.clone()),
+            mock_transaction_with_proof(102, small_transaction),
+        ],
+        oversized_versions: vec![101],
+    };
+    let size_limited_transactions_clone = size_limited_transactions.clone();
+    let transactions_clone = transactions.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetTransactionsWithSizeLimit(request) => {
+                    assert_eq!(request.max_transaction_bytes, max_transaction_bytes as u64);
+                    StorageServiceResponse::TransactionsWithSizeLimit(
+                        size_limited_transactions_clone.clone(),
+                    )
+                }
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new(
+                        transactions_clone.clone(),
+                        None,
+                        Some(request.start_version),
+                        TransactionInfoListWithProof::new_empty(),
+                    ))
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Verify the peer excludes the oversized transaction
+    let request_options = RequestOptions {
+        max_transaction_bytes: Some(max_transaction_bytes),
+        ..Default::default()
+    };
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload,
+        FilteredTransactions::SizeLimitedByPeer(size_limited_transactions)
+    );
+
+    // Update the peer so that it runs the base protocol version (i.e., it no
+    // longer supports size limits)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the client flags the oversized transaction (and keeps the list intact)
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options)
+        .await
+        .unwrap();
+    match response.payload {
+        FilteredTransactions::FlaggedOversized {
+            transactions: transaction_list_with_proof,
+            oversized_versions,
+        } => {
+            assert_eq!(transaction_list_with_proof.transactions, transactions);
+            assert_eq!(oversized_versions, vec![101]);
+        }
+        payload => panic!("Unexpected payload: {:?}", payload),
+    }
+}
+
+#[tokio::test]
+async fn transactions_are_filtered_by_event_type() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that supports event filtering
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Create transactions where only the last two emit the target event type
+    let event_type = TypeTag::Bool;
+    let mock_event = |type_tag| ContractEvent::new(EventKey::random(), 0, type_tag, vec![]);
+    let transaction = mock_user_transaction(AccountAddress::random(), 0);
+    let transactions = vec![
+        transaction.clone(),
+        transaction.clone(),
+        transaction.clone(),
+    ];
+    let events = vec![
+        vec![mock_event(TypeTag::U64)],
+        vec![mock_event(event_type.clone())],
+        vec![mock_event(TypeTag::U8), mock_event(event_type.clone())],
+    ];
+
+    // Spawn a handler that prunes the transactions (or returns all of them)
+    let mut filtered_transactions = vec![];
+    for version in [101, 102] {
+        let mut transaction_with_proof = mock_transaction_with_proof(version, transaction.clone());
+        transaction_with_proof.events = Some(events[(version - 100) as usize].clone());
+        filtered_transactions.push(transaction_with_proof);
+    }
+    let filtered_transactions_clone = filtered_transactions.clone();
+    let transactions_clone = transactions.clone();
+    let event_type_clone = event_type.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetTransactionsWithEvent(request) => {
+                    assert_eq!(request.event_type, event_type_clone);
+                    StorageServiceResponse::TransactionsWithEvent(
+                        filtered_transactions_clone.clone(),
+                    )
+                }
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    assert!(request.include_events);
+                    StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new(
+                        transactions_clone.clone(),
+                        Some(events.clone()),
+                        Some(request.start_version),
+                        TransactionInfoListWithProof::new_empty(),
+                    ))
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Verify the peer prunes the transactions
+    let request_options = RequestOptions {
+        event_type_filter: Some(event_type),
+        ..Default::default()
+    };
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload,
+        FilteredTransactions::EventFilteredByPeer(filtered_transactions)
+    );
+
+    // Update the peer so that it runs the base protocol version (i.e., it no
+    // longer supports event filtering)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the client flags the matching transactions (and keeps the list intact)
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 102, false, request_options)
+        .await
+        .unwrap();
+    match response.payload {
+        FilteredTransactions::FlaggedEventMatches {
+            transactions: transaction_list_with_proof,
+            matching_versions,
+        } => {
+            assert_eq!(transaction_list_with_proof.transactions, transactions);
+            assert_eq!(matching_versions, vec![101, 102]);
+        }
+        payload => panic!("Unexpected payload: {:?}", payload),
+    }
+}
+
+#[tokio::test]
+async fn request_rate_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Verify the request rate is zero before any requests are sent
+    assert_eq!(client.current_request_rate(), 0.0);
+
+    // Add a peer that advertises data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler for the peer
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send a known number of requests (spread across the window)
+    let num_requests = 20;
+    for _ in 0..num_requests {
+        client
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+            .unwrap();
+        mock_time.advance(Duration::from_millis(100));
+    }
+
+    // Verify the reported rate matches the number of requests in the window
+    let window_secs = REQUEST_RATE_WINDOW_SECS as f64;
+    assert_eq!(
+        client.current_request_rate(),
+        num_requests as f64 / window_secs
+    );
+
+    // Elapse the window and verify the requests are no longer counted
+    mock_time.advance(Duration::from_secs(REQUEST_RATE_WINDOW_SECS));
+    assert_eq!(client.current_request_rate(), 0.0);
+}
+
+#[tokio::test]
+async fn optimal_chunk_size_calculations() {
+    // Create a test storage service config
+    let max_account_states_chunk_sizes = 500;
+    let max_epoch_chunk_size = 600;
+    let max_transaction_chunk_size = 700;
+    let max_transaction_output_chunk_size = 800;
+    let storage_service_config = StorageServiceConfig {
+        max_account_states_chunk_sizes,
+        max_concurrent_requests: 0,
+        max_epoch_chunk_size,
+        max_network_channel_size: 0,
+        max_transaction_chunk_size,
+        max_transaction_output_chunk_size,
+        storage_summary_refresh_interval_ms: 0,
+    };
+
+    // Test median calculations
+    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
+        &storage_service_config,
+        0.5,
+        vec![100, 200, 300, 100],
+        vec![7, 5, 6, 8, 10],
+        vec![900, 700, 500],
+        vec![40],
+    );
+    assert_eq!(200, optimal_chunk_sizes.account_states_chunk_size);
+    assert_eq!(7, optimal_chunk_sizes.epoch_chunk_size);
+    assert_eq!(700, optimal_chunk_sizes.transaction_chunk_size);
+    assert_eq!(40, optimal_chunk_sizes.transaction_output_chunk_size);
+
+    // Test 25th percentile calculations
+    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
+        &storage_service_config,
+        0.25,
+        vec![100, 200, 300, 100],
+        vec![7, 5, 6, 8, 10],
+        vec![900, 700, 500],
+        vec![40],
+    );
+    assert_eq!(100, optimal_chunk_sizes.account_states_chunk_size);
+    assert_eq!(6, optimal_chunk_sizes.epoch_chunk_size);
+    assert_eq!(500, optimal_chunk_sizes.transaction_chunk_size);
+    assert_eq!(40, optimal_chunk_sizes.transaction_output_chunk_size);
+
+    // Test 75th percentile calculations (the transaction chunk size is capped)
+    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
+        &storage_service_config,
+        0.75,
+        vec![100, 200, 300, 100],
+        vec![7, 5, 6, 8, 10],
+        vec![900, 700, 500],
+        vec![40],
+    );
+    assert_eq!(300, optimal_chunk_sizes.account_states_chunk_size);
+    assert_eq!(8, optimal_chunk_sizes.epoch_chunk_size);
+    assert_eq!(
+        max_transaction_chunk_size,
+        optimal_chunk_sizes.transaction_chunk_size
+    );
+    assert_eq!(40, optimal_chunk_sizes.transaction_output_chunk_size);
+
+    // Test no advertised data
+    let optimal_chunk_sizes =
+        calculate_optimal_chunk_sizes(&storage_service_config, 0.5, vec![], vec![], vec![], vec![]);
+    assert_eq!(
+        max_account_states_chunk_sizes,
+        optimal_chunk_sizes.account_states_chunk_size
+    );
+    assert_eq!(max_epoch_chunk_size, optimal_chunk_sizes.epoch_chunk_size);
+    assert_eq!(
+        max_transaction_chunk_size,
+        optimal_chunk_sizes.transaction_chunk_size
+    );
+    assert_eq!(
+        max_transaction_output_chunk_size,
+        optimal_chunk_sizes.transaction_output_chunk_size
+    );
+
+    // Verify the config caps the amount of chunks
+    let optimal_chunk_sizes = calculate_optimal_chunk_sizes(
+        &storage_service_config,
+        0.5,
+        vec![1000, 1000, 2000, 3000],
+        vec![70, 50, 60, 80, 100],
+        vec![9000, 7000, 5000],
+        vec![400],
+    );
+    assert_eq!(
+        max_account_states_chunk_sizes,
+        optimal_chunk_sizes.account_states_chunk_size
+    );
+    assert_eq!(70, optimal_chunk_sizes.epoch_chunk_size);
+    assert_eq!(
+        max_transaction_chunk_size,
+        optimal_chunk_sizes.transaction_chunk_size
+    );
+    assert_eq!(400, optimal_chunk_sizes.transaction_output_chunk_size);
+}
+
+#[tokio::test]
+async fn persisted_summary_is_loaded_on_restart() {
+    ::aptos_logger::Logger::init_for_testing();
+    let summary_persistence_path = TempPath::new();
+    let data_client_config = AptosDataClientConfig {
+        summary_persistence: SummaryPersistenceConfig {
+            path: Some(summary_persistence_path.path().to_path_buf()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config.clone());
+
+    // Add a peer, update the global summary and persist it
+    let peer = mock_network.add_peer(NetworkId::Validator);
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    client.persist_global_summary().unwrap();
+    let persisted_summary = client.get_global_data_summary();
+    assert!(!persisted_summary.is_empty());
+
+    // Reconstruct the client and verify the summary is loaded as a hint
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+    assert_eq!(client.get_global_data_summary(), persisted_summary);
+
+    // Verify the hint is replaced by the first fresh poll
+    let peer = mock_network.add_peer(NetworkId::Validator);
+    client.update_summary(peer, mock_storage_summary(500));
+    client.update_global_summary_cache();
+    let global_data_summary = client.get_global_data_summary();
+    assert_ne!(global_data_summary, persisted_summary);
+    assert_eq!(
+        global_data_summary
+            .advertised_data
+            .highest_synced_ledger_info()
+            .unwrap()
+            .ledger_info()
+            .version(),
+        500
+    );
+}
+
+#[tokio::test]
+async fn contributors_are_reported_for_range() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add two peers that each cover part of the range, and one that doesn't
+    let peer_1 = mock_network.add_peer(NetworkId::Validator);
+    let mut storage_summary_1 = mock_storage_summary(200);
+    storage_summary_1.data_summary.transactions = Some(CompleteDataRange::new(0, 120).unwrap());
+    client.update_summary(peer_1, storage_summary_1);
+    let peer_2 = mock_network.add_peer(NetworkId::Validator);
+    let mut storage_summary_2 = mock_storage_summary(200);
+    storage_summary_2.data_summary.transactions = Some(CompleteDataRange::new(150, 200).unwrap());
+    client.update_summary(peer_2, storage_summary_2);
+    let peer_3 = mock_network.add_peer(NetworkId::Validator);
+    let mut storage_summary_3 = mock_storage_summary(200);
+    storage_summary_3.data_summary.transactions = Some(CompleteDataRange::new(0, 50).unwrap());
+    client.update_summary(peer_3, storage_summary_3);
+
+    // Verify both partially covering peers are listed as contributors
+    let mut expected_contributors = vec![peer_1, peer_2];
+    expected_contributors.sort();
+    assert_eq!(
+        client.contributors_for_range(DataType::Transactions, 100, 160),
+        expected_contributors
+    );
+
+    // Verify there are no contributors for a range no peer advertises
+    assert!(client
+        .contributors_for_range(DataType::Transactions, 300, 400)
+        .is_empty());
+}
+
+#[tokio::test]
+async fn peers_with_recent_errors_are_deprioritized() {
+    ::aptos_logger::Logger::init_for_testing();
+    let recent_error_window_ms = 1000;
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            recent_error_weight: 100,
+            recent_error_window_ms,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers advertising the same data
+    let peer_1 = mock_network.add_peer(NetworkId::Validator);
+    client.update_summary(peer_1, mock_storage_summary(200));
+    let peer_2 = mock_network.add_peer(NetworkId::Validator);
+    client.update_summary(peer_2, mock_storage_summary(200));
+
+    // Give both peers the same number of errors (so their scores are equal),
+    // but only the second peer's errors fall within the recent error window.
+    let request = StorageServiceRequest::GetServerProtocolVersion;
+    for _ in 0..5 {
+        client.notify_bad_response(0, peer_1, &request, ErrorType::NotUseful);
+    }
+    mock_time.advance(Duration::from_millis(recent_error_window_ms));
+    for _ in 0..5 {
+        client.notify_bad_response(0, peer_2, &request, ErrorType::NotUseful);
+    }
+    assert_eq!(
+        client.peer_states.read().get_peer_score(&peer_1),
+        client.peer_states.read().get_peer_score(&peer_2)
+    );
+
+    // Verify the peer without the recent error burst is preferred
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 100,
+        end_version: 150,
+        include_events: false,
+    });
+    let mut num_peer_1_selections = 0;
+    for _ in 0..100 {
+        if client.choose_peer_for_request(&request, false).unwrap() == peer_1 {
+            num_peer_1_selections += 1;
+        }
+    }
+    assert!(num_peer_1_selections > 90);
+}
+
+#[tokio::test]
+async fn connection_is_warmed_before_first_bulk_request() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        warm_connection_before_bulk: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising data
+    let peer = mock_network.add_peer(NetworkId::Validator);
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the requests received by the peer
+    let peer_infos = mock_network.peer_infos.clone();
+    let (request_sender, mut request_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match &request {
+                StorageServiceRequest::GetServerProtocolVersion => {
+                    StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                        protocol_version: 1,
+                    })
+                }
+                _ => StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                ),
+            };
+            request_sender.unbounded_send(request).unwrap();
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Send two bulk requests to the peer
+    for _ in 0..2 {
+        client
+            .get_transactions_with_proof(200, 100, 150, false)
+            .await
+            .unwrap();
+    }
+
+    // Verify that only the first bulk request was preceded by a probe
+    assert_matches!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetServerProtocolVersion
+    );
+    for _ in 0..2 {
+        assert_matches!(
+            request_receiver.next().await.unwrap(),
+            StorageServiceRequest::GetTransactionsWithProof(_)
+        );
+    }
+    assert!(request_receiver.next().now_or_never().is_none());
+
+    // Disconnect the peer and verify its warmed connection is forgotten
+    let peer_info = peer_infos.read(peer).unwrap();
+    peer_infos.remove(&peer);
+    client.remove_disconnected_warmed_peers();
+    assert!(client.warmed_peers.lock().is_empty());
+
+    // Reconnect the peer and verify the next bulk request is preceded by a probe
+    peer_infos.insert(peer, peer_info);
+    client
+        .get_transactions_with_proof(200, 100, 150, false)
+        .await
+        .unwrap();
+    assert_matches!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetServerProtocolVersion
+    );
+    assert_matches!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionsWithProof(_)
+    );
+}
+
+#[tokio::test]
+async fn failed_connection_probes_select_another_peer() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        circuit_breaker: CircuitBreakerConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        },
+        warm_connection_before_bulk: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers advertising data
+    let unhealthy_peer = mock_network.add_peer(NetworkId::Validator);
+    let healthy_peer = mock_network.add_peer(NetworkId::Validator);
+    for peer in [unhealthy_peer, healthy_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    let initial_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&unhealthy_peer)
+        .unwrap();
+
+    // Spawn a handler that fails the probes sent to the unhealthy peer (and
+    // records the peers that were sent bulk requests).
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetServerProtocolVersion => {
+                    if peer == unhealthy_peer.peer_id() {
+                        response_sender
+                            .send(Err(StorageServiceError::InternalError("".to_string())));
+                    } else {
+                        response_sender.send(Ok(StorageServiceResponse::ServerProtocolVersion(
+                            ServerProtocolVersion {
+                                protocol_version: 1,
+                            },
+                        )));
+                    }
+                }
+                _ => {
+                    peer_sender.unbounded_send(peer).unwrap();
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )));
+                }
+            }
+        }
+    });
+
+    // Verify all bulk requests succeed and are sent to the healthy peer
+    // (without opening the circuit breaker).
+    for _ in 0..10 {
+        client
+            .get_transactions_with_proof(200, 100, 150, false)
+            .await
+            .unwrap();
+        let peer = peer_receiver.next().await.unwrap();
+        assert_eq!(peer, healthy_peer.peer_id());
+    }
+
+    // Verify the failed probes were scored like any other failed request
+    let unhealthy_peer_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&unhealthy_peer)
+        .unwrap();
+    assert!(unhealthy_peer_score < initial_score);
+}
+
+#[tokio::test]
+async fn epoch_requests_respect_max_span() {
+    ::aptos_logger::Logger::init_for_testing();
+    let max_epoch_span_per_request = 10;
+    let data_client_config = AptosDataClientConfig {
+        chunk_sizes: ChunkSizeConfig {
+            max_epoch_span_per_request,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 100).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all epoch requests (and records them)
+    let (request_sender, mut request_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let validator = ValidatorSigner::random(None);
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            if let StorageServiceRequest::GetEpochEndingLedgerInfos(request) = &request {
+                let epoch_ending_ledger_infos = (request.start_epoch..=request.expected_end_epoch)
+                    .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+                    .collect();
+                let epoch_change_proof = EpochChangeProof::new(epoch_ending_ledger_infos, false);
+                response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                    epoch_change_proof,
+                )));
+            }
+            request_sender.unbounded_send(request).unwrap();
+        }
+    });
+
+    // Verify an epoch request exceeding the max span is rejected
+    let error = client
+        .get_epoch_ending_ledger_infos(0, max_epoch_span_per_request)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidRequest(_));
+
+    // Verify that fetching all epochs chunks the requests at the max span
+    let request_options = RequestOptions {
+        verification_level: VerificationLevel::StructuralOnly,
+        ..Default::default()
+    };
+    let responses = client
+        .get_all_epoch_ending_ledger_infos(0, 24, request_options)
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(
+        responses
+            .iter()
+            .map(|response| response.payload.len())
+            .sum::<usize>(),
+        25
+    );
+    for (start_epoch, end_epoch) in vec![(0, 9), (10, 19), (20, 24)] {
+        let request = request_receiver.next().await.unwrap();
+        assert_eq!(
+            request,
+            StorageServiceRequest::GetEpochEndingLedgerInfos(EpochEndingLedgerInfoRequest {
+                start_epoch,
+                expected_end_epoch: end_epoch,
+            })
+        );
+    }
+}
+
+#[tokio::test]
+async fn outcomes_are_broken_down_by_tier() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a priority peer and a regular peer
+    let priority_peer = mock_network.add_priority_peer();
+    let regular_peer = mock_network.add_regular_peer();
+
+    // Spawn a handler where only the priority peer responds successfully
+    tokio::spawn(async move {
+        while let Some((peer_id, _, _, response_sender)) = mock_network.next_request().await {
+            if peer_id == priority_peer.peer_id() {
+                response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                )));
+            } else {
+                response_sender.send(Err(StorageServiceError::InternalError(
+                    "Request failed!".into(),
+                )));
+            }
+        }
+    });
+
+    // Send requests to both peers
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 100,
+        end_version: 150,
+        include_events: false,
+    });
+    for _ in 0..3 {
+        client
+            .send_request_to_peer(priority_peer, request.clone())
+            .await
+            .unwrap();
+    }
+    for _ in 0..2 {
+        client
+            .send_request_to_peer(regular_peer, request.clone())
+            .await
+            .unwrap_err();
+    }
+
+    // Verify the outcomes are separated by peer tier
+    let outcome_breakdown = client.outcome_breakdown_by_tier();
+    assert_eq!(
+        outcome_breakdown.get(&PeerTier::Priority),
+        Some(&OutcomeStats {
+            successes: 3,
+            failures: 0,
+            timeouts: 0,
+        })
+    );
+    assert_eq!(
+        outcome_breakdown.get(&PeerTier::Regular),
+        Some(&OutcomeStats {
+            successes: 0,
+            failures: 2,
+            timeouts: 0,
+        })
+    );
+}
+
+#[tokio::test]
+async fn poller_idles_until_peer_connects() {
+    ::aptos_logger::Logger::init_for_testing();
+    let no_peers_idle_interval_ms = 10_000;
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            no_peers_idle_interval_ms,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let poll_interval_ms = data_client_config.summary_poll_interval_ms;
+    let (mut mock_network, mock_time, client, poller) =
+        MockNetwork::new_with_config(data_client_config);
+
+    // Start the poller with no connected peers (so that it begins idling)
+    tokio::spawn(poller.start_poller());
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+
+    // Add a peer (without notifying the poller) and advance time by several
+    // poll intervals (but less than the idle interval).
+    let peer = mock_network.add_priority_peer();
+    for _ in 0..10 {
+        mock_time
+            .advance_async(Duration::from_millis(poll_interval_ms))
+            .await;
+    }
+
+    // Verify the poller is still idling (i.e., no summary requests were sent)
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+
+    // Notify the poller that a peer connected and verify the peer is polled
+    // promptly (i.e., without waiting out the idle interval).
+    client.notify_peer_connected();
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    mock_time
+        .advance_async(Duration::from_millis(poll_interval_ms))
+        .await;
+    let (polled_peer, _, request, _) = mock_network.next_request().await.unwrap();
+    assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+    assert_eq!(polled_peer, peer.peer_id());
+}
+
+#[tokio::test]
+async fn write_sets_are_omitted_from_outputs() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising transaction outputs (that supports write set pruning)
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.transaction_outputs =
+        Some(CompleteDataRange::new(0, 200).unwrap());
+    client.update_summary(peer, storage_summary);
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to output requests (and records them)
+    let (request_sender, mut request_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let transaction = mock_user_transaction(AccountAddress::random(), 0);
+        let output_list_with_proof =
+            mock_transaction_output_list_with_proof(100, vec![transaction; 3]);
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let output_list_with_proof = match &request {
+                StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(_) => {
+                    strip_write_sets(output_list_with_proof.clone())
+                }
+                _ => output_list_with_proof.clone(),
+            };
+            response_sender.send(Ok(StorageServiceResponse::TransactionOutputsWithProof(
+                output_list_with_proof,
+            )));
+            request_sender.unbounded_send(request).unwrap();
+        }
+    });
+
+    // Verify the peer omits the write sets
+    let request_options = RequestOptions {
+        omit_write_sets: true,
+        ..Default::default()
+    };
+    let response = client
+        .get_transaction_outputs_with_proof_with_options(
+            200,
+            100,
+            102,
+            true,
+            request_options.clone(),
+        )
+        .await
+        .unwrap();
+    assert_matches!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(_)
+    );
+    assert_eq!(response.payload.transactions_and_outputs.len(), 3);
+    for (_, output) in response.payload.transactions_and_outputs {
+        assert!(output.write_set().is_empty());
+    }
+
+    // Update the peer so that it runs the base protocol version (i.e., it
+    // doesn't support write set pruning)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the full outputs are fetched and the write sets stripped locally
+    let response = client
+        .get_transaction_outputs_with_proof_with_options(200, 100, 102, true, request_options)
+        .await
+        .unwrap();
+    assert_matches!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionOutputsWithProof(_)
+    );
+    assert_eq!(response.payload.transactions_and_outputs.len(), 3);
+    for (_, output) in response.payload.transactions_and_outputs {
+        assert!(output.write_set().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn unavailable_data_types_fail_fast() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        fail_fast_on_unavailable_data_type: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add peers that only advertise transactions (and no account states)
+    for _ in 0..2 {
+        let peer = mock_network.add_peer(NetworkId::Validator);
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+
+    // Verify an account states request fails because the data type is unavailable
+    let error = client
+        .get_account_states_with_proof(100, 0, 10)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataTypeUnavailable(_));
+
+    // Verify a request for an unadvertised transaction range is a range gap
+    let error = client
+        .get_transactions_with_proof(500, 300, 400, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+}
+
+#[tokio::test]
+async fn vfn_summary_polls_are_not_starved() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            max_concurrent_summary_polls: 4,
+            max_concurrent_summary_polls_per_network: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a large set of validator peers and a small set of VFN peers
+    let mut validator_peers = vec![];
+    for _ in 0..10 {
+        validator_peers.push(mock_network.add_priority_peer());
+    }
+    let vfn_peer_1 = mock_network.add_regular_peer();
+    let vfn_peer_2 = mock_network.add_regular_peer();
+
+    // Poll all peers in the background
+    let poll_client = client.clone();
+    let poll_handle =
+        tokio::spawn(async move { poll_client.poll_all_peers(Duration::from_secs(10)).await });
+
+    // Hold the first batch of polls (i.e., up to the global cap)
+    let mut held_requests = vec![];
+    for _ in 0..4 {
+        let (peer, _, request, response_sender) = mock_network.next_request().await.unwrap();
+        assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+        held_requests.push((peer, response_sender));
+    }
+
+    // Verify no more polls are sent and that the VFN peers were polled
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+    let held_peers: Vec<_> = held_requests.iter().map(|(peer, _)| *peer).collect();
+    assert!(held_peers.contains(&vfn_peer_1.peer_id()));
+    assert!(held_peers.contains(&vfn_peer_2.peer_id()));
+
+    // Verify the outstanding polls are reported per network
+    let outstanding_polls = client.outstanding_summary_polls();
+    assert_eq!(outstanding_polls[&NetworkId::Validator], 2);
+    assert_eq!(outstanding_polls[&NetworkId::Vfn], 2);
+
+    // Respond to the held polls (and all remaining polls)
+    for (_, response_sender) in held_requests {
+        response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+            mock_storage_summary(100),
+        )));
+    }
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+                mock_storage_summary(100),
+            )));
+        }
+    });
+
+    // Verify every peer was polled successfully
+    let poll_results = poll_handle.await.unwrap();
+    assert_eq!(poll_results.len(), 12);
+    for peer in validator_peers
+        .iter()
+        .chain(vec![vfn_peer_1, vfn_peer_2].iter())
+    {
+        assert_eq!(
+            poll_results[peer].as_ref().unwrap(),
+            &mock_storage_summary(100)
+        );
+    }
+    assert_eq!(client.outstanding_summary_polls()[&NetworkId::Vfn], 0);
+}
+
+#[tokio::test]
+async fn success_streaks_grow_peer_trust() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_scoring: PeerScoringConfig {
+            max_success_streak_multiplier: 4.0,
+            success_streak_growth_factor: 1.5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer with a long success streak and a peer with interspersed failures
+    let streak_peer = mock_network.add_priority_peer();
+    let interspersed_peer = mock_network.add_priority_peer();
+
+    // Record the score gained by each peer from its successful responses
+    let num_successes = 10;
+    let (mut streak_gain, mut interspersed_gain) = (0.0, 0.0);
+    for _ in 0..num_successes {
+        streak_gain += record_success_and_get_gain(&client, streak_peer);
+        interspersed_gain += record_success_and_get_gain(&client, interspersed_peer);
+        client
+            .peer_states
+            .write()
+            .update_score_error(interspersed_peer, ErrorType::NotUseful);
+    }
+
+    // Verify the streak peer earned accelerating (but capped) rewards, while
+    // the interspersed peer only earned the base reward for each success.
+    assert!(streak_gain > interspersed_gain);
+    assert!(streak_gain <= 4.0 * num_successes as f64);
+    assert!((interspersed_gain - num_successes as f64).abs() < 1e-9);
+}
+
+/// Records a successful response from the peer and returns the score gained
+fn record_success_and_get_gain(client: &AptosNetDataClient, peer: PeerNetworkId) -> f64 {
+    let config = &client.data_client_config;
+    let mut peer_states = client.peer_states.write();
+    let old_score = peer_states.get_peer_score(&peer).unwrap_or(50.0);
+    peer_states.update_score_success(
+        peer,
+        config.peer_scoring.success_streak_growth_factor,
+        config.peer_scoring.max_success_streak_multiplier,
+    );
+    peer_states.get_peer_score(&peer).unwrap() - old_score
+}
+
+#[tokio::test]
+async fn peer_serving_bandwidth_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Verify no bandwidths are reported before any responses
+    assert!(client.peer_serving_bandwidth().is_empty());
+
+    // Add a peer serving a large response and a peer serving a small response
+    let large_response_peer = mock_network.add_priority_peer();
+    let small_response_peer = mock_network.add_priority_peer();
+    let large_response = create_epoch_ending_response(100);
+    let small_response = create_epoch_ending_response(1);
+
+    // Complete the large response after 2 seconds and the small response after 1 second
+    for (peer, response, elapsed_secs) in vec![
+        (large_response_peer, large_response.clone(), 2),
+        (small_response_peer, small_response.clone(), 1),
+    ] {
+        let request =
+            StorageServiceRequest::GetEpochEndingLedgerInfos(EpochEndingLedgerInfoRequest {
+                start_epoch: 0,
+                expected_end_epoch: 100,
+            });
+        let client_clone = client.clone();
+        let request_handle =
+            tokio::spawn(async move { client_clone.send_request_to_peer(peer, request).await });
+        let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        mock_time
+            .advance_async(Duration::from_secs(elapsed_secs))
+            .await;
+        response_sender.send(Ok(response));
+        request_handle.await.unwrap().unwrap();
+    }
+
+    // Verify the reported bandwidths (i.e., bytes per second) differ as expected
+    let serving_bandwidths = client.peer_serving_bandwidth();
+    let large_response_bytes = wire_response_bytes(&large_response) as f64;
+    let small_response_bytes = wire_response_bytes(&small_response) as f64;
+    assert_eq!(
+        serving_bandwidths[&large_response_peer],
+        large_response_bytes / 2.0
+    );
+    assert_eq!(
+        serving_bandwidths[&small_response_peer],
+        small_response_bytes
+    );
+    assert!(serving_bandwidths[&large_response_peer] > serving_bandwidths[&small_response_peer]);
+}
+
+/// Creates an epoch ending ledger info response with the given number of ledger infos
+fn create_epoch_ending_response(num_ledger_infos: u64) -> StorageServiceResponse {
+    let ledger_infos = (0..num_ledger_infos).map(mock_ledger_info).collect();
+    StorageServiceResponse::EpochEndingLedgerInfos(EpochChangeProof::new(ledger_infos, false))
+}
+
+/// Returns the size (in bytes) of the given response on the wire
+fn wire_response_bytes(response: &StorageServiceResponse) -> u64 {
+    let message = StorageServiceMessage::Response(Ok(response.clone()));
+    bcs::serialized_size(&message).unwrap() as u64
+}
+
+#[tokio::test]
+async fn requests_are_spread_across_ip_addresses() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            max_requests_fraction_per_ip: 0.5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers behind the same IP and one peer behind a distinct IP
+    let shared_ip_address = NetworkAddress::from_str("/ip4/10.0.0.1/tcp/6180").unwrap();
+    let distinct_ip_address = NetworkAddress::from_str("/ip4/10.0.0.2/tcp/6180").unwrap();
+    let mut peers = vec![];
+    for address in vec![
+        shared_ip_address.clone(),
+        shared_ip_address,
+        distinct_ip_address,
+    ] {
+        let peer = PeerNetworkId::new(NetworkId::Validator, PeerId::random());
+        mock_network.add_peer_with_address(peer, address);
+        client.update_summary(peer, mock_storage_summary(200));
+        peers.push(peer);
+    }
+    client.update_global_summary_cache();
+    let distinct_ip_peer = peers[2];
+
+    // Spawn a handler that responds to all requests (and counts the requests
+    // sent to the distinct IP peer).
+    let distinct_ip_requests = Arc::new(AtomicU64::new(0));
+    let distinct_ip_requests_clone = distinct_ip_requests.clone();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            if peer == distinct_ip_peer.peer_id() {
+                distinct_ip_requests_clone.fetch_add(1, Ordering::Relaxed);
+            }
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Send many requests
+    let num_requests = 100;
+    for _ in 0..num_requests {
+        client
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+            .unwrap();
+    }
+
+    // Verify the distinct IP peer received (roughly) half of the requests,
+    // i.e., a disproportionate share relative to a uniform split across peers.
+    let distinct_ip_requests = distinct_ip_requests.load(Ordering::Relaxed);
+    assert!(distinct_ip_requests >= num_requests / 2 - 1);
+    assert!(distinct_ip_requests <= num_requests / 2 + 1);
+}
+
+#[tokio::test]
+async fn ban_listener_is_notified_of_bans_and_restores() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Install the ban listener
+    let ban_listener = Arc::new(CountingBanListener::default());
+    client.set_peer_ban_listener(ban_listener.clone());
+
+    // Drive the peer's score well below the ignore threshold
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    for _ in 0..10 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peer, ErrorType::Malicious);
+    }
+
+    // Drive the peer's score well above the ignore threshold
+    for _ in 0..30 {
+        client
+            .peer_states
+            .write()
+            .update_score_success(peer, 1.0, 1.0);
+    }
+
+    // Verify each callback fired exactly once
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(ban_listener.num_bans.load(Ordering::Relaxed), 1);
+    assert_eq!(ban_listener.num_restores.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn inclusion_proofs_are_returned() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer covering the requested transactions
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Create a transaction accumulator with four leaves (i.e., versions 0 to 3)
+    let leaves: Vec<_> = (0..4u8).map(|i| HashValue::sha3_256_of(&[i])).collect();
+    let hash_internal = |left: HashValue, right: HashValue| {
+        MerkleTreeInternalNode::<TransactionAccumulatorHasher>::new(left, right).hash()
+    };
+    let left_subtree = hash_internal(leaves[0], leaves[1]);
+    let right_subtree = hash_internal(leaves[2], leaves[3]);
+    let root_hash = hash_internal(left_subtree, right_subtree);
+
+    // Spawn a handler that responds with the inclusion proofs of versions 1 and 2
+    let accumulator_version = 3;
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionInclusionProofs(request) => {
+                    assert_eq!(request.versions, vec![1, 2]);
+                    assert_eq!(request.accumulator_version, accumulator_version);
+                    let inclusion_proofs = vec![
+                        TransactionAccumulatorProof::new(vec![leaves[0], right_subtree]),
+                        TransactionAccumulatorProof::new(vec![leaves[3], left_subtree]),
+                    ];
+                    response_sender.send(Ok(StorageServiceResponse::TransactionInclusionProofs(
+                        inclusion_proofs,
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Request the inclusion proofs and verify they're valid against the accumulator
+    let versions = vec![1, 2];
+    let response = client
+        .get_inclusion_proofs(versions.clone(), accumulator_version)
+        .await
+        .unwrap();
+    assert_eq!(response.payload.len(), 2);
+    for (version, inclusion_proof) in versions.into_iter().zip(response.payload.iter()) {
+        let leaf_hash = HashValue::sha3_256_of(&[version as u8]);
+        inclusion_proof
+            .verify(root_hash, leaf_hash, version)
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn truncated_epoch_responses_are_penalized_only_if_buggy() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_scoring: PeerScoringConfig {
+            penalize_short_epoch_ending_responses: true,
+            ..Default::default()
+        },
+        peer_selection: PeerSelectionConfig {
+            allow_partial_coverage_peers: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer only advertising epoch ending ledger infos up to epoch 5
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 5).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that first responds with all advertised ledger infos
+    // (i.e., up to epoch 5) and then with fewer ledger infos (up to epoch 2).
+    let validator = ValidatorSigner::random(None);
+    let mut responses = vec![6, 3].into_iter().map(move |num_ledger_infos| {
+        let ledger_infos = (0..num_ledger_infos)
+            .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+            .collect();
+        StorageServiceResponse::EpochEndingLedgerInfos(EpochChangeProof::new(ledger_infos, false))
+    });
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(responses.next().unwrap()));
+        }
+    });
+
+    // Verify the response truncated at the advertised end epoch is accepted
+    // and the peer is not penalized.
+    let request_options = RequestOptions {
+        bypass_cache: true,
+        verification_level: VerificationLevel::StructuralOnly,
+        ..Default::default()
+    };
+    let score_before = client.peer_states.read().get_peer_score(&peer).unwrap();
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(0, 10, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.payload.len(), 6);
+    let score_after = client.peer_states.read().get_peer_score(&peer).unwrap();
+    assert!(score_after >= score_before);
+
+    // Verify the response that stops before the advertised end epoch is
+    // rejected and the peer is penalized.
+    let error = client
+        .get_epoch_ending_ledger_infos_with_options(0, 10, request_options)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidResponse(_));
+    assert!(client.peer_states.read().get_peer_score(&peer).unwrap() < score_after);
+}
+
+#[tokio::test]
+async fn memory_usage_scales_with_entries() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Verify nothing is reported before any peers or cache entries exist
+    let initial_usage = client.memory_usage();
+    assert_eq!(initial_usage.peer_summaries_bytes, 0);
+    assert_eq!(initial_usage.scoring_state_bytes, 0);
+    assert_eq!(initial_usage.epoch_ending_cache_bytes, 0);
+
+    // Add several peers and cache entries, and record the memory usage
+    let validator = ValidatorSigner::random(None);
+    let num_entries = 5;
+    add_peers_and_cache_entries(&mut mock_network, &client, &validator, 0, num_entries);
+    let usage = client.memory_usage();
+    assert!(usage.peer_summaries_bytes > 0);
+    assert!(usage.scoring_state_bytes > 0);
+    assert!(usage.epoch_ending_cache_bytes > 0);
+
+    // Double the number of peers and cache entries, and verify the usage doubles
+    add_peers_and_cache_entries(
+        &mut mock_network,
+        &client,
+        &validator,
+        num_entries,
+        num_entries,
+    );
+    let doubled_usage = client.memory_usage();
+    assert_eq!(
+        doubled_usage.peer_summaries_bytes,
+        2 * usage.peer_summaries_bytes
+    );
+    assert_eq!(
+        doubled_usage.scoring_state_bytes,
+        2 * usage.scoring_state_bytes
+    );
+    assert_eq!(
+        doubled_usage.epoch_ending_cache_bytes,
+        2 * usage.epoch_ending_cache_bytes
+    );
+    assert!(doubled_usage.total_bytes() > usage.total_bytes());
+}
+
+/// Adds the given number of peers (with storage summaries) and epoch ending
+/// cache entries (each holding a single ledger info) to the client.
+fn add_peers_and_cache_entries(
+    mock_network: &mut MockNetwork,
+    client: &AptosNetDataClient,
+    validator: &ValidatorSigner,
+    start_epoch: u64,
+    num_entries: u64,
+) {
+    for epoch in start_epoch..start_epoch + num_entries {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(200));
+        client
+            .epoch_ending_ledger_info_cache
+            .lock()
+            .insert(CachedLedgerInfos {
+                peer,
+                ledger_infos: vec![mock_epoch_ending_ledger_info(epoch, validator)],
+            });
+    }
+}
+
+#[tokio::test]
+async fn conservative_chunk_sizes_are_used_until_enough_summaries() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        chunk_sizes: ChunkSizeConfig {
+            min_summaries_for_chunk_calc: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a single peer advertising large chunk sizes
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.protocol_metadata.max_transaction_chunk_size = 800;
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, storage_summary.clone());
+    client.update_global_summary_cache();
+
+    // Verify conservative chunk sizes are used (i.e., half the configured max)
+    let max_transaction_chunk_size = StorageServiceConfig::default().max_transaction_chunk_size;
+    let optimal_chunk_sizes = client.get_global_data_summary().optimal_chunk_sizes;
+    assert_eq!(
+        optimal_chunk_sizes.transaction_chunk_size,
+        max_transaction_chunk_size / 2
+    );
+
+    // Add another peer and verify the advertised chunk sizes are now used
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+    let optimal_chunk_sizes = client.get_global_data_summary().optimal_chunk_sizes;
+    assert_eq!(optimal_chunk_sizes.transaction_chunk_size, 800);
+}
+
+#[tokio::test]
+async fn request_spans_are_children_of_the_provided_span() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising transactions
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all transaction requests
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify no span is created when no trace context is provided
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 150, false, RequestOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(response.context.trace_context, None);
+
+    // Verify the request's span is created as a child of the provided span
+    let parent_span = TraceContext::new_root(10, 1_000);
+    let request_options = RequestOptions {
+        trace_context: Some(parent_span),
+        ..Default::default()
+    };
+    let response = client
+        .get_transactions_with_proof_with_options(200, 100, 150, false, request_options)
+        .await
+        .unwrap();
+    let request_span = response.context.trace_context.unwrap();
+    assert_eq!(request_span.trace_id, parent_span.trace_id);
+    assert_eq!(request_span.parent_span_id, Some(parent_span.span_id));
+    assert_ne!(request_span.span_id, parent_span.span_id);
+}
+
+#[tokio::test]
+async fn gap_triggered_poll_discovers_stale_summaries() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            poll_on_gap: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer with a stale summary (i.e., the peer can serve more data)
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(100));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds with the fresh summary and transactions
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetStorageServerSummary => {
+                    StorageServiceResponse::StorageServerSummary(mock_storage_summary(200))
+                }
+                StorageServiceRequest::GetTransactionsWithProof(_) => {
+                    StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Verify the gap-triggered poll discovers the data and the request succeeds
+    client
+        .get_transactions_with_proof(200, 150, 180, false)
+        .await
+        .unwrap();
+    let advertised_data = client.get_global_data_summary().advertised_data;
+    assert_eq!(
+        advertised_data
+            .highest_synced_ledger_info()
+            .unwrap()
+            .ledger_info()
+            .version(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn rejections_identify_the_config_field() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        chunk_sizes: ChunkSizeConfig {
+            max_epoch_span_per_request: 10_000,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Verify a request exceeding the max epoch span identifies the field and both values
+    let error = client
+        .get_epoch_ending_ledger_infos(0, 14_999)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        Error::InvalidRequest(InvalidRequestReason::exceeds_config_limit(
+            "chunk_sizes.max_epoch_span_per_request",
+            10_000,
+            15_000
+        ))
+    );
+    let error_message = error.to_string();
+    assert!(error_message.contains("max_epoch_span_per_request = 10000"));
+    assert!(error_message.contains("requested = 15000"));
+}
+
+#[tokio::test]
+async fn failed_priority_requests_fall_back_to_regular_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    for allow_regular_peer_fallback in [true, false] {
+        let data_client_config = AptosDataClientConfig {
+            peer_selection: PeerSelectionConfig {
+                allow_regular_peer_fallback,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+        // Add a priority peer and a regular peer (both advertising the data)
+        let priority_peer = mock_network.add_priority_peer();
+        client.update_summary(priority_peer, mock_storage_summary(200));
+        let regular_peer = mock_network.add_regular_peer();
+        client.update_summary(regular_peer, mock_storage_summary(200));
+        client.update_global_summary_cache();
+
+        // Spawn a handler where only the regular peer responds successfully
+        tokio::spawn(async move {
+            while let Some((peer_id, _, _, response_sender)) = mock_network.next_request().await {
+                if peer_id == regular_peer.peer_id() {
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )));
+                } else {
+                    response_sender.send(Err(StorageServiceError::InternalError(
+                        "Request failed!".into(),
+                    )));
+                }
+            }
+        });
+
+        // Send requests until the priority peer is chosen (and fails)
+        let mut priority_peer_failed = false;
+        for _ in 0..30 {
+            let result = client
+                .get_transactions_with_proof(200, 100, 150, false)
+                .await;
+            let priority_outcomes = client
+                .outcome_breakdown_by_tier()
+                .get(&PeerTier::Priority)
+                .copied()
+                .unwrap_or_default();
+            if priority_outcomes.failures > 0 {
+                // Verify the request only succeeds if the fallback is enabled
+                assert_eq!(result.is_ok(), allow_regular_peer_fallback);
+                priority_peer_failed = true;
+                break;
+            }
+            result.unwrap();
+        }
+        assert!(priority_peer_failed);
+    }
+}
+
+#[tokio::test]
+async fn selection_latency_is_measured_with_many_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let selection_latency_window = 10;
+    let data_client_config = AptosDataClientConfig {
+        monitoring: DataClientMonitoringConfig {
+            selection_latency_window,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Verify no stats are reported before any peers are selected
+    assert_eq!(client.selection_latency_stats(), LatencyStats::default());
+
+    // Add many peers that can service the requests
+    for _ in 0..500 {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all transaction requests
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(_) => {
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Send more requests than the window and verify a selection latency is
+    // recorded for each request (up to the window size).
+    for num_requests in 1..=(2 * selection_latency_window) {
+        client
+            .get_transactions_with_proof(200, num_requests, 200, false)
+            .await
+            .unwrap();
+        let selection_latency_stats = client.selection_latency_stats();
+        assert_eq!(
+            selection_latency_stats.num_samples,
+            num_requests.min(selection_latency_window)
+        );
+        assert!(selection_latency_stats.min <= selection_latency_stats.mean);
+        assert!(selection_latency_stats.mean <= selection_latency_stats.max);
+        assert!(selection_latency_stats.p99 <= selection_latency_stats.max);
+    }
+
+    // Verify selecting a peer from many peers takes a measurable amount of time
+    assert!(!client.selection_latency_stats().max.is_zero());
+}
+
+#[tokio::test]
+async fn maintenance_mode_refuses_data_requests_but_keeps_polling() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, poller) = MockNetwork::new();
+
+    tokio::spawn(poller.start_poller());
+
+    // Add a connected peer and enter maintenance mode
+    let expected_peer = mock_network.add_priority_peer();
+    client.enter_maintenance_mode();
+    assert!(client.is_in_maintenance());
+
+    // Verify poll rounds still send data summary requests (and that the
+    // protocol version of the peer is fetched after the first summary).
+    for round in 0..2 {
+        tokio::task::yield_now().await;
+        mock_time.advance_async(Duration::from_millis(1_000)).await;
+
+        let (peer, _, request, response_sender) = mock_network.next_request().await.unwrap();
+        assert_eq!(peer, expected_peer.peer_id());
+        assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+        response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+            mock_storage_summary(200),
+        )));
+        if round == 0 {
+            mock_network
+                .handle_protocol_version_request(expected_peer.peer_id())
+                .await;
+        }
+        tokio::task::yield_now().await;
+    }
+
+    // Verify data requests are refused (even though the peer advertises the data)
+    let error = client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap_err();
+    assert_eq!(error, Error::InMaintenance);
+    assert_eq!(error.get_label(), "in_maintenance");
+
+    // Exit maintenance mode and handle the client's transactions request
+    client.exit_maintenance_mode();
+    assert!(!client.is_in_maintenance());
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetStorageServerSummary => {
+                    StorageServiceResponse::StorageServerSummary(mock_storage_summary(200))
+                }
+                StorageServiceRequest::GetTransactionsWithProof(_) => {
+                    StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Verify data requests are sent again
+    client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn bootstrap_target_weighting_trades_freshness_for_redundancy() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Verify no target is suggested without any advertised data
+    let advertised_data = client.get_global_data_summary().advertised_data;
+    assert_eq!(advertised_data.suggest_bootstrap_target(0.5, 1), None);
+
+    // Add several peers synced to a lower version, and a single peer synced
+    // to a higher version.
+    let widely_served_version = 100;
+    let freshest_version = 200;
+    for _ in 0..3 {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(widely_served_version));
+    }
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(freshest_version));
+    client.update_global_summary_cache();
+
+    // Verify weighting towards freshness selects the highest version, and
+    // weighting towards redundancy selects the more widely served version.
+    let advertised_data = client.get_global_data_summary().advertised_data;
+    assert_eq!(
+        advertised_data.suggest_bootstrap_target(1.0, 1),
+        Some(freshest_version)
+    );
+    assert_eq!(
+        advertised_data.suggest_bootstrap_target(0.0, 1),
+        Some(widely_served_version)
+    );
+
+    // Verify the minimum number of peers is respected (regardless of the weight)
+    assert_eq!(
+        advertised_data.suggest_bootstrap_target(1.0, 2),
+        Some(widely_served_version)
+    );
+    assert_eq!(advertised_data.suggest_bootstrap_target(1.0, 5), None);
+}
+
+#[tokio::test]
+async fn state_proofs_are_composed_across_epochs() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Create a chain of epoch ending ledger infos (for epochs 0 to 2), and a
+    // target ledger info in epoch 3.
+    let validators = (0..4)
+        .map(|_| ValidatorSigner::random(None))
+        .collect::<Vec<_>>();
+    let epoch_ending_ledger_infos = (0..3)
+        .map(|epoch| {
+            mock_signed_ledger_info(
+                epoch,
+                (epoch + 1) * 10,
+                &validators[epoch as usize],
+                Some(&validators[epoch as usize + 1]),
+            )
+        })
+        .collect::<Vec<_>>();
+    let target_version = 35;
+    let target_ledger_info = mock_signed_ledger_info(3, target_version, &validators[3], None);
+
+    // Add a peer advertising the epochs and the target ledger info
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(target_version);
+    storage_summary.data_summary.synced_ledger_info = Some(target_ledger_info.clone());
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 2).unwrap());
+    client.update_summary(peer, storage_summary.clone());
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all epoch requests (starting at the
+    // trusted epoch) and counts the requests.
+    let num_epoch_requests = Arc::new(AtomicU64::new(0));
+    let num_epoch_requests_clone = num_epoch_requests.clone();
+    let epoch_ending_ledger_infos_clone = epoch_ending_ledger_infos.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetEpochEndingLedgerInfos(request) => {
+                    assert_eq!(request.start_epoch, 1);
+                    num_epoch_requests_clone.fetch_add(1, Ordering::Relaxed);
+                    let ledger_infos = epoch_ending_ledger_infos_clone
+                        [request.start_epoch as usize..=request.expected_end_epoch as usize]
+                        .to_vec();
+                    response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                        EpochChangeProof::new(ledger_infos, false),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Verify the state proof contains the epoch changes since the trusted epoch
+    let known_version = 15; // In epoch 1
+    let trusted_epoch_state = epoch_ending_ledger_infos[0]
+        .ledger_info()
+        .next_epoch_state()
+        .unwrap()
+        .clone();
+    let response = client
+        .get_state_proof_across_epoch(known_version, &trusted_epoch_state, target_version)
+        .await
+        .unwrap();
+    let state_proof = response.payload;
+    assert_eq!(state_proof.latest_ledger_info_w_sigs(), &target_ledger_info);
+    assert_eq!(
+        state_proof.epoch_changes().ledger_info_with_sigs,
+        epoch_ending_ledger_infos[1..].to_vec()
+    );
+    assert_eq!(num_epoch_requests.load(Ordering::Relaxed), 1);
+
+    // Verify the epoch changes are served from the cache on the next request
+    client
+        .get_state_proof_across_epoch(known_version, &trusted_epoch_state, target_version)
+        .await
+        .unwrap();
+    assert_eq!(num_epoch_requests.load(Ordering::Relaxed), 1);
+
+    // Verify epoch changes that aren't signed by the trusted validator set are rejected
+    let untrusted_epoch_state = EpochState {
+        epoch: 1,
+        verifier: ValidatorVerifier::new_single(validators[0].author(), validators[0].public_key()),
+    };
+    let error = client
+        .get_state_proof_across_epoch(known_version, &untrusted_epoch_state, target_version)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidResponse(_));
+
+    // Verify a target in the trusted epoch is verified against the trusted validator set
+    let target_epoch_state = epoch_ending_ledger_infos[2]
+        .ledger_info()
+        .next_epoch_state()
+        .unwrap()
+        .clone();
+    let response = client
+        .get_state_proof_across_epoch(31, &target_epoch_state, target_version)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload.latest_ledger_info_w_sigs(),
+        &target_ledger_info
+    );
+    assert!(response
+        .payload
+        .epoch_changes()
+        .ledger_info_with_sigs
+        .is_empty());
+
+    // Verify a target ledger info that isn't signed by the validator set of
+    // its epoch is rejected (both with and without epoch changes).
+    let invalid_target_ledger_info =
+        mock_signed_ledger_info(3, target_version, &validators[0], None);
+    storage_summary.data_summary.synced_ledger_info = Some(invalid_target_ledger_info);
+    client.update_summary(peer, storage_summary);
+    for (known_version, epoch_state) in [
+        (known_version, &trusted_epoch_state),
+        (31, &target_epoch_state),
+    ] {
+        let error = client
+            .get_state_proof_across_epoch(known_version, epoch_state, target_version)
+            .await
+            .unwrap_err();
+        assert_matches!(error, Error::InvalidResponse(_));
+    }
+
+    // Verify requests for unadvertised target versions are unavailable
+    let error = client
+        .get_state_proof_across_epoch(known_version, &trusted_epoch_state, target_version + 1)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+}
+
+#[tokio::test]
+async fn malformed_responses_are_penalized() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that sends malformed responses
+    let malformed_peer = mock_network.add_priority_peer();
+    client.update_summary(malformed_peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    let initial_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&malformed_peer)
+        .unwrap();
+    spawn_malformed_response_handler(mock_network, malformed_peer);
+
+    // Verify the client returns a malformed response error (without panicking)
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::MalformedResponse(_));
+    assert_eq!(error.get_label(), "malformed_response");
+
+    // Verify the peer was penalized
+    let malformed_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&malformed_peer)
+        .unwrap();
+    assert!(malformed_score < initial_score);
+}
+
+#[tokio::test]
+async fn malformed_responses_are_retried_with_another_peer() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        retry_malformed_responses: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer that sends malformed responses, and a peer that doesn't
+    let malformed_peer = mock_network.add_priority_peer();
+    let valid_peer = mock_network.add_priority_peer();
+    for peer in [malformed_peer, valid_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    let initial_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&malformed_peer)
+        .unwrap();
+    spawn_malformed_response_handler(mock_network, malformed_peer);
+
+    // Verify all requests succeed (malformed responses are retried with the valid peer)
+    for start_version in 0..20 {
+        client
+            .get_transactions_with_proof(200, start_version, 200, false)
+            .await
+            .unwrap();
+    }
+
+    // Verify the malformed peer was penalized
+    let malformed_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&malformed_peer)
+        .unwrap();
+    assert!(malformed_score < initial_score);
+}
+
+/// Spawns a handler that sends a malformed (i.e., undeserializable) response
+/// for all requests sent to the malformed peer, and a valid transactions
+/// response for all requests sent to other peers.
+fn spawn_malformed_response_handler(mut mock_network: MockNetwork, malformed_peer: PeerNetworkId) {
+    tokio::spawn(async move {
+        while let Some((peer_id, _, request, res_tx)) = mock_network.next_raw_request().await {
+            if peer_id == malformed_peer.peer_id() {
+                let _ = res_tx.send(Ok(Bytes::from(vec![u8::MAX; 4])));
+                continue;
+            }
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(_) => {
+                    ResponseSender::new(res_tx).send(Ok(
+                        StorageServiceResponse::TransactionsWithProof(
+                            TransactionListWithProof::new_empty(),
+                        ),
+                    ));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+}
+
+#[tokio::test]
+async fn batched_ranges_are_served_by_different_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the lower transactions
+    let lower_peer = mock_network.add_priority_peer();
+    client.update_summary(lower_peer, mock_storage_summary(100));
+
+    // Add a peer advertising the higher transactions
+    let higher_peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(300);
+    storage_summary.data_summary.transactions = Some(CompleteDataRange::new(150, 300).unwrap());
+    client.update_summary(higher_peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that verifies each range is sent to the peer advertising it
+    tokio::spawn(async move {
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    let expected_peer = if request.end_version <= 100 {
+                        lower_peer
+                    } else {
+                        higher_peer
+                    };
+                    assert_eq!(peer, expected_peer.peer_id());
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Send a batch containing a range for each peer (and a range that no peer advertises)
+    let requests = vec![
+        TransactionsWithProofRequest {
+            proof_version: 100,
+            start_version: 0,
+            end_version: 50,
+            include_events: false,
+        },
+        TransactionsWithProofRequest {
+            proof_version: 300,
+            start_version: 200,
+            end_version: 250,
+            include_events: false,
+        },
+        TransactionsWithProofRequest {
+            proof_version: 500,
+            start_version: 400,
+            end_version: 450,
+            include_events: false,
+        },
+    ];
+    let results = client.get_transactions_with_proof_batch(requests).await;
+
+    // Verify the advertised ranges succeed independently (and in order)
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0].as_ref().unwrap().payload,
+        TransactionListWithProof::new_empty()
+    );
+    assert_eq!(
+        results[1].as_ref().unwrap().payload,
+        TransactionListWithProof::new_empty()
+    );
+    assert_matches!(results[2], Err(Error::DataIsUnavailable(_)));
+}
+
+#[tokio::test]
+async fn batched_requests_respect_the_circuit_breaker() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        circuit_breaker: CircuitBreakerConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Send a batch that fails because no peers are connected
+    let request = TransactionsWithProofRequest {
+        proof_version: 100,
+        start_version: 0,
+        end_version: 50,
+        include_events: false,
+    };
+    let results = client
+        .get_transactions_with_proof_batch(vec![request.clone()])
+        .await;
+    assert_matches!(results[0], Err(Error::DataIsUnavailable(_)));
+
+    // Verify the circuit is now open for subsequent batches
+    let results = client
+        .get_transactions_with_proof_batch(vec![request])
+        .await;
+    assert_matches!(results[0], Err(Error::CircuitOpen(_)));
+}
+
+#[tokio::test]
+async fn peer_protocol_versions_are_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Verify no versions are reported without any peers
+    assert!(client.peer_protocol_versions().is_empty());
+
+    // Add a peer that only supports the storage service protocol
+    let storage_service_peer = mock_network.add_priority_peer();
+
+    // Add a peer that also supports a newer protocol
+    let upgraded_peer = PeerNetworkId::new(NetworkId::Validator, PeerId::random());
+    let mut connection_metadata = ConnectionMetadata::mock(upgraded_peer.peer_id());
+    connection_metadata
+        .application_protocols
+        .insert(ProtocolId::StorageServiceRpc);
+    connection_metadata
+        .application_protocols
+        .insert(ProtocolId::MempoolRpc);
+    mock_network
+        .peer_infos
+        .insert_connection(upgraded_peer.network_id(), connection_metadata);
+
+    // Verify the newest protocol of each peer is reported
+    let peer_protocol_versions = client.peer_protocol_versions();
+    assert_eq!(peer_protocol_versions.len(), 2);
+    assert_eq!(
+        peer_protocol_versions.get(&storage_service_peer),
+        Some(&ProtocolId::StorageServiceRpc)
+    );
+    assert_eq!(
+        peer_protocol_versions.get(&upgraded_peer),
+        Some(&ProtocolId::MempoolRpc)
+    );
+}
+
+#[tokio::test]
+async fn peers_with_range_margin_are_preferred() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            edge_margin_versions: 20,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer that would serve the request from the edge of its range
+    let edge_peer = mock_network.add_priority_peer();
+    client.update_summary(edge_peer, mock_storage_summary(200));
+
+    // Add a peer with an ample margin around the request
+    let margin_peer = mock_network.add_priority_peer();
+    client.update_summary(margin_peer, mock_storage_summary(400));
+    client.update_global_summary_cache();
+
+    // Verify the margin peer is always chosen for a request at the edge of the other peer
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 151,
+        end_version: 200,
+        include_events: false,
+    });
+    for _ in 0..20 {
+        let peer = client.choose_peer_for_request(&request, false).unwrap();
+        assert_eq!(peer, margin_peer);
+    }
+
+    // Verify the edge peer is still chosen if no peer has the margin
+    client.update_summary(margin_peer, mock_storage_summary(100));
+    client.update_global_summary_cache();
+    for _ in 0..20 {
+        let peer = client.choose_peer_for_request(&request, false).unwrap();
+        assert_eq!(peer, edge_peer);
+    }
+}
+
+#[tokio::test]
+async fn transaction_streams_follow_the_optimal_chunk_size() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising small transaction chunks
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(1000);
+    storage_summary.protocol_metadata.max_transaction_chunk_size = 100;
+    client.update_summary(peer, storage_summary.clone());
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the requested transaction ranges
+    let (range_sender, mut range_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    range_sender
+                        .unbounded_send((request.start_version, request.end_version))
+                        .unwrap();
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new_empty(),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Verify the first chunk uses the advertised chunk size
+    let transaction_stream = client.stream_transactions(0, 349, 1000, false);
+    futures::pin_mut!(transaction_stream);
+    transaction_stream.next().await.unwrap().unwrap();
+    assert_eq!(range_receiver.next().await.unwrap(), (0, 99));
+
+    // Increase the advertised chunk size and verify it takes effect mid-stream
+    storage_summary.protocol_metadata.max_transaction_chunk_size = 200;
+    client.update_summary(peer, storage_summary.clone());
+    client.update_global_summary_cache();
+    transaction_stream.next().await.unwrap().unwrap();
+    assert_eq!(range_receiver.next().await.unwrap(), (100, 299));
+
+    // Open a gap in the advertised data and verify the stream fails and ends
+    storage_summary.data_summary.transactions = Some(CompleteDataRange::new(0, 320).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+    assert_matches!(
+        transaction_stream.next().await.unwrap(),
+        Err(Error::DataIsUnavailable(_))
+    );
+    assert!(transaction_stream.next().await.is_none());
+    assert!(range_receiver.next().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn timeout_overrides_apply_to_a_single_request() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        late_response_grace_ms: 1_000, // Ensures the client enforces the response timeout
+        response_timeout_ms: 1_000,
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Send a request (without an override) and verify it times out
+    let client_clone = client.clone();
+    let request_handle = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof(200, 0, 100, false)
+            .await
+    });
+    let (_, _, _, _response_sender) = mock_network.next_request().await.unwrap();
+    mock_time.advance_async(Duration::from_millis(1_000)).await;
+    let error = request_handle.await.unwrap().unwrap_err();
+    assert_matches!(error, Error::TimeoutWaitingForResponse(_));
+
+    // Send a request with a longer timeout override
+    let client_clone = client.clone();
+    let request_handle = tokio::spawn(async move {
+        let request_options = RequestOptions {
+            timeout_override: Some(Duration::from_millis(5_000)),
+            ..Default::default()
+        };
+        client_clone
+            .get_transactions_with_proof_with_options(200, 101, 200, false, request_options)
+            .await
+    });
+    let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+
+    // Elapse the default timeout (but not the override) and respond
+    mock_time.advance_async(Duration::from_millis(2_000)).await;
+    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        TransactionListWithProof::new_empty(),
+    )));
+
+    // Verify the request still succeeds
+    let response = request_handle.await.unwrap().unwrap();
+    assert_matches!(response.payload, FilteredTransactions::Unfiltered(_));
+}
+
+#[tokio::test]
+async fn selection_policy_overrides_apply_to_a_single_request() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            selection_policy: PeerSelectionPolicy::HighestScore,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a high scoring (but slow) peer and a lower scoring (but fast) peer
+    let high_score_peer = mock_network.add_priority_peer();
+    let low_latency_peer = mock_network.add_priority_peer();
+    for peer in [high_score_peer, low_latency_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    {
+        let mut peer_states = client.peer_states.write();
+        peer_states.update_score_error(low_latency_peer, ErrorType::NotUseful);
+        peer_states.record_response_latency(high_score_peer, Duration::from_millis(500));
+        peer_states.record_response_latency(low_latency_peer, Duration::from_millis(10));
+    }
+
+    // Spawn a handler that records the peer servicing each request
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify the global policy selects the highest scoring peer
+    client
+        .get_transactions_with_proof_with_options(200, 0, 100, false, RequestOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        peer_receiver.next().await.unwrap(),
+        high_score_peer.peer_id()
+    );
+
+    // Verify the override selects the lowest latency peer for that request
+    let request_options = RequestOptions {
+        selection_policy_override: Some(PeerSelectionPolicy::LowestLatency),
+        ..Default::default()
+    };
+    client
+        .get_transactions_with_proof_with_options(200, 101, 200, false, request_options)
+        .await
+        .unwrap();
+    assert_eq!(
+        peer_receiver.next().await.unwrap(),
+        low_latency_peer.peer_id()
+    );
+
+    // Verify the global policy is used again for subsequent requests
+    client
+        .get_transactions_with_proof_with_options(200, 0, 50, false, RequestOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        peer_receiver.next().await.unwrap(),
+        high_score_peer.peer_id()
+    );
+}
+
+#[tokio::test]
+async fn requests_require_the_minimum_number_of_advertising_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            min_peers_for_request: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a single peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Verify the request fails because too few peers advertise the data
+    let error = client
+        .get_transactions_with_proof(200, 50, 100, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+
+    // Add another peer advertising the data
+    let other_peer = mock_network.add_priority_peer();
+    client.update_summary(other_peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Verify the request fails if one of the peers is excluded
+    let request_options = RequestOptions {
+        excluded_peers: HashSet::from([other_peer]),
+        ..Default::default()
+    };
+    let error = client
+        .get_transactions_with_proof_with_options(200, 50, 100, false, request_options)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(message) if message.contains("at least 2"));
+
+    // Spawn a handler for both peers
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify the request now succeeds
+    let response = client
+        .get_transactions_with_proof(200, 50, 100, false)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+}
+
+#[tokio::test]
+async fn time_since_last_success_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Verify nothing is reported before any request succeeds
+    assert_eq!(client.time_since_last_success(), None);
+
+    // Spawn a handler for the peer
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Complete a successful request and verify no time has elapsed
+    client
+        .get_transactions_with_proof(200, 50, 100, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        client.time_since_last_success(),
+        Some(Duration::from_secs(0))
+    );
+
+    // Advance the time and verify the elapsed time is reported
+    mock_time.advance(Duration::from_secs(42));
+    assert_eq!(
+        client.time_since_last_success(),
+        Some(Duration::from_secs(42))
+    );
+}
+
+#[tokio::test]
+async fn chunk_sizes_grow_as_probes_succeed() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        chunk_sizes: ChunkSizeConfig {
+            probe_initial_size: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising large chunk sizes
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(1000));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that can only serve chunks of up to 20 transactions
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    if request.end_version - request.start_version < 20 {
+                        response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                            TransactionListWithProof::new_empty(),
+                        )));
+                    } else {
+                        response_sender.send(Err(StorageServiceError::InternalError(
+                            "The chunk is too large!".into(),
+                        )));
+                    }
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Verify the client starts with small chunks and grows them as probes succeed
+    for expected_chunk_size in [10, 20] {
+        let transaction_chunk_size = client
+            .get_global_data_summary()
+            .optimal_chunk_sizes
+            .transaction_chunk_size;
+        assert_eq!(transaction_chunk_size, expected_chunk_size);
+        client
+            .get_transactions_with_proof(1000, 0, transaction_chunk_size - 1, false)
+            .await
+            .unwrap();
+        assert_eq!(client.discovered_chunk_size(), None);
+    }
+
+    // Verify a failed probe stops the growth and records the safe chunk size
+    let transaction_chunk_size = client
+        .get_global_data_summary()
+        .optimal_chunk_sizes
+        .transaction_chunk_size;
+    assert_eq!(transaction_chunk_size, 40);
+    client
+        .get_transactions_with_proof(1000, 0, transaction_chunk_size - 1, false)
+        .await
+        .unwrap_err();
+    assert_eq!(client.discovered_chunk_size(), Some(20));
+    assert_eq!(
+        client
+            .get_global_data_summary()
+            .optimal_chunk_sizes
+            .transaction_chunk_size,
+        20
+    );
+}
+
+#[tokio::test]
+async fn freshness_weighted_polling_favours_fresher_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            poll_selection_strategy: PeerSelectionStrategy::FreshnessWeighted,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a priority peer advertising fresh data and one advertising stale data
+    let fresh_peer = mock_network.add_priority_peer();
+    client.update_summary(fresh_peer, mock_storage_summary(200));
+    let stale_peer = mock_network.add_priority_peer();
+    client.update_summary(stale_peer, mock_storage_summary(100));
+
+    // Poll both (new) peers once
+    assert_eq!(client.fetch_peers_to_poll().unwrap().len(), 2);
+
+    // Verify the fresher peer is polled more often
+    let mut num_fresh_polls = 0;
+    let mut num_stale_polls = 0;
+    for _ in 0..100 {
+        let peers_to_poll = client.fetch_peers_to_poll().unwrap();
+        assert_eq!(peers_to_poll.len(), 1);
+        if peers_to_poll[0] == fresh_peer {
+            num_fresh_polls += 1;
+        } else {
+            assert_eq!(peers_to_poll[0], stale_peer);
+            num_stale_polls += 1;
+        }
+    }
+    assert!(num_fresh_polls > num_stale_polls);
+
+    // Verify the peers are polled in round robin order when the versions tie
+    client.update_summary(stale_peer, mock_storage_summary(200));
+    let first_peer = client.fetch_peers_to_poll().unwrap()[0];
+    let second_peer = client.fetch_peers_to_poll().unwrap()[0];
+    assert_ne!(first_peer, second_peer);
+    assert_eq!(client.fetch_peers_to_poll().unwrap()[0], first_peer);
+}
+
+#[tokio::test]
+async fn transient_errors_are_retried_with_another_peer() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            selection_policy: PeerSelectionPolicy::HighestScore,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add an overloaded peer (with the highest score) and a working peer
+    let failing_peer = mock_network.add_priority_peer();
+    let working_peer = mock_network.add_priority_peer();
+    for peer in [failing_peer, working_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    {
+        let config = AptosDataClientConfig::default();
+        let mut peer_states = client.peer_states.write();
+        peer_states.update_score_success(
+            failing_peer,
+            config.peer_scoring.success_streak_growth_factor,
+            config.peer_scoring.max_success_streak_multiplier,
+        );
+        peer_states.update_score_error(working_peer, ErrorType::NotUseful);
+    }
+
+    // Spawn a handler that records the peer servicing each request
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            if peer == failing_peer.peer_id() {
+                response_sender.send(Err(StorageServiceError::Overloaded {
+                    retry_after_ms: 10_000,
+                }));
+            } else {
+                response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                )));
+            }
+        }
+    });
+
+    // Send a request with retries (elapsing time until it completes)
+    let base_backoff = Duration::from_millis(100);
+    let request_options = RequestOptions::default().with_retries(3, base_backoff);
+    let client_clone = client.clone();
+    let mut request_handle = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof_with_options(200, 0, 100, false, request_options)
+            .await
+    });
+    let response = loop {
+        tokio::task::yield_now().await;
+        if let Some(result) = (&mut request_handle).now_or_never() {
+            break result.unwrap().unwrap();
+        }
+        mock_time.advance_async(base_backoff).await;
+    };
+
+    // Verify the overloaded peer was skipped when retrying, and that the
+    // request then succeeded on the working peer.
+    assert_eq!(response.context.peer, Some(working_peer));
+    for expected_peer in [failing_peer, working_peer] {
+        assert_eq!(peer_receiver.next().await.unwrap(), expected_peer.peer_id());
+    }
+
+    // Verify only timeouts, network errors and overloaded peers are retried
+    assert!(Error::NetworkError("".into()).is_transient());
+    assert!(Error::PeerIsOverloaded("".into()).is_transient());
+    assert!(Error::TimeoutWaitingForResponse("".into()).is_transient());
+    assert!(!Error::InvalidResponse("".into()).is_transient());
+    assert!(!Error::UnexpectedErrorEncountered("".into()).is_transient());
+}
+
+#[tokio::test]
+async fn invalid_requests_are_not_retried() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that rejects every request as invalid
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            response_sender.send(Err(StorageServiceError::InvalidRequest("".into())));
+        }
+    });
+
+    // Verify the request fails after a single attempt (despite the retries)
+    let request_options = RequestOptions::default().with_retries(3, Duration::from_millis(100));
+    let error = client
+        .get_transactions_with_proof_with_options(200, 0, 100, false, request_options)
+        .await
+        .unwrap_err();
+    assert!(!error.is_transient());
+    assert_eq!(peer_receiver.next().await.unwrap(), peer.peer_id());
+    assert!(peer_receiver.next().now_or_never().is_none());
+
+    // Verify requests rejected by the client are never retried
+    let invalid_request = Error::InvalidRequest(InvalidRequestReason::InvalidArguments("".into()));
+    assert!(!invalid_request.is_transient());
+}
+
+#[tokio::test]
+async fn time_going_backwards_reports_zero_elapsed_time() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Record times that are ahead of the time service (i.e., as if the time
+    // service went backwards after the times were recorded).
+    let time_now = mock_time.now();
+    let future_time = time_now + Duration::from_secs(10);
+    let peer = mock_network.add_priority_peer();
+    client
+        .peer_states
+        .write()
+        .update_summary(peer, mock_storage_summary(100), future_time);
+    *client.last_data_response_time.write() = Some(future_time);
+    client.record_serving_bandwidth(
+        peer,
+        &StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new_empty()),
+        future_time,
+    );
+
+    // Verify the elapsed times are clamped to zero (and don't panic)
+    assert_eq!(elapsed_since(time_now, future_time), Duration::from_secs(0));
+    assert_eq!(client.summary_ages()[&peer], Duration::from_secs(0));
+    assert_eq!(
+        client.time_since_last_success(),
+        Some(Duration::from_secs(0))
+    );
+    assert!(client.peer_serving_bandwidth().is_empty());
+
+    // Verify the elapsed times are reported once time catches up
+    mock_time.advance(Duration::from_secs(15));
+    assert_eq!(
+        elapsed_since(mock_time.now(), future_time),
+        Duration::from_secs(5)
+    );
+    assert_eq!(client.summary_ages()[&peer], Duration::from_secs(5));
+    assert_eq!(
+        client.time_since_last_success(),
+        Some(Duration::from_secs(5))
+    );
+}
+
+#[tokio::test]
+async fn exhausted_rate_limits_are_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            max_requests_per_sec_per_peer: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer that advertises data and respond to all requests
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify no rate limits are active before any requests are sent
+    assert!(client.active_rate_limits().is_empty());
+
+    // Exhaust the peer's bucket for transaction requests
+    for _ in 0..2 {
+        client
+            .get_transactions_with_proof(200, 0, 200, false)
+            .await
+            .unwrap();
+    }
+
+    // Verify the peer is reported as rate limited (for transactions only)
+    let active_rate_limits = client.active_rate_limits();
+    assert_eq!(active_rate_limits.len(), 1);
+    let (limited_peer, data_type, time_until_refill) = active_rate_limits[0];
+    assert_eq!(limited_peer, peer);
+    assert_eq!(data_type, DataType::Transactions);
+    assert!(time_until_refill > Duration::from_millis(0));
+    assert!(time_until_refill <= Duration::from_millis(500));
+
+    // Verify further transaction requests aren't sent to the peer
+    assert!(client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .is_err());
+
+    // Elapse enough time for the bucket to refill and verify the rate limit is lifted
+    mock_time.advance(time_until_refill);
+    assert!(client.active_rate_limits().is_empty());
+    client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn summary_changes_are_streamed_to_subscribers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, poller) = MockNetwork::new();
+
+    // Add a peer that advertises the current version (which can be updated)
+    mock_network.add_priority_peer();
+    let advertised_version = Arc::new(AtomicU64::new(100));
+    let advertised_version_clone = advertised_version.clone();
+    tokio::spawn(poller.start_poller());
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetStorageServerSummary => {
+                    let version = advertised_version_clone.load(Ordering::Relaxed);
+                    StorageServiceResponse::StorageServerSummary(mock_storage_summary(version))
+                }
+                StorageServiceRequest::GetServerProtocolVersion => {
+                    StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                        protocol_version: EXTENDED_PROTOCOL_VERSION,
+                    })
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Advance time so the poller fetches the peer's summary
+    let summary_poll_interval = Duration::from_millis(1_000);
+    tokio::task::yield_now().await;
+    mock_time.advance_async(summary_poll_interval).await;
+    let mut summary_changes = Box::pin(client.subscribe_to_summary_changes());
+
+    // Advance time so the poller fetches the same summary and verify nothing is emitted
+    mock_time.advance_async(summary_poll_interval).await;
+    assert!(summary_changes.next().now_or_never().is_none());
+
+    // Have the peer advertise a new version and verify the updated summary is emitted
+    advertised_version.store(200, Ordering::Relaxed);
+    mock_time.advance_async(summary_poll_interval).await;
+    let global_data_summary = summary_changes.next().now_or_never().unwrap().unwrap();
+    assert!(global_data_summary
+        .advertised_data
+        .transactions
+        .contains(&CompleteDataRange::new(0, 200).unwrap()));
+    assert_eq!(global_data_summary, client.get_global_data_summary());
+    assert!(summary_changes.next().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn inflight_requests_are_rerouted_when_peer_is_banned() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        cancel_inflight_on_ban: true,
+        peer_selection: PeerSelectionConfig {
+            selection_policy: PeerSelectionPolicy::HighestScore,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add an unresponsive peer (with the highest score) and a working peer
+    let unresponsive_peer = mock_network.add_priority_peer();
+    let working_peer = mock_network.add_priority_peer();
+    for peer in [unresponsive_peer, working_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    client
+        .peer_states
+        .write()
+        .update_score_error(working_peer, ErrorType::NotUseful);
+
+    // Spawn a handler that never responds to the unresponsive peer's requests
+    // (and notifies the test when the peer receives a request).
+    let (inflight_sender, mut inflight_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut pending_response_senders = vec![];
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            if peer == unresponsive_peer.peer_id() {
+                pending_response_senders.push(response_sender);
+                inflight_sender.unbounded_send(()).unwrap();
+            } else {
+                response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                )));
+            }
+        }
+    });
+
+    // Send a request and wait until it's in-flight to the unresponsive peer
+    let client_clone = client.clone();
+    let request_handle = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof(200, 0, 100, false)
+            .await
+    });
+    inflight_receiver.next().await.unwrap();
+
+    // Ban the unresponsive peer
+    for _ in 0..10 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(unresponsive_peer, ErrorType::Malicious);
+    }
+
+    // Verify the in-flight request was cancelled and re-routed to the working
+    // peer (without time elapsing, i.e., without waiting for a timeout).
+    let response = request_handle.await.unwrap().unwrap();
+    assert_eq!(response.context.peer, Some(working_peer));
+
+    // Verify the cancelled request is no longer tracked as in-flight
+    assert_eq!(
+        client
+            .inflight_requests
+            .lock()
+            .cancel_peer_requests(&unresponsive_peer),
+        0
+    );
+}
+
+#[tokio::test]
+async fn dropped_inflight_requests_are_no_longer_tracked() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        cancel_inflight_on_ban: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add an unresponsive peer
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that never responds (and notifies the test when the
+    // peer receives a request).
+    let (inflight_sender, mut inflight_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut pending_response_senders = vec![];
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            pending_response_senders.push(response_sender);
+            inflight_sender.unbounded_send(()).unwrap();
+        }
+    });
+
+    // Send a request and wait until it's in-flight to the peer
+    let client_clone = client.clone();
+    let request_handle = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof(200, 0, 100, false)
+            .await
+    });
+    inflight_receiver.next().await.unwrap();
+
+    // Drop the request (before a response arrives)
+    request_handle.abort();
+    assert!(request_handle.await.unwrap_err().is_cancelled());
+
+    // Verify the dropped request is no longer tracked as in-flight
+    assert_eq!(
+        client.inflight_requests.lock().cancel_peer_requests(&peer),
+        0
+    );
+}
+
+#[tokio::test]
+async fn minimal_transaction_proof_covers_the_trusted_gap() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer covering the requested transactions
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Create a transaction accumulator with four transactions (i.e., versions 0 to 3)
+    let transactions: Vec<_> = (0..4)
+        .map(|sequence_number| mock_user_transaction(AccountAddress::random(), sequence_number))
+        .collect();
+    let transaction_infos: Vec<_> = transactions
+        .iter()
+        .map(|transaction| {
+            TransactionInfo::new(
+                transaction.hash(),
+                HashValue::zero(),
+                HashValue::zero(),
+                0,
+                ExecutionStatus::Success,
+            )
+        })
+        .collect();
+    let leaves: Vec<_> = transaction_infos.iter().map(CryptoHash::hash).collect();
+    let hash_internal = |left: HashValue, right: HashValue| {
+        MerkleTreeInternalNode::<TransactionAccumulatorHasher>::new(left, right).hash()
+    };
+    let left_subtree = hash_internal(leaves[0], leaves[1]);
+    let root_hash = hash_internal(left_subtree, hash_internal(leaves[2], leaves[3]));
+
+    // Spawn a handler that responds with the transactions after version 1
+    let (trusted_low, trusted_high) = (1, 3);
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    assert_eq!(request.proof_version, trusted_high);
+                    assert_eq!(request.start_version, trusted_low + 1);
+                    assert_eq!(request.end_version, trusted_high);
+                    let proof = TransactionInfoListWithProof::new(
+                        TransactionAccumulatorRangeProof::new(vec![left_subtree], vec![]),
+                        transaction_infos[2..].to_vec(),
+                    );
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        TransactionListWithProof::new(
+                            transactions[2..].to_vec(),
+                            None,
+                            Some(trusted_low + 1),
+                            proof,
+                        ),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Request the minimal proof and verify it covers exactly the gap
+    let response = client
+        .get_minimal_transaction_proof(trusted_low, trusted_high)
+        .await
+        .unwrap();
+    let transaction_list_with_proof = response.payload;
+    assert_eq!(
+        transaction_list_with_proof.first_transaction_version,
+        Some(trusted_low + 1)
+    );
+    assert_eq!(
+        transaction_list_with_proof.transactions.len() as u64,
+        trusted_high - trusted_low
+    );
+
+    // Verify the proof against the trusted ledger info at the high version
+    let trusted_high_ledger_info = LedgerInfo::new(
+        BlockInfo::new(0, 0, HashValue::zero(), root_hash, trusted_high, 0, None),
+        HashValue::zero(),
+    );
+    transaction_list_with_proof
+        .verify(&trusted_high_ledger_info, Some(trusted_low + 1))
+        .unwrap();
+
+    // Verify requests without a gap between the trusted versions are rejected
+    let error = client
+        .get_minimal_transaction_proof(trusted_high, trusted_high)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidRequest(_));
+}
+
+#[tokio::test]
+async fn account_states_are_fetched_by_prefix() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the account states at the requested version
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.account_states = Some(CompleteDataRange::new(0, 200).unwrap());
+    client.update_summary(peer, storage_summary);
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that verifies the request and responds with the account states
+    let version = 100;
+    let prefix = StateKeyPrefix::from(AccountAddress::ONE);
+    let expected_request = AccountStatesByPrefixRequest {
+        version,
+        prefix: prefix.clone(),
+        cursor: Some(StateKey::Raw(vec![0])),
+        limit: 2,
+    };
+    let account_states = vec![
+        (StateKey::Raw(vec![1]), StateValue::from(vec![1])),
+        (StateKey::Raw(vec![2]), StateValue::from(vec![2])),
+    ];
+    let response_account_states = account_states.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetAccountStatesByPrefix(request) => {
+                    assert_eq!(request, expected_request);
+                    response_sender.send(Ok(StorageServiceResponse::AccountStatesByPrefix(
+                        response_account_states.clone(),
+                    )));
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            }
+        }
+    });
+
+    // Fetch the page of account states and verify the response
+    let response = client
+        .get_account_states_by_prefix(version, prefix, Some(StateKey::Raw(vec![0])), 2)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, account_states);
+
+    // Verify requests at versions that aren't advertised are rejected
+    let error = client
+        .get_account_states_by_prefix(300, StateKeyPrefix::from(AccountAddress::ONE), None, 2)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+}
+
+#[tokio::test]
+async fn circuit_breaker_opens_after_repeated_failures() {
+    ::aptos_logger::Logger::init_for_testing();
+    let cooldown_ms = 5_000;
+    let data_client_config = AptosDataClientConfig {
+        circuit_breaker: CircuitBreakerConfig {
+            cooldown_ms,
+            failure_threshold: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Send requests that fail because no peers are connected
+    for _ in 0..3 {
+        let error = client
+            .get_transactions_with_proof(100, 50, 100, false)
+            .await
+            .unwrap_err();
+        assert_matches!(error, Error::DataIsUnavailable(_));
+    }
+
+    // Verify the circuit is now open (i.e., requests fail without being sent)
+    let error = client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::CircuitOpen(_));
+
+    // Elapse the cooldown and verify the half-open circuit re-opens on a failure
+    mock_time
+        .advance_async(Duration::from_millis(cooldown_ms))
+        .await;
+    let error = client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+    let error = client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::CircuitOpen(_));
+
+    // Add a peer advertising the data and spawn a handler to respond to requests
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Elapse the cooldown and verify the circuit closes on the first success
+    mock_time
+        .advance_async(Duration::from_millis(cooldown_ms))
+        .await;
+    client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+
+    // Verify a subsequent failure doesn't re-open the closed circuit
+    let error = client
+        .get_transactions_with_proof(300, 250, 300, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+    client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn circuit_breaker_counts_coalesced_failures_once() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            enable_request_coalescing: true,
+            ..Default::default()
+        },
+        circuit_breaker: CircuitBreakerConfig {
+            failure_threshold: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts all requests and fails them
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            response_sender.send(Err(StorageServiceError::InternalError("".to_string())));
+        }
+    });
+
+    // Send two identical requests concurrently and verify they're coalesced (and fail)
+    let (first_result, second_result) = futures::join!(
+        client.get_transactions_with_proof(200, 0, 200, false),
+        client.get_transactions_with_proof(200, 0, 200, false)
+    );
+    assert_matches!(first_result, Err(Error::UnexpectedErrorEncountered(_)));
+    assert_matches!(second_result, Err(Error::UnexpectedErrorEncountered(_)));
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Verify the circuit is still closed (the coalesced failure was only counted once)
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::UnexpectedErrorEncountered(_));
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+
+    // Verify the second failed request opened the circuit
+    let error = client
+        .get_transactions_with_proof(200, 0, 200, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::CircuitOpen(_));
+}
+
+#[tokio::test]
+async fn unchanged_summaries_skip_global_summary_recomputation() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            dedup_summary_updates: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Update the summary of a peer and verify the global summary is recomputed
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(100));
+    client.update_global_summary_cache_if_changed();
+    assert!(!client.get_global_data_summary().is_empty());
+
+    // Clear the global summary cache (so that any recomputation is detected)
+    *client.global_summary_cache.write() = GlobalDataSummary::empty();
+
+    // Feed the same summary again and verify the global summary isn't recomputed
+    client.update_summary(peer, mock_storage_summary(100));
+    client.update_global_summary_cache_if_changed();
+    assert!(client.get_global_data_summary().is_empty());
+
+    // Feed a new summary and verify the global summary is recomputed
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache_if_changed();
+    let highest_synced_ledger_info = client
+        .get_global_data_summary()
+        .advertised_data
+        .highest_synced_ledger_info()
+        .unwrap();
+    assert_eq!(highest_synced_ledger_info.ledger_info().version(), 200);
+}
+
+#[tokio::test]
+async fn request_completion_events_are_broadcast() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+
+    // Add a peer advertising the data and subscribe to the request events
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    let mut request_event_receiver = client.subscribe_to_request_events();
+
+    // Spawn a handler that responds to the request after some latency
+    let response_latency = Duration::from_millis(100);
+    let response =
+        StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new_empty());
+    let expected_response_bytes = wire_response_bytes(&response);
+    tokio::spawn(async move {
+        let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        mock_time.advance(response_latency);
+        response_sender.send(Ok(response));
+    });
+
+    // Complete the request and verify the corresponding event is received
+    client
+        .get_transactions_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+    let request_event = request_event_receiver.recv().await.unwrap();
+    assert_eq!(
+        request_event,
+        RequestCompletionEvent {
+            peer,
+            request_type: "get_transactions_with_proof",
+            outcome: Ok(()),
+            latency: response_latency,
+            response_bytes: expected_response_bytes,
+        }
+    );
+}
+
+#[tokio::test]
+async fn excluded_peers_are_skipped_during_selection() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            selection_policy: PeerSelectionPolicy::HighestScore,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a high scoring peer and a lower scoring peer (both advertising the data)
+    let high_score_peer = mock_network.add_priority_peer();
+    let low_score_peer = mock_network.add_priority_peer();
+    for peer in [high_score_peer, low_score_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+    client
+        .peer_states
+        .write()
+        .update_score_error(low_score_peer, ErrorType::NotUseful);
+
+    // Spawn a handler that records the peer servicing each request
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Verify the highest scoring peer is normally selected
+    client
+        .get_transactions_with_proof_with_options(200, 0, 100, false, RequestOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        peer_receiver.next().await.unwrap(),
+        high_score_peer.peer_id()
+    );
+
+    // Verify excluding the highest scoring peer routes the request to the other peer
+    let request_options = RequestOptions {
+        excluded_peers: HashSet::from([high_score_peer]),
+        ..Default::default()
+    };
+    client
+        .get_transactions_with_proof_with_options(200, 0, 100, false, request_options)
+        .await
+        .unwrap();
+    assert_eq!(
+        peer_receiver.next().await.unwrap(),
+        low_score_peer.peer_id()
+    );
+
+    // Verify excluding every advertising peer fails the request
+    let request_options = RequestOptions {
+        excluded_peers: HashSet::from([high_score_peer, low_score_peer]),
+        ..Default::default()
+    };
+    let error = client
+        .get_transactions_with_proof_with_options(200, 0, 100, false, request_options)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(message) if message.contains("excluded"));
+}
+
+#[tokio::test]
+async fn oversized_responses_are_rejected() {
+    ::aptos_logger::Logger::init_for_testing();
+    let max_response_bytes = 1_000;
+    let data_client_config = AptosDataClientConfig {
+        max_response_bytes,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    let initial_score = client.peer_states.read().get_peer_score(&peer).unwrap();
+
+    // Create a response that exceeds the max response size
+    let transactions = (0..20)
+        .map(|sequence_number| mock_user_transaction(AccountAddress::random(), sequence_number))
+        .collect();
+    let oversized_response =
+        StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new(
+            transactions,
+            None,
+            Some(50),
+            TransactionInfoListWithProof::new_empty(),
+        ));
+    assert!(wire_response_bytes(&oversized_response) > max_response_bytes);
+
+    // Spawn a handler that responds with the oversized response, followed by
+    // oversized garbage (that can't be deserialized).
+    tokio::spawn(async move {
+        let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        response_sender.send(Ok(oversized_response));
+        let (_, _, _, res_tx) = mock_network.next_raw_request().await.unwrap();
+        let garbage_bytes = vec![u8::MAX; max_response_bytes as usize + 1];
+        let _ = res_tx.send(Ok(Bytes::from(garbage_bytes)));
+    });
+
+    // Verify the responses are rejected (before they're deserialized) and the
+    // peer is penalized.
+    let mut previous_score = initial_score;
+    for _ in 0..2 {
+        let error = client
+            .get_transactions_with_proof(100, 50, 100, false)
+            .await
+            .unwrap_err();
+        assert_matches!(error, Error::ResponseTooLarge(_));
+        let score = client.peer_states.read().get_peer_score(&peer).unwrap();
+        assert!(score < previous_score);
+        previous_score = score;
+    }
+}
+
+#[tokio::test]
+async fn peers_with_stale_proofs_are_skipped_for_tip_requests() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer synced to version 150 and a peer synced to version 200
+    let stale_peer = mock_network.add_priority_peer();
+    let synced_peer = mock_network.add_priority_peer();
+    client.update_summary(stale_peer, mock_storage_summary(150));
+    client.update_summary(synced_peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the peer servicing each request
+    let (peer_sender, mut peer_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((peer, _, _, response_sender)) = mock_network.next_request().await {
+            peer_sender.unbounded_send(peer).unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+
+    // Request transactions (that both peers store) with a proof at version 200,
+    // and verify only the fully synced peer is ever attempted.
+    for start_version in 0..10 {
+        client
+            .get_transactions_with_proof(200, start_version, 100, false)
+            .await
+            .unwrap();
+        assert_eq!(peer_receiver.next().await.unwrap(), synced_peer.peer_id());
+    }
+}
+
+#[tokio::test]
+async fn summaries_are_reconciled_across_networks() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            reconcile_summaries_across_networks: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add the same peer (identity) on the validator and VFN networks
+    let peer_id = PeerId::random();
+    let validator_peer = PeerNetworkId::new(NetworkId::Validator, peer_id);
+    let vfn_peer = PeerNetworkId::new(NetworkId::Vfn, peer_id);
+    mock_network.add_peer_with_id(validator_peer);
+    mock_network.add_peer_with_id(vfn_peer);
+
+    // Advertise complementary transaction ranges on each network
+    let mut validator_summary = mock_storage_summary(100);
+    validator_summary.data_summary.transactions = Some(CompleteDataRange::new(0, 100).unwrap());
+    let mut vfn_summary = mock_storage_summary(200);
+    vfn_summary.data_summary.transactions = Some(CompleteDataRange::new(101, 200).unwrap());
+    client.update_summary(validator_peer, validator_summary);
+    client.update_summary(vfn_peer, vfn_summary);
+    client.update_global_summary_cache();
+
+    // Verify neither peer can service a request spanning both ranges (yet)
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 50,
+        end_version: 150,
+        include_events: false,
+    });
+    for peer in [validator_peer, vfn_peer] {
+        assert!(!client
+            .peer_states
+            .read()
+            .can_service_request(&peer, &request));
+    }
+
+    // Link the peers and verify the reconciled coverage is the union of the ranges
+    client.set_peer_identity_resolver(Arc::new(PeerIdIdentityResolver));
+    for peer in [validator_peer, vfn_peer] {
+        let peer_states = client.peer_states.read();
+        let reconciled_summary = peer_states
+            .reconciled_summary_if_not_ignored(&peer)
+            .unwrap();
+        assert_eq!(
+            reconciled_summary.data_summary.transactions,
+            Some(CompleteDataRange::new(0, 200).unwrap())
+        );
+        let synced_ledger_info = reconciled_summary.data_summary.synced_ledger_info.as_ref();
+        assert_eq!(synced_ledger_info.unwrap().ledger_info().version(), 200);
+        assert!(peer_states.can_service_request(&peer, &request));
+    }
+}
+
+#[tokio::test]
+async fn epoch_ending_ledger_infos_are_served_from_containing_cache_entries() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts and responds to all epoch requests
+    let validator = ValidatorSigner::random(None);
+    let epoch_ending_ledger_infos: Vec<_> = (0..=10)
+        .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+        .collect();
+    let epoch_ending_ledger_infos_clone = epoch_ending_ledger_infos.clone();
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            let request = match request {
+                StorageServiceRequest::GetEpochEndingLedgerInfos(request) => request,
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            let ledger_infos = epoch_ending_ledger_infos_clone
+                [request.start_epoch as usize..=request.expected_end_epoch as usize]
+                .to_vec();
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                EpochChangeProof::new(ledger_infos, false),
+            )));
+        }
+    });
+
+    // Fetch a range of ledger infos
+    let request_options = RequestOptions {
+        verification_level: VerificationLevel::StructuralOnly,
+        ..Default::default()
+    };
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(0, 5, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[0..=5].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Verify a range within the cached range is served from the cache
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(2, 4, request_options.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[2..=4].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Verify a range extending past the cached range is fetched from the network
+    let response = client
+        .get_epoch_ending_ledger_infos_with_options(4, 7, request_options)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos[4..=7].to_vec());
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn epoch_ending_ledger_infos_are_cached_by_returned_epochs() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts all epoch requests and truncates the
+    // responses at epoch 5.
+    let validator = ValidatorSigner::random(None);
+    let epoch_ending_ledger_infos: Vec<_> = (0..=5)
+        .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+        .collect();
+    let epoch_ending_ledger_infos_clone = epoch_ending_ledger_infos.clone();
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+            let request = match request {
+                StorageServiceRequest::GetEpochEndingLedgerInfos(request) => request,
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            let ledger_infos = epoch_ending_ledger_infos_clone
+                [request.start_epoch as usize..=min(request.expected_end_epoch, 5) as usize]
+                .to_vec();
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                EpochChangeProof::new(ledger_infos, false),
+            )));
+        }
+    });
+
+    // Fetch a range of ledger infos and verify the truncated response is returned
+    let response = client.get_epoch_ending_ledger_infos(0, 10).await.unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos);
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Verify the same range isn't served from the cache (only the returned epochs were cached)
+    client.get_epoch_ending_ledger_infos(0, 10).await.unwrap();
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+
+    // Verify the returned epochs are served from the cache
+    let response = client.get_epoch_ending_ledger_infos(0, 5).await.unwrap();
+    assert_eq!(response.payload, epoch_ending_ledger_infos);
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn serviceability_is_checked_without_sending_requests() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Verify the request can't be serviced because no peers are connected
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 100,
+        start_version: 50,
+        end_version: 100,
+        include_events: false,
+    });
+    assert!(!client.can_service(&request));
+    assert!(client.serviceable_peers(&request).is_empty());
+
+    // Add a connected peer and verify the request still can't be serviced
+    // (the peer isn't advertising any data yet).
+    let peer = mock_network.add_priority_peer();
+    assert!(!client.can_service(&request));
+
+    // Update the peer's summary and verify the request can now be serviced
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    assert!(client.can_service(&request));
+    assert_eq!(client.serviceable_peers(&request), vec![peer]);
+
+    // Verify requests beyond the advertised data can't be serviced
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 300,
+        start_version: 250,
+        end_version: 300,
+        include_events: false,
+    });
+    assert!(!client.can_service(&request));
+
+    // Verify no requests were sent to the peer
+    assert!(mock_network.next_request().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn serviceable_events_report_recovered_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Watch a range of transactions (before any peer advertises it)
+    let mut serviceability_events =
+        client.watch_range_serviceability(DataType::Transactions, 50, 100);
+
+    // Add a peer advertising the range and verify the range became serviceable
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    assert_eq!(
+        serviceability_events.next().await.unwrap(),
+        ServiceabilityEvent::Serviceable(ServiceableReason::NewPeer(peer))
+    );
+
+    // Drive the peer's score below the ignore threshold and verify the range
+    // became unserviceable.
+    for _ in 0..10 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peer, ErrorType::Malicious);
+    }
+    client.update_global_summary_cache();
+    assert_eq!(
+        serviceability_events.next().await.unwrap(),
+        ServiceabilityEvent::Unserviceable
+    );
+
+    // Restore the peer and verify the serviceable event reports the recovery
+    for _ in 0..30 {
+        client
+            .peer_states
+            .write()
+            .update_score_success(peer, 1.0, 1.0);
+    }
+    client.update_global_summary_cache();
+    assert_eq!(
+        serviceability_events.next().await.unwrap(),
+        ServiceabilityEvent::Serviceable(ServiceableReason::PeerRecovered(peer))
+    );
+
+    // Verify no events are emitted while the range remains serviceable
+    client.update_summary(peer, mock_storage_summary(300));
+    client.update_global_summary_cache();
+    assert!(serviceability_events.next().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn only_high_priority_requests_are_hedged() {
+    ::aptos_logger::Logger::init_for_testing();
+    let hedge_delay_ms = 100;
+    let data_client_config = AptosDataClientConfig {
+        hedge_delay_ms,
+        hedge_only_high_priority: true,
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers advertising transactions
+    for _ in 0..2 {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, mock_storage_summary(200));
+    }
+    client.update_global_summary_cache();
+
+    // Send a high priority request
+    let client_clone = client.clone();
+    let request_options = RequestOptions {
+        priority: RequestPriority::High,
+        ..Default::default()
+    };
+    let high_priority_request = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof_with_options(100, 50, 100, false, request_options)
+            .await
+    });
+
+    // Don't respond to the request until the hedge delay elapses, and verify
+    // the request is hedged to the other peer.
+    let (primary_peer, _, _, _primary_response_sender) = mock_network.next_request().await.unwrap();
+    mock_time
+        .advance_async(Duration::from_millis(hedge_delay_ms))
+        .await;
+    let (hedge_peer, _, request, hedge_response_sender) =
+        mock_network.next_request().await.unwrap();
+    assert_ne!(primary_peer, hedge_peer);
+    assert_matches!(request, StorageServiceRequest::GetTransactionsWithProof(_));
+
+    // Respond to the hedged request and verify the request succeeds
+    hedge_response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        TransactionListWithProof::new_empty(),
+    )));
+    high_priority_request.await.unwrap().unwrap();
+
+    // Send an identical low priority request
+    let client_clone = client.clone();
+    let request_options = RequestOptions {
+        priority: RequestPriority::Low,
+        ..Default::default()
+    };
+    let low_priority_request = tokio::spawn(async move {
+        client_clone
+            .get_transactions_with_proof_with_options(100, 50, 100, false, request_options)
+            .await
+    });
+
+    // Verify the request isn't hedged after the hedge delay elapses
+    let (_, _, _, primary_response_sender) = mock_network.next_request().await.unwrap();
+    mock_time
+        .advance_async(Duration::from_millis(hedge_delay_ms))
+        .await;
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+
+    // Respond to the request and verify the request succeeds
+    primary_response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        TransactionListWithProof::new_empty(),
+    )));
+    low_priority_request.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn higher_recovery_increments_restore_banned_peers_sooner() {
+    ::aptos_logger::Logger::init_for_testing();
+
+    // Verify a higher recovery increment restores the banned peer in fewer polls
+    let default_num_polls =
+        num_polls_until_banned_peer_recovers(AptosDataClientConfig::default()).await;
+    let data_client_config = AptosDataClientConfig {
+        peer_scoring: PeerScoringConfig {
+            score_recovery_increment: 5.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let num_polls = num_polls_until_banned_peer_recovers(data_client_config).await;
+    assert!(num_polls < default_num_polls);
+}
+
+/// Bans a peer (that responds to all summary polls) and returns the number of
+/// poll intervals until the peer's score recovers (i.e., it's no longer ignored).
+async fn num_polls_until_banned_peer_recovers(data_client_config: AptosDataClientConfig) -> u64 {
+    let summary_poll_interval = Duration::from_millis(data_client_config.summary_poll_interval_ms);
+    let (mut mock_network, mock_time, client, poller) =
+        MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer and spawn a handler that responds to all summary polls (and
+    // protocol version requests)
+    let peer = mock_network.add_priority_peer();
+    tokio::spawn(poller.start_poller());
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let response = match request {
+                StorageServiceRequest::GetStorageServerSummary => {
+                    StorageServiceResponse::StorageServerSummary(mock_storage_summary(200))
+                }
+                StorageServiceRequest::GetServerProtocolVersion => {
+                    StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                        protocol_version: EXTENDED_PROTOCOL_VERSION,
+                    })
+                }
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            response_sender.send(Ok(response));
+        }
+    });
+
+    // Drive the peer's score below the ignore threshold
+    for _ in 0..10 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peer, ErrorType::Malicious);
+    }
+    assert!(client.get_peer_states()[&peer].is_ignored);
+
+    // Advance time (one poll interval at a time) until the peer recovers
+    tokio::task::yield_now().await;
+    for num_polls in 1..=100 {
+        mock_time.advance_async(summary_poll_interval).await;
+        if !client.get_peer_states()[&peer].is_ignored {
+            return num_polls;
+        }
+    }
+    panic!("The banned peer never recovered!");
+}
+
+#[tokio::test]
+async fn cache_hit_rate_is_reported() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Verify the hit rate is zero before any requests are made
+    assert_eq!(client.cache_hit_rate(), 0.0);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all epoch requests
+    let validator = ValidatorSigner::random(None);
+    let epoch_ending_ledger_infos: Vec<_> = (0..=10)
+        .map(|epoch| mock_epoch_ending_ledger_info(epoch, &validator))
+        .collect();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            let request = match request {
+                StorageServiceRequest::GetEpochEndingLedgerInfos(request) => request,
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            let ledger_infos = epoch_ending_ledger_infos
+                [request.start_epoch as usize..=request.expected_end_epoch as usize]
+                .to_vec();
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                EpochChangeProof::new(ledger_infos, false),
+            )));
+        }
+    });
+
+    // Fetch the ledger infos once from the network and three times from the cache
+    for _ in 0..4 {
+        client.get_epoch_ending_ledger_infos(0, 5).await.unwrap();
+    }
+    assert_eq!(client.cache_hit_rate(), 0.75);
+
+    // Fetch different ledger infos from the network and verify the hit rate
+    client.get_epoch_ending_ledger_infos(0, 6).await.unwrap();
+    assert_eq!(client.cache_hit_rate(), 0.6);
+
+    // Reset the hit rate and verify only the subsequent requests are counted
+    client.reset_cache_hit_rate();
+    assert_eq!(client.cache_hit_rate(), 0.0);
+    client.get_epoch_ending_ledger_infos(0, 6).await.unwrap();
+    assert_eq!(client.cache_hit_rate(), 1.0);
+}
+
+#[tokio::test]
+async fn proof_errors_are_penalized_more_than_internal_errors() {
+    ::aptos_logger::Logger::init_for_testing();
+
+    // Verify fewer proof errors than internal errors are required to ignore a peer
+    let num_internal_errors = num_errors_until_peer_is_ignored(false).await;
+    let num_proof_errors = num_errors_until_peer_is_ignored(true).await;
+    assert!(num_proof_errors < num_internal_errors);
+}
+
+/// Sends requests to a single peer until the peer is ignored, and returns the
+/// number of requests sent. Each request either fails with an internal error,
+/// or receives a response that is reported as failing proof verification.
+async fn num_errors_until_peer_is_ignored(report_proof_errors: bool) -> u64 {
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer advertising transactions
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to all requests (or fails them)
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            if report_proof_errors {
+                response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                    TransactionListWithProof::new_empty(),
+                )));
+            } else {
+                response_sender.send(Err(StorageServiceError::InternalError("".to_string())));
+            }
+        }
+    });
+
+    // Send requests until the peer crosses the ignore threshold
+    for num_errors in 1..=100 {
+        let result = client.get_transactions_with_proof(200, 0, 200, false).await;
+        if report_proof_errors {
+            result
+                .unwrap()
+                .context
+                .response_callback
+                .notify_bad_response(crate::ResponseError::ProofVerificationError);
+        } else {
+            assert_matches!(result, Err(Error::UnexpectedErrorEncountered(_)));
+        }
+        if client.get_peer_states()[&peer].is_ignored {
+            return num_errors;
+        }
+    }
+    panic!("The peer was never ignored!");
+}
+
+#[tokio::test]
+async fn peer_summaries_are_refreshed_on_demand() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer and refresh its summary
+    let peer = mock_network.add_priority_peer();
+    let client_clone = client.clone();
+    let refresh_request =
+        tokio::spawn(async move { client_clone.refresh_peer_summary(peer).await });
+
+    // Fulfill the summary request (without advancing time)
+    let storage_summary = mock_storage_summary(200);
+    let (peer_id, _, request, response_sender) = mock_network.next_request().await.unwrap();
+    assert_eq!(peer_id, peer.peer_id());
+    assert_matches!(request, StorageServiceRequest::GetStorageServerSummary);
+    response_sender.send(Ok(StorageServiceResponse::StorageServerSummary(
+        storage_summary.clone(),
+    )));
+    mock_network
+        .handle_protocol_version_request(peer.peer_id())
+        .await;
+
+    // Verify the fetched summary is returned and reflected in the global summary
+    assert_eq!(refresh_request.await.unwrap().unwrap(), storage_summary);
+    let global_summary = client.get_global_data_summary();
+    assert!(global_summary
+        .advertised_data
+        .transactions
+        .contains(&CompleteDataRange::new(0, 200).unwrap()));
+}
+
+#[tokio::test]
+async fn transaction_streams_reject_chunks_with_different_proof_anchors() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        enforce_stream_proof_anchor: true,
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers advertising small transaction chunks
+    let mut storage_summary = mock_storage_summary(1000);
+    storage_summary.protocol_metadata.max_transaction_chunk_size = 100;
+    for _ in 0..2 {
+        let peer = mock_network.add_priority_peer();
+        client.update_summary(peer, storage_summary.clone());
+    }
+    client.update_global_summary_cache();
+
+    // Spawn a handler that records the requested chunks, and responds to the
+    // first request with a proof anchored at a different version.
+    let proof_version = 1000;
+    let (request_sender, mut request_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut anchored_proof_version = 100;
+        while let Some((peer, _, request, response_sender)) = mock_network.next_request().await {
+            let request = match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => request,
+                request => panic!("Unexpected request: {:?}", request),
+            };
+            request_sender
+                .unbounded_send((peer, request.start_version))
+                .unwrap();
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                mock_anchored_transaction_list(
+                    request.start_version,
+                    request.end_version,
+                    anchored_proof_version,
+                ),
+            )));
+            anchored_proof_version = request.proof_version;
+        }
+    });
+
+    // Verify the first chunk is rejected and re-fetched from the other peer
+    let transaction_stream = client.stream_transactions(0, 199, proof_version, false);
+    futures::pin_mut!(transaction_stream);
+    let response = transaction_stream.next().await.unwrap().unwrap();
+    assert_eq!(response.payload.first_transaction_version, Some(0));
+    let (rejected_peer, rejected_start_version) = request_receiver.next().await.unwrap();
+    let (refetch_peer, refetch_start_version) = request_receiver.next().await.unwrap();
+    assert_eq!(rejected_start_version, 0);
+    assert_eq!(refetch_start_version, 0);
+    assert_ne!(rejected_peer, refetch_peer);
+
+    // Verify the second chunk is accepted and the stream ends
+    let response = transaction_stream.next().await.unwrap().unwrap();
+    assert_eq!(response.payload.first_transaction_version, Some(100));
+    assert_eq!(request_receiver.next().await.unwrap().1, 100);
+    assert!(transaction_stream.next().await.is_none());
+}
+
+/// Creates a transaction list (from start to end versions, inclusive) with a
+/// range proof that has the siblings of an accumulator at the proof version
+fn mock_anchored_transaction_list(
+    start_version: Version,
+    end_version: Version,
+    proof_version: Version,
+) -> TransactionListWithProof {
+    let transactions = (start_version..=end_version)
+        .map(|sequence_number| mock_user_transaction(AccountAddress::random(), sequence_number))
+        .collect::<Vec<_>>();
+    let transaction_infos = transactions
+        .iter()
+        .map(|_| {
+            TransactionInfo::new(
+                HashValue::zero(),
+                HashValue::zero(),
+                HashValue::zero(),
+                0,
+                ExecutionStatus::Success,
+            )
+        })
+        .collect();
+
+    // The accumulator depth is the ceiling of log2(number of leaves)
+    let depth = (u64::BITS - proof_version.leading_zeros()) as usize;
+    let left_siblings = vec![HashValue::zero(); start_version.count_ones() as usize];
+    let right_siblings = vec![HashValue::zero(); depth - end_version.count_ones() as usize];
+    let proof = TransactionInfoListWithProof::new(
+        TransactionAccumulatorRangeProof::new(left_siblings, right_siblings),
+        transaction_infos,
+    );
+    TransactionListWithProof::new(transactions, None, Some(start_version), proof)
+}
+
+#[tokio::test]
+async fn cross_check_peers_are_diversified_across_ip_addresses() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            diversify_cross_check_peers: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add two peers behind the same IP and one peer behind a distinct IP
+    let shared_ip_address = NetworkAddress::from_str("/ip4/10.0.0.1/tcp/6180").unwrap();
+    let distinct_ip_address = NetworkAddress::from_str("/ip4/10.0.0.2/tcp/6180").unwrap();
+    let mut peers = vec![];
+    for address in vec![
+        shared_ip_address.clone(),
+        shared_ip_address,
+        distinct_ip_address,
+    ] {
+        let peer = PeerNetworkId::new(NetworkId::Validator, PeerId::random());
+        mock_network.add_peer_with_address(peer, address);
+        client.update_summary(peer, mock_storage_summary(200));
+        peers.push(peer);
+    }
+    client.update_global_summary_cache();
+    let distinct_ip_peer = peers[2];
+
+    // Score the shared IP peers above the distinct IP peer
+    for shared_ip_peer in &peers[0..2] {
+        record_success_and_get_gain(&client, *shared_ip_peer);
+    }
+    client
+        .peer_states
+        .write()
+        .update_score_error(distinct_ip_peer, ErrorType::NotUseful);
+
+    // Verify the distinct IP peer is chosen to cross-check a request (despite
+    // its lower score), along with one of the shared IP peers.
+    let request = StorageServiceRequest::GetTransactionsWithProof(TransactionsWithProofRequest {
+        proof_version: 200,
+        start_version: 0,
+        end_version: 200,
+        include_events: false,
+    });
+    let cross_check_peers = client.choose_cross_check_peers(&request, 2).unwrap();
+    assert_eq!(cross_check_peers.len(), 2);
+    assert!(cross_check_peers.contains(&distinct_ip_peer));
+    assert!(peers[0..2].contains(&cross_check_peers[0]));
+
+    // Verify the request can't be cross-checked by more peers than exist
+    assert_err!(client.choose_cross_check_peers(&request, 4));
+
+    // Respond to the cross-checked requests (with identical responses), and
+    // verify the cross-checked request succeeds.
+    tokio::spawn(async move {
+        while let Some((_, _, _, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        }
+    });
+    let response = client
+        .get_transactions_with_proof_cross_checked(200, 0, 200, false, 2)
+        .await
+        .unwrap();
+    assert_eq!(response.payload, TransactionListWithProof::new_empty());
+}
+
+#[tokio::test]
+async fn rpc_latencies_are_recorded_by_request_type_and_network() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, client, _) = MockNetwork::new();
+    let peer = mock_network.add_priority_peer();
+
+    // Record the latency histogram before the request is sent
+    let request = StorageServiceRequest::GetNumberOfAccountsAtVersion(100);
+    let rpc_latencies = metrics::RPC_LATENCIES
+        .with_label_values(&[request.get_label(), peer.network_id().as_str()]);
+    let old_sample_count = rpc_latencies.get_sample_count();
+    let old_sample_sum = rpc_latencies.get_sample_sum();
+
+    // Serve the request after advancing the mock time
+    let client_clone = client.clone();
+    let request_handle =
+        tokio::spawn(async move { client_clone.send_request_to_peer(peer, request).await });
+    let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+    mock_time.advance_async(Duration::from_millis(1_500)).await;
+    response_sender.send(Ok(StorageServiceResponse::NumberOfAccountsAtVersion(10)));
+    request_handle.await.unwrap().unwrap();
+
+    // Verify the recorded latency matches the time advance
+    assert_eq!(rpc_latencies.get_sample_count(), old_sample_count + 1);
+    assert!((rpc_latencies.get_sample_sum() - old_sample_sum - 1.5).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn banned_peers_must_pass_a_data_probe_to_recover() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_scoring: PeerScoringConfig {
+            require_data_probe_for_recovery: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, client, poller) =
+        MockNetwork::new_with_config(data_client_config);
+    let peer = mock_network.add_priority_peer();
+
+    // Create a trusted ledger info (at version 0) and a transaction that is
+    // proven against it (i.e., the only leaf of the transaction accumulator).
+    let transaction = mock_user_transaction(AccountAddress::random(), 0);
+    let transaction_info = TransactionInfo::new(
+        transaction.hash(),
+        HashValue::zero(),
+        HashValue::zero(),
+        0,
+        ExecutionStatus::Success,
+    );
+    let trusted_ledger_info = LedgerInfoWithSignatures::new(
+        LedgerInfo::new(
+            BlockInfo::new(0, 0, HashValue::zero(), transaction_info.hash(), 0, 0, None),
+            HashValue::zero(),
+        ),
+        BTreeMap::new(),
+    );
+    let valid_transaction_list = TransactionListWithProof::new(
+        vec![transaction],
+        None,
+        Some(0),
+        TransactionInfoListWithProof::new(
+            TransactionAccumulatorRangeProof::new(vec![], vec![]),
+            vec![transaction_info],
+        ),
+    );
+    client.set_trusted_ledger_info_source(Arc::new(StaticTrustedLedgerInfoSource {
+        ledger_info: trusted_ledger_info,
+    }));
+
+    // Spawn a handler that answers all summary polls, but only passes the
+    // data probes once allowed (and counts the probes). Until then, the
+    // probes are answered with a single (unproven) transaction.
+    let num_probes = Arc::new(AtomicU64::new(0));
+    let probes_pass = Arc::new(AtomicBool::new(false));
+    let (num_probes_clone, probes_pass_clone) = (num_probes.clone(), probes_pass.clone());
+    tokio::spawn(poller.start_poller());
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            match request {
+                StorageServiceRequest::GetTransactionsWithProof(request) => {
+                    assert_eq!((request.start_version, request.proof_version), (0, 0));
+                    num_probes_clone.fetch_add(1, Ordering::Relaxed);
+                    let transaction_list = if probes_pass_clone.load(Ordering::Relaxed) {
+                        valid_transaction_list.clone()
+                    } else {
+                        mock_anchored_transaction_list(0, 0, 0)
+                    };
+                    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                        transaction_list,
+                    )))
+                }
+                StorageServiceRequest::GetStorageServerSummary => response_sender.send(Ok(
+                    StorageServiceResponse::StorageServerSummary(mock_storage_summary(200)),
+                )),
+                StorageServiceRequest::GetServerProtocolVersion => response_sender.send(Ok(
+                    StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                        protocol_version: EXTENDED_PROTOCOL_VERSION,
+                    }),
+                )),
+                _ => panic!("unexpected: {:?}", request),
+            }
+        }
+    });
+
+    // Advance time so the poller fetches the peer's summary, and ban the peer
+    tokio::task::yield_now().await;
+    let summary_poll_interval = Duration::from_millis(1_000);
+    mock_time.advance_async(summary_poll_interval).await;
+    for _ in 0..4 {
+        client
+            .peer_states
+            .write()
+            .update_score_error(peer, ErrorType::Malicious);
+    }
+    assert!(client.get_peer_states()[&peer].is_ignored);
+
+    // Verify the peer remains banned while it answers the summary polls (and
+    // its score recovers), but fails the data probes.
+    for _ in 0..20 {
+        mock_time.advance_async(summary_poll_interval).await;
+    }
+    assert!(num_probes.load(Ordering::Relaxed) > 0);
+    assert!(client.get_peer_states()[&peer].is_ignored);
+
+    // Verify the peer is un-banned once it passes a data probe
+    probes_pass.store(true, Ordering::Relaxed);
+    for _ in 0..20 {
+        mock_time.advance_async(summary_poll_interval).await;
+    }
+    assert!(!client.get_peer_states()[&peer].is_ignored);
+    client.update_global_summary_cache();
+    assert!(client
+        .get_global_data_summary()
+        .advertised_data
+        .transactions
+        .contains(&CompleteDataRange::new(0, 200).unwrap()));
+}
+
+#[tokio::test]
+async fn events_are_omitted_from_outputs() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add a peer that advertises fewer transaction outputs than transactions
+    // (and that supports event pruning).
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.transaction_outputs =
+        Some(CompleteDataRange::new(0, 100).unwrap());
+    client.update_summary(peer, storage_summary);
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that responds to output requests (and records them)
+    let (request_sender, mut request_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            response_sender.send(Ok(StorageServiceResponse::TransactionOutputsWithProof(
+                TransactionOutputListWithProof::new_empty(),
+            )));
+            request_sender.unbounded_send(request).unwrap();
+        }
+    });
+
+    // Verify the peer only omits the events if they're excluded
+    let outputs_request = TransactionOutputsWithProofRequest {
+        proof_version: 100,
+        start_version: 50,
+        end_version: 100,
+    };
+    client
+        .get_transaction_outputs_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionOutputsWithoutEvents(outputs_request.clone())
+    );
+    client
+        .get_transaction_outputs_with_proof(100, 50, 100, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionOutputsWithProof(outputs_request.clone())
+    );
+
+    // Update the peer so that it runs the base protocol version (i.e., it
+    // doesn't support event pruning)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the client falls back to the original request
+    client
+        .get_transaction_outputs_with_proof(100, 50, 100, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        request_receiver.next().await.unwrap(),
+        StorageServiceRequest::GetTransactionOutputsWithProof(outputs_request)
+    );
+
+    // Verify outputs that aren't advertised can't be fetched (even though
+    // the transactions are advertised).
+    let error = client
+        .get_transaction_outputs_with_proof(200, 150, 200, true)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataIsUnavailable(_));
+}
+
+#[tokio::test]
+async fn poll_interval_adapts_to_the_number_of_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            max_summary_poll_interval_ms: 1_000,
+            min_summary_poll_interval_ms: 200,
+            summary_poll_interval_per_peer_ms: 100,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, mock_time, _, poller) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a single peer and handle the first poll round
+    mock_network.add_priority_peer();
+    tokio::spawn(poller.start_poller());
+    tokio::task::yield_now().await;
+    mock_time.advance_async(Duration::from_millis(0)).await;
+    assert!(respond_to_summary_polls(&mut mock_network).await > 0);
+
+    // Verify each round fires after the interval computed (at the end of the
+    // previous round) for the number of connected peers: the min interval for
+    // 1 peer, 500 milliseconds for 5 peers and the max interval for 25 peers.
+    for (num_new_peers, expected_interval_ms) in [(4, 200), (20, 500), (0, 1_000)] {
+        mock_time
+            .advance_async(Duration::from_millis(expected_interval_ms - 1))
+            .await;
+        assert_eq!(respond_to_summary_polls(&mut mock_network).await, 0);
+        mock_time.advance_async(Duration::from_millis(1)).await;
+        for _ in 0..num_new_peers {
+            mock_network.add_priority_peer();
+        }
+        assert!(respond_to_summary_polls(&mut mock_network).await > 0);
+    }
+}
+
+/// Responds to all pending summary polls and protocol version requests
+/// (letting the poller send any further polls of the round) and returns the
+/// number of polls
+async fn respond_to_summary_polls(mock_network: &mut MockNetwork) -> u64 {
+    let mut num_polls = 0;
+    loop {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        match mock_network.next_request().now_or_never() {
+            Some(Some((_, _, request, response_sender))) => {
+                let response = match request {
+                    StorageServiceRequest::GetStorageServerSummary => {
+                        num_polls += 1;
+                        StorageServiceResponse::StorageServerSummary(mock_storage_summary(200))
+                    }
+                    StorageServiceRequest::GetServerProtocolVersion => {
+                        StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                            protocol_version: EXTENDED_PROTOCOL_VERSION,
+                        })
+                    }
+                    request => panic!("Unexpected request: {:?}", request),
+                };
+                response_sender.send(Ok(response));
+            }
+            _ => return num_polls,
+        }
+    }
+}
+
+#[tokio::test]
+async fn in_flight_requests_are_limited_per_peer() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        peer_selection: PeerSelectionConfig {
+            max_in_flight_per_peer: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising the data and saturate it with in-flight requests
+    let peer_1 = mock_network.add_priority_peer();
+    client.update_summary(peer_1, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    let mut request_handles = vec![];
+    let mut response_senders = vec![];
+    for start_version in [0, 10] {
+        request_handles.push(spawn_transactions_request(&client, start_version));
+        let (peer, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        assert_eq!(peer, peer_1);
+        response_senders.push(response_sender);
+    }
+
+    // Add another peer advertising the data and verify it receives the overflow
+    let peer_2 = mock_network.add_priority_peer();
+    client.update_summary(peer_2, mock_storage_summary(200));
+    client.update_global_summary_cache();
+    for start_version in [20, 30] {
+        request_handles.push(spawn_transactions_request(&client, start_version));
+        let (peer, _, _, response_sender) = mock_network.next_request().await.unwrap();
+        assert_eq!(peer, peer_2);
+        response_senders.push(response_sender);
+    }
+
+    // Verify a request waits while both peers are saturated
+    let waiting_request_handle = spawn_transactions_request(&client, 40);
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(mock_network.next_request().now_or_never().is_none());
+
+    // Complete the in-flight requests (both successfully and with errors)
+    for (index, response_sender) in response_senders.into_iter().enumerate() {
+        if index % 2 == 0 {
+            response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+                TransactionListWithProof::new_empty(),
+            )));
+        } else {
+            response_sender.send(Err(StorageServiceError::InternalError("".to_string())));
+        }
+    }
+    for (index, request_handle) in request_handles.into_iter().enumerate() {
+        assert_eq!(request_handle.await.unwrap().is_ok(), index % 2 == 0);
+    }
+
+    // Verify the released slots are used by the waiting request
+    let (_, _, _, response_sender) = mock_network.next_request().await.unwrap();
+    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        TransactionListWithProof::new_empty(),
+    )));
+    waiting_request_handle.await.unwrap().unwrap();
+}
+
+/// Spawns a transactions request (for 10 transactions at the given start
+/// version) and returns the handle of the request
+fn spawn_transactions_request(
+    client: &AptosNetDataClient,
+    start_version: Version,
+) -> tokio::task::JoinHandle<Result<Response<TransactionListWithProof>, Error>> {
+    let client = client.clone();
+    tokio::spawn(async move {
+        client
+            .get_transactions_with_proof(200, start_version, start_version + 9, false)
+            .await
+    })
+}
+
+#[tokio::test]
+async fn advertised_ranges_are_coalesced_in_the_global_summary() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add peers advertising touching and disjoint transaction ranges
+    for (lowest, highest) in [(100, 200), (0, 100), (202, 300)] {
+        let peer = mock_network.add_priority_peer();
+        let mut storage_summary = mock_storage_summary(highest);
+        storage_summary.data_summary.transactions =
+            Some(CompleteDataRange::new(lowest, highest).unwrap());
+        client.update_summary(peer, storage_summary);
+    }
+    client.update_global_summary_cache();
+
+    // Verify the touching ranges are coalesced (but the disjoint range isn't)
+    let global_summary = client.get_global_data_summary();
+    assert_eq!(
+        global_summary.advertised_data.transactions,
+        vec![
+            CompleteDataRange::new(0, 200).unwrap(),
+            CompleteDataRange::new(202, 300).unwrap(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn highest_synced_version_is_tracked_across_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Verify there's no highest synced version before any peer advertises
+    let peer_1 = mock_network.add_priority_peer();
+    assert_eq!(client.highest_synced_version(), None);
+
+    // Add peers advertising different synced versions and verify the highest
+    client.update_summary(peer_1, mock_storage_summary(100));
+    let peer_2 = mock_network.add_priority_peer();
+    client.update_summary(peer_2, mock_storage_summary(200));
+    assert_eq!(client.highest_synced_version(), Some(200));
+
+    // Verify the highest synced version updates after a new advertisement
+    client.update_summary(peer_1, mock_storage_summary(300));
+    assert_eq!(client.highest_synced_version(), Some(300));
+}
+
+#[tokio::test]
+async fn stopped_poller_sends_no_summary_requests() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, mock_time, _, poller) = MockNetwork::new();
+    mock_network.add_priority_peer();
+
+    // Start the poller and verify the peer is polled
+    let mut poller_handle = PollerHandle::new(poller);
+    poller_handle.start();
+    assert!(poller_handle.is_running());
+    let poll_interval =
+        Duration::from_millis(AptosDataClientConfig::default().summary_poll_interval_ms);
+    tokio::task::yield_now().await;
+    mock_time.advance_async(poll_interval).await;
+    assert!(respond_to_summary_polls(&mut mock_network).await > 0);
+
+    // Stop the poller and verify the peer is no longer polled
+    poller_handle.stop();
+    assert!(!poller_handle.is_running());
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    for _ in 0..10 {
+        mock_time.advance_async(poll_interval).await;
+        assert_eq!(respond_to_summary_polls(&mut mock_network).await, 0);
+    }
+
+    // Restart the poller and verify the peer is polled again
+    poller_handle.start();
+    assert!(poller_handle.is_running());
+    tokio::task::yield_now().await;
+    mock_time.advance_async(poll_interval).await;
+    assert!(respond_to_summary_polls(&mut mock_network).await > 0);
+}
+
+#[tokio::test]
+async fn inconsistent_summaries_are_dropped() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        summary_poller: SummaryPollerConfig {
+            reject_inconsistent_summaries: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer with a consistent summary
+    let consistent_peer = mock_network.add_priority_peer();
+    client.update_summary(consistent_peer, mock_storage_summary(100));
+    let consistent_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&consistent_peer)
+        .unwrap();
+
+    // Add a peer that advertises a synced version beyond its transactions
+    let inconsistent_peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.transactions = Some(CompleteDataRange::new(0, 100).unwrap());
+    client.update_summary(inconsistent_peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Verify the inconsistent summary is excluded from the global summary
+    let advertised_data = client.get_global_data_summary().advertised_data;
+    assert_eq!(
+        advertised_data.transactions,
+        vec![CompleteDataRange::new(0, 100).unwrap()]
+    );
+    assert_eq!(
+        advertised_data.synced_ledger_infos,
+        vec![mock_ledger_info(100)]
+    );
+
+    // Verify the inconsistent peer was penalized
+    let inconsistent_score = client
+        .peer_states
+        .read()
+        .get_peer_score(&inconsistent_peer)
+        .unwrap();
+    assert!(inconsistent_score < consistent_score);
+}
+
+#[tokio::test]
+async fn compression_falls_back_for_unsupported_peers() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        preferred_compression_algorithms: vec![CompressionAlgo::Zstd],
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer that supports compression
+    let peer = mock_network.add_priority_peer();
+    client.update_summary(peer, mock_storage_summary(200));
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, EXTENDED_PROTOCOL_VERSION);
+    client.update_global_summary_cache();
+
+    // Send a request and respond with a compressed transaction list
+    let transaction_list = mock_anchored_transaction_list(0, 9, 200);
+    let request_handle = spawn_transactions_request(&client, 0);
+    let (_, _, request, response_sender) = mock_network.next_request().await.unwrap();
+    assert_matches!(request, StorageServiceRequest::GetCompressedResponse(_));
+    let response = StorageServiceResponse::TransactionsWithProof(transaction_list.clone());
+    let compressed_response =
+        CompressedResponse::compress(&response, CompressionAlgo::Zstd).unwrap();
+    response_sender.send(Ok(StorageServiceResponse::CompressedResponse(
+        compressed_response,
+    )));
+
+    // Verify the client yields the original transaction list
+    let response = request_handle.await.unwrap().unwrap();
+    assert_eq!(response.payload, transaction_list);
+
+    // Update the peer so that it runs the base protocol version (i.e., it no
+    // longer supports compression)
+    client
+        .peer_states
+        .write()
+        .update_server_protocol_version(peer, BASE_PROTOCOL_VERSION);
+
+    // Verify the client falls back to an uncompressed request
+    let request_handle = spawn_transactions_request(&client, 0);
+    let (_, _, request, response_sender) = mock_network.next_request().await.unwrap();
+    assert_matches!(request, StorageServiceRequest::GetTransactionsWithProof(_));
+    response_sender.send(Ok(StorageServiceResponse::TransactionsWithProof(
+        transaction_list.clone(),
+    )));
+    let response = request_handle.await.unwrap().unwrap();
+    assert_eq!(response.payload, transaction_list);
+}
+
+#[tokio::test]
+async fn good_responses_speed_up_peer_recovery() {
+    ::aptos_logger::Logger::init_for_testing();
+    let (mut mock_network, _, client, _) = MockNetwork::new();
+
+    // Add two peers and ban both of them
+    let rewarded_peer = mock_network.add_priority_peer();
+    let polled_peer = mock_network.add_priority_peer();
+    for peer in [rewarded_peer, polled_peer] {
+        client.update_summary(peer, mock_storage_summary(200));
+        for _ in 0..10 {
+            client
+                .peer_states
+                .write()
+                .update_score_error(peer, ErrorType::Malicious);
+        }
+        assert!(client.get_peer_states()[&peer].is_ignored);
+    }
+
+    // Create a response served by the rewarded peer
+    let response = client.create_response(
+        rewarded_peer,
+        StorageServiceRequest::GetStorageServerSummary,
+        (),
+        &RequestOptions::default(),
+    );
+
+    // Report good responses for the rewarded peer, while the polled peer only
+    // recovers from successful polls, and record when each peer is un-banned.
+    let mut recovery_rounds = HashMap::new();
+    for round in 0..100 {
+        response
+            .context
+            .response_callback
+            .notify_good_response(ResponseQuality::Normal);
+        record_success_and_get_gain(&client, polled_peer);
+        for peer in [rewarded_peer, polled_peer] {
+            if !client.get_peer_states()[&peer].is_ignored {
+                recovery_rounds.entry(peer).or_insert(round);
+            }
+        }
+    }
+
+    // Verify the rewarded peer recovered faster than the polled peer
+    assert!(recovery_rounds[&rewarded_peer] < recovery_rounds[&polled_peer]);
+}
+
+#[tokio::test]
+async fn corrupted_cache_entries_are_refetched() {
+    ::aptos_logger::Logger::init_for_testing();
+    let data_client_config = AptosDataClientConfig {
+        caching: ResponseCachingConfig {
+            max_epoch_ending_cache_entries: 10,
+            verify_cache_integrity: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mut mock_network, _, client, _) = MockNetwork::new_with_config(data_client_config);
+
+    // Add a peer advertising epoch ending ledger infos
+    let peer = mock_network.add_priority_peer();
+    let mut storage_summary = mock_storage_summary(200);
+    storage_summary.data_summary.epoch_ending_ledger_infos =
+        Some(CompleteDataRange::new(0, 10).unwrap());
+    client.update_summary(peer, storage_summary);
+    client.update_global_summary_cache();
+
+    // Spawn a handler that counts and responds to all epoch requests
+    let epoch_ending_ledger_info = mock_epoch_ending_ledger_info(0, &ValidatorSigner::random(None));
+    let epoch_ending_ledger_info_clone = epoch_ending_ledger_info.clone();
+    let num_requests = Arc::new(AtomicU64::new(0));
+    let num_requests_clone = num_requests.clone();
+    tokio::spawn(async move {
+        while let Some((_, _, request, response_sender)) = mock_network.next_request().await {
+            assert_matches!(request, StorageServiceRequest::GetEpochEndingLedgerInfos(_));
+            num_requests_clone.fetch_add(1, Ordering::SeqCst);
+
+            let epoch_change_proof =
+                EpochChangeProof::new(vec![epoch_ending_ledger_info_clone.clone()], false);
+            response_sender.send(Ok(StorageServiceResponse::EpochEndingLedgerInfos(
+                epoch_change_proof,
+            )));
+        }
+    });
+
+    // Fetch the ledger infos twice and verify the second fetch is served from the cache
+    for _ in 0..2 {
+        let response = client.get_epoch_ending_ledger_infos(0, 0).await.unwrap();
+        assert_eq!(response.payload, vec![epoch_ending_ledger_info.clone()]);
+    }
+    assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+
+    // Corrupt the cached ledger infos
+    client
+        .epoch_ending_ledger_info_cache
+        .lock()
+        .get_mut_unchecked(&(0, 0))
+        .unwrap()
+        .ledger_infos = vec![mock_ledger_info(100)];
+
+    // Verify the corruption is detected and the ledger infos are fetched again
+    let response = client.get_epoch_ending_ledger_infos(0, 0).await.unwrap();
+    assert_eq!(response.payload, vec![epoch_ending_ledger_info.clone()]);
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+
+    // Verify the fresh ledger infos are cached again
+    let response = client.get_epoch_ending_ledger_infos(0, 0).await.unwrap();
+    assert_eq!(response.payload, vec![epoch_ending_ledger_info]);
+    assert_eq!(num_requests.load(Ordering::SeqCst), 2);
 }
@@ -0,0 +1,544 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    aptosnet::logging::{LogEntry, LogEvent, LogSchema},
+    Error, ResponseError, VerificationLevel,
+};
+use aptos_logger::prelude::*;
+use aptos_types::{
+    account_address::AccountAddress,
+    epoch_change::Verifier,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    proof::TransactionAccumulatorProof,
+    transaction::{Transaction, TransactionListWithProof, TransactionWithProof, Version},
+};
+use move_core_types::language_storage::TypeTag;
+use std::{cmp::min, time::Duration};
+use storage_service_types::{Epoch, SizeLimitedTransactions, StorageServerSummary};
+use thiserror::Error;
+
+// Useful constants for client-side verification
+const SKIPPED_VERIFICATION_LOG_FREQ_SECS: u64 = 5;
+
+/// An error encountered when verifying a response on the client-side
+#[derive(Clone, Debug, Error)]
+pub(crate) enum VerificationError {
+    #[error("Invalid response structure: {0}")]
+    InvalidStructure(String),
+    #[error("Invalid response signatures: {0}")]
+    InvalidSignatures(String),
+    #[error("Invalid response proof: {0}")]
+    InvalidProof(String),
+}
+
+impl VerificationError {
+    /// Returns the response error to report for the peer that sent the response
+    pub fn get_response_error(&self) -> ResponseError {
+        match self {
+            VerificationError::InvalidStructure(_) => ResponseError::InvalidData,
+            VerificationError::InvalidSignatures(_) | VerificationError::InvalidProof(_) => {
+                ResponseError::ProofVerificationError
+            }
+        }
+    }
+}
+
+impl From<VerificationError> for Error {
+    fn from(error: VerificationError) -> Self {
+        Error::InvalidResponse(error.to_string())
+    }
+}
+
+/// Verifies the given epoch ending ledger infos according to the verification
+/// level. Note: the first ledger info can only be cryptographically verified
+/// using a trusted state, so this remains the responsibility of the caller.
+pub(crate) fn verify_epoch_ending_ledger_infos(
+    start_epoch: Epoch,
+    expected_end_epoch: Epoch,
+    ledger_infos: &[LedgerInfoWithSignatures],
+    verification_level: VerificationLevel,
+) -> Result<(), VerificationError> {
+    match verification_level {
+        VerificationLevel::Full => {
+            verify_epoch_ending_structure(start_epoch, expected_end_epoch, ledger_infos)?;
+            verify_epoch_ending_signatures(ledger_infos)
+        }
+        VerificationLevel::StructuralOnly => {
+            verify_epoch_ending_structure(start_epoch, expected_end_epoch, ledger_infos)
+        }
+        VerificationLevel::None => {
+            sample!(
+                SampleRate::Duration(Duration::from_secs(SKIPPED_VERIFICATION_LOG_FREQ_SECS)),
+                debug!(
+                    (LogSchema::new(LogEntry::StorageServiceResponse)
+                        .event(LogEvent::VerificationSkipped)
+                        .message("Skipping verification of epoch ending ledger infos!"))
+                );
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Verifies that the ledger infos are non-empty, that each ledger info ends
+/// an epoch, and that the epochs are contiguous (starting at the start epoch
+/// and not exceeding the expected end epoch).
+fn verify_epoch_ending_structure(
+    start_epoch: Epoch,
+    expected_end_epoch: Epoch,
+    ledger_infos: &[LedgerInfoWithSignatures],
+) -> Result<(), VerificationError> {
+    if ledger_infos.is_empty() {
+        return Err(VerificationError::InvalidStructure(
+            "No epoch ending ledger infos were returned!".into(),
+        ));
+    }
+
+    for (expected_epoch, ledger_info) in (start_epoch..).zip(ledger_infos) {
+        let ledger_info = ledger_info.ledger_info();
+        if ledger_info.epoch() != expected_epoch || expected_epoch > expected_end_epoch {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Unexpected ledger info epoch: {:?}, expected: {:?} (end epoch: {:?})",
+                ledger_info.epoch(),
+                expected_epoch,
+                expected_end_epoch
+            )));
+        }
+        if !ledger_info.ends_epoch() {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Ledger info does not end the epoch: {:?}",
+                expected_epoch
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies that each ledger info (after the first) is signed by the
+/// validator set of the previous epoch.
+fn verify_epoch_ending_signatures(
+    ledger_infos: &[LedgerInfoWithSignatures],
+) -> Result<(), VerificationError> {
+    for ledger_infos in ledger_infos.windows(2) {
+        verify_signed_by_next_epoch(&ledger_infos[0], &ledger_infos[1])?;
+    }
+
+    Ok(())
+}
+
+/// Verifies that the ledger info is signed by the validator set of the next
+/// epoch, as announced by the given epoch ending ledger info.
+fn verify_signed_by_next_epoch(
+    epoch_ending_ledger_info: &LedgerInfoWithSignatures,
+    ledger_info: &LedgerInfoWithSignatures,
+) -> Result<(), VerificationError> {
+    let epoch_state = epoch_ending_ledger_info
+        .ledger_info()
+        .next_epoch_state()
+        .ok_or_else(|| {
+            VerificationError::InvalidStructure("Missing the next epoch state!".into())
+        })?;
+    epoch_state
+        .verify(ledger_info)
+        .map_err(|error| VerificationError::InvalidSignatures(error.to_string()))
+}
+
+/// Verifies that the given epoch changes form a continuous chain from the
+/// trusted epoch state, i.e., that each epoch change ends a contiguous epoch
+/// (starting at the trusted epoch, and at or after the known version) and is
+/// signed by the validator set of the previous epoch (or the trusted validator
+/// set). Returns the epoch state that follows the last epoch change (or the
+/// trusted epoch state if there are no epoch changes).
+pub(crate) fn verify_epoch_change_continuity<'a>(
+    known_version: Version,
+    trusted_epoch_state: &'a EpochState,
+    epoch_changes: &'a [LedgerInfoWithSignatures],
+) -> Result<&'a EpochState, VerificationError> {
+    let (first_epoch_change, last_epoch_change) =
+        match (epoch_changes.first(), epoch_changes.last()) {
+            (Some(first_epoch_change), Some(last_epoch_change)) => {
+                (first_epoch_change, last_epoch_change)
+            }
+            _ => return Ok(trusted_epoch_state), // There are no epoch changes to verify
+        };
+
+    let first_epoch_version = first_epoch_change.ledger_info().version();
+    if first_epoch_version < known_version {
+        return Err(VerificationError::InvalidStructure(format!(
+            "The first epoch change (version: {:?}) is before the known version: {:?}",
+            first_epoch_version, known_version
+        )));
+    }
+    let last_epoch = last_epoch_change.ledger_info().epoch();
+    verify_epoch_ending_structure(trusted_epoch_state.epoch, last_epoch, epoch_changes)?;
+    trusted_epoch_state
+        .verify(first_epoch_change)
+        .map_err(|error| VerificationError::InvalidSignatures(error.to_string()))?;
+    verify_epoch_ending_signatures(epoch_changes)?;
+
+    last_epoch_change
+        .ledger_info()
+        .next_epoch_state()
+        .ok_or_else(|| VerificationError::InvalidStructure("Missing the next epoch state!".into()))
+}
+
+/// Verifies that the target ledger info is in the epoch of the given (verified)
+/// epoch state, and that it is signed by the validator set of that epoch.
+pub(crate) fn verify_target_ledger_info(
+    epoch_state: &EpochState,
+    target_ledger_info: &LedgerInfoWithSignatures,
+) -> Result<(), VerificationError> {
+    let target_epoch = target_ledger_info.ledger_info().epoch();
+    if target_epoch != epoch_state.epoch {
+        return Err(VerificationError::InvalidStructure(format!(
+            "The target epoch ({:?}) doesn't match the verified epoch: {:?}",
+            target_epoch, epoch_state.epoch
+        )));
+    }
+    epoch_state
+        .verify(target_ledger_info)
+        .map_err(|error| VerificationError::InvalidSignatures(error.to_string()))
+}
+
+/// Verifies that the ledger infos returned by a peer cover the requested
+/// epochs, up to the end of the peer's advertised epoch range. A response that
+/// stops at the advertised end is a legitimate truncation (e.g., because the
+/// peer is lagging), while a response that stops earlier is invalid.
+pub(crate) fn verify_epoch_ending_coverage(
+    start_epoch: Epoch,
+    expected_end_epoch: Epoch,
+    advertised_end_epoch: Option<Epoch>,
+    ledger_infos: &[LedgerInfoWithSignatures],
+) -> Result<(), VerificationError> {
+    let advertised_end_epoch = match advertised_end_epoch {
+        Some(advertised_end_epoch) => advertised_end_epoch,
+        None => return Ok(()), // We don't know what the peer can serve
+    };
+    let covered_end_epoch = min(expected_end_epoch, advertised_end_epoch);
+    if covered_end_epoch < start_epoch {
+        return Ok(());
+    }
+
+    let num_expected_ledger_infos = covered_end_epoch - start_epoch + 1;
+    if (ledger_infos.len() as u64) < num_expected_ledger_infos {
+        return Err(VerificationError::InvalidStructure(format!(
+            "Too few epoch ending ledger infos: {:?}, expected: {:?} (advertised end epoch: {:?})",
+            ledger_infos.len(),
+            num_expected_ledger_infos,
+            advertised_end_epoch
+        )));
+    }
+
+    Ok(())
+}
+
+/// Verifies that the ranges advertised in the storage summary are consistent
+/// with the advertised synced ledger info, i.e., the transactions and outputs
+/// end at the synced version, the account states don't exceed the synced
+/// version, and the epoch ending ledger infos don't exceed the synced epoch.
+/// Note: malformed ranges (e.g., where start > end) are already rejected when
+/// the summary is deserialized.
+pub(crate) fn verify_summary_consistency(
+    summary: &StorageServerSummary,
+) -> Result<(), VerificationError> {
+    let data_summary = &summary.data_summary;
+    let synced_ledger_info = match &data_summary.synced_ledger_info {
+        Some(synced_ledger_info) => synced_ledger_info.ledger_info(),
+        None => return Ok(()), // There's nothing to check the ranges against
+    };
+    let synced_version = synced_ledger_info.version();
+
+    for (data_name, advertised_range) in [
+        ("transactions", data_summary.transactions),
+        ("transaction outputs", data_summary.transaction_outputs),
+    ] {
+        if let Some(advertised_range) = advertised_range {
+            if advertised_range.highest() != synced_version {
+                return Err(VerificationError::InvalidStructure(format!(
+                    "The advertised {} end at version {:?}, but the synced version is {:?}!",
+                    data_name,
+                    advertised_range.highest(),
+                    synced_version
+                )));
+            }
+        }
+    }
+    if let Some(account_states) = data_summary.account_states {
+        if account_states.highest() > synced_version {
+            return Err(VerificationError::InvalidStructure(format!(
+                "The advertised account states end at version {:?}, after the synced version {:?}!",
+                account_states.highest(),
+                synced_version
+            )));
+        }
+    }
+    if let Some(epoch_ending_ledger_infos) = data_summary.epoch_ending_ledger_infos {
+        if epoch_ending_ledger_infos.highest() > synced_ledger_info.epoch() {
+            return Err(VerificationError::InvalidStructure(format!(
+                "The advertised epoch ending ledger infos end at epoch {:?}, after the synced epoch {:?}!",
+                epoch_ending_ledger_infos.highest(),
+                synced_ledger_info.epoch()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies the structure of the transaction inclusion proofs returned by a
+/// peer, i.e., that there is one proof per requested version and that each
+/// proof has the depth of the accumulator at the accumulator version. Note:
+/// the proofs can only be fully verified using the accumulator root hash at
+/// the accumulator version, so this remains the responsibility of the caller.
+pub(crate) fn verify_inclusion_proofs(
+    versions: &[Version],
+    accumulator_version: Version,
+    inclusion_proofs: &[TransactionAccumulatorProof],
+) -> Result<(), VerificationError> {
+    if inclusion_proofs.len() != versions.len() {
+        return Err(VerificationError::InvalidStructure(format!(
+            "Unexpected number of inclusion proofs: {:?}, expected: {:?}",
+            inclusion_proofs.len(),
+            versions.len()
+        )));
+    }
+
+    let expected_depth = get_accumulator_depth(accumulator_version);
+    for (version, inclusion_proof) in versions.iter().zip(inclusion_proofs) {
+        if inclusion_proof.siblings().len() != expected_depth {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Inclusion proof for version {:?} has {:?} siblings, expected: {:?}",
+                version,
+                inclusion_proof.siblings().len(),
+                expected_depth
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Verifies the response to a recovery probe, i.e., that it holds the single
+/// transaction at the version of the trusted ledger info, and that the
+/// transaction is proven against the trusted ledger info.
+pub(crate) fn verify_recovery_probe(
+    trusted_ledger_info: &LedgerInfo,
+    transaction_list_with_proof: &TransactionListWithProof,
+) -> Result<(), VerificationError> {
+    let num_transactions = transaction_list_with_proof.transactions.len();
+    if num_transactions != 1 {
+        return Err(VerificationError::InvalidStructure(format!(
+            "The recovery probe expected 1 transaction, but got {}!",
+            num_transactions
+        )));
+    }
+    transaction_list_with_proof
+        .verify(trusted_ledger_info, Some(trusted_ledger_info.version()))
+        .map_err(|error| VerificationError::InvalidProof(error.to_string()))
+}
+
+/// Verifies that the proof of the transactions returned by a peer is anchored
+/// at the given proof version, i.e., that the range proof has the siblings of
+/// a range in an accumulator at the proof version. Note: this only checks the
+/// structure of the proof (so proof versions with the same accumulator depth
+/// can't be distinguished). The proof must still be verified by the caller
+/// against the ledger info at the proof version.
+pub(crate) fn verify_transaction_proof_anchor(
+    proof_version: Version,
+    transaction_list_with_proof: &TransactionListWithProof,
+) -> Result<(), VerificationError> {
+    let num_transactions = transaction_list_with_proof.proof.transaction_infos.len() as u64;
+    let first_version = match transaction_list_with_proof.first_transaction_version {
+        Some(first_version) if num_transactions > 0 => first_version,
+        _ => return Ok(()), // Empty lists have no siblings to verify
+    };
+    let last_version = first_version.saturating_add(num_transactions - 1);
+    if last_version > proof_version {
+        return Err(VerificationError::InvalidProof(format!(
+            "The last transaction version ({:?}) is beyond the proof version: {:?}",
+            last_version, proof_version
+        )));
+    }
+
+    // The first leaf needs a left sibling at each level where it's a right
+    // child, and the last leaf needs a right sibling at each level where it's
+    // a left child (up to the depth of the accumulator).
+    let expected_left_siblings = first_version.count_ones() as usize;
+    let expected_right_siblings =
+        get_accumulator_depth(proof_version).saturating_sub(last_version.count_ones() as usize);
+    let range_proof = &transaction_list_with_proof
+        .proof
+        .ledger_info_to_transaction_infos_proof;
+    if range_proof.left_siblings().len() != expected_left_siblings
+        || range_proof.right_siblings().len() != expected_right_siblings
+    {
+        return Err(VerificationError::InvalidProof(format!(
+            "The range proof has {:?} left and {:?} right siblings, expected: {:?} and {:?} \
+            (for proof version {:?})",
+            range_proof.left_siblings().len(),
+            range_proof.right_siblings().len(),
+            expected_left_siblings,
+            expected_right_siblings,
+            proof_version
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the depth of the transaction accumulator at the given version,
+/// i.e., the ceiling of log2(number of leaves).
+fn get_accumulator_depth(accumulator_version: Version) -> usize {
+    let num_leaves = accumulator_version.saturating_add(1);
+    if num_leaves <= 1 {
+        0
+    } else {
+        (u64::BITS - (num_leaves - 1).leading_zeros()) as usize
+    }
+}
+
+/// Verifies the structure of the transactions returned by a peer for the gap
+/// between two trusted versions, i.e., that the transactions (and transaction
+/// infos) cover exactly the versions after the low version, up to (and
+/// including) the high version. Note: the proof must be verified by the caller
+/// (using the trusted ledger info at the high version).
+pub(crate) fn verify_transaction_gap_coverage(
+    trusted_low: Version,
+    trusted_high: Version,
+    transaction_list_with_proof: &TransactionListWithProof,
+) -> Result<(), VerificationError> {
+    let expected_first_version = trusted_low + 1;
+    if transaction_list_with_proof.first_transaction_version != Some(expected_first_version) {
+        return Err(VerificationError::InvalidStructure(format!(
+            "Unexpected first transaction version: {:?}, expected: {:?}",
+            transaction_list_with_proof.first_transaction_version, expected_first_version
+        )));
+    }
+
+    let expected_num_transactions = (trusted_high - trusted_low) as usize;
+    let num_transactions = transaction_list_with_proof.transactions.len();
+    let num_transaction_infos = transaction_list_with_proof.proof.transaction_infos.len();
+    if num_transactions != expected_num_transactions
+        || num_transaction_infos != expected_num_transactions
+    {
+        return Err(VerificationError::InvalidStructure(format!(
+            "Unexpected number of transactions: {:?} (with {:?} transaction infos), expected: {:?}",
+            num_transactions, num_transaction_infos, expected_num_transactions
+        )));
+    }
+
+    Ok(())
+}
+
+/// Verifies the structure of transactions returned by a peer with a size
+/// limit, i.e., that each transaction is within the size limit and that the
+/// returned and oversized versions together cover exactly the requested range.
+/// Note: the proof of each transaction must be verified by the caller.
+pub(crate) fn verify_size_limited_transactions(
+    start_version: Version,
+    end_version: Version,
+    max_transaction_bytes: usize,
+    size_limited_transactions: &SizeLimitedTransactions,
+) -> Result<(), VerificationError> {
+    for transaction_with_proof in &size_limited_transactions.transactions {
+        let transaction_bytes = bcs::serialized_size(&transaction_with_proof.transaction)
+            .map_err(|error| VerificationError::InvalidStructure(error.to_string()))?;
+        if transaction_bytes > max_transaction_bytes {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Transaction at version {:?} exceeds the size limit: {:?} bytes, limit: {:?}",
+                transaction_with_proof.version, transaction_bytes, max_transaction_bytes
+            )));
+        }
+    }
+
+    let mut versions = size_limited_transactions
+        .transactions
+        .iter()
+        .map(|transaction_with_proof| transaction_with_proof.version)
+        .chain(size_limited_transactions.oversized_versions.iter().copied())
+        .collect::<Vec<_>>();
+    versions.sort_unstable();
+    if !versions.iter().copied().eq(start_version..=end_version) {
+        return Err(VerificationError::InvalidStructure(format!(
+            "The returned versions don't cover the requested range: {:?} to {:?}",
+            start_version, end_version
+        )));
+    }
+
+    Ok(())
+}
+
+/// Verifies the structure of transactions pruned by a peer to those that
+/// emitted an event of the given type, i.e., that each transaction emitted an
+/// event of the type and that the versions are increasing and within the
+/// requested range. Note: the proof of each transaction must be verified by
+/// the caller (using a trusted ledger info).
+pub(crate) fn verify_transactions_with_event(
+    start_version: Version,
+    end_version: Version,
+    event_type: &TypeTag,
+    transactions_with_proof: &[TransactionWithProof],
+) -> Result<(), VerificationError> {
+    let mut lowest_expected_version = start_version;
+    for transaction_with_proof in transactions_with_proof {
+        let version = transaction_with_proof.version;
+        if version < lowest_expected_version || version > end_version {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Unexpected transaction version: {:?}, expected a version between {:?} and {:?}",
+                version, lowest_expected_version, end_version
+            )));
+        }
+        let emitted_event = transaction_with_proof
+            .events
+            .as_ref()
+            .map(|events| events.iter().any(|event| event.type_tag() == event_type))
+            .unwrap_or(false);
+        if !emitted_event {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Transaction at version {:?} didn't emit an event of type {:?}",
+                version, event_type
+            )));
+        }
+        lowest_expected_version = version.saturating_add(1);
+    }
+
+    Ok(())
+}
+
+/// Verifies the structure of transactions pruned by a peer to a single sender,
+/// i.e., that each transaction was sent by the sender and that the versions are
+/// increasing and within the requested range. Note: the proof of each
+/// transaction must be verified by the caller (using a trusted ledger info).
+pub(crate) fn verify_transactions_from_sender(
+    start_version: Version,
+    end_version: Version,
+    sender: AccountAddress,
+    transactions_with_proof: &[TransactionWithProof],
+) -> Result<(), VerificationError> {
+    let mut lowest_expected_version = start_version;
+    for transaction_with_proof in transactions_with_proof {
+        let version = transaction_with_proof.version;
+        if version < lowest_expected_version || version > end_version {
+            return Err(VerificationError::InvalidStructure(format!(
+                "Unexpected transaction version: {:?}, expected a version between {:?} and {:?}",
+                version, lowest_expected_version, end_version
+            )));
+        }
+        match &transaction_with_proof.transaction {
+            Transaction::UserTransaction(signed_transaction)
+                if signed_transaction.sender() == sender => {}
+            _ => {
+                return Err(VerificationError::InvalidStructure(format!(
+                    "Transaction at version {:?} was not sent by {:?}",
+                    version, sender
+                )));
+            }
+        }
+        lowest_expected_version = version.saturating_add(1);
+    }
+
+    Ok(())
+}
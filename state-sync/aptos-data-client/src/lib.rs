@@ -3,17 +3,25 @@
 
 #![forbid(unsafe_code)]
 
+use aptos_config::{config::PeerSelectionPolicy, network_id::PeerNetworkId};
 use aptos_types::{
+    account_address::AccountAddress,
     ledger_info::LedgerInfoWithSignatures,
     state_store::state_value::StateValueChunkWithProof,
-    transaction::{TransactionListWithProof, TransactionOutputListWithProof, Version},
+    transaction::{
+        Transaction, TransactionListWithProof, TransactionOutputListWithProof,
+        TransactionWithProof, Version,
+    },
 };
 use async_trait::async_trait;
 use itertools::Itertools;
+use move_core_types::language_storage::TypeTag;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::HashSet, fmt, time::Duration};
 use storage_service::UnexpectedResponseError;
-use storage_service_types::{self as storage_service, CompleteDataRange, Epoch};
+use storage_service_types::{
+    self as storage_service, CompleteDataRange, Epoch, SizeLimitedTransactions,
+};
 use thiserror::Error;
 
 pub type ResponseId = u64;
@@ -26,32 +34,119 @@ pub type Result<T, E = Error> = ::std::result::Result<T, E>;
 /// An error returned by the Aptos Data Client for failed API calls.
 #[derive(Clone, Debug, Deserialize, Error, PartialEq, Serialize)]
 pub enum Error {
+    #[error("The circuit breaker is open (after repeated failures) and requests aren't sent: {0}")]
+    CircuitOpen(String),
     #[error("The requested data is unavailable and cannot be found! Error: {0}")]
     DataIsUnavailable(String),
     #[error("The requested data is too large: {0}")]
     DataIsTooLarge(String),
+    #[error("No peer serves the requested data type: {0}")]
+    DataTypeUnavailable(String),
+    #[error("The client is in maintenance mode and isn't sending data requests")]
+    InMaintenance,
     #[error("Invalid request: {0}")]
-    InvalidRequest(String),
+    InvalidRequest(InvalidRequestReason),
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("The response is malformed (i.e., it can't be deserialized): {0}")]
+    MalformedResponse(String),
+    #[error("A network error was encountered: {0}")]
+    NetworkError(String),
+    #[error("The peer is overloaded: {0}")]
+    PeerIsOverloaded(String),
+    #[error("The request was cancelled while in-flight: {0}")]
+    RequestCancelled(String),
+    #[error("The response is too large: {0}")]
+    ResponseTooLarge(String),
     #[error("Timed out waiting for a response: {0}")]
     TimeoutWaitingForResponse(String),
     #[error("Unexpected error encountered: {0}")]
     UnexpectedErrorEncountered(String),
 }
 
+/// The reason a request was rejected by the client (before being sent). If a
+/// config guardrail rejected the request, the reason identifies the config
+/// field and its value (so that operators can easily adjust it).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum InvalidRequestReason {
+    /// The request exceeds the limit set by the config field
+    ExceedsConfigLimit {
+        config_field: String,
+        limit: u64,
+        requested: u64,
+    },
+    /// The config field has a value that doesn't allow the request
+    InvalidConfigValue { config_field: String, value: u64 },
+    /// The request arguments are invalid (e.g., an invalid proof version)
+    InvalidArguments(String),
+}
+
+impl InvalidRequestReason {
+    pub fn exceeds_config_limit(config_field: &str, limit: u64, requested: u64) -> Self {
+        Self::ExceedsConfigLimit {
+            config_field: config_field.into(),
+            limit,
+            requested,
+        }
+    }
+}
+
+impl fmt::Display for InvalidRequestReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ExceedsConfigLimit {
+                config_field,
+                limit,
+                requested,
+            } => write!(
+                f,
+                "The request exceeds the config limit: {} = {}, requested = {}",
+                config_field, limit, requested
+            ),
+            Self::InvalidConfigValue {
+                config_field,
+                value,
+            } => write!(
+                f,
+                "The config value is invalid: {} = {}",
+                config_field, value
+            ),
+            Self::InvalidArguments(message) => write!(f, "{}", message),
+        }
+    }
+}
+
 impl Error {
     /// Returns a summary label for the error
     pub fn get_label(&self) -> &'static str {
         match self {
+            Self::CircuitOpen(_) => "circuit_open",
             Self::DataIsUnavailable(_) => "data_is_unavailable",
             Self::DataIsTooLarge(_) => "data_is_too_large",
+            Self::DataTypeUnavailable(_) => "data_type_unavailable",
+            Self::InMaintenance => "in_maintenance",
             Self::InvalidRequest(_) => "invalid_request",
             Self::InvalidResponse(_) => "invalid_response",
+            Self::MalformedResponse(_) => "malformed_response",
+            Self::NetworkError(_) => "network_error",
+            Self::PeerIsOverloaded(_) => "peer_is_overloaded",
+            Self::RequestCancelled(_) => "request_cancelled",
+            Self::ResponseTooLarge(_) => "response_too_large",
             Self::TimeoutWaitingForResponse(_) => "timeout_waiting_for_response",
             Self::UnexpectedErrorEncountered(_) => "unexpected_error_encountered",
         }
     }
+
+    /// Returns true iff the error may be transient (i.e., a timeout, a network
+    /// error or an overloaded peer), i.e., the request may succeed if it's
+    /// retried. Invalid requests and responses (e.g., responses that fail
+    /// verification) are never transient.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::NetworkError(_) | Self::PeerIsOverloaded(_) | Self::TimeoutWaitingForResponse(_)
+        )
+    }
 }
 
 // TODO(philiphayes): better error wrapping
@@ -94,13 +189,15 @@ pub trait AptosDataClient {
 
     /// Returns a transaction output list with proof object, with transaction
     /// outputs from start to end versions (inclusive). The proof is relative to
-    /// the specified `proof_version`. If the data cannot be fetched (e.g., the
+    /// the specified `proof_version`. If `include_events` is false, events are
+    /// omitted from the outputs. If the data cannot be fetched (e.g., the
     /// number of transaction outputs is too large), an error is returned.
     async fn get_transaction_outputs_with_proof(
         &self,
         proof_version: Version,
         start_version: Version,
         end_version: Version,
+        include_events: bool,
     ) -> Result<Response<TransactionOutputListWithProof>>;
 
     /// Returns a transaction list with proof object, with transactions from
@@ -117,6 +214,278 @@ pub trait AptosDataClient {
     ) -> Result<Response<TransactionListWithProof>>;
 }
 
+/// Per-request options that callers can use to alter how the Aptos Data
+/// Client handles a single request.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RequestOptions {
+    /// If true, any client-side caches are bypassed (including responses shared
+    /// by coalesced requests) and the data is always fetched from the network.
+    /// The fresh response may still update the cache.
+    pub bypass_cache: bool,
+    /// How strictly the client verifies responses before returning them.
+    /// Currently, this only applies to epoch ending ledger infos. By default,
+    /// responses are not verified (i.e., verification is left to the caller).
+    pub verification_level: VerificationLevel,
+    /// If set, only the transactions sent by this account are returned. Peers
+    /// that support sender filtering prune the transactions themselves,
+    /// otherwise the transactions are filtered by the client.
+    pub sender_filter: Option<AccountAddress>,
+    /// If true, archival (i.e., full-history) peers are strongly preferred
+    /// when selecting a peer for the request. Pruned peers are only used if
+    /// no archival peer can service the request.
+    pub prefer_archival: bool,
+    /// If true, the write sets are omitted from transaction outputs (e.g., for
+    /// consumers that only require events and execution statuses). Peers that
+    /// support write set pruning omit them, otherwise the client strips them.
+    pub omit_write_sets: bool,
+    /// The (externally created) span that the request belongs to, if any.
+    /// The client creates a child span for the request under this span (so
+    /// that requests can be correlated with the operations that made them).
+    pub trace_context: Option<TraceContext>,
+    /// If set, transactions larger than this (when serialized) are excluded.
+    /// Peers that support size limits exclude them (and report their
+    /// versions), otherwise the client flags the oversized transactions.
+    pub max_transaction_bytes: Option<usize>,
+    /// If set, this overrides the configured response timeout for the
+    /// request (e.g., for requests that legitimately take much longer).
+    pub timeout_override: Option<Duration>,
+    /// If set, this overrides the configured peer selection policy for the
+    /// request (e.g., latency sensitive requests may prefer the fastest peer).
+    pub selection_policy_override: Option<PeerSelectionPolicy>,
+    /// If set, requests that fail with a transient error are retried (with
+    /// exponential backoff), re-selecting the peer for each attempt.
+    pub retry_policy: Option<RetryPolicy>,
+    /// If set, only the transactions that emitted at least one event of this
+    /// type are returned (events are always included). Peers that support
+    /// event filtering prune the transactions themselves, otherwise the client
+    /// flags the matching transactions.
+    pub event_type_filter: Option<TypeTag>,
+    /// The peers that must not service the request, even if they advertise
+    /// the requested data (e.g., to isolate a suspected-bad peer).
+    pub excluded_peers: HashSet<PeerNetworkId>,
+    /// The priority of the request (e.g., latency sensitive requests at the
+    /// tip are high priority, while bulk backfill requests are low priority).
+    pub priority: RequestPriority,
+}
+
+impl RequestOptions {
+    /// Retries requests that fail with a transient error, for up to
+    /// `max_attempts` attempts in total. The backoff before each retry starts
+    /// at `base_backoff` and doubles with each retry.
+    pub fn with_retries(mut self, max_attempts: u64, base_backoff: Duration) -> Self {
+        self.retry_policy = Some(RetryPolicy {
+            max_attempts,
+            base_backoff,
+        });
+        self
+    }
+}
+
+/// The policy for retrying requests that fail with a transient error
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u64, // The max number of attempts (including the first attempt)
+    pub base_backoff: Duration, // The backoff before the first retry (doubled for each retry)
+}
+
+/// The context of a span in a trace, used to correlate data client requests
+/// with the (externally traced) operations that made them.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TraceContext {
+    pub trace_id: u64,               // The trace that the span belongs to
+    pub span_id: u64,                // The unique identifier of the span
+    pub parent_span_id: Option<u64>, // The parent of the span (None for root spans)
+}
+
+impl TraceContext {
+    /// Creates a root span (i.e., without a parent) in the given trace
+    pub fn new_root(trace_id: u64, span_id: u64) -> Self {
+        Self {
+            trace_id,
+            span_id,
+            parent_span_id: None,
+        }
+    }
+
+    /// Creates a child of this span (in the same trace) with the given span id
+    pub fn new_child(&self, span_id: u64) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id,
+            parent_span_id: Some(self.span_id),
+        }
+    }
+}
+
+/// The level of client-side verification performed on a response
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerificationLevel {
+    Full,           // Verifies the response structure and all signatures
+    StructuralOnly, // Verifies only the response structure (e.g., data ordering)
+    None,           // Performs no verification (verification is left to the caller)
+}
+
+impl Default for VerificationLevel {
+    fn default() -> Self {
+        VerificationLevel::None
+    }
+}
+
+/// The priority of a request
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RequestPriority {
+    High,   // Latency sensitive requests (e.g., when following the tip)
+    Normal, // Requests without specific latency requirements
+    Low,    // Bulk requests (e.g., when backfilling historical data)
+}
+
+impl Default for RequestPriority {
+    fn default() -> Self {
+        RequestPriority::Normal
+    }
+}
+
+/// The transactions returned for a request that may filter by sender
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FilteredTransactions {
+    /// No sender filter (or event type filter or size limit) was given, so the
+    /// full transaction list is returned
+    Unfiltered(TransactionListWithProof),
+    /// The transactions were pruned by the peer. Each transaction carries its
+    /// own proof, so the subset remains verifiable.
+    PrunedByPeer(Vec<TransactionWithProof>),
+    /// The transactions were filtered by the client (the peer doesn't support
+    /// sender filtering). The list proof no longer applies to the filtered
+    /// transactions, so they are unverified.
+    UnverifiedAfterFilter(Vec<(Version, Transaction)>),
+    /// The oversized transactions were excluded by the peer (and their
+    /// versions reported). Each transaction carries its own proof.
+    SizeLimitedByPeer(SizeLimitedTransactions),
+    /// The oversized transactions were flagged by the client (the peer doesn't
+    /// support size limits). The transaction list is left intact (so that the
+    /// list proof still applies), and consumers should skip the flagged versions.
+    FlaggedOversized {
+        transactions: TransactionListWithProof,
+        oversized_versions: Vec<Version>,
+    },
+    /// The transactions that emitted the event type were pruned by the peer.
+    /// Each transaction carries its own proof (and its events).
+    EventFilteredByPeer(Vec<TransactionWithProof>),
+    /// The transactions that emitted the event type were flagged by the client
+    /// (the peer doesn't support event filtering). The transaction list is left
+    /// intact (so that the list proof still applies), and consumers should only
+    /// read the flagged versions.
+    FlaggedEventMatches {
+        transactions: TransactionListWithProof,
+        matching_versions: Vec<Version>,
+    },
+}
+
+/// The high-level sync phase of the Aptos Data Client
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncPhase {
+    Bootstrapping, // The global summary is empty or no data was recently fetched successfully
+    Following,     // Data is actively and successfully being fetched from the network
+}
+
+/// The reason a peer would be excluded when selecting a peer for a request
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PeerExclusionReason {
+    NotExcluded,        // The peer would be considered for the request
+    NotConnected,       // The peer is not connected (or doesn't support the storage service)
+    Overloaded,         // The peer asked us not to send requests until later (i.e., backpressure)
+    MissingSummary,     // The peer hasn't advertised a storage summary yet
+    Ignored,            // The peer's score is too low (i.e., the peer is banned)
+    MissingCoverage,    // The peer doesn't advertise the requested data
+    PartialCoverage, // The peer only advertises the start of the requested data (and is only used if no other peer advertises all of it)
+    UnsupportedRequest, // The peer runs a storage server protocol version that doesn't support the request
+}
+
+/// The tier of a peer, i.e., whether the peer is high-priority (e.g., an
+/// upstream or hinted peer) or a regular peer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PeerTier {
+    Priority,
+    Regular,
+}
+
+/// A snapshot of the scoring state of a single peer. This is a copy of the
+/// internal state, so modifying it has no effect on the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeerState {
+    pub score: f64, // The current score (as adjusted by the scoring strategy, if any)
+    pub is_ignored: bool, // Whether the score is below the ignore threshold (i.e., the peer is banned)
+    pub advertised_version: Option<Version>, // The synced version of the last advertised summary (if any)
+}
+
+/// The outcomes of the requests sent to a set of peers
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OutcomeStats {
+    pub successes: u64, // The number of requests that received a response
+    pub failures: u64,  // The number of requests that failed (excluding timeouts)
+    pub timeouts: u64,  // The number of requests that timed out
+}
+
+/// The latency stats over a window of recent samples (all zero if there are
+/// no samples).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LatencyStats {
+    pub num_samples: u64, // The number of samples in the window
+    pub min: Duration,    // The lowest latency
+    pub max: Duration,    // The highest latency
+    pub mean: Duration,   // The mean latency
+    pub p99: Duration,    // The 99th percentile latency
+}
+
+/// The completion of a single request sent to a peer (i.e., a response or an
+/// error was received), as broadcast to the request event subscribers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestCompletionEvent {
+    pub peer: PeerNetworkId,        // The peer the request was sent to
+    pub request_type: &'static str, // The label of the request type
+    pub outcome: Result<()>,        // The error (if the request failed)
+    pub latency: Duration,          // The time from sending the request until it completed
+    pub response_bytes: u64,        // The size of the response on the wire (zero if it failed)
+}
+
+/// A transition of a watched range between serviceable (i.e., at least one
+/// non-ignored peer advertises the entire range) and unserviceable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServiceabilityEvent {
+    Serviceable(ServiceableReason), // The range became serviceable (for the given reason)
+    Unserviceable,                  // No non-ignored peer advertises the entire range anymore
+}
+
+/// The reason a watched range became serviceable, along with the peer that
+/// caused the transition.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServiceableReason {
+    NewPeer(PeerNetworkId), // A peer without a previous summary advertised the range
+    RangeExtended(PeerNetworkId), // A known peer extended its advertised range to cover the range
+    PeerRecovered(PeerNetworkId), // A banned peer advertising the range was restored
+}
+
+/// The estimated memory (in bytes) used by the internal caches and state of
+/// the client. The estimates are approximate (i.e., entry counts multiplied by
+/// per-entry sizes, plus the serialized sizes of any variable-length data).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheMemoryStats {
+    pub peer_summaries_bytes: u64, // The storage summaries advertised by peers
+    pub scoring_state_bytes: u64,  // The peer states, scores and polling queues
+    pub epoch_ending_cache_bytes: u64, // The cached epoch ending ledger infos
+    pub request_tracking_bytes: u64, // The coalesced requests, request rates and per-peer/IP tracking
+}
+
+impl CacheMemoryStats {
+    /// Returns the total estimated memory (in bytes)
+    pub fn total_bytes(&self) -> u64 {
+        self.peer_summaries_bytes
+            + self.scoring_state_bytes
+            + self.epoch_ending_cache_bytes
+            + self.request_tracking_bytes
+    }
+}
+
 /// A response error that users of the Aptos Data Client can use to notify
 /// the Data Client about invalid or malformed responses.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -126,6 +495,17 @@ pub enum ResponseError {
     ProofVerificationError,
 }
 
+/// The quality of a response that users of the Aptos Data Client have
+/// independently verified (e.g., to reward the peers that served it).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ResponseQuality {
+    /// The response was valid and useful
+    Normal,
+    /// The response was valid and exceptionally useful (e.g., it unblocked
+    /// progress that other peers couldn't)
+    Exceptional,
+}
+
 /// A callback that lets the consumer provide error feedback about a response.
 /// Typically, this will contain a reference to the underlying data client and
 /// any additional request context needed to update internal scoring.
@@ -136,12 +516,17 @@ pub enum ResponseError {
 ///
 /// This trait provides a simple feedback mechanism for users of the Data Client
 /// to alert it to bad responses so that the peers responsible for providing this
-/// data can be penalized.
+/// data can be penalized. Likewise, good responses can be reported so that the
+/// peers are rewarded (beyond the automatic reward for each response).
 pub trait ResponseCallback: fmt::Debug + Send + 'static {
     // TODO(philiphayes): ideally this would take a `self: Box<Self>`, i.e.,
     // consume the callback, which better communicates that you should only report
     // an error once. however, the current state-sync-v2 code makes this difficult...
     fn notify_bad_response(&self, error: ResponseError);
+
+    /// Notifies the data client that the response was independently verified
+    /// (with the given quality), so that the serving peer can be rewarded
+    fn notify_good_response(&self, quality: ResponseQuality);
 }
 
 #[derive(Debug)]
@@ -149,9 +534,15 @@ pub struct ResponseContext {
     /// A unique identifier for this request/response pair. Intended mostly for
     /// debugging.
     pub id: ResponseId,
+    /// The peer that served the response (if known). Note: cached and
+    /// coalesced responses carry the peer that originally served the data.
+    pub peer: Option<PeerNetworkId>,
     /// A callback for notifying the data-client source about an error with this
     /// response.
     pub response_callback: Box<dyn ResponseCallback>,
+    /// The span created for the request (if the request was made with a
+    /// trace context). This is a child of the caller-provided span.
+    pub trace_context: Option<TraceContext>,
 }
 
 /// A response from the Data Client for a single API call.
@@ -236,7 +627,7 @@ impl From<TransactionListWithProof> for ResponsePayload {
 }
 
 /// A snapshot of the global state of data available in the Aptos network.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct GlobalDataSummary {
     pub advertised_data: AdvertisedData,
     pub optimal_chunk_sizes: OptimalChunkSizes,
@@ -260,7 +651,7 @@ impl GlobalDataSummary {
 
 /// Holds the optimal chunk sizes that clients should use when
 /// requesting data. This makes the request *more likely* to succeed.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct OptimalChunkSizes {
     pub account_states_chunk_size: u64,
     pub epoch_chunk_size: u64,
@@ -280,7 +671,7 @@ impl OptimalChunkSizes {
 }
 
 /// A summary of all data that is currently advertised in the network.
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
 pub struct AdvertisedData {
     /// The ranges of account states advertised, e.g., if a range is
     /// (X,Y), it means all account states are held for every version X->Y
@@ -381,6 +772,55 @@ impl AdvertisedData {
         }
     }
 
+    /// Suggests a bootstrap target version from the synced ledger infos
+    /// advertised by peers. Each candidate version is scored by a weighted sum
+    /// of its freshness (i.e., its position between the lowest and highest
+    /// advertised versions) and its redundancy (i.e., the fraction of peers
+    /// that have synced to at least that version). The freshness weight is
+    /// clamped to [0, 1], where 1 favours the newest target and 0 favours the
+    /// most widely served target. Only versions served by at least
+    /// `min_peers` peers are considered, and ties favour the higher version.
+    pub fn suggest_bootstrap_target(
+        &self,
+        freshness_weight: f64,
+        min_peers: usize,
+    ) -> Option<Version> {
+        let freshness_weight = freshness_weight.clamp(0.0, 1.0);
+        let synced_versions = self
+            .synced_ledger_infos
+            .iter()
+            .map(|ledger_info_with_sigs| ledger_info_with_sigs.ledger_info().version())
+            .collect::<Vec<_>>();
+        let lowest_version = *synced_versions.iter().min()?;
+        let highest_version = *synced_versions.iter().max()?;
+
+        let mut best_target: Option<(f64, Version)> = None;
+        for &version in &synced_versions {
+            let num_peers = synced_versions
+                .iter()
+                .filter(|synced_version| **synced_version >= version)
+                .count();
+            if num_peers < min_peers {
+                continue;
+            }
+
+            let freshness = if highest_version == lowest_version {
+                1.0
+            } else {
+                (version - lowest_version) as f64 / (highest_version - lowest_version) as f64
+            };
+            let redundancy = num_peers as f64 / synced_versions.len() as f64;
+            let score = freshness_weight * freshness + (1.0 - freshness_weight) * redundancy;
+            let is_better = best_target.map_or(true, |(best_score, best_version)| {
+                score > best_score || (score == best_score && version > best_version)
+            });
+            if is_better {
+                best_target = Some((score, version));
+            }
+        }
+        best_target.map(|(_, version)| version)
+    }
+
     /// Returns the lowest advertised version containing all account states
     pub fn lowest_account_states_version(&self) -> Option<Version> {
         get_lowest_version_from_range_set(&self.account_states)
@@ -115,7 +115,7 @@ impl<T: AptosDataClient + Send + Clone + 'static> DataStream<T> {
         let data_stream_listener = DataStreamListener::new(notification_receiver);
 
         // Create a new stream engine
-        let stream_engine = StreamEngine::new(stream_request, advertised_data)?;
+        let stream_engine = StreamEngine::new(stream_request, advertised_data, &config)?;
 
         // Create a new data stream
         let data_stream = Self {
@@ -777,6 +777,7 @@ async fn get_transaction_outputs_with_proof<T: AptosDataClient + Send + Clone +
         request.proof_version,
         request.start_version,
         request.end_version,
+        true, // The outputs are applied to storage, so the events are required
     );
     client_response
         .await
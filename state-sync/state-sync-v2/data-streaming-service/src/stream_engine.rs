@@ -17,6 +17,7 @@ use crate::{
         Epoch, GetAllAccountsRequest, GetAllEpochEndingLedgerInfosRequest, StreamRequest,
     },
 };
+use aptos_config::config::DataStreamingServiceConfig;
 use aptos_data_client::{AdvertisedData, GlobalDataSummary, ResponsePayload};
 use aptos_id_generator::{IdGenerator, U64IdGenerator};
 use aptos_logger::prelude::*;
@@ -97,6 +98,7 @@ impl StreamEngine {
     pub fn new(
         stream_request: &StreamRequest,
         advertised_data: &AdvertisedData,
+        config: &DataStreamingServiceConfig,
     ) -> Result<Self, Error> {
         match stream_request {
             StreamRequest::ContinuouslyStreamTransactionOutputs(_) => {
@@ -111,12 +113,16 @@ impl StreamEngine {
             StreamRequest::GetAllEpochEndingLedgerInfos(request) => {
                 Ok(EpochEndingStreamEngine::new(request, advertised_data)?.into())
             }
-            StreamRequest::GetAllTransactionOutputs(_) => {
-                Ok(TransactionStreamEngine::new(stream_request)?.into())
-            }
-            StreamRequest::GetAllTransactions(_) => {
-                Ok(TransactionStreamEngine::new(stream_request)?.into())
-            }
+            StreamRequest::GetAllTransactionOutputs(_) => Ok(TransactionStreamEngine::new(
+                stream_request,
+                config.transaction_chunk_alignment,
+            )?
+            .into()),
+            StreamRequest::GetAllTransactions(_) => Ok(TransactionStreamEngine::new(
+                stream_request,
+                config.transaction_chunk_alignment,
+            )?
+            .into()),
             _ => Err(Error::UnsupportedRequestEncountered(format!(
                 "Stream request not supported: {:?}",
                 stream_request
@@ -207,6 +213,7 @@ impl DataStreamEngine for AccountsStreamEngine {
                 global_data_summary
                     .optimal_chunk_sizes
                     .account_states_chunk_size,
+                0,
                 self.clone().into(),
             )?;
             self.update_request_tracking(&client_requests)?;
@@ -598,6 +605,7 @@ impl DataStreamEngine for ContinuousTransactionStreamEngine {
             target_ledger_info_version,
             max_number_of_requests,
             optimal_chunk_sizes,
+            0,
             self.clone().into(),
         )?;
         self.update_request_tracking(&client_requests)?;
@@ -800,6 +808,7 @@ impl DataStreamEngine for EpochEndingStreamEngine {
             self.end_epoch,
             max_number_of_requests,
             global_data_summary.optimal_chunk_sizes.epoch_chunk_size,
+            0,
             self.clone().into(),
         )?;
         self.update_request_tracking(&client_requests)?;
@@ -874,22 +883,27 @@ pub struct TransactionStreamEngine {
 
     // True iff all data has been sent across the stream.
     pub stream_is_complete: bool,
+
+    // The modulus to which chunk boundaries are aligned (zero if unaligned)
+    pub chunk_alignment: u64,
 }
 
 impl TransactionStreamEngine {
-    fn new(stream_request: &StreamRequest) -> Result<Self, Error> {
+    fn new(stream_request: &StreamRequest, chunk_alignment: u64) -> Result<Self, Error> {
         match stream_request {
             StreamRequest::GetAllTransactions(request) => Ok(TransactionStreamEngine {
                 request: stream_request.clone(),
                 next_stream_version: request.start_version,
                 next_request_version: request.start_version,
                 stream_is_complete: false,
+                chunk_alignment,
             }),
             StreamRequest::GetAllTransactionOutputs(request) => Ok(TransactionStreamEngine {
                 request: stream_request.clone(),
                 next_stream_version: request.start_version,
                 next_request_version: request.start_version,
                 stream_is_complete: false,
+                chunk_alignment,
             }),
             request => invalid_stream_request!(request),
         }
@@ -987,6 +1001,7 @@ impl DataStreamEngine for TransactionStreamEngine {
             request_end_version,
             max_number_of_requests,
             optimal_chunk_sizes,
+            self.chunk_alignment,
             self.clone().into(),
         )?;
         self.update_request_tracking(&client_requests)?;
@@ -1074,12 +1089,14 @@ fn verify_client_request_indices(expected_next_index: u64, start_index: u64, end
     }
 }
 
-/// Creates a batch of data client requests for the given stream engine
+/// Creates a batch of data client requests for the given stream engine. If a
+/// chunk alignment is given, chunk boundaries are aligned to it.
 fn create_data_client_requests(
     start_index: u64,
     end_index: u64,
     max_number_of_requests: u64,
     optimal_chunk_size: u64,
+    chunk_alignment: u64,
     stream_engine: StreamEngine,
 ) -> Result<Vec<DataClientRequest>, Error> {
     if start_index > end_index {
@@ -1098,7 +1115,11 @@ fn create_data_client_requests(
     let mut next_index_to_request = start_index;
     while total_items_to_fetch > 0 && num_requests_made < max_number_of_requests {
         // Calculate the number of items to fetch in this request
-        let num_items_to_fetch = cmp::min(total_items_to_fetch, optimal_chunk_size);
+        let num_items_to_fetch = align_chunk_size(
+            next_index_to_request,
+            cmp::min(total_items_to_fetch, optimal_chunk_size),
+            chunk_alignment,
+        );
 
         // Calculate the start and end indices for the request
         let request_start_index = next_index_to_request;
@@ -1127,6 +1148,25 @@ fn create_data_client_requests(
     Ok(data_client_requests)
 }
 
+/// Shrinks the given chunk size (if possible) so that the chunk starting at
+/// the start index ends just before a multiple of the chunk alignment, i.e.,
+/// so that the next chunk starts at an aligned index. If no aligned boundary
+/// falls within the chunk (or alignment is disabled), the chunk is unchanged.
+/// Note: this never drops items, it only moves chunk boundaries.
+fn align_chunk_size(start_index: u64, chunk_size: u64, chunk_alignment: u64) -> u64 {
+    if chunk_alignment == 0 {
+        return chunk_size;
+    }
+
+    let chunk_end_exclusive = start_index.saturating_add(chunk_size);
+    let aligned_end_exclusive = chunk_end_exclusive - (chunk_end_exclusive % chunk_alignment);
+    if aligned_end_exclusive > start_index {
+        aligned_end_exclusive - start_index
+    } else {
+        chunk_size
+    }
+}
+
 /// Creates a data client request for the given stream engine using the
 /// specified start and end indices.
 fn create_data_client_request(
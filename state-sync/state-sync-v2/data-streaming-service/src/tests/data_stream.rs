@@ -55,7 +55,9 @@ async fn test_stream_blocked() {
             });
         let context = ResponseContext {
             id: 0,
+            peer: None,
             response_callback: Box::new(NoopResponseCallback),
+            trace_context: None,
         };
         let pending_response = PendingClientResponse {
             client_request: client_request.clone(),
@@ -222,7 +224,9 @@ async fn test_stream_invalid_response() {
     });
     let context = ResponseContext {
         id: 0,
+        peer: None,
         response_callback: Box::new(NoopResponseCallback),
+        trace_context: None,
     };
     let client_response = Response::new(context, ResponsePayload::NumberOfAccountStates(10));
     let pending_response = PendingClientResponse {
@@ -5,9 +5,12 @@ use crate::{
     data_notification::{DataClientRequest, EpochEndingLedgerInfosRequest},
     error::Error,
     stream_engine::{DataStreamEngine, EpochEndingStreamEngine, StreamEngine},
-    streaming_client::{GetAllEpochEndingLedgerInfosRequest, StreamRequest},
+    streaming_client::{
+        GetAllEpochEndingLedgerInfosRequest, GetAllTransactionsRequest, StreamRequest,
+    },
     tests::utils::initialize_logger,
 };
+use aptos_config::config::DataStreamingServiceConfig;
 use aptos_data_client::{GlobalDataSummary, OptimalChunkSizes, ResponsePayload};
 use aptos_id_generator::U64IdGenerator;
 use claim::{assert_matches, assert_ok};
@@ -140,7 +143,11 @@ fn test_epoch_ending_stream_engine() {
 
     // Try to create a stream engine where there is no advertised data
     // and verify an error is returned.
-    let result = StreamEngine::new(&stream_request, &GlobalDataSummary::empty().advertised_data);
+    let result = StreamEngine::new(
+        &stream_request,
+        &GlobalDataSummary::empty().advertised_data,
+        &DataStreamingServiceConfig::default(),
+    );
     assert_matches!(result, Err(Error::DataIsUnavailable(_)));
 
     // Create a data summary with various advertised epoch ranges (highest is one)
@@ -154,7 +161,11 @@ fn test_epoch_ending_stream_engine() {
     ];
 
     // Try to create a stream engine where the highest epoch is one
-    let result = StreamEngine::new(&stream_request, &global_data_summary.advertised_data);
+    let result = StreamEngine::new(
+        &stream_request,
+        &global_data_summary.advertised_data,
+        &DataStreamingServiceConfig::default(),
+    );
     assert_ok!(result);
 
     // Create a global data summary with non-zero advertised epoch ranges
@@ -169,7 +180,13 @@ fn test_epoch_ending_stream_engine() {
     ];
 
     // Create a new data stream engine and verify the highest epoch is chosen
-    match StreamEngine::new(&stream_request, &global_data_summary.advertised_data).unwrap() {
+    match StreamEngine::new(
+        &stream_request,
+        &global_data_summary.advertised_data,
+        &DataStreamingServiceConfig::default(),
+    )
+    .unwrap()
+    {
         StreamEngine::EpochEndingStreamEngine(stream_engine) => {
             assert_eq!(stream_engine.end_epoch, 1000);
         }
@@ -238,6 +255,69 @@ fn test_update_epoch_ending_stream_panic() {
         .unwrap();
 }
 
+#[test]
+fn test_create_aligned_transaction_requests() {
+    initialize_logger();
+
+    // Create a transaction stream engine with a chunk alignment of 1000
+    let (start_version, end_version) = (1234, 10_500);
+    let stream_request = StreamRequest::GetAllTransactions(GetAllTransactionsRequest {
+        start_version,
+        end_version,
+        proof_version: end_version,
+        include_events: false,
+    });
+    let config = DataStreamingServiceConfig {
+        transaction_chunk_alignment: 1000,
+        ..Default::default()
+    };
+    let mut stream_engine = match StreamEngine::new(
+        &stream_request,
+        &GlobalDataSummary::empty().advertised_data,
+        &config,
+    )
+    .unwrap()
+    {
+        StreamEngine::TransactionStreamEngine(stream_engine) => stream_engine,
+        unexpected_engine => {
+            panic!(
+                "Expected transaction stream engine but got {:?}",
+                unexpected_engine
+            );
+        }
+    };
+
+    // Create the client requests (using an unaligned chunk size)
+    let mut optimal_chunk_sizes = OptimalChunkSizes::empty();
+    optimal_chunk_sizes.transaction_chunk_size = 2500;
+    let mut global_data_summary = GlobalDataSummary::empty();
+    global_data_summary.optimal_chunk_sizes = optimal_chunk_sizes;
+    let client_requests = stream_engine
+        .create_data_client_requests(100, &global_data_summary)
+        .unwrap();
+
+    // Verify the requests cover the entire range (in order) and that all
+    // interior chunk boundaries are aligned.
+    let mut expected_start_version = start_version;
+    for client_request in client_requests.iter() {
+        match client_request {
+            DataClientRequest::TransactionsWithProof(request) => {
+                assert_eq!(request.start_version, expected_start_version);
+                if request.start_version != start_version {
+                    assert_eq!(request.start_version % 1000, 0);
+                }
+                if request.end_version != end_version {
+                    assert_eq!((request.end_version + 1) % 1000, 0);
+                }
+                assert!(request.end_version - request.start_version < 2500);
+                expected_start_version = request.end_version + 1;
+            }
+            request => panic!("Unexpected client request: {:?}", request),
+        }
+    }
+    assert_eq!(expected_start_version, end_version + 1);
+}
+
 fn create_epoch_ending_stream_engine(start_epoch: u64, end_epoch: u64) -> EpochEndingStreamEngine {
     initialize_logger();
 
@@ -254,7 +334,13 @@ fn create_epoch_ending_stream_engine(start_epoch: u64, end_epoch: u64) -> EpochE
         .epoch_ending_ledger_infos = vec![CompleteDataRange::new(start_epoch, end_epoch).unwrap()];
 
     // Create a new epoch ending stream engine
-    match StreamEngine::new(&stream_request, &global_data_summary.advertised_data).unwrap() {
+    match StreamEngine::new(
+        &stream_request,
+        &global_data_summary.advertised_data,
+        &DataStreamingServiceConfig::default(),
+    )
+    .unwrap()
+    {
         StreamEngine::EpochEndingStreamEngine(stream_engine) => stream_engine,
         unexpected_engine => {
             panic!(
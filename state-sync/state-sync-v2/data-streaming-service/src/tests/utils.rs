@@ -5,7 +5,7 @@ use crate::{data_notification::DataNotification, data_stream::DataStreamListener
 use aptos_crypto::{ed25519::Ed25519PrivateKey, HashValue, PrivateKey, SigningKey, Uniform};
 use aptos_data_client::{
     AdvertisedData, AptosDataClient, GlobalDataSummary, OptimalChunkSizes, Response,
-    ResponseCallback, ResponseContext, ResponseError,
+    ResponseCallback, ResponseContext, ResponseError, ResponseQuality,
 };
 use aptos_logger::Level;
 use aptos_types::{
@@ -177,6 +177,7 @@ impl AptosDataClient for MockAptosDataClient {
         _proof_version: Version,
         start_version: Version,
         end_version: Version,
+        _include_events: bool,
     ) -> Result<Response<TransactionOutputListWithProof>, aptos_data_client::Error> {
         self.emulate_network_latencies();
 
@@ -217,6 +218,8 @@ impl ResponseCallback for NoopResponseCallback {
     fn notify_bad_response(&self, _error: ResponseError) {
         // TODO(philiphayes): do something here
     }
+
+    fn notify_good_response(&self, _quality: ResponseQuality) {}
 }
 
 /// Creates a data client response using a specified payload and random id
@@ -225,7 +228,9 @@ pub fn create_data_client_response<T>(payload: T) -> Response<T> {
     let response_callback = Box::new(NoopResponseCallback);
     let context = ResponseContext {
         id,
+        peer: None,
         response_callback,
+        trace_context: None,
     };
     Response::new(context, payload)
 }
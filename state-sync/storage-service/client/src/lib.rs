@@ -13,9 +13,7 @@ use network::{
     },
     error::NetworkError,
     peer_manager::{ConnectionRequestSender, PeerManagerRequestSender},
-    protocols::network::{
-        AppConfig, ApplicationNetworkSender, NetworkSender, NewNetworkSender, RpcError,
-    },
+    protocols::network::{AppConfig, ApplicationNetworkSender, NewNetworkSender, RpcError},
     ProtocolId,
 };
 use std::{sync::Arc, time::Duration};
@@ -31,6 +29,9 @@ pub enum Error {
 
     #[error("Error from remote storage service: {0}")]
     StorageServiceError(#[from] StorageServiceError),
+
+    #[error("Response too large: {0}")]
+    ResponseTooLarge(String),
 }
 
 // TODO(philiphayes): need to expose access to somewhere to store per-peer data?
@@ -60,12 +61,33 @@ impl StorageServiceClient {
         request: StorageServiceRequest,
         timeout: Duration,
     ) -> Result<StorageServiceResponse, Error> {
-        let message = self
+        self.send_request_with_max_response_bytes(recipient, request, timeout, 0)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Sends the request and returns the response along with its size (in
+    /// bytes) on the wire. Responses larger than `max_response_bytes` are
+    /// rejected before they are deserialized (zero disables the limit).
+    pub async fn send_request_with_max_response_bytes(
+        &self,
+        recipient: PeerNetworkId,
+        request: StorageServiceRequest,
+        timeout: Duration,
+        max_response_bytes: u64,
+    ) -> Result<(StorageServiceResponse, u64), Error> {
+        let (message, response_bytes) = self
             .network_sender
-            .send_rpc(recipient, StorageServiceMessage::Request(request), timeout)
+            .sender(&recipient.network_id())
+            .send_rpc_with_max_response_bytes(
+                recipient.peer_id(),
+                StorageServiceMessage::Request(request),
+                timeout,
+                max_response_bytes,
+            )
             .await?;
         match message {
-            StorageServiceMessage::Response(Ok(response)) => Ok(response),
+            StorageServiceMessage::Response(Ok(response)) => Ok((response, response_bytes)),
             StorageServiceMessage::Response(Err(err)) => Err(Error::StorageServiceError(err)),
             StorageServiceMessage::Request(_) => Err(Error::RpcError(RpcError::InvalidRpcResponse)),
         }
@@ -108,17 +130,52 @@ pub fn network_endpoint_config() -> AppConfig {
 /// The Storage Service network sender for a single network.
 #[derive(Clone, Debug)]
 pub struct StorageServiceNetworkSender {
-    inner: NetworkSender<StorageServiceMessage>,
+    peer_mgr_reqs_tx: PeerManagerRequestSender,
+}
+
+impl StorageServiceNetworkSender {
+    /// Sends the rpc to the recipient and returns the response along with its
+    /// size (in bytes) on the wire. Responses larger than `max_response_bytes`
+    /// are rejected before they are deserialized (zero disables the limit).
+    pub async fn send_rpc_with_max_response_bytes(
+        &self,
+        recipient: PeerId,
+        message: StorageServiceMessage,
+        timeout: Duration,
+        max_response_bytes: u64,
+    ) -> Result<(StorageServiceMessage, u64), Error> {
+        // The response is deserialized here (rather than by the generic network
+        // sender) so that malformed responses are identifiable as bcs errors.
+        // Note: the response is decoded using the encoding of the rpc protocol.
+        let protocol = ProtocolId::StorageServiceRpc;
+        let request_data = protocol.to_bytes(&message).map_err(RpcError::from)?;
+        let response_data = self
+            .peer_mgr_reqs_tx
+            .send_rpc(recipient, protocol, request_data.into(), timeout)
+            .await?;
+
+        // Reject responses that are too large (before deserializing them)
+        let response_bytes = response_data.len() as u64;
+        if max_response_bytes > 0 && response_bytes > max_response_bytes {
+            return Err(Error::ResponseTooLarge(format!(
+                "The response has {} bytes, but the max is {} bytes!",
+                response_bytes, max_response_bytes
+            )));
+        }
+
+        let message = protocol
+            .from_bytes(&response_data)
+            .map_err(|error| RpcError::BcsError(bcs::Error::Custom(error.to_string())))?;
+        Ok((message, response_bytes))
+    }
 }
 
 impl NewNetworkSender for StorageServiceNetworkSender {
     fn new(
         peer_mgr_reqs_tx: PeerManagerRequestSender,
-        connection_reqs_tx: ConnectionRequestSender,
+        _connection_reqs_tx: ConnectionRequestSender,
     ) -> Self {
-        Self {
-            inner: NetworkSender::new(peer_mgr_reqs_tx, connection_reqs_tx),
-        }
+        Self { peer_mgr_reqs_tx }
     }
 }
 
@@ -140,8 +197,13 @@ impl ApplicationNetworkSender<StorageServiceMessage> for StorageServiceNetworkSe
         message: StorageServiceMessage,
         timeout: Duration,
     ) -> Result<StorageServiceMessage, RpcError> {
-        self.inner
-            .send_rpc(recipient, ProtocolId::StorageServiceRpc, message, timeout)
+        // The response size isn't limited, so only rpc errors are possible
+        self.send_rpc_with_max_response_bytes(recipient, message, timeout, 0)
             .await
+            .map(|(message, _)| message)
+            .map_err(|error| match error {
+                Error::RpcError(error) => error,
+                error => RpcError::Error(error.into()),
+            })
     }
 }
@@ -14,20 +14,33 @@ use aptos_infallible::RwLock;
 use aptos_logger::prelude::*;
 use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
+    account_address::AccountAddress,
     epoch_change::EpochChangeProof,
-    state_store::state_value::StateValueChunkWithProof,
-    transaction::{TransactionListWithProof, TransactionOutputListWithProof, Version},
+    proof::TransactionAccumulatorProof,
+    state_store::{
+        state_key::StateKey,
+        state_key_prefix::StateKeyPrefix,
+        state_value::{StateValue, StateValueChunkWithProof},
+    },
+    transaction::{
+        Transaction, TransactionListWithProof, TransactionOutputListWithProof,
+        TransactionWithProof, Version,
+    },
 };
 use bounded_executor::BoundedExecutor;
 use futures::stream::StreamExt;
+use move_core_types::language_storage::TypeTag;
 use serde::{Deserialize, Serialize};
 use std::{sync::Arc, time::Duration};
 use storage_interface::DbReader;
 use storage_service_types::{
-    AccountStatesChunkWithProofRequest, CompleteDataRange, DataSummary,
-    EpochEndingLedgerInfoRequest, ProtocolMetadata, Result, ServerProtocolVersion,
-    StorageServerSummary, StorageServiceError, StorageServiceRequest, StorageServiceResponse,
-    TransactionOutputsWithProofRequest, TransactionsWithProofRequest,
+    strip_events, strip_write_sets, AccountStatesByPrefixRequest,
+    AccountStatesChunkWithProofRequest, CompleteDataRange, CompressedResponse,
+    CompressedResponseRequest, DataSummary, EpochEndingLedgerInfoRequest, ProtocolMetadata, Result,
+    ServerProtocolVersion, SizeLimitedTransactions, StorageServerSummary, StorageServiceError,
+    StorageServiceRequest, StorageServiceResponse, TransactionInclusionProofsRequest,
+    TransactionOutputsWithProofRequest, TransactionsFromSenderRequest,
+    TransactionsWithEventRequest, TransactionsWithProofRequest, TransactionsWithSizeLimitRequest,
 };
 use thiserror::Error;
 use tokio::runtime::Handle;
@@ -40,7 +53,7 @@ pub mod network;
 mod tests;
 
 /// Storage server constants.
-pub const STORAGE_SERVER_VERSION: u64 = 1;
+pub const STORAGE_SERVER_VERSION: u64 = 2;
 const SUMMARY_LOG_FREQUENCY_SECS: u64 = 5;
 
 #[derive(Clone, Debug, Deserialize, Error, PartialEq, Serialize)]
@@ -239,25 +252,7 @@ impl<T: StorageReaderInterface> Handler<T> {
         );
 
         // Process the request
-        let response = match &request {
-            StorageServiceRequest::GetAccountStatesChunkWithProof(request) => {
-                self.get_account_states_chunk_with_proof(request)
-            }
-            StorageServiceRequest::GetEpochEndingLedgerInfos(request) => {
-                self.get_epoch_ending_ledger_infos(request)
-            }
-            StorageServiceRequest::GetNumberOfAccountsAtVersion(version) => {
-                self.get_number_of_accounts_at_version(*version)
-            }
-            StorageServiceRequest::GetServerProtocolVersion => self.get_server_protocol_version(),
-            StorageServiceRequest::GetStorageServerSummary => self.get_storage_server_summary(),
-            StorageServiceRequest::GetTransactionOutputsWithProof(request) => {
-                self.get_transaction_outputs_with_proof(request)
-            }
-            StorageServiceRequest::GetTransactionsWithProof(request) => {
-                self.get_transactions_with_proof(request)
-            }
-        };
+        let response = self.process_request(&request);
 
         // Process the response and handle any errors
         match response {
@@ -290,6 +285,86 @@ impl<T: StorageReaderInterface> Handler<T> {
         }
     }
 
+    /// Processes the given request and returns the corresponding response
+    fn process_request(
+        &self,
+        request: &StorageServiceRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        match request {
+            StorageServiceRequest::GetAccountStatesByPrefix(request) => {
+                self.get_account_states_by_prefix(request)
+            }
+            StorageServiceRequest::GetAccountStatesChunkWithProof(request) => {
+                self.get_account_states_chunk_with_proof(request)
+            }
+            StorageServiceRequest::GetCompressedResponse(request) => {
+                self.get_compressed_response(request)
+            }
+            StorageServiceRequest::GetEpochEndingLedgerInfos(request) => {
+                self.get_epoch_ending_ledger_infos(request)
+            }
+            StorageServiceRequest::GetNumberOfAccountsAtVersion(version) => {
+                self.get_number_of_accounts_at_version(*version)
+            }
+            StorageServiceRequest::GetServerProtocolVersion => self.get_server_protocol_version(),
+            StorageServiceRequest::GetStorageServerSummary => self.get_storage_server_summary(),
+            StorageServiceRequest::GetTransactionInclusionProofs(request) => {
+                self.get_transaction_inclusion_proofs(request)
+            }
+            StorageServiceRequest::GetTransactionOutputsWithProof(request) => {
+                self.get_transaction_outputs_with_proof(request)
+            }
+            StorageServiceRequest::GetTransactionOutputsWithoutEvents(request) => {
+                self.get_transaction_outputs_without_events(request)
+            }
+            StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(request) => {
+                self.get_transaction_outputs_without_write_sets(request)
+            }
+            StorageServiceRequest::GetTransactionsFromSender(request) => {
+                self.get_transactions_from_sender(request)
+            }
+            StorageServiceRequest::GetTransactionsWithEvent(request) => {
+                self.get_transactions_with_event(request)
+            }
+            StorageServiceRequest::GetTransactionsWithProof(request) => {
+                self.get_transactions_with_proof(request)
+            }
+            StorageServiceRequest::GetTransactionsWithSizeLimit(request) => {
+                self.get_transactions_with_size_limit(request)
+            }
+        }
+    }
+
+    fn get_account_states_by_prefix(
+        &self,
+        request: &AccountStatesByPrefixRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        // Verify the account states are held at the requested version (prefix
+        // scans aren't bounded by a proof, so pruned versions must be rejected)
+        let storage_request = StorageServiceRequest::GetAccountStatesByPrefix(request.clone());
+        if !self
+            .cached_storage_server_summary
+            .read()
+            .can_service(&storage_request)
+        {
+            return Err(Error::InvalidRequest(format!(
+                "The account states by prefix request can't be serviced: {:?}",
+                request
+            )));
+        }
+
+        let account_states = self.storage.get_account_states_by_prefix(
+            request.version,
+            &request.prefix,
+            request.cursor.as_ref(),
+            request.limit,
+        )?;
+
+        Ok(StorageServiceResponse::AccountStatesByPrefix(
+            account_states,
+        ))
+    }
+
     fn get_account_states_chunk_with_proof(
         &self,
         request: &AccountStatesChunkWithProofRequest,
@@ -305,6 +380,37 @@ impl<T: StorageReaderInterface> Handler<T> {
         ))
     }
 
+    fn get_compressed_response(
+        &self,
+        request: &CompressedResponseRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        // The server supports all compression algorithms, so use the client's preference
+        let compression_algorithm =
+            request
+                .supported_algorithms
+                .first()
+                .copied()
+                .ok_or_else(|| {
+                    Error::InvalidRequest("No supported compression algorithms were given!".into())
+                })?;
+
+        // Compressed responses can't be nested (the inner response is only
+        // compressed once, and the recursion must be bounded).
+        if let StorageServiceRequest::GetCompressedResponse(_) = *request.request {
+            return Err(Error::InvalidRequest(
+                "Compressed response requests can't be nested!".into(),
+            ));
+        }
+
+        let response = self.process_request(&request.request)?;
+        let compressed_response = CompressedResponse::compress(&response, compression_algorithm)
+            .map_err(|error| Error::UnexpectedErrorEncountered(error.to_string()))?;
+
+        Ok(StorageServiceResponse::CompressedResponse(
+            compressed_response,
+        ))
+    }
+
     fn get_epoch_ending_ledger_infos(
         &self,
         request: &EpochEndingLedgerInfoRequest,
@@ -345,6 +451,19 @@ impl<T: StorageReaderInterface> Handler<T> {
         ))
     }
 
+    fn get_transaction_inclusion_proofs(
+        &self,
+        request: &TransactionInclusionProofsRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let inclusion_proofs = self
+            .storage
+            .get_transaction_inclusion_proofs(request.accumulator_version, &request.versions)?;
+
+        Ok(StorageServiceResponse::TransactionInclusionProofs(
+            inclusion_proofs,
+        ))
+    }
+
     fn get_transaction_outputs_with_proof(
         &self,
         request: &TransactionOutputsWithProofRequest,
@@ -360,6 +479,69 @@ impl<T: StorageReaderInterface> Handler<T> {
         ))
     }
 
+    fn get_transaction_outputs_without_events(
+        &self,
+        request: &TransactionOutputsWithProofRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let transaction_output_list_with_proof = self.storage.get_transaction_outputs_with_proof(
+            request.proof_version,
+            request.start_version,
+            request.end_version,
+        )?;
+
+        Ok(StorageServiceResponse::TransactionOutputsWithProof(
+            strip_events(transaction_output_list_with_proof),
+        ))
+    }
+
+    fn get_transaction_outputs_without_write_sets(
+        &self,
+        request: &TransactionOutputsWithProofRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let transaction_output_list_with_proof = self.storage.get_transaction_outputs_with_proof(
+            request.proof_version,
+            request.start_version,
+            request.end_version,
+        )?;
+
+        Ok(StorageServiceResponse::TransactionOutputsWithProof(
+            strip_write_sets(transaction_output_list_with_proof),
+        ))
+    }
+
+    fn get_transactions_from_sender(
+        &self,
+        request: &TransactionsFromSenderRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let transactions_with_proof = self.storage.get_transactions_from_sender(
+            request.proof_version,
+            request.start_version,
+            request.end_version,
+            request.include_events,
+            request.sender,
+        )?;
+
+        Ok(StorageServiceResponse::TransactionsFromSender(
+            transactions_with_proof,
+        ))
+    }
+
+    fn get_transactions_with_event(
+        &self,
+        request: &TransactionsWithEventRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let transactions_with_proof = self.storage.get_transactions_with_event(
+            request.proof_version,
+            request.start_version,
+            request.end_version,
+            &request.event_type,
+        )?;
+
+        Ok(StorageServiceResponse::TransactionsWithEvent(
+            transactions_with_proof,
+        ))
+    }
+
     fn get_transactions_with_proof(
         &self,
         request: &TransactionsWithProofRequest,
@@ -375,6 +557,23 @@ impl<T: StorageReaderInterface> Handler<T> {
             transactions_with_proof,
         ))
     }
+
+    fn get_transactions_with_size_limit(
+        &self,
+        request: &TransactionsWithSizeLimitRequest,
+    ) -> Result<StorageServiceResponse, Error> {
+        let size_limited_transactions = self.storage.get_transactions_with_size_limit(
+            request.proof_version,
+            request.start_version,
+            request.end_version,
+            request.include_events,
+            request.max_transaction_bytes,
+        )?;
+
+        Ok(StorageServiceResponse::TransactionsWithSizeLimit(
+            size_limited_transactions,
+        ))
+    }
 }
 
 /// The interface into local storage (e.g., the Aptos DB) used by the storage
@@ -395,6 +594,53 @@ pub trait StorageReaderInterface: Clone + Send + 'static {
         include_events: bool,
     ) -> Result<TransactionListWithProof, Error>;
 
+    /// Returns the transactions sent by `sender` between `start_version` and
+    /// `end_version` (inclusive). Each transaction has its own proof relative
+    /// to the `proof_version`. If `include_events` is true, events are also
+    /// returned.
+    fn get_transactions_from_sender(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        include_events: bool,
+        sender: AccountAddress,
+    ) -> Result<Vec<TransactionWithProof>, Error>;
+
+    /// Returns the transactions between `start_version` and `end_version`
+    /// (inclusive) that emitted at least one event of the given `event_type`.
+    /// Each transaction is returned with its events and its own proof relative
+    /// to the `proof_version`.
+    fn get_transactions_with_event(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        event_type: &TypeTag,
+    ) -> Result<Vec<TransactionWithProof>, Error>;
+
+    /// Returns the transactions between `start_version` and `end_version`
+    /// (inclusive), excluding (but reporting the versions of) any transactions
+    /// larger than `max_transaction_bytes` when serialized. Each transaction
+    /// has its own proof relative to the `proof_version`. If `include_events`
+    /// is true, events are also returned.
+    fn get_transactions_with_size_limit(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        include_events: bool,
+        max_transaction_bytes: u64,
+    ) -> Result<SizeLimitedTransactions, Error>;
+
+    /// Returns the transaction accumulator inclusion proof for each of the
+    /// given versions, relative to the accumulator at the `accumulator_version`.
+    fn get_transaction_inclusion_proofs(
+        &self,
+        accumulator_version: u64,
+        versions: &[Version],
+    ) -> Result<Vec<TransactionAccumulatorProof>, Error>;
+
     /// Returns a list of epoch ending ledger infos, starting at `start_epoch`
     /// and ending at the `expected_end_epoch` (inclusive). For example, if
     /// `start_epoch` is 0 and `end_epoch` is 1, this will return 2 epoch ending
@@ -428,6 +674,18 @@ pub trait StorageReaderInterface: Clone + Send + 'static {
         start_account_index: u64,
         end_account_index: u64,
     ) -> Result<StateValueChunkWithProof, Error>;
+
+    /// Returns (at most) `limit` account states at the specified version with
+    /// state keys matching the given `prefix`, starting after the `cursor`
+    /// state key (if any). The matching states are ordered by state key (so
+    /// that callers can page through them).
+    fn get_account_states_by_prefix(
+        &self,
+        version: u64,
+        prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        limit: u64,
+    ) -> Result<Vec<(StateKey, StateValue)>, Error>;
 }
 
 /// The underlying implementation of the StorageReaderInterface, used by the
@@ -593,6 +851,141 @@ impl StorageReaderInterface for StorageReader {
         Ok(transaction_list_with_proof)
     }
 
+    fn get_transactions_from_sender(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        include_events: bool,
+        sender: AccountAddress,
+    ) -> Result<Vec<TransactionWithProof>, Error> {
+        // Fetch the transactions (this also verifies the chunk size)
+        let transaction_list_with_proof =
+            self.get_transactions_with_proof(proof_version, start_version, end_version, false)?;
+
+        // Fetch a proof for each transaction sent by the sender
+        let mut transactions_from_sender = vec![];
+        for (version, transaction) in
+            (start_version..).zip(transaction_list_with_proof.transactions.iter())
+        {
+            if let Transaction::UserTransaction(signed_transaction) = transaction {
+                if signed_transaction.sender() == sender {
+                    let transaction_with_proof = self
+                        .storage
+                        .get_transaction_by_version(version, proof_version, include_events)
+                        .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?;
+                    transactions_from_sender.push(transaction_with_proof);
+                }
+            }
+        }
+        Ok(transactions_from_sender)
+    }
+
+    fn get_transactions_with_event(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        event_type: &TypeTag,
+    ) -> Result<Vec<TransactionWithProof>, Error> {
+        // Fetch the transactions and events (this also verifies the chunk size)
+        let transaction_list_with_proof =
+            self.get_transactions_with_proof(proof_version, start_version, end_version, true)?;
+        let events = transaction_list_with_proof.events.ok_or_else(|| {
+            Error::UnexpectedErrorEncountered("The transaction events are missing!".into())
+        })?;
+
+        // Fetch a proof for each transaction that emitted an event of the type
+        let mut transactions_with_event = vec![];
+        for (version, transaction_events) in (start_version..).zip(events.iter()) {
+            if transaction_events
+                .iter()
+                .any(|event| event.type_tag() == event_type)
+            {
+                let transaction_with_proof = self
+                    .storage
+                    .get_transaction_by_version(version, proof_version, true)
+                    .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?;
+                transactions_with_event.push(transaction_with_proof);
+            }
+        }
+        Ok(transactions_with_event)
+    }
+
+    fn get_transactions_with_size_limit(
+        &self,
+        proof_version: u64,
+        start_version: u64,
+        end_version: u64,
+        include_events: bool,
+        max_transaction_bytes: u64,
+    ) -> Result<SizeLimitedTransactions, Error> {
+        // Fetch the transactions (this also verifies the chunk size)
+        let transaction_list_with_proof =
+            self.get_transactions_with_proof(proof_version, start_version, end_version, false)?;
+
+        // Fetch a proof for each transaction within the size limit
+        let mut size_limited_transactions = SizeLimitedTransactions {
+            transactions: vec![],
+            oversized_versions: vec![],
+        };
+        for (version, transaction) in
+            (start_version..).zip(transaction_list_with_proof.transactions.iter())
+        {
+            let transaction_bytes = bcs::serialized_size(transaction)
+                .map_err(|error| Error::UnexpectedErrorEncountered(error.to_string()))?;
+            if transaction_bytes as u64 > max_transaction_bytes {
+                size_limited_transactions.oversized_versions.push(version);
+            } else {
+                let transaction_with_proof = self
+                    .storage
+                    .get_transaction_by_version(version, proof_version, include_events)
+                    .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?;
+                size_limited_transactions
+                    .transactions
+                    .push(transaction_with_proof);
+            }
+        }
+        Ok(size_limited_transactions)
+    }
+
+    fn get_transaction_inclusion_proofs(
+        &self,
+        accumulator_version: u64,
+        versions: &[Version],
+    ) -> Result<Vec<TransactionAccumulatorProof>, Error> {
+        let num_proofs = versions.len() as u64;
+        let max_transaction_chunk_size = self.config.max_transaction_chunk_size;
+        if num_proofs > max_transaction_chunk_size {
+            return Err(Error::InvalidRequest(format!(
+                "Requested number of inclusion proofs is larger than the maximum! \
+             Requested: {:?}, maximum: {:?}.",
+                num_proofs, max_transaction_chunk_size
+            )));
+        }
+
+        let mut inclusion_proofs = vec![];
+        for version in versions {
+            if *version > accumulator_version {
+                return Err(Error::InvalidRequest(format!(
+                    "Requested version is higher than the accumulator version! \
+                 Version: {:?}, accumulator version: {:?}.",
+                    version, accumulator_version
+                )));
+            }
+            let transaction_with_proof = self
+                .storage
+                .get_transaction_by_version(*version, accumulator_version, false)
+                .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?;
+            inclusion_proofs.push(
+                transaction_with_proof
+                    .proof
+                    .ledger_info_to_transaction_info_proof,
+            );
+        }
+        Ok(inclusion_proofs)
+    }
+
     fn get_epoch_ending_ledger_infos(
         &self,
         start_epoch: u64,
@@ -677,6 +1070,45 @@ impl StorageReaderInterface for StorageReader {
             .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?;
         Ok(account_states_chunk_with_proof)
     }
+
+    fn get_account_states_by_prefix(
+        &self,
+        version: u64,
+        prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        limit: u64,
+    ) -> Result<Vec<(StateKey, StateValue)>, Error> {
+        let max_account_chunk_size = self.config.max_account_states_chunk_sizes;
+        if limit > max_account_chunk_size {
+            return Err(Error::InvalidRequest(format!(
+                "Requested number of accounts is larger than the maximum! \
+             Requested: {:?}, maximum: {:?}.",
+                limit, max_account_chunk_size
+            )));
+        }
+
+        if let Some(cursor) = cursor {
+            let matches_prefix = prefix
+                .is_prefix(cursor)
+                .map_err(|error| Error::UnexpectedErrorEncountered(error.to_string()))?;
+            if !matches_prefix {
+                return Err(Error::InvalidRequest(format!(
+                    "The cursor doesn't match the requested prefix! Cursor: {:?}, prefix: {:?}.",
+                    cursor, prefix
+                )));
+            }
+        }
+
+        // Only read the states that are returned (the iterator is lazy)
+        let account_states = self
+            .storage
+            .get_prefixed_state_value_iterator(prefix, cursor, version)
+            .map_err(|error| Error::StorageErrorEncountered(error.to_string()))?
+            .take(limit as usize)
+            .map(|result| result.map_err(|error| Error::StorageErrorEncountered(error.to_string())))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(account_states)
+    }
 }
 
 /// Calculate `(start..=end).len()`. Returns an error if `end < start` or
@@ -5,7 +5,7 @@
 
 use crate::{network::StorageServiceNetworkEvents, StorageReader, StorageServiceServer};
 use anyhow::Result;
-use aptos_config::config::StorageServiceConfig;
+use aptos_config::config::{CompressionAlgo, StorageServiceConfig};
 use aptos_crypto::{ed25519::Ed25519PrivateKey, HashValue, PrivateKey, SigningKey, Uniform};
 use aptos_logger::Level;
 use aptos_time_service::{MockTimeService, TimeService};
@@ -19,8 +19,9 @@ use aptos_types::{
     ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
     proof::{SparseMerkleRangeProof, TransactionInfoListWithProof},
     state_store::{
-        state_key::StateKey,
-        state_value::{StateKeyAndValue, StateValueChunkWithProof},
+        state_key::{StateKey, StateKeyTag},
+        state_key_prefix::StateKeyPrefix,
+        state_value::{StateKeyAndValue, StateValue, StateValueChunkWithProof},
     },
     transaction::{
         RawTransaction, Script, SignedTransaction, Transaction, TransactionListWithProof,
@@ -40,13 +41,14 @@ use network::{
         network::NewNetworkEvents, rpc::InboundRpcRequest, wire::handshake::v1::ProtocolId,
     },
 };
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, convert::TryInto, sync::Arc};
 use storage_interface::DbReader;
 use storage_service_types::{
-    AccountStatesChunkWithProofRequest, CompleteDataRange, DataSummary,
-    EpochEndingLedgerInfoRequest, ProtocolMetadata, ServerProtocolVersion, StorageServerSummary,
-    StorageServiceError, StorageServiceMessage, StorageServiceRequest, StorageServiceResponse,
-    TransactionOutputsWithProofRequest, TransactionsWithProofRequest,
+    AccountStatesByPrefixRequest, AccountStatesChunkWithProofRequest, CompleteDataRange,
+    CompressedResponseRequest, DataSummary, EpochEndingLedgerInfoRequest, ProtocolMetadata,
+    ServerProtocolVersion, StorageServerSummary, StorageServiceError, StorageServiceMessage,
+    StorageServiceRequest, StorageServiceResponse, TransactionOutputsWithProofRequest,
+    TransactionsWithProofRequest, MAX_DECOMPRESSED_RESPONSE_BYTES,
 };
 
 // TODO(joshlind): Expand these test cases to better test storage interaction
@@ -58,7 +60,7 @@ const FIRST_TXN_VERSION: u64 = 10;
 const LAST_EPOCH: u64 = 10;
 const LAST_TXN_VERSION: u64 = 100;
 const NUM_ACCOUNTS_AT_VERSION: u64 = 1000;
-const PROTOCOL_VERSION: u64 = 1;
+const PROTOCOL_VERSION: u64 = 2;
 const STATE_PRUNE_WINDOW: u64 = 50;
 
 #[tokio::test]
@@ -166,6 +168,123 @@ async fn test_get_invalid_account_states_request() {
     assert_matches!(response, StorageServiceError::InvalidRequest(_));
 }
 
+#[tokio::test]
+async fn test_get_account_states_by_prefix() {
+    let (mut mock_client, service, mock_time) = MockClient::new();
+    tokio::spawn(service.start());
+
+    // Elapse enough time to force a cache update (so the account states are advertised)
+    let cache_update_freq_ms = StorageServiceConfig::default().storage_summary_refresh_interval_ms;
+    mock_time.advance_ms_async(cache_update_freq_ms).await;
+
+    // Fetch the first page of account states and verify the response
+    let prefix = StateKeyPrefix::new(StateKeyTag::Raw, vec![]);
+    let request = create_account_states_by_prefix_request(LAST_TXN_VERSION, &prefix, None, 10);
+    let response = mock_client.send_request(request).await.unwrap();
+    let expected_states = (0..10).map(create_test_state).collect();
+    assert_eq!(
+        response,
+        StorageServiceResponse::AccountStatesByPrefix(expected_states)
+    );
+
+    // Fetch the next page (starting after the last state key) and verify the response
+    let cursor = create_test_state_key(9);
+    let request =
+        create_account_states_by_prefix_request(LAST_TXN_VERSION, &prefix, Some(cursor), 5);
+    let response = mock_client.send_request(request).await.unwrap();
+    let expected_states = (10..15).map(create_test_state).collect();
+    assert_eq!(
+        response,
+        StorageServiceResponse::AccountStatesByPrefix(expected_states)
+    );
+}
+
+#[tokio::test]
+async fn test_get_invalid_account_states_by_prefix_request() {
+    let (mut mock_client, service, mock_time) = MockClient::new();
+    tokio::spawn(service.start());
+
+    // Elapse enough time to force a cache update (so the account states are advertised)
+    let cache_update_freq_ms = StorageServiceConfig::default().storage_summary_refresh_interval_ms;
+    mock_time.advance_ms_async(cache_update_freq_ms).await;
+
+    // Create a request to fetch the account states at a pruned version
+    let prefix = StateKeyPrefix::new(StateKeyTag::Raw, vec![]);
+    let pruned_version = LAST_TXN_VERSION - STATE_PRUNE_WINDOW;
+    let request = create_account_states_by_prefix_request(pruned_version, &prefix, None, 10);
+
+    // Process and verify the response
+    let response = mock_client.send_request(request).await.unwrap_err();
+    assert_matches!(response, StorageServiceError::InvalidRequest(_));
+
+    // Create a request to fetch too much data
+    let max_account_chunk_size = StorageServiceConfig::default().max_account_states_chunk_sizes;
+    let request = create_account_states_by_prefix_request(
+        LAST_TXN_VERSION,
+        &prefix,
+        None,
+        max_account_chunk_size + 1,
+    );
+
+    // Process and verify the response
+    let response = mock_client.send_request(request).await.unwrap_err();
+    assert_matches!(response, StorageServiceError::InvalidRequest(_));
+
+    // Create a request with a cursor that doesn't match the prefix
+    let prefix = StateKeyPrefix::new(StateKeyTag::Raw, vec![1]);
+    let cursor = create_test_state_key(0);
+    let request =
+        create_account_states_by_prefix_request(LAST_TXN_VERSION, &prefix, Some(cursor), 10);
+
+    // Process and verify the response
+    let response = mock_client.send_request(request).await.unwrap_err();
+    assert_matches!(response, StorageServiceError::InvalidRequest(_));
+}
+
+#[tokio::test]
+async fn test_get_compressed_response() {
+    let (mut mock_client, service, _) = MockClient::new();
+    tokio::spawn(service.start());
+
+    // Fetch the number of accounts (both uncompressed and compressed)
+    let request = StorageServiceRequest::GetNumberOfAccountsAtVersion(0);
+    let response = mock_client.send_request(request.clone()).await.unwrap();
+    let compressed_request = create_compressed_response_request(request, CompressionAlgo::Zstd);
+    let compressed_response = mock_client.send_request(compressed_request).await.unwrap();
+
+    // Verify the compressed response decompresses to the uncompressed response
+    match compressed_response {
+        StorageServiceResponse::CompressedResponse(compressed_response) => {
+            assert_eq!(
+                compressed_response.compression_algorithm,
+                CompressionAlgo::Zstd
+            );
+            assert_eq!(
+                compressed_response
+                    .decompress(MAX_DECOMPRESSED_RESPONSE_BYTES)
+                    .unwrap(),
+                response
+            );
+        }
+        response => panic!("Expected a compressed response but got: {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn test_get_invalid_compressed_response_request() {
+    let (mut mock_client, service, _) = MockClient::new();
+    tokio::spawn(service.start());
+
+    // Create a request for a nested compressed response
+    let request = StorageServiceRequest::GetNumberOfAccountsAtVersion(0);
+    let compressed_request = create_compressed_response_request(request, CompressionAlgo::Zstd);
+    let request = create_compressed_response_request(compressed_request, CompressionAlgo::Lz4);
+
+    // Process and verify the response
+    let response = mock_client.send_request(request).await.unwrap_err();
+    assert_matches!(response, StorageServiceError::InvalidRequest(_));
+}
+
 #[tokio::test]
 async fn test_get_number_of_accounts_at_version() {
     let (mut mock_client, service, _) = MockClient::new();
@@ -590,6 +709,38 @@ impl MockClient {
     }
 }
 
+fn create_account_states_by_prefix_request(
+    version: Version,
+    prefix: &StateKeyPrefix,
+    cursor: Option<StateKey>,
+    limit: u64,
+) -> StorageServiceRequest {
+    StorageServiceRequest::GetAccountStatesByPrefix(AccountStatesByPrefixRequest {
+        version,
+        prefix: prefix.clone(),
+        cursor,
+        limit,
+    })
+}
+
+fn create_compressed_response_request(
+    request: StorageServiceRequest,
+    compression_algorithm: CompressionAlgo,
+) -> StorageServiceRequest {
+    StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+        request: Box::new(request),
+        supported_algorithms: vec![compression_algorithm],
+    })
+}
+
+fn create_test_state(index: u64) -> (StateKey, StateValue) {
+    (create_test_state_key(index), vec![].into())
+}
+
+fn create_test_state_key(index: u64) -> StateKey {
+    StateKey::Raw(index.to_be_bytes().to_vec())
+}
+
 fn create_test_event(sequence_number: u64) -> ContractEvent {
     ContractEvent::new(
         EventKey::new_from_address(&AccountAddress::random(), 0),
@@ -769,6 +920,24 @@ impl DbReader for MockDbReader {
     fn get_state_prune_window(&self) -> Result<Option<usize>> {
         Ok(Some(STATE_PRUNE_WINDOW as usize))
     }
+
+    fn get_prefixed_state_value_iterator(
+        &self,
+        _key_prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        _version: Version,
+    ) -> Result<Box<dyn Iterator<Item = Result<(StateKey, StateValue)>> + '_>> {
+        // Return an unbounded stream of states (only the requested states
+        // should ever be read).
+        let start_index = match cursor {
+            Some(StateKey::Raw(bytes)) => u64::from_be_bytes(bytes[..].try_into()?) + 1,
+            Some(cursor) => panic!("Unexpected cursor: {:?}", cursor),
+            None => 0,
+        };
+        Ok(Box::new(
+            (start_index..).map(|index| Ok(create_test_state(index))),
+        ))
+    }
 }
 
 /// Initializes the Aptos logger for tests
@@ -3,13 +3,24 @@
 
 #![forbid(unsafe_code)]
 
-use aptos_config::config::StorageServiceConfig;
+use aptos_config::config::{CompressionAlgo, StorageServiceConfig};
 use aptos_types::{
+    account_address::AccountAddress,
     epoch_change::EpochChangeProof,
     ledger_info::LedgerInfoWithSignatures,
-    state_store::state_value::StateValueChunkWithProof,
-    transaction::{TransactionListWithProof, TransactionOutputListWithProof, Version},
+    proof::TransactionAccumulatorProof,
+    state_store::{
+        state_key::StateKey,
+        state_key_prefix::StateKeyPrefix,
+        state_value::{StateValue, StateValueChunkWithProof},
+    },
+    transaction::{
+        TransactionListWithProof, TransactionOutput, TransactionOutputListWithProof,
+        TransactionWithProof, Version,
+    },
+    write_set::WriteSet,
 };
+use move_core_types::language_storage::TypeTag;
 use num_traits::{int::PrimInt, Zero};
 #[cfg(test)]
 use proptest::{
@@ -20,6 +31,7 @@ use serde::{de, Deserialize, Serialize};
 use std::{
     convert::TryFrom,
     fmt::{Display, Formatter},
+    io::Read,
 };
 use thiserror::Error;
 
@@ -28,6 +40,14 @@ pub type Epoch = u64;
 
 pub type Result<T, E = StorageServiceError> = ::std::result::Result<T, E>;
 
+/// The storage server protocol version that supports the original requests
+pub const BASE_PROTOCOL_VERSION: u64 = 1;
+
+/// The storage server protocol version that also supports the requests
+/// appended after the original requests (e.g., compressed responses, filtered
+/// transactions and inclusion proofs).
+pub const EXTENDED_PROTOCOL_VERSION: u64 = 2;
+
 /// A storage service error that can be returned to the client on a failure
 /// to process a service request.
 #[derive(Clone, Debug, Deserialize, Eq, Error, PartialEq, Serialize)]
@@ -36,6 +56,8 @@ pub enum StorageServiceError {
     InternalError(String),
     #[error("Invalid storage request: {0}")]
     InvalidRequest(String),
+    #[error("Storage service is overloaded! Retry after (ms): {retry_after_ms}")]
+    Overloaded { retry_after_ms: u64 },
 }
 
 /// A single storage service message sent or received over AptosNet.
@@ -50,8 +72,9 @@ pub enum StorageServiceMessage {
     Response(Result<StorageServiceResponse>),
 }
 
-/// A storage service request.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+/// A storage service request. Note: the variants are encoded (e.g., by BCS)
+/// using their index, so new variants must only ever be appended.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum StorageServiceRequest {
     GetAccountStatesChunkWithProof(AccountStatesChunkWithProofRequest), // Fetches a list of account states with a proof
     GetEpochEndingLedgerInfos(EpochEndingLedgerInfoRequest), // Fetches a list of epoch ending ledger infos
@@ -60,28 +83,73 @@ pub enum StorageServiceRequest {
     GetStorageServerSummary,               // Fetches a summary of the storage server state
     GetTransactionOutputsWithProof(TransactionOutputsWithProofRequest), // Fetches a list of transaction outputs with a proof
     GetTransactionsWithProof(TransactionsWithProofRequest), // Fetches a list of transactions with a proof
+    GetCompressedResponse(CompressedResponseRequest), // Fetches the response to the inner request in a compressed form
+    GetTransactionsFromSender(TransactionsFromSenderRequest), // Fetches the transactions sent by a single account (each with a proof)
+    GetTransactionOutputsWithoutWriteSets(TransactionOutputsWithProofRequest), // Fetches a list of transaction outputs (with the write sets omitted) with a proof
+    GetTransactionInclusionProofs(TransactionInclusionProofsRequest), // Fetches the accumulator inclusion proofs of a list of transactions
+    GetTransactionsWithSizeLimit(TransactionsWithSizeLimitRequest), // Fetches the transactions within a size limit (each with a proof)
+    GetTransactionsWithEvent(TransactionsWithEventRequest), // Fetches the transactions that emitted an event of a single type (each with a proof)
+    GetAccountStatesByPrefix(AccountStatesByPrefixRequest), // Fetches a page of the account states with keys matching a prefix (without a proof)
+    GetTransactionOutputsWithoutEvents(TransactionOutputsWithProofRequest), // Fetches a list of transaction outputs (with the events omitted) with a proof
 }
 
 impl StorageServiceRequest {
     /// Returns a summary label for the request
     pub fn get_label(&self) -> &'static str {
         match self {
+            Self::GetAccountStatesByPrefix(_) => "get_account_states_by_prefix",
             Self::GetAccountStatesChunkWithProof(_) => "get_account_states_chunk_with_proof",
+            Self::GetCompressedResponse(request) => request.request.get_label(),
             Self::GetEpochEndingLedgerInfos(_) => "get_epoch_ending_ledger_infos",
             Self::GetNumberOfAccountsAtVersion(_) => "get_number_of_accounts_at_version",
             Self::GetServerProtocolVersion => "get_server_protocol_version",
             Self::GetStorageServerSummary => "get_storage_server_summary",
+            Self::GetTransactionInclusionProofs(_) => "get_transaction_inclusion_proofs",
             Self::GetTransactionOutputsWithProof(_) => "get_transaction_outputs_with_proof",
+            Self::GetTransactionOutputsWithoutEvents(_) => "get_transaction_outputs_without_events",
+            Self::GetTransactionOutputsWithoutWriteSets(_) => {
+                "get_transaction_outputs_without_write_sets"
+            }
+            Self::GetTransactionsFromSender(_) => "get_transactions_from_sender",
+            Self::GetTransactionsWithEvent(_) => "get_transactions_with_event",
             Self::GetTransactionsWithProof(_) => "get_transactions_with_proof",
+            Self::GetTransactionsWithSizeLimit(_) => "get_transactions_with_size_limit",
         }
     }
 
     pub fn is_get_storage_server_summary(&self) -> bool {
         matches!(self, &Self::GetStorageServerSummary)
     }
+
+    /// Returns the minimum storage server protocol version required to
+    /// service the request. Servers running older versions can't deserialize
+    /// the request, so it should never be sent to them.
+    pub fn min_protocol_version(&self) -> u64 {
+        match self {
+            Self::GetAccountStatesChunkWithProof(_)
+            | Self::GetEpochEndingLedgerInfos(_)
+            | Self::GetNumberOfAccountsAtVersion(_)
+            | Self::GetServerProtocolVersion
+            | Self::GetStorageServerSummary
+            | Self::GetTransactionOutputsWithProof(_)
+            | Self::GetTransactionsWithProof(_) => BASE_PROTOCOL_VERSION,
+            Self::GetCompressedResponse(request) => std::cmp::max(
+                EXTENDED_PROTOCOL_VERSION,
+                request.request.min_protocol_version(),
+            ),
+            Self::GetAccountStatesByPrefix(_)
+            | Self::GetTransactionInclusionProofs(_)
+            | Self::GetTransactionOutputsWithoutEvents(_)
+            | Self::GetTransactionOutputsWithoutWriteSets(_)
+            | Self::GetTransactionsFromSender(_)
+            | Self::GetTransactionsWithEvent(_)
+            | Self::GetTransactionsWithSizeLimit(_) => EXTENDED_PROTOCOL_VERSION,
+        }
+    }
 }
 
-/// A storage service response.
+/// A storage service response. Note: the variants are encoded (e.g., by BCS)
+/// using their index, so new variants must only ever be appended.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 // TODO(philiphayes): do something about this without making it ugly :(
 #[allow(clippy::large_enum_variant)]
@@ -93,6 +161,12 @@ pub enum StorageServiceResponse {
     StorageServerSummary(StorageServerSummary),
     TransactionOutputsWithProof(TransactionOutputListWithProof),
     TransactionsWithProof(TransactionListWithProof),
+    CompressedResponse(CompressedResponse),
+    TransactionsFromSender(Vec<TransactionWithProof>),
+    TransactionInclusionProofs(Vec<TransactionAccumulatorProof>),
+    TransactionsWithSizeLimit(SizeLimitedTransactions),
+    TransactionsWithEvent(Vec<TransactionWithProof>),
+    AccountStatesByPrefix(Vec<(StateKey, StateValue)>),
 }
 
 // TODO(philiphayes): is there a proc-macro for this?
@@ -100,13 +174,19 @@ impl StorageServiceResponse {
     /// Returns a summary label for the response
     pub fn get_label(&self) -> &'static str {
         match self {
+            Self::AccountStatesByPrefix(_) => "account_states_by_prefix",
             Self::AccountStatesChunkWithProof(_) => "account_states_chunk_with_proof",
+            Self::CompressedResponse(_) => "compressed_response",
             Self::EpochEndingLedgerInfos(_) => "epoch_ending_ledger_infos",
             Self::NumberOfAccountsAtVersion(_) => "number_of_accounts_at_version",
             Self::ServerProtocolVersion(_) => "server_protocol_version",
             Self::StorageServerSummary(_) => "storage_server_summary",
+            Self::TransactionInclusionProofs(_) => "transaction_inclusion_proofs",
             Self::TransactionOutputsWithProof(_) => "transaction_outputs_with_proof",
+            Self::TransactionsFromSender(_) => "transactions_from_sender",
+            Self::TransactionsWithEvent(_) => "transactions_with_event",
             Self::TransactionsWithProof(_) => "transactions_with_proof",
+            Self::TransactionsWithSizeLimit(_) => "transactions_with_size_limit",
         }
     }
 }
@@ -136,6 +216,19 @@ pub struct UnexpectedResponseError(pub String);
 // Conversions from the outer StorageServiceResponse enum to the inner types.
 // TODO(philiphayes): is there a proc-macro for this?
 
+impl TryFrom<StorageServiceResponse> for Vec<(StateKey, StateValue)> {
+    type Error = UnexpectedResponseError;
+    fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
+        match response {
+            StorageServiceResponse::AccountStatesByPrefix(inner) => Ok(inner),
+            _ => Err(UnexpectedResponseError(format!(
+                "expected account_states_by_prefix, found {}",
+                response.get_label()
+            ))),
+        }
+    }
+}
+
 impl TryFrom<StorageServiceResponse> for StateValueChunkWithProof {
     type Error = UnexpectedResponseError;
     fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
@@ -214,6 +307,46 @@ impl TryFrom<StorageServiceResponse> for TransactionOutputListWithProof {
     }
 }
 
+impl TryFrom<StorageServiceResponse> for Vec<TransactionAccumulatorProof> {
+    type Error = UnexpectedResponseError;
+    fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
+        match response {
+            StorageServiceResponse::TransactionInclusionProofs(inner) => Ok(inner),
+            _ => Err(UnexpectedResponseError(format!(
+                "expected transaction_inclusion_proofs, found {}",
+                response.get_label()
+            ))),
+        }
+    }
+}
+
+impl TryFrom<StorageServiceResponse> for Vec<TransactionWithProof> {
+    type Error = UnexpectedResponseError;
+    fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
+        match response {
+            StorageServiceResponse::TransactionsFromSender(inner)
+            | StorageServiceResponse::TransactionsWithEvent(inner) => Ok(inner),
+            _ => Err(UnexpectedResponseError(format!(
+                "expected transactions_from_sender or transactions_with_event, found {}",
+                response.get_label()
+            ))),
+        }
+    }
+}
+
+impl TryFrom<StorageServiceResponse> for SizeLimitedTransactions {
+    type Error = UnexpectedResponseError;
+    fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
+        match response {
+            StorageServiceResponse::TransactionsWithSizeLimit(inner) => Ok(inner),
+            _ => Err(UnexpectedResponseError(format!(
+                "expected transactions_with_size_limit, found {}",
+                response.get_label()
+            ))),
+        }
+    }
+}
+
 impl TryFrom<StorageServiceResponse> for TransactionListWithProof {
     type Error = UnexpectedResponseError;
     fn try_from(response: StorageServiceResponse) -> Result<Self, Self::Error> {
@@ -227,27 +360,168 @@ impl TryFrom<StorageServiceResponse> for TransactionListWithProof {
     }
 }
 
+/// A storage service request for fetching a page of the account states at a
+/// specified version with state keys matching the given prefix. The matching
+/// states are ordered by state key, so callers can page through them (using
+/// the last state key of the previous page as the cursor). Note: the states
+/// are returned without a proof.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct AccountStatesByPrefixRequest {
+    pub version: u64,             // The version to fetch the account states at
+    pub prefix: StateKeyPrefix,   // The prefix that the state keys must match
+    pub cursor: Option<StateKey>, // The state key after which to start returning states (exclusive)
+    pub limit: u64,               // The max number of states to return
+}
+
 /// A storage service request for fetching a list of account states at a
 /// specified version.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct AccountStatesChunkWithProofRequest {
     pub version: u64,             // The version to fetch the account states at
     pub start_account_index: u64, // The account index to start fetching account states
     pub end_account_index: u64,   // The account index to stop fetching account states (inclusive)
 }
 
+/// A storage service request for fetching the response to the inner request
+/// in a compressed form. The server compresses the response using one of the
+/// supported algorithms (given in order of preference).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct CompressedResponseRequest {
+    pub request: Box<StorageServiceRequest>, // The request to fetch a compressed response for
+    pub supported_algorithms: Vec<CompressionAlgo>, // The algorithms supported by the client (in order of preference)
+}
+
+#[derive(Clone, Debug, Error)]
+#[error("compression error: {0}")]
+pub struct CompressionError(pub String);
+
+/// The maximum number of bytes a compressed response may decompress to (if no
+/// smaller limit is given). This protects against decompression bombs.
+pub const MAX_DECOMPRESSED_RESPONSE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A compressed storage service response, along with the algorithm that was
+/// used to compress it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CompressedResponse {
+    pub compression_algorithm: CompressionAlgo,
+    pub compressed_data: Vec<u8>,
+}
+
+impl CompressedResponse {
+    /// Serializes and compresses the given response using the specified algorithm
+    pub fn compress(
+        response: &StorageServiceResponse,
+        compression_algorithm: CompressionAlgo,
+    ) -> Result<Self, CompressionError> {
+        let raw_data =
+            bcs::to_bytes(response).map_err(|error| CompressionError(error.to_string()))?;
+        let compressed_data = match compression_algorithm {
+            CompressionAlgo::Lz4 => lz4::block::compress(&raw_data, None, true),
+            CompressionAlgo::Zstd => zstd::encode_all(raw_data.as_slice(), 0),
+        }
+        .map_err(|error| CompressionError(error.to_string()))?;
+
+        Ok(Self {
+            compression_algorithm,
+            compressed_data,
+        })
+    }
+
+    /// Decompresses and deserializes the original response. Responses that
+    /// would decompress to more than the given number of bytes are rejected
+    /// (without decompressing them entirely).
+    pub fn decompress(
+        &self,
+        max_decompressed_bytes: u64,
+    ) -> Result<StorageServiceResponse, CompressionError> {
+        let raw_data = match self.compression_algorithm {
+            CompressionAlgo::Lz4 => {
+                // Check the (prepended) decompressed size before allocating
+                let decompressed_bytes = match self.compressed_data.get(..4) {
+                    Some(size) => u32::from_le_bytes([size[0], size[1], size[2], size[3]]),
+                    None => {
+                        return Err(CompressionError(
+                            "The lz4 data is missing the decompressed size!".into(),
+                        ))
+                    }
+                };
+                if decompressed_bytes as u64 > max_decompressed_bytes {
+                    return Err(CompressionError(format!(
+                        "The response decompresses to {} bytes, but the max is {} bytes!",
+                        decompressed_bytes, max_decompressed_bytes
+                    )));
+                }
+                lz4::block::decompress(&self.compressed_data, None)
+            }
+            CompressionAlgo::Zstd => {
+                // Stop decompressing as soon as the max is exceeded
+                let mut raw_data = vec![];
+                zstd::stream::read::Decoder::new(self.compressed_data.as_slice())
+                    .and_then(|decoder| {
+                        decoder
+                            .take(max_decompressed_bytes.saturating_add(1))
+                            .read_to_end(&mut raw_data)
+                    })
+                    .map(|_| raw_data)
+            }
+        }
+        .map_err(|error| CompressionError(error.to_string()))?;
+        if raw_data.len() as u64 > max_decompressed_bytes {
+            return Err(CompressionError(format!(
+                "The response decompresses to more than the max of {} bytes!",
+                max_decompressed_bytes
+            )));
+        }
+        bcs::from_bytes(&raw_data).map_err(|error| CompressionError(error.to_string()))
+    }
+}
+
 /// A storage service request for fetching a transaction output list with a
 /// corresponding proof.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct TransactionOutputsWithProofRequest {
     pub proof_version: u64, // The version the proof should be relative to
     pub start_version: u64, // The starting version of the transaction output list
     pub end_version: u64,   // The ending version of the transaction output list (inclusive)
 }
 
+/// Omits the write sets from the given transaction outputs (e.g., for consumers
+/// that only require events and execution statuses). Note: the write set
+/// hashes in the proof no longer apply to the stripped outputs.
+pub fn strip_write_sets(
+    mut output_list_with_proof: TransactionOutputListWithProof,
+) -> TransactionOutputListWithProof {
+    for (_, output) in output_list_with_proof.transactions_and_outputs.iter_mut() {
+        *output = TransactionOutput::new(
+            WriteSet::default(),
+            output.events().to_vec(),
+            output.gas_used(),
+            output.status().clone(),
+        );
+    }
+    output_list_with_proof
+}
+
+/// Omits the events from the given transaction outputs (e.g., for consumers
+/// that don't require events). Note: the event hashes in the proof no longer
+/// apply to the stripped outputs.
+pub fn strip_events(
+    mut output_list_with_proof: TransactionOutputListWithProof,
+) -> TransactionOutputListWithProof {
+    for (_, output) in output_list_with_proof.transactions_and_outputs.iter_mut() {
+        *output = TransactionOutput::new(
+            output.write_set().clone(),
+            vec![],
+            output.gas_used(),
+            output.status().clone(),
+        );
+    }
+    output_list_with_proof
+}
+
 /// A storage service request for fetching a transaction list with a
 /// corresponding proof.
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct TransactionsWithProofRequest {
     pub proof_version: u64,   // The version the proof should be relative to
     pub start_version: u64,   // The starting version of the transaction list
@@ -255,8 +529,60 @@ pub struct TransactionsWithProofRequest {
     pub include_events: bool, // Whether or not to include events in the response
 }
 
-/// A storage service request for fetching a list of epoch ending ledger infos.
+/// A storage service request for fetching the transactions sent by a single
+/// account within a version range. Each transaction is returned with its own
+/// proof (relative to the `proof_version`), so the pruned list is verifiable.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TransactionsFromSenderRequest {
+    pub proof_version: u64,     // The version the proofs should be relative to
+    pub start_version: u64,     // The starting version of the transaction range
+    pub end_version: u64,       // The ending version of the transaction range (inclusive)
+    pub include_events: bool,   // Whether or not to include events in the response
+    pub sender: AccountAddress, // The sender of the transactions to return
+}
+
+/// A storage service request for fetching the transactions that emitted at
+/// least one event of the given type within a version range. Each transaction
+/// is returned (with its events) with its own proof (relative to the
+/// `proof_version`), so the filtered list is verifiable.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TransactionsWithEventRequest {
+    pub proof_version: u64,  // The version the proofs should be relative to
+    pub start_version: u64,  // The starting version of the transaction range
+    pub end_version: u64,    // The ending version of the transaction range (inclusive)
+    pub event_type: TypeTag, // The type of event that the returned transactions must emit
+}
+
+/// A storage service request for fetching the transactions within a version
+/// range, excluding any transactions larger than the size limit. Each
+/// transaction is returned with its own proof (relative to the `proof_version`).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TransactionsWithSizeLimitRequest {
+    pub proof_version: u64,         // The version the proofs should be relative to
+    pub start_version: u64,         // The starting version of the transaction range
+    pub end_version: u64,           // The ending version of the transaction range (inclusive)
+    pub include_events: bool,       // Whether or not to include events in the response
+    pub max_transaction_bytes: u64, // The max (serialized) size of each returned transaction
+}
+
+/// The transactions returned for a size limited transaction request
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SizeLimitedTransactions {
+    pub transactions: Vec<TransactionWithProof>, // The transactions within the size limit
+    pub oversized_versions: Vec<Version>, // The versions of the transactions excluded for their size
+}
+
+/// A storage service request for fetching the transaction accumulator
+/// inclusion proofs of a list of transactions. Each proof is relative to the
+/// accumulator at the `accumulator_version`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TransactionInclusionProofsRequest {
+    pub versions: Vec<Version>,   // The versions of the transactions to prove
+    pub accumulator_version: u64, // The version of the accumulator the proofs should be relative to
+}
+
+/// A storage service request for fetching a list of epoch ending ledger infos.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct EpochEndingLedgerInfoRequest {
     pub start_epoch: u64,
     pub expected_end_epoch: u64,
@@ -282,6 +608,11 @@ impl StorageServerSummary {
     pub fn can_service(&self, request: &StorageServiceRequest) -> bool {
         self.protocol_metadata.can_service(request) && self.data_summary.can_service(request)
     }
+
+    pub fn can_partially_service(&self, request: &StorageServiceRequest) -> bool {
+        self.protocol_metadata.can_service(request)
+            && self.data_summary.can_partially_service(request)
+    }
 }
 
 /// A summary of the protocol metadata for the storage service instance, such as
@@ -301,6 +632,10 @@ impl ProtocolMetadata {
             GetServerProtocolVersion
             | GetStorageServerSummary
             | GetNumberOfAccountsAtVersion(_) => true,
+            GetCompressedResponse(request) => self.can_service(&request.request),
+            GetAccountStatesByPrefix(request) => {
+                self.max_account_states_chunk_size >= request.limit
+            }
             GetAccountStatesChunkWithProof(request) => {
                 CompleteDataRange::new(request.start_account_index, request.end_account_index)
                     .map_or(false, |range| {
@@ -327,6 +662,37 @@ impl ProtocolMetadata {
                     self.max_transaction_output_chunk_size >= chunk_size
                 })
             }),
+            GetTransactionInclusionProofs(request) => {
+                request.versions.len() as u64 <= self.max_transaction_chunk_size
+            }
+            GetTransactionOutputsWithoutEvents(request)
+            | GetTransactionOutputsWithoutWriteSets(request) => CompleteDataRange::new(
+                request.start_version,
+                request.end_version,
+            )
+            .map_or(false, |range| {
+                range.len().map_or(false, |chunk_size| {
+                    self.max_transaction_output_chunk_size >= chunk_size
+                })
+            }),
+            GetTransactionsFromSender(request) => CompleteDataRange::new(
+                request.start_version,
+                request.end_version,
+            )
+            .map_or(false, |range| {
+                range.len().map_or(false, |chunk_size| {
+                    self.max_transaction_chunk_size >= chunk_size
+                })
+            }),
+            GetTransactionsWithEvent(request) => CompleteDataRange::new(
+                request.start_version,
+                request.end_version,
+            )
+            .map_or(false, |range| {
+                range.len().map_or(false, |chunk_size| {
+                    self.max_transaction_chunk_size >= chunk_size
+                })
+            }),
             GetTransactionsWithProof(request) => CompleteDataRange::new(
                 request.start_version,
                 request.end_version,
@@ -336,6 +702,15 @@ impl ProtocolMetadata {
                     self.max_transaction_chunk_size >= chunk_size
                 })
             }),
+            GetTransactionsWithSizeLimit(request) => CompleteDataRange::new(
+                request.start_version,
+                request.end_version,
+            )
+            .map_or(false, |range| {
+                range.len().map_or(false, |chunk_size| {
+                    self.max_transaction_chunk_size >= chunk_size
+                })
+            }),
         }
     }
 }
@@ -382,6 +757,11 @@ impl DataSummary {
             // storage services can always serve these metadata requests
             GetServerProtocolVersion => true,
             GetStorageServerSummary => true,
+            GetCompressedResponse(request) => self.can_service(&request.request),
+            GetAccountStatesByPrefix(request) => self
+                .account_states
+                .map(|range| range.contains(request.version))
+                .unwrap_or(false),
             GetAccountStatesChunkWithProof(request) => {
                 let proof_version = request.version;
 
@@ -412,7 +792,20 @@ impl DataSummary {
                 .account_states
                 .map(|range| range.contains(*version))
                 .unwrap_or(false),
-            GetTransactionOutputsWithProof(request) => {
+            GetTransactionInclusionProofs(request) => {
+                let can_serve_txns = request.versions.iter().all(|version| {
+                    *version <= request.accumulator_version
+                        && self
+                            .transactions
+                            .map(|range| range.contains(*version))
+                            .unwrap_or(false)
+                });
+
+                can_serve_txns && self.can_create_proof(request.accumulator_version)
+            }
+            GetTransactionOutputsWithProof(request)
+            | GetTransactionOutputsWithoutEvents(request)
+            | GetTransactionOutputsWithoutWriteSets(request) => {
                 let desired_range =
                     match CompleteDataRange::new(request.start_version, request.end_version) {
                         Ok(desired_range) => desired_range,
@@ -432,6 +825,20 @@ impl DataSummary {
 
                 can_serve_outputs && can_create_proof
             }
+            GetTransactionsFromSender(request) => {
+                let desired_range =
+                    match CompleteDataRange::new(request.start_version, request.end_version) {
+                        Ok(desired_range) => desired_range,
+                        Err(_) => return false,
+                    };
+
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.superset_of(&desired_range))
+                    .unwrap_or(false);
+
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
             GetTransactionsWithProof(request) => {
                 let desired_range =
                     match CompleteDataRange::new(request.start_version, request.end_version) {
@@ -452,8 +859,97 @@ impl DataSummary {
 
                 can_serve_txns && can_create_proof
             }
+            GetTransactionsWithEvent(request) => {
+                let desired_range =
+                    match CompleteDataRange::new(request.start_version, request.end_version) {
+                        Ok(desired_range) => desired_range,
+                        Err(_) => return false,
+                    };
+
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.superset_of(&desired_range))
+                    .unwrap_or(false);
+
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
+            GetTransactionsWithSizeLimit(request) => {
+                let desired_range =
+                    match CompleteDataRange::new(request.start_version, request.end_version) {
+                        Ok(desired_range) => desired_range,
+                        Err(_) => return false,
+                    };
+
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.superset_of(&desired_range))
+                    .unwrap_or(false);
+
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
         }
     }
+
+    /// Returns true iff the data summary can service the start of the requested
+    /// range (i.e., it can service a prefix of the request). Requests that don't
+    /// specify a data range must be fully serviceable.
+    pub fn can_partially_service(&self, request: &StorageServiceRequest) -> bool {
+        use StorageServiceRequest::*;
+        match request {
+            GetCompressedResponse(request) => self.can_partially_service(&request.request),
+            GetEpochEndingLedgerInfos(request) => self
+                .epoch_ending_ledger_infos
+                .map(|range| range.contains(request.start_epoch))
+                .unwrap_or(false),
+            GetTransactionOutputsWithProof(request)
+            | GetTransactionOutputsWithoutEvents(request)
+            | GetTransactionOutputsWithoutWriteSets(request) => {
+                let can_serve_outputs = self
+                    .transaction_outputs
+                    .map(|range| range.contains(request.start_version))
+                    .unwrap_or(false);
+                can_serve_outputs && self.can_create_proof(request.proof_version)
+            }
+            GetTransactionsFromSender(request) => {
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.contains(request.start_version))
+                    .unwrap_or(false);
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
+            GetTransactionsWithEvent(request) => {
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.contains(request.start_version))
+                    .unwrap_or(false);
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
+            GetTransactionsWithProof(request) => {
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.contains(request.start_version))
+                    .unwrap_or(false);
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
+            GetTransactionsWithSizeLimit(request) => {
+                let can_serve_txns = self
+                    .transactions
+                    .map(|range| range.contains(request.start_version))
+                    .unwrap_or(false);
+                can_serve_txns && self.can_create_proof(request.proof_version)
+            }
+            request => self.can_service(request),
+        }
+    }
+
+    /// Returns true iff the synced ledger info can be used to create proofs
+    /// at the given version.
+    fn can_create_proof(&self, proof_version: u64) -> bool {
+        self.synced_ledger_info
+            .as_ref()
+            .map(|li| li.ledger_info().version() >= proof_version)
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Clone, Debug, Error)]
@@ -622,6 +1118,35 @@ mod tests {
         })
     }
 
+    fn get_txns_from_sender_request(start: Version, end: Version) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionsFromSender(TransactionsFromSenderRequest {
+            proof_version: end,
+            start_version: start,
+            end_version: end,
+            include_events: false,
+            sender: AccountAddress::random(),
+        })
+    }
+
+    fn get_txns_with_event_request(start: Version, end: Version) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionsWithEvent(TransactionsWithEventRequest {
+            proof_version: end,
+            start_version: start,
+            end_version: end,
+            event_type: TypeTag::Bool,
+        })
+    }
+
+    fn get_txns_with_size_limit_request(start: Version, end: Version) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionsWithSizeLimit(TransactionsWithSizeLimitRequest {
+            proof_version: end,
+            start_version: start,
+            end_version: end,
+            include_events: false,
+            max_transaction_bytes: 1000,
+        })
+    }
+
     fn get_txn_outputs_request(
         proof_version: Version,
         start_version: Version,
@@ -634,6 +1159,39 @@ mod tests {
         })
     }
 
+    fn get_txn_outputs_without_events_request(
+        start: Version,
+        end: Version,
+    ) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionOutputsWithoutEvents(
+            TransactionOutputsWithProofRequest {
+                proof_version: end,
+                start_version: start,
+                end_version: end,
+            },
+        )
+    }
+
+    fn get_txn_outputs_without_write_sets_request(
+        start: Version,
+        end: Version,
+    ) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionOutputsWithoutWriteSets(
+            TransactionOutputsWithProofRequest {
+                proof_version: end,
+                start_version: start,
+                end_version: end,
+            },
+        )
+    }
+
+    fn get_txn_inclusion_proofs_request(num_versions: u64) -> StorageServiceRequest {
+        StorageServiceRequest::GetTransactionInclusionProofs(TransactionInclusionProofsRequest {
+            versions: (0..num_versions).collect(),
+            accumulator_version: num_versions,
+        })
+    }
+
     fn get_account_state_chunks_request(
         version: Version,
         start_account_index: u64,
@@ -650,6 +1208,15 @@ mod tests {
         get_account_state_chunks_request(version, 0, 1000)
     }
 
+    fn get_account_states_by_prefix_request(version: Version, limit: u64) -> StorageServiceRequest {
+        StorageServiceRequest::GetAccountStatesByPrefix(AccountStatesByPrefixRequest {
+            version,
+            prefix: StateKeyPrefix::from(AccountAddress::ONE),
+            cursor: None,
+            limit,
+        })
+    }
+
     #[test]
     fn test_complete_data_range() {
         // good ranges
@@ -786,6 +1353,11 @@ mod tests {
         // can provide proof, but out of range ==> cannot service
         assert!(!summary.can_service(&get_account_states_request(50)));
         assert!(!summary.can_service(&get_account_states_request(99)));
+
+        // prefix requests don't require a proof, only the account states
+        assert!(summary.can_service(&get_account_states_by_prefix_request(300, 10)));
+        assert!(!summary.can_service(&get_account_states_by_prefix_request(99, 10)));
+        assert!(!summary.can_service(&get_account_states_by_prefix_request(301, 10)));
     }
 
     #[test]
@@ -808,6 +1380,234 @@ mod tests {
 
         assert!(metadata.can_service(&get_account_state_chunks_request(200, 100, 199)));
         assert!(!metadata.can_service(&get_account_state_chunks_request(200, 100, 200)));
+
+        assert!(metadata.can_service(&get_account_states_by_prefix_request(200, 100)));
+        assert!(!metadata.can_service(&get_account_states_by_prefix_request(200, 101)));
+
+        assert!(metadata.can_service(&get_txns_from_sender_request(100, 199)));
+        assert!(!metadata.can_service(&get_txns_from_sender_request(100, 200)));
+
+        assert!(metadata.can_service(&get_txn_outputs_without_write_sets_request(100, 199)));
+        assert!(!metadata.can_service(&get_txn_outputs_without_write_sets_request(100, 200)));
+
+        assert!(metadata.can_service(&get_txn_inclusion_proofs_request(100)));
+        assert!(!metadata.can_service(&get_txn_inclusion_proofs_request(101)));
+
+        assert!(metadata.can_service(&get_txns_with_size_limit_request(100, 199)));
+        assert!(!metadata.can_service(&get_txns_with_size_limit_request(100, 200)));
+
+        assert!(metadata.can_service(&get_txns_with_event_request(100, 199)));
+        assert!(!metadata.can_service(&get_txns_with_event_request(100, 200)));
+
+        let compressed_request =
+            StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+                request: Box::new(get_txns_request(200, 100, 199)),
+                supported_algorithms: vec![CompressionAlgo::Zstd],
+            });
+        assert!(metadata.can_service(&compressed_request));
+    }
+
+    #[test]
+    fn test_decompressed_response_size_is_bounded() {
+        let response = StorageServiceResponse::TransactionsFromSender(vec![]);
+        let response_bytes = bcs::to_bytes(&response).unwrap().len() as u64;
+        for compression_algorithm in [CompressionAlgo::Lz4, CompressionAlgo::Zstd] {
+            let compressed_response =
+                CompressedResponse::compress(&response, compression_algorithm).unwrap();
+
+            // Verify the response can be decompressed within the limit
+            assert_eq!(
+                compressed_response.decompress(response_bytes).unwrap(),
+                response
+            );
+
+            // Verify the response is rejected if it exceeds the limit
+            assert_err!(compressed_response.decompress(response_bytes - 1));
+        }
+    }
+
+    #[test]
+    fn test_min_protocol_version() {
+        // The original requests can be serviced by all servers
+        for request in [
+            get_account_state_chunks_request(200, 100, 199),
+            get_epochs_request(100, 199),
+            get_txn_outputs_request(200, 100, 199),
+            get_txns_request(200, 100, 199),
+            StorageServiceRequest::GetNumberOfAccountsAtVersion(100),
+            StorageServiceRequest::GetServerProtocolVersion,
+            StorageServiceRequest::GetStorageServerSummary,
+        ] {
+            assert_eq!(request.min_protocol_version(), BASE_PROTOCOL_VERSION);
+        }
+
+        // The appended requests require the extended protocol version
+        for request in [
+            get_account_states_by_prefix_request(200, 100),
+            get_txn_inclusion_proofs_request(100),
+            get_txn_outputs_without_events_request(100, 199),
+            get_txn_outputs_without_write_sets_request(100, 199),
+            get_txns_from_sender_request(100, 199),
+            get_txns_with_event_request(100, 199),
+            get_txns_with_size_limit_request(100, 199),
+            StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+                request: Box::new(get_txns_request(200, 100, 199)),
+                supported_algorithms: vec![CompressionAlgo::Zstd],
+            }),
+        ] {
+            assert_eq!(request.min_protocol_version(), EXTENDED_PROTOCOL_VERSION);
+        }
+    }
+
+    #[test]
+    fn test_storage_service_message_encodings() {
+        // Verify the encodings of the original requests never change
+        let requests = vec![
+            (
+                get_account_state_chunks_request(1, 2, 3),
+                encode(0, &[1, 2, 3]),
+            ),
+            (get_epochs_request(1, 2), encode(1, &[1, 2])),
+            (
+                StorageServiceRequest::GetNumberOfAccountsAtVersion(5),
+                encode(2, &[5]),
+            ),
+            (StorageServiceRequest::GetServerProtocolVersion, vec![3]),
+            (StorageServiceRequest::GetStorageServerSummary, vec![4]),
+            (get_txn_outputs_request(3, 1, 2), encode(5, &[3, 1, 2])),
+            (
+                get_txns_request(3, 1, 2),
+                [encode(6, &[3, 1, 2]), vec![1]].concat(),
+            ),
+        ];
+        for (request, expected_bytes) in requests {
+            assert_eq!(bcs::to_bytes(&request).unwrap(), expected_bytes);
+            assert_eq!(
+                bcs::from_bytes::<StorageServiceRequest>(&expected_bytes).unwrap(),
+                request
+            );
+        }
+
+        // Verify the new requests that reuse the original request types only
+        // differ from the original requests by their variant index
+        let requests = vec![
+            (
+                get_txn_outputs_without_write_sets_request(1, 2),
+                encode(9, &[2, 1, 2]),
+            ),
+            (
+                get_txn_outputs_without_events_request(1, 2),
+                encode(14, &[2, 1, 2]),
+            ),
+        ];
+        for (request, expected_bytes) in requests {
+            assert_eq!(bcs::to_bytes(&request).unwrap(), expected_bytes);
+        }
+
+        // Verify the new requests are appended to the original requests
+        let compressed_request =
+            StorageServiceRequest::GetCompressedResponse(CompressedResponseRequest {
+                request: Box::new(get_txns_request(3, 1, 2)),
+                supported_algorithms: vec![CompressionAlgo::Zstd],
+            });
+        let requests = vec![
+            compressed_request,
+            get_txns_from_sender_request(1, 2),
+            get_txn_outputs_without_write_sets_request(1, 2),
+            get_txn_inclusion_proofs_request(2),
+            get_txns_with_size_limit_request(1, 2),
+            get_txns_with_event_request(1, 2),
+            get_account_states_by_prefix_request(1, 2),
+            get_txn_outputs_without_events_request(1, 2),
+        ];
+        for (index, request) in requests.iter().enumerate() {
+            assert_eq!(bcs::to_bytes(request).unwrap()[0] as usize, index + 7);
+        }
+
+        // Verify the encodings of the original responses never change
+        let responses = vec![
+            (
+                StorageServiceResponse::EpochEndingLedgerInfos(EpochChangeProof::new(
+                    vec![],
+                    false,
+                )),
+                vec![1, 0, 0],
+            ),
+            (
+                StorageServiceResponse::NumberOfAccountsAtVersion(5),
+                encode(2, &[5]),
+            ),
+            (
+                StorageServiceResponse::ServerProtocolVersion(ServerProtocolVersion {
+                    protocol_version: 1,
+                }),
+                encode(3, &[1]),
+            ),
+        ];
+        for (response, expected_bytes) in responses {
+            assert_eq!(bcs::to_bytes(&response).unwrap(), expected_bytes);
+        }
+        let responses = vec![
+            (
+                StorageServiceResponse::StorageServerSummary(StorageServerSummary::default()),
+                4,
+            ),
+            (
+                StorageServiceResponse::TransactionOutputsWithProof(
+                    TransactionOutputListWithProof::new_empty(),
+                ),
+                5,
+            ),
+            (
+                StorageServiceResponse::TransactionsWithProof(TransactionListWithProof::new_empty()),
+                6,
+            ),
+        ];
+        for (response, variant_index) in responses {
+            assert_eq!(bcs::to_bytes(&response).unwrap()[0], variant_index);
+        }
+
+        // Verify the encoding of the protocol metadata (advertised by all
+        // servers in their summaries) never changes.
+        let protocol_metadata = ProtocolMetadata {
+            max_epoch_chunk_size: 1,
+            max_transaction_chunk_size: 2,
+            max_transaction_output_chunk_size: 3,
+            max_account_states_chunk_size: 4,
+        };
+        let expected_bytes: Vec<u8> = [1u64, 2, 3, 4]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        assert_eq!(bcs::to_bytes(&protocol_metadata).unwrap(), expected_bytes);
+
+        // Verify the new responses are appended to the original responses
+        let responses = vec![
+            StorageServiceResponse::CompressedResponse(CompressedResponse {
+                compression_algorithm: CompressionAlgo::Zstd,
+                compressed_data: vec![],
+            }),
+            StorageServiceResponse::TransactionsFromSender(vec![]),
+            StorageServiceResponse::TransactionInclusionProofs(vec![]),
+            StorageServiceResponse::TransactionsWithSizeLimit(SizeLimitedTransactions {
+                transactions: vec![],
+                oversized_versions: vec![],
+            }),
+            StorageServiceResponse::TransactionsWithEvent(vec![]),
+            StorageServiceResponse::AccountStatesByPrefix(vec![]),
+        ];
+        for (index, response) in responses.iter().enumerate() {
+            assert_eq!(bcs::to_bytes(response).unwrap()[0] as usize, index + 7);
+        }
+    }
+
+    /// Returns the expected encoding of a variant holding the given integers
+    fn encode(variant_index: u8, values: &[u64]) -> Vec<u8> {
+        let mut bytes = vec![variant_index];
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
     }
 
     proptest! {
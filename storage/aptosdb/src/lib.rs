@@ -689,6 +689,21 @@ impl DbReader for AptosDB {
         })
     }
 
+    fn get_prefixed_state_value_iterator(
+        &self,
+        key_prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        version: Version,
+    ) -> Result<Box<dyn Iterator<Item = Result<(StateKey, StateValue)>> + '_>> {
+        gauged_api("get_prefixed_state_value_iterator", || {
+            let iter: Box<dyn Iterator<Item = Result<(StateKey, StateValue)>> + '_> = Box::new(
+                self.state_store
+                    .get_prefixed_state_value_iter(key_prefix, cursor, version)?,
+            );
+            Ok(iter)
+        })
+    }
+
     fn get_latest_ledger_info_option(&self) -> Result<Option<LedgerInfoWithSignatures>> {
         gauged_api("get_latest_ledger_info_option", || {
             Ok(self.ledger_store.get_latest_ledger_info_option())
@@ -32,7 +32,7 @@ use aptos_types::{
     transaction::Version,
 };
 use itertools::process_results;
-use schemadb::{SchemaBatch, DB};
+use schemadb::{SchemaBatch, SchemaIterator, DB};
 use std::{cmp::Ordering, collections::HashMap, sync::Arc};
 use storage_interface::StateSnapshotReceiver;
 
@@ -71,48 +71,10 @@ impl StateStore {
         key_prefix: &StateKeyPrefix,
         desired_version: Version,
     ) -> Result<HashMap<StateKey, NodeKey>> {
-        let mut iter = self.db.iter::<StateValueIndexSchema>(Default::default())?;
         let mut result = HashMap::new();
-        iter.seek(&(key_prefix))?;
-        while let Some(((state_key, first_version), num_nibbles)) = iter.next().transpose()? {
-            // Cursor is currently at the first available version of the state key.
-            // Check if the key_prefix is a valid prefix of the state_key we got from DB.
-
-            if !key_prefix.is_prefix(&state_key)? {
-                // No more keys matching the key_prefix, we can return the result.
-                return Ok(result);
-            }
-            match first_version.cmp(&desired_version) {
-                Ordering::Less => {
-                    iter.seek_for_prev(&(state_key.clone(), desired_version))?;
-                    let ((state_key, db_version), num_nibbles) =
-                        iter.next().transpose()?.ok_or_else(|| {
-                            anyhow!(
-                                "Failure seeking to desired version {:?} for state key {:?}",
-                                desired_version,
-                                state_key
-                            )
-                        })?;
-                    result.insert(
-                        state_key.clone(),
-                        NodeKey::new(
-                            db_version,
-                            NibblePath::new_from_state_key(&state_key, num_nibbles as usize),
-                        ),
-                    );
-                }
-
-                Ordering::Equal => {
-                    result.insert(
-                        state_key.clone(),
-                        NodeKey::new(
-                            first_version,
-                            NibblePath::new_from_state_key(&state_key, num_nibbles as usize),
-                        ),
-                    );
-                }
-                Ordering::Greater => {}
-            }
+        for node_key_result in self.get_prefixed_node_key_iter(key_prefix, None, desired_version)? {
+            let (state_key, node_key) = node_key_result?;
+            result.insert(state_key, node_key);
             // We don't allow fetching arbitrarily large number of values to be fetched as this can
             // potentially slowdown the DB.
             if result.len() > MAX_VALUES_TO_FETCH_FOR_KEY_PREFIX {
@@ -122,12 +84,33 @@ impl StateStore {
                     MAX_VALUES_TO_FETCH_FOR_KEY_PREFIX
                 ));
             }
-            // Seek to the next key - this can be done by seeking to the current key with max version
-            iter.seek(&(state_key, u64::MAX))?;
         }
         Ok(result)
     }
 
+    /// Returns an iterator over the state keys with a particular prefix and the JMT leaf node keys
+    /// of their values at the desired version (ordered by state key). If a cursor is given,
+    /// iteration starts at the first state key after the cursor.
+    fn get_prefixed_node_key_iter(
+        &self,
+        key_prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        desired_version: Version,
+    ) -> Result<PrefixedNodeKeyIter> {
+        let mut iter = self.db.iter::<StateValueIndexSchema>(Default::default())?;
+        match cursor {
+            // Seek to the next key - this can be done by seeking to the cursor with max version
+            Some(cursor) => iter.seek(&(cursor.clone(), u64::MAX))?,
+            None => iter.seek(key_prefix)?,
+        }
+        Ok(PrefixedNodeKeyIter {
+            inner: iter,
+            key_prefix: key_prefix.clone(),
+            desired_version,
+            is_finished: false,
+        })
+    }
+
     /// Returns the key, value pairs for a particular state key prefix at at desired version. This
     /// API can be used to get all resources of an account by passing the account address as the
     /// key prefix.
@@ -146,6 +129,22 @@ impl StateStore {
         Ok(result)
     }
 
+    /// Returns an iterator over the key, value pairs for a particular state key prefix at the
+    /// desired version. The pairs are ordered by state key and read lazily (i.e., only the values
+    /// consumed by the caller are read). If a cursor is given, iteration starts at the first
+    /// state key after the cursor.
+    pub fn get_prefixed_state_value_iter(
+        &self,
+        key_prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        desired_version: Version,
+    ) -> Result<PrefixedStateValueIter> {
+        Ok(PrefixedStateValueIter {
+            state_store: self,
+            node_key_iter: self.get_prefixed_node_key_iter(key_prefix, cursor, desired_version)?,
+        })
+    }
+
     /// Get the state value given the state key and root hash of state Merkle tree by using the
     /// state value index. Only used for testing for now but should replace the
     /// `get_value_with_proof_by_version` call for VM execution to fetch the value without proof.
@@ -348,6 +347,94 @@ impl StateStore {
     }
 }
 
+/// An iterator over the state keys with a particular prefix, yielding the JMT leaf node key of each
+/// state key at the desired version. State keys created after the desired version are skipped.
+struct PrefixedNodeKeyIter<'a> {
+    inner: SchemaIterator<'a, StateValueIndexSchema>,
+    key_prefix: StateKeyPrefix,
+    desired_version: Version,
+    is_finished: bool,
+}
+
+impl<'a> PrefixedNodeKeyIter<'a> {
+    fn next_impl(&mut self) -> Result<Option<(StateKey, NodeKey)>> {
+        if self.is_finished {
+            return Ok(None);
+        }
+
+        while let Some(((state_key, first_version), num_nibbles)) = self.inner.next().transpose()? {
+            // Cursor is currently at the first available version of the state key.
+            // Check if the key_prefix is a valid prefix of the state_key we got from DB.
+            if !self.key_prefix.is_prefix(&state_key)? {
+                // No more keys matching the key_prefix
+                break;
+            }
+            let node_key = match first_version.cmp(&self.desired_version) {
+                Ordering::Less => {
+                    self.inner
+                        .seek_for_prev(&(state_key.clone(), self.desired_version))?;
+                    let ((state_key, db_version), num_nibbles) =
+                        self.inner.next().transpose()?.ok_or_else(|| {
+                            anyhow!(
+                                "Failure seeking to desired version {:?} for state key {:?}",
+                                self.desired_version,
+                                state_key
+                            )
+                        })?;
+                    Some(NodeKey::new(
+                        db_version,
+                        NibblePath::new_from_state_key(&state_key, num_nibbles as usize),
+                    ))
+                }
+                Ordering::Equal => Some(NodeKey::new(
+                    first_version,
+                    NibblePath::new_from_state_key(&state_key, num_nibbles as usize),
+                )),
+                Ordering::Greater => None,
+            };
+            // Seek to the next key - this can be done by seeking to the current key with max version
+            self.inner.seek(&(state_key.clone(), u64::MAX))?;
+
+            if let Some(node_key) = node_key {
+                return Ok(Some((state_key, node_key)));
+            }
+        }
+
+        self.is_finished = true;
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for PrefixedNodeKeyIter<'a> {
+    type Item = Result<(StateKey, NodeKey)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_impl().transpose()
+    }
+}
+
+/// An iterator over the key, value pairs for a particular state key prefix at the desired version.
+/// The values are read lazily (i.e., only when the corresponding pair is consumed).
+pub struct PrefixedStateValueIter<'a> {
+    state_store: &'a StateStore,
+    node_key_iter: PrefixedNodeKeyIter<'a>,
+}
+
+impl<'a> Iterator for PrefixedStateValueIter<'a> {
+    type Item = Result<(StateKey, StateValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.node_key_iter.next().map(|node_key_result| {
+            let (state_key, node_key) = node_key_result?;
+            let state_value = self
+                .state_store
+                .get_value_by_node_key(&node_key)?
+                .ok_or_else(|| anyhow!("Failure reading value for node_key {:?}", node_key))?;
+            Ok((state_key, state_value))
+        })
+    }
+}
+
 impl TreeReader<StateKeyAndValue> for StateStore {
     fn get_node_option(&self, node_key: &NodeKey) -> Result<Option<Node>> {
         self.db.get::<JellyfishMerkleNodeSchema>(node_key)
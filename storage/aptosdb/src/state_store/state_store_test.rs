@@ -228,6 +228,89 @@ fn test_get_values_by_key_prefix() {
     assert_eq!(*key_value_map.get(&key5).unwrap(), value5_v2);
 }
 
+#[test]
+fn test_get_prefixed_state_value_iter() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let address = AccountAddress::new([12u8; AccountAddress::LENGTH]);
+    let account_key_prefx = StateKeyPrefix::new(StateKeyTag::AccessPath, address.to_vec());
+
+    let key1 = StateKey::AccessPath(AccessPath::new(address, b"state_key1".to_vec()));
+    let key2 = StateKey::AccessPath(AccessPath::new(address, b"state_key2".to_vec()));
+    let key3 = StateKey::AccessPath(AccessPath::new(address, b"state_key3".to_vec()));
+
+    let value1_v0 = StateValue::from(String::from("value1_v0").into_bytes());
+    let value2_v0 = StateValue::from(String::from("value2_v0").into_bytes());
+    let value2_v1 = StateValue::from(String::from("value2_v1").into_bytes());
+    let value3_v1 = StateValue::from(String::from("value3_v1").into_bytes());
+
+    put_value_set(
+        store,
+        vec![
+            (key1.clone(), value1_v0.clone()),
+            (key2.clone(), value2_v0.clone()),
+        ],
+        0,
+    );
+    put_value_set(
+        store,
+        vec![
+            (key2.clone(), value2_v1.clone()),
+            (key3.clone(), value3_v1.clone()),
+        ],
+        1,
+    );
+
+    // Add a value for another account (which shouldn't be returned)
+    let address1 = AccountAddress::new([22u8; AccountAddress::LENGTH]);
+    let key4 = StateKey::AccessPath(AccessPath::new(address1, b"state_key4".to_vec()));
+    put_value_set(store, vec![(key4, value3_v1.clone())], 2);
+
+    // Ensure the values are returned in order for each version
+    let values = store
+        .get_prefixed_state_value_iter(&account_key_prefx, None, 0)
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        values,
+        vec![(key1.clone(), value1_v0.clone()), (key2.clone(), value2_v0)]
+    );
+    let values = store
+        .get_prefixed_state_value_iter(&account_key_prefx, None, 2)
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        values,
+        vec![
+            (key1.clone(), value1_v0),
+            (key2.clone(), value2_v1.clone()),
+            (key3.clone(), value3_v1.clone()),
+        ]
+    );
+
+    // Ensure iteration resumes after the cursor
+    let values = store
+        .get_prefixed_state_value_iter(&account_key_prefx, Some(&key1), 1)
+        .unwrap()
+        .take(1)
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(values, vec![(key2.clone(), value2_v1)]);
+    let values = store
+        .get_prefixed_state_value_iter(&account_key_prefx, Some(&key2), 1)
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(values, vec![(key3.clone(), value3_v1)]);
+    let mut iter = store
+        .get_prefixed_state_value_iter(&account_key_prefx, Some(&key3), 1)
+        .unwrap();
+    assert!(iter.next().is_none());
+}
+
 #[test]
 fn test_retired_records() {
     let key1 = StateKey::Raw(String::from("test_key1").into_bytes());
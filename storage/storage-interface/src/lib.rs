@@ -335,6 +335,19 @@ pub trait DbReader: Send + Sync {
         unimplemented!()
     }
 
+    /// Returns an iterator over the key, value pairs for a particular state key prefix at the
+    /// desired version. The pairs are ordered by state key and read lazily, so callers can page
+    /// through large prefixes. If a cursor is given, iteration starts at the first state key
+    /// after the cursor.
+    fn get_prefixed_state_value_iterator(
+        &self,
+        key_prefix: &StateKeyPrefix,
+        cursor: Option<&StateKey>,
+        version: Version,
+    ) -> Result<Box<dyn Iterator<Item = Result<(StateKey, StateValue)>> + '_>> {
+        unimplemented!()
+    }
+
     /// Returns the latest ledger info, if any.
     fn get_latest_ledger_info_option(&self) -> Result<Option<LedgerInfoWithSignatures>> {
         unimplemented!()